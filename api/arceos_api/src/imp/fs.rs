@@ -1,17 +1,22 @@
-use alloc::string::String;
+use alloc::{string::String, sync::Arc};
 use axerrno::AxResult;
-use axfs::fops::{Directory, File};
+use axfs::fops::{Directory, File, VfsOps};
 
 pub use axfs::fops::DirEntry as AxDirEntry;
 pub use axfs::fops::FileAttr as AxFileAttr;
+pub use axfs::fops::FileSystemStats as AxFileSystemStats;
 pub use axfs::fops::FilePerm as AxFilePerm;
 pub use axfs::fops::FileType as AxFileType;
 pub use axfs::fops::OpenOptions as AxOpenOptions;
+pub use axfs::fops::Quota as AxQuota;
 pub use axio::SeekFrom as AxSeekFrom;
 
 #[cfg(feature = "myfs")]
 pub use axfs::fops::{Disk as AxDisk, MyFileSystemIf};
 
+/// A mountable filesystem instance.
+pub type AxFileSystem = Arc<dyn VfsOps>;
+
 /// A handle to an opened file.
 pub struct AxFileHandle(File);
 
@@ -78,6 +83,28 @@ pub fn ax_rename(old: &str, new: &str) -> AxResult {
     axfs::api::rename(old, new)
 }
 
+pub fn ax_symlink(target: &str, path: &str) -> AxResult {
+    axfs::api::soft_link(target, path)
+}
+
+pub fn ax_read_link(path: &str) -> AxResult<String> {
+    axfs::api::read_link(path)
+}
+
+pub fn ax_hard_link(old: &str, new: &str) -> AxResult {
+    axfs::api::hard_link(old, new)
+}
+
+pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr> {
+    let meta = axfs::api::symlink_metadata(path)?;
+    Ok(AxFileAttr::new(
+        meta.permissions(),
+        meta.file_type(),
+        meta.size(),
+        meta.blocks(),
+    ))
+}
+
 pub fn ax_current_dir() -> AxResult<String> {
     axfs::api::current_dir()
 }
@@ -85,3 +112,31 @@ pub fn ax_current_dir() -> AxResult<String> {
 pub fn ax_set_current_dir(path: &str) -> AxResult {
     axfs::api::set_current_dir(path)
 }
+
+pub fn ax_new_filesystem(fstype: &str) -> AxResult<AxFileSystem> {
+    axfs::api::new_filesystem(fstype)
+}
+
+pub fn ax_mount(path: &str, fs: AxFileSystem) -> AxResult {
+    axfs::api::mount(path, fs)
+}
+
+pub fn ax_mount_virtual(fstype: &str, path: &str, options: &str) -> AxResult {
+    axfs::api::mount_virtual_with_options(fstype, path, options)
+}
+
+pub fn ax_mount_volume(volume: &str, path: &str) -> AxResult {
+    axfs::api::mount_volume(volume, path)
+}
+
+pub fn ax_umount(path: &str) -> AxResult {
+    axfs::api::umount(path)
+}
+
+pub fn ax_statfs(path: &str) -> AxResult<AxFileSystemStats> {
+    axfs::api::statfs(path)
+}
+
+pub fn ax_set_quota(path: &str, quota: Option<AxQuota>) -> AxResult {
+    axfs::api::set_quota(path, quota)
+}
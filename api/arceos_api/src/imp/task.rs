@@ -42,6 +42,15 @@ cfg_task! {
     /// A mask to specify the CPU affinity.
     pub use axtask::AxCpuMask;
 
+    /// The scheduling policy of a task.
+    pub use axtask::SchedPolicy as AxSchedPolicy;
+
+    /// CPU time and scheduling statistics of a task.
+    pub use axtask::TaskStats as AxTaskStats;
+
+    /// CPU time and scheduling statistics of a CPU.
+    pub use axtask::CpuStats as AxCpuStats;
+
     /// A handle to a wait queue.
     ///
     /// A wait queue is used to store sleeping tasks waiting for a certain event
@@ -96,6 +105,70 @@ cfg_task! {
         }
     }
 
+    pub fn ax_set_task_priority(task: &AxTaskHandle, prio: isize) -> crate::AxResult {
+        if axtask::set_task_priority(&task.inner, prio) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                BadState,
+                "ax_set_task_priority: failed to set task priority"
+            )
+        }
+    }
+
+    pub fn ax_set_task_affinity(task: &AxTaskHandle, cpumask: AxCpuMask) -> crate::AxResult {
+        if axtask::set_task_affinity(&task.inner, cpumask) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                BadState,
+                "ax_set_task_affinity: failed to set task affinity"
+            )
+        }
+    }
+
+    pub fn ax_set_current_policy(policy: AxSchedPolicy) -> crate::AxResult {
+        if axtask::set_current_policy(policy) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_policy: invalid scheduling policy"
+            )
+        }
+    }
+
+    pub fn ax_set_task_policy(task: &AxTaskHandle, policy: AxSchedPolicy) -> crate::AxResult {
+        if axtask::set_task_policy(&task.inner, policy) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_task_policy: invalid scheduling policy"
+            )
+        }
+    }
+
+    pub fn ax_current_task_stats() -> AxTaskStats {
+        axtask::current().stats()
+    }
+
+    pub fn ax_task_stats(task: &AxTaskHandle) -> AxTaskStats {
+        task.inner.stats()
+    }
+
+    pub fn ax_all_tasks_stats() -> AxTaskStats {
+        axtask::all_tasks_stats()
+    }
+
+    pub fn ax_cpu_stats(cpu_id: usize) -> crate::AxResult<AxCpuStats> {
+        if cpu_id < axconfig::plat::CPU_NUM {
+            Ok(axtask::cpu_stats(cpu_id))
+        } else {
+            axerrno::ax_err!(InvalidInput, "ax_cpu_stats: invalid CPU ID")
+        }
+    }
+
     pub fn ax_wait_queue_wait(wq: &AxWaitQueueHandle, timeout: Option<Duration>) -> bool {
         #[cfg(feature = "irq")]
         if let Some(dur) = timeout {
@@ -122,6 +122,9 @@ pub mod task {
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
         pub type AxCpuMask;
+        pub type AxSchedPolicy;
+        pub type AxTaskStats;
+        pub type AxCpuStats;
     }
 
     define_api! {
@@ -158,6 +161,29 @@ pub mod task {
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
         /// Sets the cpu affinity of the current task.
         pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult;
+        /// Sets the priority of the given task, which may be running on
+        /// another CPU, ready or blocked.
+        pub fn ax_set_task_priority(task: &AxTaskHandle, prio: isize) -> crate::AxResult;
+        /// Sets the cpu affinity of the given task, which may be running on
+        /// another CPU, ready or blocked. It is moved to an allowed CPU when
+        /// it is not running.
+        pub fn ax_set_task_affinity(task: &AxTaskHandle, cpumask: AxCpuMask) -> crate::AxResult;
+        /// Sets the scheduling policy of the current task.
+        pub fn ax_set_current_policy(policy: AxSchedPolicy) -> crate::AxResult;
+        /// Sets the scheduling policy of the given task, which may be running
+        /// on another CPU, ready or blocked. Real-time and deadline tasks
+        /// always run before normal tasks.
+        pub fn ax_set_task_policy(task: &AxTaskHandle, policy: AxSchedPolicy) -> crate::AxResult;
+        /// Returns the CPU time and scheduling statistics of the current task.
+        pub fn ax_current_task_stats() -> AxTaskStats;
+        /// Returns the CPU time and scheduling statistics of the given task.
+        pub fn ax_task_stats(task: &AxTaskHandle) -> AxTaskStats;
+        /// Returns the sum of the statistics of all tasks except idle tasks,
+        /// including the exited ones.
+        pub fn ax_all_tasks_stats() -> AxTaskStats;
+        /// Returns the busy time, idle time and number of context switches
+        /// of the given CPU.
+        pub fn ax_cpu_stats(cpu_id: usize) -> crate::AxResult<AxCpuStats>;
         /// Blocks the current task and put it into the wait queue, until
         /// other tasks notify the wait queue, or the the given duration has
         /// elapsed (if specified).
@@ -192,6 +218,9 @@ pub mod fs {
         pub type AxFilePerm;
         pub type AxDirEntry;
         pub type AxSeekFrom;
+        pub type AxFileSystem;
+        pub type AxFileSystemStats;
+        pub type AxQuota;
         #[cfg(feature = "myfs")]
         pub type AxDisk;
         #[cfg(feature = "myfs")]
@@ -255,11 +284,44 @@ pub mod fs {
         ///
         /// It will delete the original file if `old` already exists.
         pub fn ax_rename(old: &str, new: &str) -> AxResult;
+        /// Creates a symbolic link `path` which links to `target`.
+        pub fn ax_symlink(target: &str, path: &str) -> AxResult;
+        /// Returns the target of the symbolic link `path`.
+        pub fn ax_read_link(path: &str) -> AxResult<alloc::string::String>;
+        /// Creates a hard link `new` to the file `old`.
+        ///
+        /// Both must be in the same filesystem, which supports hard links.
+        pub fn ax_hard_link(old: &str, new: &str) -> AxResult;
+        /// Returns attributes of the file at `path`, or of the symbolic link
+        /// itself if it is one.
+        pub fn ax_symlink_attr(path: &str) -> AxResult<AxFileAttr>;
 
         /// Returns the current working directory.
         pub fn ax_current_dir() -> AxResult<alloc::string::String>;
         /// Changes the current working directory to the specified path.
         pub fn ax_set_current_dir(path: &str) -> AxResult;
+
+        /// Creates a new virtual filesystem of the given type (e.g. `ramfs`),
+        /// which can be mounted by [`ax_mount`].
+        pub fn ax_new_filesystem(fstype: &str) -> AxResult<AxFileSystem>;
+        /// Mounts the filesystem `fs` at the provided path.
+        pub fn ax_mount(path: &str, fs: AxFileSystem) -> AxResult;
+        /// Mounts a new virtual filesystem of the given type at the provided
+        /// path, with comma-separated options (e.g. `size=16m` of `ramfs`).
+        pub fn ax_mount_virtual(fstype: &str, path: &str, options: &str) -> AxResult;
+        /// Mounts the filesystem on a block volume (e.g. `disk1p2` or a GPT
+        /// partition label) at the provided path.
+        pub fn ax_mount_volume(volume: &str, path: &str) -> AxResult;
+        /// Unmounts the filesystem mounted at the provided path.
+        ///
+        /// It fails if the filesystem is still in use.
+        pub fn ax_umount(path: &str) -> AxResult;
+        /// Returns the statistics of the filesystem containing the provided
+        /// path, limited to its quota.
+        pub fn ax_statfs(path: &str) -> AxResult<AxFileSystemStats>;
+        /// Sets the quota of the filesystem mounted at the provided path, or
+        /// removes it if `quota` is `None`.
+        pub fn ax_set_quota(path: &str, quota: Option<AxQuota>) -> AxResult;
     }
 }
 
@@ -37,6 +37,11 @@ typedef struct {{
 
         let allow_types = [
             "stat",
+            "flock",
+            "statx",
+            "statfs",
+            "statvfs",
+            "dirent",
             "size_t",
             "ssize_t",
             "off_t",
@@ -49,10 +54,18 @@ typedef struct {{
             "pthread_mutex_t",
             "pthread_mutexattr_t",
             "epoll_event",
+            "inotify_event",
             "iovec",
+            "io_ring_.*",
             "clockid_t",
             "rlimit",
             "aibuf",
+            "cpu_set_t",
+            "id_t",
+            "sched_param",
+            "clock_t",
+            "rusage",
+            "tms",
         ];
         let allow_vars = [
             "CLOCK_.*",
@@ -66,8 +79,25 @@ typedef struct {{
             "EPOLL_CTL_.*",
             "EPOLL.*",
             "RLIMIT_.*",
+            "RUSAGE_.*",
             "EAI_.*",
             "MAXADDRS",
+            "MS_.*",
+            "MNT_.*",
+            "UMOUNT_.*",
+            "AT_.*",
+            "RENAME_.*",
+            "STATX_.*",
+            "UTIME_.*",
+            "[RWX]_OK",
+            "LOCK_.*",
+            "IN_.*",
+            "RWF_.*",
+            "IO_RING_.*",
+            "SYNC_FILE_RANGE_.*",
+            "ST_.*",
+            "PRIO_.*",
+            "SCHED_.*",
         ];
 
         #[derive(Debug)]
@@ -20,6 +20,59 @@ pub trait FileLike: Send + Sync {
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync>;
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
+
+    /// Reads into the buffers in order, until one of them is not filled.
+    fn read_vectored(&self, bufs: &mut [&mut [u8]]) -> LinuxResult<usize> {
+        let mut read_len = 0;
+        for buf in bufs.iter_mut() {
+            let n = match self.read(buf) {
+                Ok(n) => n,
+                Err(_) if read_len > 0 => break,
+                Err(e) => return Err(e),
+            };
+            read_len += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(read_len)
+    }
+
+    /// Writes the buffers in order, until one of them is not fully written.
+    fn write_vectored(&self, bufs: &[&[u8]]) -> LinuxResult<usize> {
+        let mut write_len = 0;
+        for buf in bufs {
+            let n = match self.write(buf) {
+                Ok(n) => n,
+                Err(_) if write_len > 0 => break,
+                Err(e) => return Err(e),
+            };
+            write_len += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(write_len)
+    }
+
+    /// Reads into the buffers at the given position, without moving the
+    /// cursor. Only regular files are seekable.
+    fn read_vectored_at(&self, _offset: u64, _bufs: &mut [&mut [u8]]) -> LinuxResult<usize> {
+        Err(LinuxError::ESPIPE)
+    }
+
+    /// Writes the buffers at the given position, without moving the cursor.
+    /// Only regular files are seekable.
+    fn write_vectored_at(&self, _offset: u64, _bufs: &[&[u8]]) -> LinuxResult<usize> {
+        Err(LinuxError::ESPIPE)
+    }
+
+    /// Synchronizes the file to the underlying device, only the data and the
+    /// metadata needed to read it back if `data_only`. Only files and
+    /// directories can be synchronized.
+    fn sync(&self, _data_only: bool) -> LinuxResult {
+        Err(LinuxError::EINVAL)
+    }
 }
 
 def_resource! {
@@ -38,11 +91,20 @@ pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
     Ok(FD_TABLE.write().add(f).map_err(|_| LinuxError::EMFILE)? as c_int)
 }
 
+/// Returns the identity of the fd table of the calling process, which owns
+/// the record locks taken through its descriptors, for all its threads.
+#[cfg(feature = "fs")]
+pub(crate) fn lock_owner() -> u64 {
+    &*FD_TABLE as *const _ as usize as u64
+}
+
 pub fn close_file_like(fd: c_int) -> LinuxResult {
     let f = FD_TABLE
         .write()
         .remove(fd as usize)
         .ok_or(LinuxError::EBADF)?;
+    #[cfg(feature = "fs")]
+    super::fs::release_record_locks(&f);
     drop(f);
     Ok(())
 }
@@ -115,6 +177,10 @@ pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
                 get_file_like(fd)?.set_nonblocking(arg & (ctypes::O_NONBLOCK as usize) > 0)?;
                 Ok(0)
             }
+            #[cfg(feature = "fs")]
+            ctypes::F_GETLK | ctypes::F_SETLK | ctypes::F_SETLKW => {
+                super::fs::fcntl_lock(fd, cmd as u32, arg)
+            }
             _ => {
                 warn!("unsupported fcntl parameters: cmd {}", cmd);
                 Ok(0)
@@ -123,6 +189,23 @@ pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
     })
 }
 
+/// Synchronize the data and metadata of a file to the storage device.
+///
+/// Return 0 if success.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, get_file_like(fd)?.sync(false).map(|_| 0))
+}
+
+/// Synchronize the data of a file to the storage device, and the metadata
+/// needed to read it back.
+///
+/// Return 0 if success.
+pub fn sys_fdatasync(fd: c_int) -> c_int {
+    debug!("sys_fdatasync <= {}", fd);
+    syscall_body!(sys_fdatasync, get_file_like(fd)?.sync(true).map(|_| 0))
+}
+
 #[ctor_bare::register_ctor]
 fn init_stdio() {
     let mut fd_table = flatten_objects::FlattenObjects::new();
@@ -1,8 +1,12 @@
-use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use alloc::{string::String, sync::Arc};
+use core::ffi::{c_char, c_int, c_uint, c_ulong, c_void};
+use core::mem::offset_of;
+use core::time::Duration;
 
-use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
+use axfs::fops::{
+    DirEntry, FileAttr, FileMeta, FilePerm, FileSystemStats, LockKind, OpenOptions, RecordLock,
+};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -42,21 +46,8 @@ impl FileLike for File {
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        let inner = self.inner.lock();
+        Ok(attr_to_stat(&inner.get_attr()?, &inner.get_meta()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -73,6 +64,152 @@ impl FileLike for File {
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn read_vectored(&self, bufs: &mut [&mut [u8]]) -> LinuxResult<usize> {
+        Ok(self.inner.lock().read_vectored(bufs)?)
+    }
+
+    fn write_vectored(&self, bufs: &[&[u8]]) -> LinuxResult<usize> {
+        Ok(self.inner.lock().write_vectored(bufs)?)
+    }
+
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [&mut [u8]]) -> LinuxResult<usize> {
+        Ok(self.inner.lock().read_vectored_at(offset, bufs)?)
+    }
+
+    fn write_vectored_at(&self, offset: u64, bufs: &[&[u8]]) -> LinuxResult<usize> {
+        Ok(self.inner.lock().write_vectored_at(offset, bufs)?)
+    }
+
+    fn sync(&self, data_only: bool) -> LinuxResult {
+        let inner = self.inner.lock();
+        if data_only {
+            Ok(inner.sync_data()?)
+        } else {
+            Ok(inner.sync_all()?)
+        }
+    }
+}
+
+/// An opened directory, which can be used as `dirfd` of the `*at` functions.
+pub struct Directory {
+    inner: Mutex<axfs::fops::Directory>,
+}
+
+impl Directory {
+    fn new(inner: axfs::fops::Directory) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    fn add_to_fd_table(self) -> LinuxResult<c_int> {
+        super::fd_ops::add_file_like(Arc::new(self))
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::ENOTDIR)
+    }
+}
+
+impl FileLike for Directory {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        let inner = self.inner.lock();
+        Ok(attr_to_stat(&inner.get_attr()?, &inner.get_meta()?))
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+
+    fn sync(&self, _data_only: bool) -> LinuxResult {
+        Ok(self.inner.lock().sync_all()?)
+    }
+}
+
+/// Returns the directory that relative `path` is looked up from, or `None`
+/// for the current directory (`AT_FDCWD`) and absolute paths.
+fn dir_at(dirfd: c_int, path: &str) -> LinuxResult<Option<Arc<Directory>>> {
+    if dirfd == ctypes::AT_FDCWD || path.starts_with('/') {
+        Ok(None)
+    } else {
+        Directory::from_fd(dirfd).map(Some)
+    }
+}
+
+/// Returns `path` relative to `dirfd` as a path that can be passed to
+/// [`axfs::api`]. The last component is not followed if it is a symbolic link.
+fn path_at(dirfd: c_int, path: &str) -> LinuxResult<String> {
+    if path.is_empty() {
+        return Err(LinuxError::ENOENT);
+    }
+    match dir_at(dirfd, path)? {
+        Some(dir) => Ok(dir.inner.lock().real_path_at(path, false)?),
+        None => Ok(path.into()),
+    }
+}
+
+/// Calls `file` or `dir` with the opened file or directory `fd`.
+fn with_fd<T>(
+    fd: c_int,
+    file: impl FnOnce(&axfs::fops::File) -> AxResult<T>,
+    dir: impl FnOnce(&axfs::fops::Directory) -> AxResult<T>,
+) -> LinuxResult<T> {
+    let f = get_file_like(fd)?.into_any();
+    if let Some(f) = f.downcast_ref::<File>() {
+        Ok(file(&f.inner.lock())?)
+    } else if let Some(d) = f.downcast_ref::<Directory>() {
+        Ok(dir(&d.inner.lock())?)
+    } else {
+        Err(LinuxError::EBADF)
+    }
+}
+
+fn attr_to_stat(attr: &FileAttr, meta: &FileMeta) -> ctypes::stat {
+    let ty = attr.file_type() as u8;
+    let perm = attr.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: meta.ino,
+        st_nlink: meta.nlink as _,
+        st_mode,
+        st_uid: meta.uid,
+        st_gid: meta.gid,
+        st_size: attr.size() as _,
+        st_blocks: attr.blocks() as _,
+        st_blksize: 512,
+        st_atime: meta.atime.into(),
+        st_mtime: meta.mtime.into(),
+        st_ctime: meta.ctime.into(),
+        ..Default::default()
+    }
+}
+
+/// Converts `owner` or `group` of `chown` to an ID, `-1` leaves it unchanged.
+fn owner_id(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
 }
 
 /// Convert open flags to [`OpenOptions`].
@@ -102,6 +239,38 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     options
 }
 
+/// Open `path` relative to `dir` (or the current directory if `None`), and
+/// insert it into the file descriptor table.
+///
+/// Directories are opened as [`Directory`], so that they can be used as
+/// `dirfd`.
+fn open_at(
+    dir: Option<&Directory>,
+    path: &str,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> LinuxResult<c_int> {
+    let options = flags_to_options(flags, mode);
+    let open_dir = || match dir {
+        Some(dir) => dir.inner.lock().open_dir_at(path, &options),
+        None => axfs::fops::Directory::open_dir(path, &options),
+    };
+    if flags as u32 & ctypes::O_DIRECTORY != 0 {
+        return Directory::new(open_dir()?).add_to_fd_table();
+    }
+
+    let file = match dir {
+        Some(dir) => dir.inner.lock().open_file_at(path, &options)?,
+        None => axfs::fops::File::open(path, &options)?,
+    };
+    if file.get_attr()?.is_dir() {
+        drop(file);
+        Directory::new(open_dir()?).add_to_fd_table()
+    } else {
+        File::new(file).add_to_fd_table()
+    }
+}
+
 /// Open a file by `filename` and insert it into the file descriptor table.
 ///
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
@@ -109,10 +278,29 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
     let filename = char_ptr_to_str(filename);
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
-    syscall_body!(sys_open, {
-        let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
-        File::new(file).add_to_fd_table()
+    syscall_body!(sys_open, open_at(None, filename?, flags, mode))
+}
+
+/// Open a file by `filename` relative to the directory `dirfd`, and insert it
+/// into the file descriptor table.
+///
+/// `filename` is relative to the current directory if `dirfd` is `AT_FDCWD`.
+///
+/// Return its index in the file table (`fd`).
+pub fn sys_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    let filename = char_ptr_to_str(filename);
+    debug!(
+        "sys_openat <= {} {:?} {:#o} {:#o}",
+        dirfd, filename, flags, mode
+    );
+    syscall_body!(sys_openat, {
+        let filename = filename?;
+        open_at(dir_at(dirfd, filename)?.as_deref(), filename, flags, mode)
     })
 }
 
@@ -128,6 +316,17 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
             2 => SeekFrom::End(offset as _),
             _ => return Err(LinuxError::EINVAL),
         };
+        if let Ok(dir) = Directory::from_fd(fd) {
+            // the position of a directory is the index of its next entry
+            let mut dir = dir.inner.lock();
+            let idx = match pos {
+                SeekFrom::Start(idx) => idx as usize,
+                SeekFrom::Current(0) => dir.entry_idx(),
+                _ => return Err(LinuxError::EINVAL),
+            };
+            dir.set_entry_idx(idx);
+            return Ok(idx as u64);
+        }
         let off = File::from_fd(fd)?.inner.lock().seek(pos)?;
         Ok(off)
     })
@@ -143,11 +342,7 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let mut options = OpenOptions::new();
-        options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
-        let st = File::new(file).stat()?;
-        unsafe { *buf = st };
+        unsafe { *buf = stat_at(ctypes::AT_FDCWD, path?, 0)? };
         Ok(0)
     })
 }
@@ -169,6 +364,8 @@ pub unsafe fn sys_fstat(fd: c_int, buf: *mut ctypes::stat) -> c_int {
 
 /// Get the metadata of the symbolic link and write into `buf`.
 ///
+/// It is the same as `sys_stat` if `path` is not a symbolic link.
+///
 /// Return 0 if success.
 pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::ssize_t {
     let path = char_ptr_to_str(path);
@@ -177,11 +374,637 @@ pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        unsafe { *buf = stat_at(ctypes::AT_FDCWD, path?, ctypes::AT_SYMLINK_NOFOLLOW)? };
+        Ok(0)
+    })
+}
+
+/// Get the metadata of `path` relative to `dirfd`, for `fstatat` and `statx`.
+///
+/// If `path` is empty and `flags` contains `AT_EMPTY_PATH`, it is the metadata
+/// of `dirfd` itself.
+fn stat_at(dirfd: c_int, path: &str, flags: u32) -> LinuxResult<ctypes::stat> {
+    if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 {
+        return if dirfd == ctypes::AT_FDCWD {
+            stat_at(dirfd, ".", flags & !ctypes::AT_EMPTY_PATH)
+        } else {
+            get_file_like(dirfd)?.stat()
+        };
+    }
+    let path = path_at(dirfd, path)?;
+    let meta = if flags & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+        axfs::api::symlink_metadata(&path)?
+    } else {
+        axfs::api::metadata(&path)?
+    };
+    let attr = FileAttr::new(
+        meta.permissions(),
+        meta.file_type(),
+        meta.size(),
+        meta.blocks(),
+    );
+    let file_meta = FileMeta {
+        uid: meta.uid(),
+        gid: meta.gid(),
+        atime: meta.accessed(),
+        mtime: meta.modified(),
+        ctime: meta.changed(),
+        ino: meta.ino(),
+        nlink: meta.nlink(),
+    };
+    Ok(attr_to_stat(&attr, &file_meta))
+}
+
+/// Get the metadata of `path` relative to the directory `dirfd` and write into
+/// `buf`.
+///
+/// `flags` can contain `AT_SYMLINK_NOFOLLOW` and `AT_EMPTY_PATH`.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_fstatat <= {} {:?} {:#x} {:#x}",
+        dirfd, path, buf as usize, flags
+    );
+    syscall_body!(sys_fstatat, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if flags as u32 & !(ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe { *buf = stat_at(dirfd, path?, flags as u32)? };
+        Ok(0)
+    })
+}
+
+/// Get the extended metadata of `path` relative to the directory `dirfd` and
+/// write into `buf`.
+///
+/// All the basic stats (`STATX_BASIC_STATS`) are returned regardless of
+/// `mask`. The `AT_STATX_*` synchronization flags are ignored.
+///
+/// Return 0 if success.
+pub unsafe fn sys_statx(
+    dirfd: c_int,
+    path: *const c_char,
+    flags: c_int,
+    mask: c_uint,
+    buf: *mut ctypes::statx,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_statx <= {} {:?} {:#x} {:#x} {:#x}",
+        dirfd, path, flags, mask, buf as usize
+    );
+    syscall_body!(sys_statx, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let supported = ctypes::AT_SYMLINK_NOFOLLOW
+            | ctypes::AT_EMPTY_PATH
+            | ctypes::AT_NO_AUTOMOUNT
+            | ctypes::AT_STATX_SYNC_TYPE;
+        if flags as u32 & !supported != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let st = stat_at(dirfd, path?, flags as u32)?;
+        let timestamp = |ts: ctypes::timespec| ctypes::statx_timestamp {
+            tv_sec: ts.tv_sec as _,
+            tv_nsec: ts.tv_nsec as _,
+            ..Default::default()
+        };
+        unsafe {
+            *buf = ctypes::statx {
+                stx_mask: ctypes::STATX_BASIC_STATS,
+                stx_blksize: st.st_blksize as _,
+                stx_nlink: st.st_nlink as _,
+                stx_uid: st.st_uid as _,
+                stx_gid: st.st_gid as _,
+                stx_mode: st.st_mode as _,
+                stx_ino: st.st_ino as _,
+                stx_size: st.st_size as _,
+                stx_blocks: st.st_blocks as _,
+                stx_atime: timestamp(st.st_atime),
+                stx_ctime: timestamp(st.st_ctime),
+                stx_mtime: timestamp(st.st_mtime),
+                ..Default::default()
+            }
+        };
+        Ok(0)
+    })
+}
+
+/// Change the access and modification times of `path` relative to the
+/// directory `dirfd`, or of `dirfd` itself if `path` is null.
+///
+/// `times` holds the access and the modification time, which are set to the
+/// current time if `times` is null. A time with `tv_nsec` being `UTIME_NOW` is
+/// set to the current time, and `UTIME_OMIT` leaves it unchanged. `flags` can
+/// contain `AT_SYMLINK_NOFOLLOW` and `AT_EMPTY_PATH`.
+///
+/// Return 0 if success.
+pub unsafe fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    debug!(
+        "sys_utimensat <= {} {:#x} {:#x} {:#x}",
+        dirfd, path as usize, times as usize, flags
+    );
+    syscall_body!(sys_utimensat, {
+        let flags = flags as u32;
+        if flags & !(ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let now = axhal::time::wall_time();
+        let time = |ts: ctypes::timespec| match ts.tv_nsec as u32 {
+            ctypes::UTIME_NOW => Ok(Some(now)),
+            ctypes::UTIME_OMIT => Ok(None),
+            _ if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) => {
+                Err(LinuxError::EINVAL)
+            }
+            _ => Ok(Some(Duration::from(ts))),
+        };
+        let (atime, mtime) = if times.is_null() {
+            (Some(now), Some(now))
+        } else {
+            let times = unsafe { core::slice::from_raw_parts(times, 2) };
+            (time(times[0])?, time(times[1])?)
+        };
+
+        // a null `path` refers to `dirfd` itself, as `futimens` does
+        let path = if path.is_null() {
+            None
+        } else {
+            Some(char_ptr_to_str(path)?)
+        };
+        let path = match path {
+            Some("") if flags & ctypes::AT_EMPTY_PATH != 0 && dirfd == ctypes::AT_FDCWD => {
+                Some(".")
+            }
+            Some("") if flags & ctypes::AT_EMPTY_PATH != 0 => None,
+            path => path,
+        };
+        let Some(path) = path else {
+            with_fd(
+                dirfd,
+                |f| f.set_times(atime, mtime),
+                |d| d.set_times(atime, mtime),
+            )?;
+            return Ok(0);
+        };
+        let mut file_times = axfs::api::FileTimes::new();
+        if let Some(atime) = atime {
+            file_times = file_times.set_accessed(atime);
+        }
+        if let Some(mtime) = mtime {
+            file_times = file_times.set_modified(mtime);
+        }
+        let path = path_at(dirfd, path)?;
+        if flags & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            axfs::api::set_symlink_times(&path, file_times)?;
+        } else {
+            axfs::api::set_times(&path, file_times)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Change the permissions of the opened file `fd` to `mode`.
+///
+/// Only the permission bits of `mode` are used.
+///
+/// Return 0 if success.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        let perm = FilePerm::from_bits_truncate(mode as _);
+        with_fd(fd, |f| f.set_perm(perm), |d| d.set_perm(perm))?;
+        Ok(0)
+    })
+}
+
+/// Change the permissions of `path` relative to the directory `dirfd` to
+/// `mode`.
+///
+/// `flags` can contain `AT_EMPTY_PATH`. Symbolic links have no permissions
+/// of their own, so `AT_SYMLINK_NOFOLLOW` is not supported.
+///
+/// Return 0 if success.
+pub fn sys_fchmodat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: ctypes::mode_t,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_fchmodat, {
+        let path = char_ptr_to_str(path)?;
+        debug!(
+            "sys_fchmodat <= {} {:?} {:#o} {:#x}",
+            dirfd, path, mode, flags
+        );
+        let flags = flags as u32;
+        if flags & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            return Err(LinuxError::EOPNOTSUPP);
+        } else if flags & !ctypes::AT_EMPTY_PATH != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let perm = FilePerm::from_bits_truncate(mode as _);
+        if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 && dirfd != ctypes::AT_FDCWD {
+            with_fd(dirfd, |f| f.set_perm(perm), |d| d.set_perm(perm))?;
+        } else {
+            axfs::api::set_permissions(&path_at(dirfd, path)?, perm)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the opened file `fd`.
+///
+/// `owner` or `group` being `-1` leaves it unchanged.
+///
+/// Return 0 if success.
+pub fn sys_fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    debug!("sys_fchown <= {} {} {}", fd, owner as i32, group as i32);
+    syscall_body!(sys_fchown, {
+        let (uid, gid) = (owner_id(owner), owner_id(group));
+        with_fd(fd, |f| f.set_owner(uid, gid), |d| d.set_owner(uid, gid))?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of `path` relative to the directory
+/// `dirfd`.
+///
+/// `owner` or `group` being `-1` leaves it unchanged. `flags` can contain
+/// `AT_SYMLINK_NOFOLLOW` and `AT_EMPTY_PATH`.
+///
+/// Return 0 if success.
+pub fn sys_fchownat(
+    dirfd: c_int,
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_fchownat, {
+        let path = char_ptr_to_str(path)?;
+        debug!(
+            "sys_fchownat <= {} {:?} {} {} {:#x}",
+            dirfd, path, owner as i32, group as i32, flags
+        );
+        let flags = flags as u32;
+        if flags & !(ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let (uid, gid) = (owner_id(owner), owner_id(group));
+        if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 && dirfd != ctypes::AT_FDCWD {
+            with_fd(dirfd, |f| f.set_owner(uid, gid), |d| d.set_owner(uid, gid))?;
+            return Ok(0);
+        }
+        let path = path_at(dirfd, path)?;
+        if flags & ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            axfs::api::lchown(&path, uid, gid)?;
+        } else {
+            axfs::api::chown(&path, uid, gid)?;
+        }
+        Ok(0)
+    })
+}
+
+/// Check whether the calling task can access `path` relative to the directory
+/// `dirfd`, with `mode` being `F_OK` or a mask of `R_OK`, `W_OK` and `X_OK`.
+///
+/// Permissions are checked against the owner bits, as all tasks run as the
+/// owner of all files.
+///
+/// Return 0 if the access is permitted, otherwise return -1.
+pub fn sys_faccessat(dirfd: c_int, path: *const c_char, mode: c_int, flags: c_int) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_faccessat <= {} {:?} {:#o} {:#x}",
+        dirfd, path, mode, flags
+    );
+    syscall_body!(sys_faccessat, {
+        let supported = ctypes::AT_EACCESS | ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH;
+        let mode = mode as u32;
+        if flags as u32 & !supported != 0
+            || mode & !(ctypes::R_OK | ctypes::W_OK | ctypes::X_OK) != 0
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let flags = flags as u32 & !ctypes::AT_EACCESS;
+        let st = stat_at(dirfd, path?, flags)?;
+        // `R_OK`, `W_OK` and `X_OK` are the owner bits shifted right by 6
+        if (st.st_mode >> 6) & mode != mode {
+            return Err(LinuxError::EACCES);
+        }
+        Ok(0)
+    })
+}
+
+/// Create a directory `path` relative to the directory `dirfd`.
+///
+/// `mode` is ignored for now.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_mkdirat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_mkdirat <= {} {:?} {:#o}", dirfd, path, mode);
+        match dir_at(dirfd, path)? {
+            Some(dir) => dir.inner.lock().create_dir(path)?,
+            None => axfs::api::create_dir(path)?,
+        }
+        Ok(0)
+    })
+}
+
+/// Read the entries of the directory `fd` into `dirp` as `struct dirent`s
+/// (`linux_dirent64`), as many as fit in `count` bytes.
+///
+/// Return the number of bytes read, or 0 at the end of the directory.
+pub unsafe fn sys_getdents64(fd: c_int, dirp: *mut c_void, count: usize) -> ctypes::ssize_t {
+    debug!("sys_getdents64 <= {} {:#x} {}", fd, dirp as usize, count);
+    syscall_body!(sys_getdents64, {
+        if dirp.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dir = Directory::from_fd(fd)?;
+        let mut dir = dir.inner.lock();
+        let buf = unsafe { core::slice::from_raw_parts_mut(dirp as *mut u8, count) };
+        let name_offset = offset_of!(ctypes::dirent, d_name);
+
+        let mut len = 0;
+        let mut entry = [DirEntry::default()];
+        loop {
+            let idx = dir.entry_idx();
+            if dir.read_dir(&mut entry)? == 0 {
+                break;
+            }
+            let name = entry[0].name_as_bytes();
+            // the entry may have been removed since read
+            let ino = core::str::from_utf8(name)
+                .ok()
+                .and_then(|name| dir.entry_ino(name).ok())
+                .unwrap_or(1);
+            let reclen = (name_offset + name.len() + 1).next_multiple_of(8);
+            if len + reclen > buf.len() {
+                // read it again next time
+                dir.set_entry_idx(idx);
+                if len == 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                break;
+            }
+
+            let rec = &mut buf[len..len + reclen];
+            rec.fill(0);
+            let mut put = |offset: usize, bytes: &[u8]| {
+                rec[offset..offset + bytes.len()].copy_from_slice(bytes)
+            };
+            put(offset_of!(ctypes::dirent, d_ino), &ino.to_ne_bytes());
+            put(
+                offset_of!(ctypes::dirent, d_off),
+                &(idx as i64 + 1).to_ne_bytes(),
+            );
+            put(
+                offset_of!(ctypes::dirent, d_reclen),
+                &(reclen as u16).to_ne_bytes(),
+            );
+            // `DT_*` are the same as the file types
+            put(
+                offset_of!(ctypes::dirent, d_type),
+                &[entry[0].entry_type() as u8],
+            );
+            put(name_offset, name);
+            len += reclen;
+        }
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Create a symbolic link `linkpath` which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    syscall_body!(sys_symlink, {
+        let target = char_ptr_to_str(target)?;
+        let linkpath = char_ptr_to_str(linkpath)?;
+        debug!(
+            "sys_symlink <= target: {:?}, linkpath: {:?}",
+            target, linkpath
+        );
+        axfs::api::soft_link(target, linkpath)?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link `path` into `buf`, which is not
+/// terminated by a null byte, and is truncated if `buf` is too small.
+///
+/// Return the number of bytes placed in `buf`.
+pub fn sys_readlink(path: *const c_char, buf: *mut c_char, bufsiz: usize) -> ctypes::ssize_t {
+    syscall_body!(sys_readlink, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_readlink <= {:?} {:#x} {}", path, buf as usize, bufsiz);
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if bufsiz == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let target = axfs::api::read_link(path)?;
+        let len = target.len().min(bufsiz);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len as ctypes::ssize_t)
+    })
+}
+
+/// Create a hard link `new` to the existing file `old`.
+///
+/// Only supported within a RAM filesystem, `EOPNOTSUPP` is returned for other
+/// filesystems or across mount points.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_link(old: *const c_char, new: *const c_char) -> c_int {
+    syscall_body!(sys_link, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!("sys_link <= old: {:?}, new: {:?}", old_path, new_path);
+        axfs::api::hard_link(old_path, new_path)?;
+        Ok(0)
+    })
+}
+
+/// Remove the directory entry `path`, or the empty directory if `flags`
+/// contains `AT_REMOVEDIR`. A symbolic link is removed itself.
+///
+/// `path` is relative to the directory `dirfd`, or the current directory if
+/// `dirfd` is `AT_FDCWD`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_unlinkat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_unlinkat <= {} {:?} {:#x}", dirfd, path, flags);
+        if flags as u32 & !ctypes::AT_REMOVEDIR != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let remove_dir = flags as u32 & ctypes::AT_REMOVEDIR != 0;
+        match (dir_at(dirfd, path)?, remove_dir) {
+            (Some(dir), true) => dir.inner.lock().remove_dir(path)?,
+            (Some(dir), false) => dir.inner.lock().remove_file(path)?,
+            (None, true) => axfs::api::remove_dir(path)?,
+            (None, false) => axfs::api::remove_file(path)?,
+        }
+        Ok(0)
+    })
+}
+
+/// Apply or remove an advisory lock on the whole opened file `fd`.
+///
+/// `operation` is `LOCK_SH`, `LOCK_EX` or `LOCK_UN`, optionally with
+/// `LOCK_NB` to fail with `EWOULDBLOCK` instead of waiting for a conflicting
+/// lock. The lock is released when all descriptors of the opened file are
+/// closed.
+///
+/// Return 0 if success.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let operation = operation as u32;
+        let kind = match operation & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => Some(LockKind::Shared),
+            ctypes::LOCK_EX => Some(LockKind::Exclusive),
+            ctypes::LOCK_UN => None,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let locker = with_fd(fd, |f| Ok(f.locker()), |d| Ok(d.locker()))?;
+        locker.flock(kind, operation & ctypes::LOCK_NB == 0)?;
         Ok(0)
     })
 }
 
+/// Handle the record lock commands `F_GETLK`, `F_SETLK` and `F_SETLKW` of
+/// `fcntl`, where `arg` points to a `struct flock`.
+///
+/// Locks are owned by the calling process, identified by its fd table, so all
+/// its threads share them. The `l_pid` of a conflicting lock is the identity
+/// of the fd table of its owner.
+pub(crate) fn fcntl_lock(fd: c_int, cmd: u32, arg: usize) -> LinuxResult<c_int> {
+    let flock = arg as *mut ctypes::flock;
+    if flock.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let req = unsafe { *flock };
+    let file = File::from_fd(fd).map_err(|_| LinuxError::EBADF)?;
+    let (locker, base) = {
+        let mut inner = file.inner.lock();
+        let base = match req.l_whence {
+            0 => 0,
+            1 => inner.seek(SeekFrom::Current(0))?,
+            2 => inner.get_attr()?.size(),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        (inner.locker(), base)
+    };
+
+    // `l_len` is 0 to lock to the end of the file, or negative to lock the
+    // bytes before `l_start`
+    let start = (base as i64)
+        .checked_add(req.l_start as i64)
+        .ok_or(LinuxError::EOVERFLOW)?;
+    let (start, end) = match req.l_len as i64 {
+        0 => (start, u64::MAX),
+        len if len > 0 => (
+            start,
+            start.checked_add(len).ok_or(LinuxError::EOVERFLOW)? as u64,
+        ),
+        len => {
+            if start < 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            (
+                start.checked_add(len).ok_or(LinuxError::EINVAL)?,
+                start as u64,
+            )
+        }
+    };
+    if start < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let start = start as u64;
+    let pid = super::fd_ops::lock_owner();
+    let kind = match req.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockKind::Shared),
+        ctypes::F_WRLCK => Some(LockKind::Exclusive),
+        ctypes::F_UNLCK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+
+    if cmd == ctypes::F_GETLK {
+        let kind = kind.ok_or(LinuxError::EINVAL)?;
+        let lock = RecordLock {
+            kind,
+            start,
+            end,
+            pid,
+        };
+        let res = unsafe { &mut *flock };
+        match locker.test_record(lock) {
+            Some(held) => {
+                res.l_type = match held.kind {
+                    LockKind::Shared => ctypes::F_RDLCK,
+                    LockKind::Exclusive => ctypes::F_WRLCK,
+                } as _;
+                res.l_whence = 0;
+                res.l_start = held.start as _;
+                res.l_len = if held.end == u64::MAX {
+                    0
+                } else {
+                    (held.end - held.start) as _
+                };
+                res.l_pid = held.pid as _;
+            }
+            None => res.l_type = ctypes::F_UNLCK as _,
+        }
+        return Ok(0);
+    }
+    match kind {
+        Some(kind) => {
+            let lock = RecordLock {
+                kind,
+                start,
+                end,
+                pid,
+            };
+            locker.lock_record(lock, cmd == ctypes::F_SETLKW)?;
+        }
+        None => locker.unlock_record(pid, start, end),
+    }
+    Ok(0)
+}
+
+/// Releases the record locks of the calling process on the file `f`, which
+/// POSIX requires when the process closes any descriptor of the file.
+pub(crate) fn release_record_locks(f: &Arc<dyn FileLike>) {
+    if let Ok(file) = f.clone().into_any().downcast::<File>() {
+        let locker = file.inner.lock().locker();
+        locker.release_records(super::fd_ops::lock_owner());
+    }
+}
+
 /// Get the path of the current directory.
 #[allow(clippy::unnecessary_cast)] // `c_char` is either `i8` or `u8`
 pub fn sys_getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
@@ -216,3 +1039,226 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         Ok(0)
     })
 }
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`.
+///
+/// `flags` can be `RENAME_NOREPLACE`, to fail with `EEXIST` instead of
+/// replacing `new`. `RENAME_EXCHANGE` and `RENAME_WHITEOUT` are not supported.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_renameat2(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_uint,
+) -> c_int {
+    syscall_body!(sys_renameat2, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!(
+            "sys_renameat2 <= old: {} {:?}, new: {} {:?}, flags: {:#x}",
+            olddirfd, old_path, newdirfd, new_path, flags
+        );
+        if flags & !ctypes::RENAME_NOREPLACE != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let old_path = path_at(olddirfd, old_path)?;
+        let new_path = path_at(newdirfd, new_path)?;
+        if flags & ctypes::RENAME_NOREPLACE != 0 && axfs::api::symlink_metadata(&new_path).is_ok() {
+            return Err(LinuxError::EEXIST);
+        }
+        axfs::api::rename(&old_path, &new_path)?;
+        Ok(0)
+    })
+}
+
+/// Mount the filesystem of type `fstype` on the directory `target`.
+///
+/// For virtual filesystems (e.g. `tmpfs`, `proc`) `source` is ignored, and
+/// `data` is a string of comma-separated options, which only `tmpfs` accepts
+/// (`size=` and `swap=`). Other types mount the on-disk filesystem of the
+/// block volume `source`, such as `/dev/disk1p2` or a GPT partition label,
+/// and ignore `data`.
+///
+/// Only `MS_SILENT`, `MS_NOATIME` and `MS_NODIRATIME` are accepted in `flags`,
+/// as access times are never updated. Other flags, such as `MS_RDONLY`, are
+/// not supported and `EINVAL` is returned.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    syscall_body!(sys_mount, {
+        let target = char_ptr_to_str(target)?;
+        let fstype = char_ptr_to_str(fstype)?;
+        let options = if data.is_null() {
+            ""
+        } else {
+            char_ptr_to_str(data as *const c_char)?
+        };
+        debug!(
+            "sys_mount <= source: {:?}, target: {:?}, fstype: {:?}, flags: {:#x}, data: {:?}",
+            char_ptr_to_str(source),
+            target,
+            fstype,
+            flags,
+            options
+        );
+        let supported = ctypes::MS_SILENT | ctypes::MS_NOATIME | ctypes::MS_NODIRATIME;
+        if flags & !(supported as c_ulong) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        match axfs::api::mount_virtual_with_options(fstype, target, options) {
+            Ok(()) => {}
+            // not a virtual filesystem, try the block volume `source`
+            Err(AxError::Unsupported) if !source.is_null() => {
+                axfs::api::mount_volume(char_ptr_to_str(source)?, target)?
+            }
+            Err(AxError::Unsupported) => return Err(LinuxError::ENODEV),
+            Err(e) => return Err(e.into()),
+        }
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted on the directory `target`.
+///
+/// `MNT_FORCE` and `MNT_DETACH` are accepted but a busy filesystem is never
+/// unmounted, `EBUSY` is returned instead.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_umount2(target: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_umount2, {
+        let target = char_ptr_to_str(target)?;
+        debug!("sys_umount2 <= target: {:?}, flags: {:#x}", target, flags);
+        let supported =
+            ctypes::MNT_FORCE | ctypes::MNT_DETACH | ctypes::MNT_EXPIRE | ctypes::UMOUNT_NOFOLLOW;
+        if flags as u32 & !supported != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        axfs::api::umount(target)?;
+        Ok(0)
+    })
+}
+
+/// Write all cached modifications of all filesystems to the storage devices.
+///
+/// Errors are only logged, as `sync` cannot fail. Return 0.
+pub fn sys_sync() -> c_int {
+    debug!("sys_sync");
+    if let Err(e) = axfs::api::sync() {
+        warn!("sys_sync: {:?}", e);
+    }
+    0
+}
+
+/// Write all cached modifications of the filesystem containing the opened
+/// file or directory `fd` to its storage device.
+///
+/// Return 0 if success.
+pub fn sys_syncfs(fd: c_int) -> c_int {
+    debug!("sys_syncfs <= {}", fd);
+    syscall_body!(sys_syncfs, {
+        with_fd(fd, |f| f.sync_fs(), |d| d.sync_fs())?;
+        Ok(0)
+    })
+}
+
+/// Write the modified pages of the `nbytes` bytes at `offset` of the file
+/// `fd` back to the filesystem, until the end of the file if `nbytes` is 0.
+///
+/// As on Linux, neither the metadata nor the write cache of the device is
+/// synchronized, use `fdatasync` for durability. Writes are synchronous, so
+/// `SYNC_FILE_RANGE_WAIT_BEFORE` and `SYNC_FILE_RANGE_WAIT_AFTER` have nothing
+/// to wait for.
+///
+/// Return 0 if success.
+pub fn sys_sync_file_range(
+    fd: c_int,
+    offset: ctypes::off_t,
+    nbytes: ctypes::off_t,
+    flags: c_uint,
+) -> c_int {
+    debug!(
+        "sys_sync_file_range <= {} {} {} {:#x}",
+        fd, offset, nbytes, flags
+    );
+    syscall_body!(sys_sync_file_range, {
+        let supported = ctypes::SYNC_FILE_RANGE_WAIT_BEFORE
+            | ctypes::SYNC_FILE_RANGE_WRITE
+            | ctypes::SYNC_FILE_RANGE_WAIT_AFTER;
+        if flags & !supported != 0 || offset < 0 || nbytes < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let f = get_file_like(fd)?.into_any();
+        if let Some(f) = f.downcast_ref::<File>() {
+            if flags & ctypes::SYNC_FILE_RANGE_WRITE != 0 {
+                f.inner.lock().sync_range(offset as u64, nbytes as u64)?;
+            }
+        } else if f.downcast_ref::<Directory>().is_none() {
+            return Err(LinuxError::ESPIPE);
+        }
+        Ok(0)
+    })
+}
+
+fn stats_to_statfs(stats: &FileSystemStats) -> ctypes::statfs {
+    ctypes::statfs {
+        f_type: stats.magic as _,
+        f_bsize: stats.block_size as _,
+        f_blocks: stats.blocks,
+        f_bfree: stats.blocks_free,
+        f_bavail: stats.blocks_avail,
+        f_files: stats.files,
+        f_ffree: stats.files_free,
+        f_namelen: stats.name_max as _,
+        f_frsize: stats.block_size as _,
+        f_flags: if stats.read_only {
+            ctypes::ST_RDONLY as _
+        } else {
+            0
+        },
+        ..Default::default()
+    }
+}
+
+/// Get the statistics of the filesystem containing `path`, and write them
+/// into `buf`.
+///
+/// The free space and inodes are limited to the quota of the filesystem.
+///
+/// Return 0 if success.
+pub unsafe fn sys_statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_statfs <= {:?} {:#x}", path, buf as usize);
+    syscall_body!(sys_statfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let stats = axfs::api::statfs(path?)?;
+        unsafe { *buf = stats_to_statfs(&stats) };
+        Ok(0)
+    })
+}
+
+/// Get the statistics of the filesystem containing the opened file or
+/// directory `fd`, and write them into `buf`.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    debug!("sys_fstatfs <= {} {:#x}", fd, buf as usize);
+    syscall_body!(sys_fstatfs, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let stats = with_fd(fd, |f| f.statfs(), |d| d.statfs())?;
+        unsafe { *buf = stats_to_statfs(&stats) };
+        Ok(0)
+    })
+}
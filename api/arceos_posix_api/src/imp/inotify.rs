@@ -0,0 +1,219 @@
+use alloc::sync::Arc;
+use core::ffi::{c_char, c_int};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs::api::{Event, EventKind, EventMask, Watcher};
+use axio::PollState;
+use axsync::Mutex;
+
+use super::fd_ops::{FileLike, add_file_like, get_file_like};
+use crate::{ctypes, utils::char_ptr_to_str};
+
+/// The `IN_*` bits of the events reported by axfs.
+const EVENT_BITS: [(u32, EventKind); 10] = [
+    (ctypes::IN_MODIFY, EventKind::Modify),
+    (ctypes::IN_CLOSE_WRITE, EventKind::CloseWrite),
+    (ctypes::IN_MOVED_FROM, EventKind::MovedFrom),
+    (ctypes::IN_MOVED_TO, EventKind::MovedTo),
+    (ctypes::IN_CREATE, EventKind::Create),
+    (ctypes::IN_DELETE, EventKind::Delete),
+    (ctypes::IN_DELETE_SELF, EventKind::DeleteSelf),
+    (ctypes::IN_MOVE_SELF, EventKind::MoveSelf),
+    (ctypes::IN_IGNORED, EventKind::Ignored),
+    (ctypes::IN_Q_OVERFLOW, EventKind::Overflow),
+];
+
+/// An inotify instance, which reads the events of its watches as
+/// `struct inotify_event`s.
+pub struct Inotify {
+    watcher: Mutex<Watcher>,
+    nonblocking: AtomicBool,
+}
+
+impl Inotify {
+    fn new(nonblocking: bool) -> Self {
+        Self {
+            watcher: Mutex::new(Watcher::new()),
+            nonblocking: AtomicBool::new(nonblocking),
+        }
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+}
+
+/// Returns the length of the record of `event`, with its name padded with
+/// NULs to align the next record.
+fn event_len(event: &Event) -> usize {
+    let header = size_of::<ctypes::inotify_event>();
+    match &event.name {
+        Some(name) => header + (name.len() + 1).next_multiple_of(header),
+        None => header,
+    }
+}
+
+/// Writes the record of `event` to `buf`, which is at least as long.
+fn write_event(event: &Event, buf: &mut [u8]) {
+    let header = size_of::<ctypes::inotify_event>();
+    let len = event_len(event);
+    let mut mask = EVENT_BITS
+        .iter()
+        .find(|(_, kind)| *kind == event.kind)
+        .map_or(0, |(bit, _)| *bit);
+    if event.is_dir {
+        mask |= ctypes::IN_ISDIR;
+    }
+    let wd = event.wd.map_or(-1, |wd| wd as i32);
+    buf[0..4].copy_from_slice(&wd.to_ne_bytes());
+    buf[4..8].copy_from_slice(&mask.to_ne_bytes());
+    buf[8..12].copy_from_slice(&event.cookie.to_ne_bytes());
+    buf[12..16].copy_from_slice(&((len - header) as u32).to_ne_bytes());
+    buf[header..len].fill(0);
+    if let Some(name) = &event.name {
+        buf[header..header + name.len()].copy_from_slice(name.as_bytes());
+    }
+}
+
+impl FileLike for Inotify {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        loop {
+            let watcher = self.watcher.lock();
+            if !watcher.has_events() {
+                drop(watcher);
+                if self.nonblocking.load(Ordering::Relaxed) {
+                    return Err(LinuxError::EAGAIN);
+                }
+                crate::sys_sched_yield(); // TODO: use synconize primitive
+                continue;
+            }
+            // read as many whole events as fit
+            let mut read_len = 0;
+            while let Some(event) = watcher.peek_event() {
+                let len = event_len(&event);
+                if read_len + len > buf.len() {
+                    break;
+                }
+                write_event(&event, &mut buf[read_len..]);
+                watcher.next_event();
+                read_len += len;
+            }
+            return if read_len == 0 {
+                Err(LinuxError::EINVAL)
+            } else {
+                Ok(read_len)
+            };
+        }
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode: 0o600, // anonymous inode, rw-------
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self.watcher.lock().has_events(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Creates a new inotify instance.
+///
+/// `flags` can be `IN_NONBLOCK` and `IN_CLOEXEC`. Return the file descriptor
+/// of the instance.
+pub fn sys_inotify_init1(flags: c_int) -> c_int {
+    debug!("sys_inotify_init1 <= {:#x}", flags);
+    syscall_body!(sys_inotify_init1, {
+        let flags = flags as u32;
+        if flags & !(ctypes::IN_NONBLOCK | ctypes::IN_CLOEXEC) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        add_file_like(Arc::new(Inotify::new(flags & ctypes::IN_NONBLOCK != 0)))
+    })
+}
+
+/// Adds a watch of the file or directory at `path` to the inotify instance
+/// `fd`, or changes the events of the existing one.
+///
+/// Only the events in `EVENT_BITS` are reported. `IN_ONESHOT` is not
+/// supported. Return the watch descriptor.
+pub fn sys_inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_inotify_add_watch <= {} {:?} {:#x}", fd, path, mask);
+    syscall_body!(sys_inotify_add_watch, {
+        let path = path?;
+        let inotify = Inotify::from_fd(fd)?;
+        if mask & ctypes::IN_ALL_EVENTS == 0
+            || mask & ctypes::IN_ONESHOT != 0
+            || (mask & ctypes::IN_MASK_ADD != 0 && mask & ctypes::IN_MASK_CREATE != 0)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let follow = mask & ctypes::IN_DONT_FOLLOW == 0;
+        if mask & ctypes::IN_ONLYDIR != 0 {
+            let meta = if follow {
+                axfs::api::metadata(path)?
+            } else {
+                axfs::api::symlink_metadata(path)?
+            };
+            if !meta.is_dir() {
+                return Err(LinuxError::ENOTDIR);
+            }
+        }
+
+        let mut events = EVENT_BITS
+            .iter()
+            .filter(|(bit, _)| mask & bit != 0)
+            .fold(EventMask::empty(), |events, (_, kind)| events.with(*kind));
+        let watcher = inotify.watcher.lock();
+        if let Some(wd) = watcher.find_watch(path, follow)? {
+            if mask & ctypes::IN_MASK_CREATE != 0 {
+                return Err(LinuxError::EEXIST);
+            }
+            if mask & ctypes::IN_MASK_ADD != 0 {
+                events = events.union(watcher.watch_mask(wd)?);
+            }
+        }
+        Ok(watcher.add_watch(path, events, follow)? as c_int)
+    })
+}
+
+/// Removes the watch `wd` from the inotify instance `fd`.
+///
+/// An `IN_IGNORED` event is read for it.
+pub fn sys_inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    debug!("sys_inotify_rm_watch <= {} {}", fd, wd);
+    syscall_body!(sys_inotify_rm_watch, {
+        let inotify = Inotify::from_fd(fd)?;
+        if wd < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        inotify.watcher.lock().remove_watch(wd as u32)?;
+        Ok(0)
+    })
+}
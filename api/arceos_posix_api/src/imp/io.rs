@@ -2,32 +2,40 @@ use crate::ctypes;
 use axerrno::{LinuxError, LinuxResult};
 use core::ffi::{c_int, c_void};
 
-#[cfg(feature = "fd")]
-use crate::imp::fd_ops::get_file_like;
 #[cfg(not(feature = "fd"))]
 use axio::prelude::*;
+#[cfg(feature = "fd")]
+use {
+    crate::imp::fd_ops::{FileLike, get_file_like},
+    alloc::vec::Vec,
+};
+
+/// The maximum number of I/O vectors of a call.
+const IOV_MAX: c_int = 1024;
+
+fn read_impl(fd: c_int, buf: *mut c_void, count: usize) -> LinuxResult<ctypes::ssize_t> {
+    if buf.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
+    #[cfg(feature = "fd")]
+    {
+        Ok(get_file_like(fd)?.read(dst)? as ctypes::ssize_t)
+    }
+    #[cfg(not(feature = "fd"))]
+    match fd {
+        0 => Ok(super::stdio::stdin().read(dst)? as ctypes::ssize_t),
+        1 | 2 => Err(LinuxError::EPERM),
+        _ => Err(LinuxError::EBADF),
+    }
+}
 
 /// Read data from the file indicated by `fd`.
 ///
 /// Return the read size if success.
 pub fn sys_read(fd: c_int, buf: *mut c_void, count: usize) -> ctypes::ssize_t {
     debug!("sys_read <= {} {:#x} {}", fd, buf as usize, count);
-    syscall_body!(sys_read, {
-        if buf.is_null() {
-            return Err(LinuxError::EFAULT);
-        }
-        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
-        #[cfg(feature = "fd")]
-        {
-            Ok(get_file_like(fd)?.read(dst)? as ctypes::ssize_t)
-        }
-        #[cfg(not(feature = "fd"))]
-        match fd {
-            0 => Ok(super::stdio::stdin().read(dst)? as ctypes::ssize_t),
-            1 | 2 => Err(LinuxError::EPERM),
-            _ => Err(LinuxError::EBADF),
-        }
-    })
+    syscall_body!(sys_read, read_impl(fd, buf, count))
 }
 
 fn write_impl(fd: c_int, buf: *const c_void, count: usize) -> LinuxResult<ctypes::ssize_t> {
@@ -55,31 +63,294 @@ pub fn sys_write(fd: c_int, buf: *const c_void, count: usize) -> ctypes::ssize_t
     syscall_body!(sys_write, write_impl(fd, buf, count))
 }
 
+/// Returns the I/O vectors at `iov`.
+pub(crate) unsafe fn iovecs<'a>(
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+) -> LinuxResult<&'a [ctypes::iovec]> {
+    if !(0..=IOV_MAX).contains(&iocnt) {
+        return Err(LinuxError::EINVAL);
+    }
+    if iocnt == 0 {
+        return Ok(&[]);
+    }
+    if iov.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(iov, iocnt as usize) })
+}
+
+/// Returns the buffers described by the I/O vectors.
+#[cfg(feature = "fd")]
+pub(crate) unsafe fn iovec_bufs<'a>(iovs: &[ctypes::iovec]) -> LinuxResult<Vec<&'a mut [u8]>> {
+    iovs.iter()
+        .map(|iov| {
+            if iov.iov_len == 0 {
+                Ok(Default::default())
+            } else if iov.iov_base.is_null() {
+                Err(LinuxError::EFAULT)
+            } else {
+                Ok(
+                    unsafe {
+                        core::slice::from_raw_parts_mut(iov.iov_base as *mut u8, iov.iov_len)
+                    },
+                )
+            }
+        })
+        .collect()
+}
+
+/// Calls `f` with each non-empty I/O vector, until it transfers less than the
+/// vector.
+#[cfg(not(feature = "fd"))]
+fn for_each_iovec(
+    iovs: &[ctypes::iovec],
+    mut f: impl FnMut(&ctypes::iovec) -> LinuxResult<ctypes::ssize_t>,
+) -> LinuxResult<ctypes::ssize_t> {
+    let mut ret = 0;
+    for iov in iovs.iter() {
+        if iov.iov_len == 0 {
+            continue;
+        }
+        let result = f(iov)?;
+        if result < 0 {
+            return Ok(result);
+        }
+        ret += result;
+
+        if result < iov.iov_len as isize {
+            break;
+        }
+    }
+    Ok(ret)
+}
+
+/// Read into a vector.
+pub unsafe fn sys_readv(fd: c_int, iov: *const ctypes::iovec, iocnt: c_int) -> ctypes::ssize_t {
+    debug!("sys_readv <= fd: {}", fd);
+    syscall_body!(sys_readv, {
+        let iovs = unsafe { iovecs(iov, iocnt)? };
+        #[cfg(feature = "fd")]
+        {
+            let mut bufs = unsafe { iovec_bufs(iovs)? };
+            Ok(get_file_like(fd)?.read_vectored(&mut bufs)? as ctypes::ssize_t)
+        }
+        #[cfg(not(feature = "fd"))]
+        for_each_iovec(iovs, |iov| read_impl(fd, iov.iov_base, iov.iov_len))
+    })
+}
+
 /// Write a vector.
 pub unsafe fn sys_writev(fd: c_int, iov: *const ctypes::iovec, iocnt: c_int) -> ctypes::ssize_t {
     debug!("sys_writev <= fd: {}", fd);
     syscall_body!(sys_writev, {
-        if !(0..=1024).contains(&iocnt) {
-            return Err(LinuxError::EINVAL);
+        let iovs = unsafe { iovecs(iov, iocnt)? };
+        #[cfg(feature = "fd")]
+        {
+            let bufs = unsafe { iovec_bufs(iovs)? };
+            let bufs = bufs.iter().map(|buf| &**buf).collect::<Vec<_>>();
+            Ok(get_file_like(fd)?.write_vectored(&bufs)? as ctypes::ssize_t)
         }
+        #[cfg(not(feature = "fd"))]
+        for_each_iovec(iovs, |iov| write_impl(fd, iov.iov_base, iov.iov_len))
+    })
+}
 
-        let iovs = unsafe { core::slice::from_raw_parts(iov, iocnt as usize) };
-        let mut ret = 0;
-        for iov in iovs.iter() {
-            if iov.iov_len == 0 {
-                continue;
-            }
-            let result = write_impl(fd, iov.iov_base, iov.iov_len)?;
-            if result < 0 {
-                return Ok(result);
-            }
-            ret += result;
+/// Converts the `offset` of a positional read or write, `-1` is the current
+/// position if `allow_current` is true.
+#[cfg(feature = "fd")]
+pub(crate) fn file_offset(offset: ctypes::off_t, allow_current: bool) -> LinuxResult<Option<u64>> {
+    match offset {
+        -1 if allow_current => Ok(None),
+        0.. => Ok(Some(offset as u64)),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
 
-            if result < iov.iov_len as isize {
-                break;
-            }
+/// Checks the `RWF_*` flags of `preadv2` and `pwritev2`, and returns whether
+/// the written data should be synchronized.
+#[cfg(feature = "fd")]
+fn check_rw_flags(flags: c_int) -> LinuxResult<bool> {
+    let flags = flags as u32;
+    let supported = ctypes::RWF_HIPRI | ctypes::RWF_DSYNC | ctypes::RWF_SYNC;
+    if flags & !supported != 0 {
+        // `RWF_NOWAIT` and `RWF_APPEND` are not supported yet
+        return Err(LinuxError::EOPNOTSUPP);
+    }
+    Ok(flags & (ctypes::RWF_DSYNC | ctypes::RWF_SYNC) != 0)
+}
+
+/// Reads `file` into `bufs` at `offset`, or at the current position if it is
+/// `None`.
+#[cfg(feature = "fd")]
+pub(crate) fn preadv_impl(
+    file: &dyn FileLike,
+    bufs: &mut [&mut [u8]],
+    offset: Option<u64>,
+    flags: c_int,
+) -> LinuxResult<usize> {
+    check_rw_flags(flags)?;
+    match offset {
+        Some(offset) => file.read_vectored_at(offset, bufs),
+        None => file.read_vectored(bufs),
+    }
+}
+
+/// Writes `bufs` to `file` at `offset`, or at the current position if it is
+/// `None`.
+#[cfg(feature = "fd")]
+pub(crate) fn pwritev_impl(
+    file: &dyn FileLike,
+    bufs: &[&[u8]],
+    offset: Option<u64>,
+    flags: c_int,
+) -> LinuxResult<usize> {
+    let sync = check_rw_flags(flags)?;
+    let write_len = match offset {
+        Some(offset) => file.write_vectored_at(offset, bufs)?,
+        None => file.write_vectored(bufs)?,
+    };
+    if sync {
+        match file.sync(flags as u32 & ctypes::RWF_SYNC == 0) {
+            // other files, e.g. pipes, have nothing to synchronize
+            Ok(()) | Err(LinuxError::EINVAL) => {}
+            Err(e) => return Err(e),
         }
+    }
+    Ok(write_len)
+}
+
+#[cfg(feature = "fd")]
+unsafe fn preadv_syscall(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: Option<u64>,
+    flags: c_int,
+) -> LinuxResult<ctypes::ssize_t> {
+    let mut bufs = unsafe { iovec_bufs(iovecs(iov, iocnt)?)? };
+    Ok(preadv_impl(&*get_file_like(fd)?, &mut bufs, offset, flags)? as ctypes::ssize_t)
+}
 
-        Ok(ret)
+#[cfg(feature = "fd")]
+unsafe fn pwritev_syscall(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: Option<u64>,
+    flags: c_int,
+) -> LinuxResult<ctypes::ssize_t> {
+    let bufs = unsafe { iovec_bufs(iovecs(iov, iocnt)?)? };
+    let bufs = bufs.iter().map(|buf| &**buf).collect::<Vec<_>>();
+    Ok(pwritev_impl(&*get_file_like(fd)?, &bufs, offset, flags)? as ctypes::ssize_t)
+}
+
+/// Read data from the file indicated by `fd` at the given offset, without
+/// changing the file offset.
+#[cfg(feature = "fd")]
+pub fn sys_pread64(
+    fd: c_int,
+    buf: *mut c_void,
+    count: usize,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_pread64 <= {} {:#x} {} {}",
+        fd, buf as usize, count, offset
+    );
+    let iov = ctypes::iovec {
+        iov_base: buf,
+        iov_len: count,
+    };
+    syscall_body!(sys_pread64, unsafe {
+        preadv_syscall(fd, &iov, 1, file_offset(offset, false)?, 0)
+    })
+}
+
+/// Write data to the file indicated by `fd` at the given offset, without
+/// changing the file offset.
+#[cfg(feature = "fd")]
+pub fn sys_pwrite64(
+    fd: c_int,
+    buf: *const c_void,
+    count: usize,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_pwrite64 <= {} {:#x} {} {}",
+        fd, buf as usize, count, offset
+    );
+    let iov = ctypes::iovec {
+        iov_base: buf as *mut c_void,
+        iov_len: count,
+    };
+    syscall_body!(sys_pwrite64, unsafe {
+        pwritev_syscall(fd, &iov, 1, file_offset(offset, false)?, 0)
+    })
+}
+
+/// Read into a vector at the given offset, without changing the file offset.
+#[cfg(feature = "fd")]
+pub unsafe fn sys_preadv(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    debug!("sys_preadv <= fd: {} offset: {}", fd, offset);
+    syscall_body!(sys_preadv, unsafe {
+        preadv_syscall(fd, iov, iocnt, file_offset(offset, false)?, 0)
+    })
+}
+
+/// Write a vector at the given offset, without changing the file offset.
+#[cfg(feature = "fd")]
+pub unsafe fn sys_pwritev(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    debug!("sys_pwritev <= fd: {} offset: {}", fd, offset);
+    syscall_body!(sys_pwritev, unsafe {
+        pwritev_syscall(fd, iov, iocnt, file_offset(offset, false)?, 0)
+    })
+}
+
+/// Read into a vector at the given offset, or at the current file offset if
+/// it is `-1`, with the `RWF_*` flags.
+#[cfg(feature = "fd")]
+pub unsafe fn sys_preadv2(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_preadv2 <= fd: {} offset: {} flags: {:#x}",
+        fd, offset, flags
+    );
+    syscall_body!(sys_preadv2, unsafe {
+        preadv_syscall(fd, iov, iocnt, file_offset(offset, true)?, flags)
+    })
+}
+
+/// Write a vector at the given offset, or at the current file offset if it is
+/// `-1`, with the `RWF_*` flags.
+#[cfg(feature = "fd")]
+pub unsafe fn sys_pwritev2(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    debug!(
+        "sys_pwritev2 <= fd: {} offset: {} flags: {:#x}",
+        fd, offset, flags
+    );
+    syscall_body!(sys_pwritev2, unsafe {
+        pwritev_syscall(fd, iov, iocnt, file_offset(offset, true)?, flags)
     })
 }
@@ -0,0 +1,320 @@
+//! Asynchronous I/O rings, similar to `io_uring`.
+//!
+//! Requests are copied to the ring when submitted, and the file descriptors
+//! in them are resolved at that time. With the `multitask` feature, each ring
+//! has a worker task that runs the requests in order, so the submitter can go
+//! on with computation while the disk is accessed. Otherwise, requests are
+//! run when submitted. The buffers of a request must stay valid until its
+//! completion is reaped.
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::ffi::{c_int, c_uint, c_void};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axsync::spin::SpinNoIrq;
+#[cfg(feature = "multitask")]
+use axtask::WaitQueue;
+
+use super::fd_ops::{FileLike, add_file_like, get_file_like};
+use super::io::{file_offset, iovec_bufs, iovecs, preadv_impl, pwritev_impl};
+use crate::ctypes;
+
+/// A submitted request, with its file resolved.
+struct Request {
+    sqe: ctypes::io_ring_sqe,
+    file: LinuxResult<Arc<dyn FileLike>>,
+}
+
+struct RingState {
+    /// Requests not run yet by the worker.
+    #[cfg(feature = "multitask")]
+    pending: VecDeque<Request>,
+    /// Completions not reaped yet.
+    completions: VecDeque<ctypes::io_ring_cqe>,
+    /// Number of requests submitted, whose completions are not reaped yet.
+    in_flight: usize,
+    #[cfg(feature = "multitask")]
+    closed: bool,
+}
+
+struct RingInner {
+    entries: usize,
+    state: SpinNoIrq<RingState>,
+    #[cfg(feature = "multitask")]
+    submitted: WaitQueue,
+    #[cfg(feature = "multitask")]
+    completed: WaitQueue,
+}
+
+/// An I/O ring, which can have up to `entries` requests in flight.
+pub struct IoRing {
+    inner: Arc<RingInner>,
+    nonblocking: AtomicBool,
+}
+
+/// Runs `req`, and returns the number of bytes transferred.
+fn run_request(req: &Request) -> LinuxResult<usize> {
+    let sqe = &req.sqe;
+    let opcode = sqe.opcode as u32;
+    if opcode == ctypes::IO_RING_OP_NOP {
+        return Ok(0);
+    }
+    let file = &**req.file.as_ref().map_err(|e| *e)?;
+    if opcode == ctypes::IO_RING_OP_FSYNC {
+        let data_only = sqe.rw_flags as u32 & ctypes::IO_RING_FSYNC_DATASYNC != 0;
+        return file.sync(data_only).map(|_| 0);
+    }
+
+    let buf = ctypes::iovec {
+        iov_base: sqe.addr as *mut c_void,
+        iov_len: sqe.len as usize,
+    };
+    let iovs = match opcode {
+        ctypes::IO_RING_OP_READ | ctypes::IO_RING_OP_WRITE => core::slice::from_ref(&buf),
+        ctypes::IO_RING_OP_READV | ctypes::IO_RING_OP_WRITEV => unsafe {
+            iovecs(sqe.addr as *const ctypes::iovec, sqe.len as c_int)?
+        },
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let mut bufs = unsafe { iovec_bufs(iovs)? };
+    let offset = file_offset(sqe.off, true)?;
+    if matches!(opcode, ctypes::IO_RING_OP_READ | ctypes::IO_RING_OP_READV) {
+        preadv_impl(file, &mut bufs, offset, sqe.rw_flags)
+    } else {
+        let bufs = bufs
+            .iter()
+            .map(|buf| &**buf)
+            .collect::<alloc::vec::Vec<_>>();
+        pwritev_impl(file, &bufs, offset, sqe.rw_flags)
+    }
+}
+
+impl RingInner {
+    fn complete(&self, req: Request) {
+        let res = match run_request(&req) {
+            Ok(n) => n as i32,
+            Err(e) => -e.code(),
+        };
+        let cqe = ctypes::io_ring_cqe {
+            user_data: req.sqe.user_data,
+            res,
+            flags: 0,
+        };
+        drop(req);
+        self.state.lock().completions.push_back(cqe);
+        #[cfg(feature = "multitask")]
+        self.completed.notify_all(false);
+    }
+
+    /// Runs the submitted requests in order, until the ring is closed.
+    #[cfg(feature = "multitask")]
+    fn run_worker(&self) {
+        loop {
+            self.submitted.wait_until(|| {
+                let state = self.state.lock();
+                !state.pending.is_empty() || state.closed
+            });
+            let Some(req) = self.state.lock().pending.pop_front() else {
+                return;
+            };
+            self.complete(req);
+        }
+    }
+}
+
+impl IoRing {
+    fn new(entries: usize) -> Self {
+        let inner = Arc::new(RingInner {
+            entries,
+            state: SpinNoIrq::new(RingState {
+                #[cfg(feature = "multitask")]
+                pending: VecDeque::new(),
+                completions: VecDeque::new(),
+                in_flight: 0,
+                #[cfg(feature = "multitask")]
+                closed: false,
+            }),
+            #[cfg(feature = "multitask")]
+            submitted: WaitQueue::new(),
+            #[cfg(feature = "multitask")]
+            completed: WaitQueue::new(),
+        });
+        #[cfg(feature = "multitask")]
+        {
+            let ring = inner.clone();
+            axtask::spawn(move || ring.run_worker());
+        }
+        Self {
+            inner,
+            nonblocking: AtomicBool::new(false),
+        }
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        get_file_like(fd)?
+            .into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::EINVAL)
+    }
+
+    /// Submits as many of `sqes` as there is room for, and returns the
+    /// number of them submitted.
+    fn submit(&self, sqes: &[ctypes::io_ring_sqe]) -> LinuxResult<usize> {
+        let inner = &self.inner;
+        let mut state = inner.state.lock();
+        let count = sqes.len().min(inner.entries - state.in_flight);
+        if count == 0 && !sqes.is_empty() {
+            return Err(LinuxError::EBUSY);
+        }
+        state.in_flight += count;
+        drop(state);
+
+        for sqe in &sqes[..count] {
+            let file = if sqe.opcode as u32 == ctypes::IO_RING_OP_NOP {
+                Err(LinuxError::EBADF)
+            } else {
+                get_file_like(sqe.fd)
+            };
+            let req = Request { sqe: *sqe, file };
+            #[cfg(feature = "multitask")]
+            inner.state.lock().pending.push_back(req);
+            #[cfg(not(feature = "multitask"))]
+            inner.complete(req);
+        }
+        #[cfg(feature = "multitask")]
+        inner.submitted.notify_one(false);
+        Ok(count)
+    }
+
+    /// Waits for at least `min_complete` completions, or all the requests in
+    /// flight if fewer, and reaps up to `cqes.len()` of them.
+    fn wait(&self, cqes: &mut [ctypes::io_ring_cqe], min_complete: usize) -> LinuxResult<usize> {
+        let inner = &self.inner;
+        let min_complete = min_complete.min(cqes.len());
+        let ready =
+            |state: &RingState| state.completions.len() >= min_complete.min(state.in_flight);
+        if !ready(&inner.state.lock()) {
+            if self.nonblocking.load(Ordering::Relaxed) {
+                return Err(LinuxError::EAGAIN);
+            }
+            #[cfg(feature = "multitask")]
+            inner.completed.wait_until(|| ready(&inner.state.lock()));
+        }
+
+        let mut state = inner.state.lock();
+        let count = cqes.len().min(state.completions.len());
+        for (cqe, completion) in cqes.iter_mut().zip(state.completions.drain(..count)) {
+            *cqe = completion;
+        }
+        state.in_flight -= count;
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "multitask")]
+impl Drop for IoRing {
+    fn drop(&mut self) {
+        // stop the worker after the pending requests
+        self.inner.state.lock().closed = true;
+        self.inner.submitted.notify_one(false);
+    }
+}
+
+impl FileLike for IoRing {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(ctypes::stat {
+            st_ino: 1,
+            st_nlink: 1,
+            st_mode: 0o600, // anonymous inode, rw-------
+            st_blksize: 4096,
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        let state = self.inner.state.lock();
+        Ok(PollState {
+            readable: !state.completions.is_empty(),
+            writable: state.in_flight < self.inner.entries,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Create an I/O ring, which can have up to `entries` requests in flight.
+///
+/// Return the file descriptor of the ring if success.
+pub fn sys_io_ring_setup(entries: c_uint) -> c_int {
+    debug!("sys_io_ring_setup <= {}", entries);
+    syscall_body!(sys_io_ring_setup, {
+        if !(1..=ctypes::IO_RING_MAX_ENTRIES).contains(&entries) {
+            return Err(LinuxError::EINVAL);
+        }
+        add_file_like(Arc::new(IoRing::new(entries as usize)))
+    })
+}
+
+/// Submit `nr` requests in `sqes` to the I/O ring `ring`.
+///
+/// Return the number of requests submitted, which is less than `nr` if the
+/// ring is full.
+pub unsafe fn sys_io_ring_submit(
+    ring: c_int,
+    sqes: *const ctypes::io_ring_sqe,
+    nr: c_uint,
+) -> c_int {
+    debug!("sys_io_ring_submit <= {} {}", ring, nr);
+    syscall_body!(sys_io_ring_submit, {
+        let ring = IoRing::from_fd(ring)?;
+        if nr == 0 {
+            return Ok(0);
+        }
+        if sqes.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let sqes = unsafe { core::slice::from_raw_parts(sqes, nr as usize) };
+        ring.submit(sqes)
+    })
+}
+
+/// Wait for at least `min_complete` requests of the I/O ring `ring` to
+/// complete, and store up to `nr` completions in `cqes`.
+///
+/// Return the number of completions stored.
+pub unsafe fn sys_io_ring_wait(
+    ring: c_int,
+    cqes: *mut ctypes::io_ring_cqe,
+    nr: c_uint,
+    min_complete: c_uint,
+) -> c_int {
+    debug!("sys_io_ring_wait <= {} {} {}", ring, nr, min_complete);
+    syscall_body!(sys_io_ring_wait, {
+        let ring = IoRing::from_fd(ring)?;
+        if nr == 0 {
+            return Ok(0);
+        }
+        if cqes.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let cqes = unsafe { core::slice::from_raw_parts_mut(cqes, nr as usize) };
+        ring.wait(cqes, min_complete as usize)
+    })
+}
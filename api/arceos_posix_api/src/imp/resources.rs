@@ -2,6 +2,8 @@ use crate::ctypes;
 use axerrno::LinuxError;
 use core::ffi::c_int;
 
+use super::time::{CpuUsage, cpu_usage};
+
 /// Get resource limitations
 ///
 /// TODO: support more resource types
@@ -49,3 +51,30 @@ pub unsafe fn sys_setrlimit(resource: c_int, rlimits: *mut crate::ctypes::rlimit
         Ok(0)
     })
 }
+
+/// Get resource usage of the calling process or thread
+///
+/// Only the CPU time and context switches are reported, other fields are zero.
+pub unsafe fn sys_getrusage(who: c_int, usage: *mut ctypes::rusage) -> c_int {
+    debug!("sys_getrusage <= {} {:#x}", who, usage as usize);
+    syscall_body!(sys_getrusage, {
+        if usage.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let cpu_usage = match who {
+            w if w == ctypes::RUSAGE_SELF as c_int => cpu_usage(false),
+            w if w == ctypes::RUSAGE_THREAD as c_int => cpu_usage(true),
+            // There are no child processes.
+            w if w == ctypes::RUSAGE_CHILDREN as c_int => CpuUsage::default(),
+            _ => return Err(LinuxError::EINVAL),
+        };
+        unsafe {
+            *usage = core::mem::zeroed();
+            (*usage).ru_utime = cpu_usage.user.into();
+            (*usage).ru_stime = cpu_usage.system.into();
+            (*usage).ru_nvcsw = cpu_usage.nvcsw as _;
+            (*usage).ru_nivcsw = cpu_usage.nivcsw as _;
+        }
+        Ok(0)
+    })
+}
@@ -16,6 +16,8 @@ pub fn sys_sysconf(name: c_int) -> c_long {
             ctypes::_SC_PAGE_SIZE => Ok(PAGE_SIZE_4K),
             // Number of processors in use
             ctypes::_SC_NPROCESSORS_ONLN => Ok(axconfig::plat::CPU_NUM),
+            // Clock ticks per second, used by `times`
+            ctypes::_SC_CLK_TCK => Ok(super::time::CLK_TCK as usize),
             // Total physical pages
             ctypes::_SC_PHYS_PAGES => Ok(axhal::mem::total_ram_size() / PAGE_SIZE_4K),
             // Avaliable physical pages
@@ -1,5 +1,8 @@
 use core::ffi::c_int;
 
+#[cfg(feature = "multitask")]
+use {crate::ctypes, axerrno::LinuxError, axerrno::LinuxResult};
+
 /// Relinquish the CPU, and switches to another task.
 ///
 /// For single-threaded configuration (`multitask` feature is disabled), we just
@@ -38,3 +41,238 @@ pub fn sys_exit(exit_code: c_int) -> ! {
     #[cfg(not(feature = "multitask"))]
     axhal::power::system_off();
 }
+
+/// Returns the task of the thread `pid`, or the current task if `pid` is 0.
+#[cfg(feature = "multitask")]
+fn task_of(pid: c_int) -> LinuxResult<axtask::AxTaskRef> {
+    match pid {
+        0 => Ok(axtask::current().as_task_ref().clone()),
+        1.. => axtask::get_task(pid as u64).ok_or(LinuxError::ESRCH),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Set the CPU affinity of the thread `pid` (0 for the current thread) to the
+/// first `cpusetsize` bytes of `mask`.
+///
+/// CPUs that do not exist are ignored, `EINVAL` is returned if no CPU is left.
+/// A thread running on another CPU is moved when it yields or is preempted.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    mask: *const ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_setaffinity <= pid: {}, cpusetsize: {}, mask: {:#x}",
+        pid, cpusetsize, mask as usize
+    );
+    syscall_body!(sys_sched_setaffinity, {
+        if mask.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        // CPU `i` is bit `i % 8` of byte `i / 8` on little-endian targets
+        let bits = unsafe { core::slice::from_raw_parts(mask as *const u8, cpusetsize) };
+        let mut cpumask = axtask::AxCpuMask::new();
+        for cpu in 0..axconfig::plat::CPU_NUM.min(cpusetsize * 8) {
+            if bits[cpu / 8] & (1 << (cpu % 8)) != 0 {
+                cpumask.set(cpu, true);
+            }
+        }
+        if !axtask::set_task_affinity(&task_of(pid)?, cpumask) {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Get the CPU affinity of the thread `pid` (0 for the current thread) into
+/// the `cpusetsize` bytes of `mask`.
+///
+/// Return `EINVAL` if `mask` is too small to hold all CPUs.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_getaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    mask: *mut ctypes::cpu_set_t,
+) -> c_int {
+    debug!(
+        "sys_sched_getaffinity <= pid: {}, cpusetsize: {}, mask: {:#x}",
+        pid, cpusetsize, mask as usize
+    );
+    syscall_body!(sys_sched_getaffinity, {
+        if mask.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        if cpusetsize * 8 < axconfig::plat::CPU_NUM {
+            return Err(LinuxError::EINVAL);
+        }
+        let cpumask = task_of(pid)?.cpumask();
+        let bits = unsafe { core::slice::from_raw_parts_mut(mask as *mut u8, cpusetsize) };
+        bits.fill(0);
+        for cpu in 0..axconfig::plat::CPU_NUM {
+            if cpumask.get(cpu) {
+                bits[cpu / 8] |= 1 << (cpu % 8);
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Set the nice value of the thread `who` (0 for the current thread).
+///
+/// Only `PRIO_PROCESS` is supported for `which`, as each thread is a process.
+/// The value is clamped to `[-20, 19]`, and `EINVAL` is returned if the
+/// scheduler does not support priorities (only CFS does).
+#[cfg(feature = "multitask")]
+pub fn sys_setpriority(which: c_int, who: ctypes::id_t, prio: c_int) -> c_int {
+    debug!(
+        "sys_setpriority <= which: {}, who: {}, prio: {}",
+        which, who, prio
+    );
+    syscall_body!(sys_setpriority, {
+        if which as u32 != ctypes::PRIO_PROCESS {
+            return Err(LinuxError::EINVAL);
+        }
+        let task = task_of(who as c_int)?;
+        let prio = prio.clamp(ctypes::PRIO_MIN, ctypes::PRIO_MAX as c_int - 1);
+        if !axtask::set_task_priority(&task, prio as isize) {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Converts a POSIX scheduling policy and priority to [`axtask::SchedPolicy`].
+#[cfg(feature = "multitask")]
+fn sched_policy(policy: c_int, prio: c_int) -> LinuxResult<axtask::SchedPolicy> {
+    let rt_prio = || {
+        u8::try_from(prio)
+            .ok()
+            .filter(|prio| (axtask::RT_PRIO_MIN..=axtask::RT_PRIO_MAX).contains(prio))
+            .ok_or(LinuxError::EINVAL)
+    };
+    match policy as u32 {
+        ctypes::SCHED_OTHER if prio == 0 => Ok(axtask::SchedPolicy::Normal),
+        ctypes::SCHED_FIFO => Ok(axtask::SchedPolicy::Fifo(rt_prio()?)),
+        ctypes::SCHED_RR => Ok(axtask::SchedPolicy::RoundRobin(rt_prio()?)),
+        _ => Err(LinuxError::EINVAL),
+    }
+}
+
+/// Converts [`axtask::SchedPolicy`] to a POSIX scheduling policy.
+#[cfg(feature = "multitask")]
+fn posix_sched_policy(policy: axtask::SchedPolicy) -> u32 {
+    match policy {
+        axtask::SchedPolicy::Normal => ctypes::SCHED_OTHER,
+        axtask::SchedPolicy::Fifo(_) => ctypes::SCHED_FIFO,
+        axtask::SchedPolicy::RoundRobin(_) => ctypes::SCHED_RR,
+        axtask::SchedPolicy::Deadline { .. } => ctypes::SCHED_DEADLINE,
+    }
+}
+
+/// Set the scheduling policy and priority of the thread `pid` (0 for the
+/// current thread).
+///
+/// `SCHED_OTHER` (priority 0), `SCHED_FIFO` and `SCHED_RR` (priorities from
+/// 1 to 99) are supported. Real-time threads always run before others.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setscheduler(
+    pid: c_int,
+    policy: c_int,
+    param: *const ctypes::sched_param,
+) -> c_int {
+    debug!(
+        "sys_sched_setscheduler <= pid: {}, policy: {}, param: {:#x}",
+        pid, policy, param as usize
+    );
+    syscall_body!(sys_sched_setscheduler, {
+        if param.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let policy = sched_policy(policy, unsafe { (*param).sched_priority })?;
+        axtask::set_task_policy(&task_of(pid)?, policy);
+        Ok(0)
+    })
+}
+
+/// Get the scheduling policy of the thread `pid` (0 for the current thread).
+///
+/// Return `SCHED_DEADLINE` for deadline threads set by [`arceos_api`].
+#[cfg(feature = "multitask")]
+pub fn sys_sched_getscheduler(pid: c_int) -> c_int {
+    debug!("sys_sched_getscheduler <= pid: {}", pid);
+    syscall_body!(sys_sched_getscheduler, {
+        Ok(posix_sched_policy(task_of(pid)?.sched_policy()) as c_int)
+    })
+}
+
+/// Set the priority of the thread `pid` (0 for the current thread) in its
+/// current scheduling policy.
+///
+/// Return `EINVAL` for deadline threads, which have no priority.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_setparam(pid: c_int, param: *const ctypes::sched_param) -> c_int {
+    debug!(
+        "sys_sched_setparam <= pid: {}, param: {:#x}",
+        pid, param as usize
+    );
+    syscall_body!(sys_sched_setparam, {
+        if param.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let task = task_of(pid)?;
+        let policy = match task.sched_policy() {
+            axtask::SchedPolicy::Deadline { .. } => return Err(LinuxError::EINVAL),
+            policy => posix_sched_policy(policy),
+        };
+        let policy = sched_policy(policy as c_int, unsafe { (*param).sched_priority })?;
+        axtask::set_task_policy(&task, policy);
+        Ok(0)
+    })
+}
+
+/// Get the priority of the thread `pid` (0 for the current thread), which is
+/// 0 if it is not a real-time thread.
+#[cfg(feature = "multitask")]
+pub unsafe fn sys_sched_getparam(pid: c_int, param: *mut ctypes::sched_param) -> c_int {
+    debug!(
+        "sys_sched_getparam <= pid: {}, param: {:#x}",
+        pid, param as usize
+    );
+    syscall_body!(sys_sched_getparam, {
+        if param.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let prio = match task_of(pid)?.sched_policy() {
+            axtask::SchedPolicy::Fifo(prio) | axtask::SchedPolicy::RoundRobin(prio) => prio,
+            _ => 0,
+        };
+        unsafe { (*param).sched_priority = prio as c_int };
+        Ok(0)
+    })
+}
+
+/// Get the maximum priority of the scheduling policy.
+#[cfg(feature = "multitask")]
+pub fn sys_sched_get_priority_max(policy: c_int) -> c_int {
+    syscall_body!(sys_sched_get_priority_max, {
+        match policy as u32 {
+            ctypes::SCHED_FIFO | ctypes::SCHED_RR => Ok(axtask::RT_PRIO_MAX as c_int),
+            ctypes::SCHED_OTHER | ctypes::SCHED_DEADLINE => Ok(0),
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
+
+/// Get the minimum priority of the scheduling policy.
+#[cfg(feature = "multitask")]
+pub fn sys_sched_get_priority_min(policy: c_int) -> c_int {
+    syscall_body!(sys_sched_get_priority_min, {
+        match policy as u32 {
+            ctypes::SCHED_FIFO | ctypes::SCHED_RR => Ok(axtask::RT_PRIO_MIN as c_int),
+            ctypes::SCHED_OTHER | ctypes::SCHED_DEADLINE => Ok(0),
+            _ => Err(LinuxError::EINVAL),
+        }
+    })
+}
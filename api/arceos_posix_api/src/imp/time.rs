@@ -3,7 +3,49 @@ use core::ffi::{c_int, c_long};
 use core::time::Duration;
 
 use crate::ctypes;
-use crate::ctypes::{CLOCK_MONOTONIC, CLOCK_REALTIME};
+use crate::ctypes::{
+    CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_THREAD_CPUTIME_ID,
+};
+
+/// Number of clock ticks per second reported by [`sys_times`].
+pub(crate) const CLK_TCK: u64 = 100;
+
+/// CPU time and context switches of a thread or the whole process.
+#[derive(Default)]
+pub(crate) struct CpuUsage {
+    pub user: Duration,
+    pub system: Duration,
+    pub nvcsw: u64,
+    pub nivcsw: u64,
+}
+
+/// Get the CPU usage of the current thread, or of the whole process if
+/// `thread` is false.
+pub(crate) fn cpu_usage(thread: bool) -> CpuUsage {
+    #[cfg(feature = "multitask")]
+    {
+        let stats = if thread {
+            axtask::current().stats()
+        } else {
+            axtask::all_tasks_stats()
+        };
+        CpuUsage {
+            user: stats.user_time,
+            system: stats.system_time,
+            nvcsw: stats.voluntary_switches,
+            nivcsw: stats.involuntary_switches,
+        }
+    }
+    // The only thread has been running since booting.
+    #[cfg(not(feature = "multitask"))]
+    {
+        let _ = thread;
+        CpuUsage {
+            user: axhal::time::monotonic_time(),
+            ..Default::default()
+        }
+    }
+}
 
 impl From<ctypes::timespec> for Duration {
     fn from(ts: ctypes::timespec) -> Self {
@@ -44,6 +86,14 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
         let now = match clk as u32 {
             CLOCK_REALTIME => axhal::time::wall_time().into(),
             CLOCK_MONOTONIC => axhal::time::monotonic_time().into(),
+            CLOCK_PROCESS_CPUTIME_ID => {
+                let usage = cpu_usage(false);
+                (usage.user + usage.system).into()
+            }
+            CLOCK_THREAD_CPUTIME_ID => {
+                let usage = cpu_usage(true);
+                (usage.user + usage.system).into()
+            }
             _ => {
                 warn!("Called sys_clock_gettime for unsupported clock {}", clk);
                 return Err(LinuxError::EINVAL);
@@ -55,6 +105,29 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
     })
 }
 
+/// Get the CPU time of the process in clock ticks, and return the ticks
+/// elapsed since booting
+///
+/// There are no child processes, so their times are always zero.
+pub unsafe fn sys_times(buf: *mut ctypes::tms) -> ctypes::clock_t {
+    syscall_body!(sys_times, {
+        let ticks =
+            |d: Duration| (d.as_nanos() * CLK_TCK as u128 / 1_000_000_000) as ctypes::clock_t;
+        if !buf.is_null() {
+            let usage = cpu_usage(false);
+            unsafe {
+                *buf = ctypes::tms {
+                    tms_utime: ticks(usage.user),
+                    tms_stime: ticks(usage.system),
+                    tms_cutime: 0,
+                    tms_cstime: 0,
+                }
+            };
+        }
+        Ok(ticks(axhal::time::monotonic_time()))
+    })
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
@@ -30,20 +30,32 @@ pub mod config {
 #[allow(dead_code, non_snake_case, non_camel_case_types, non_upper_case_globals, clippy::upper_case_acronyms, missing_docs)]
 pub mod ctypes;
 
-pub use imp::io::{sys_read, sys_write, sys_writev};
-pub use imp::resources::{sys_getrlimit, sys_setrlimit};
+pub use imp::io::{sys_read, sys_readv, sys_write, sys_writev};
+pub use imp::resources::{sys_getrlimit, sys_getrusage, sys_setrlimit};
 pub use imp::sys::sys_sysconf;
 pub use imp::task::{sys_exit, sys_getpid, sys_sched_yield};
-pub use imp::time::{sys_clock_gettime, sys_nanosleep};
+pub use imp::time::{sys_clock_gettime, sys_nanosleep, sys_times};
 
 #[cfg(feature = "fd")]
-pub use imp::fd_ops::{sys_close, sys_dup, sys_dup2, sys_fcntl};
+pub use imp::fd_ops::{sys_close, sys_dup, sys_dup2, sys_fcntl, sys_fdatasync, sys_fsync};
 #[cfg(feature = "fs")]
-pub use imp::fs::{sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat};
+pub use imp::fs::{
+    sys_faccessat, sys_fchmod, sys_fchmodat, sys_fchown, sys_fchownat, sys_flock, sys_fstat,
+    sys_fstatat, sys_fstatfs, sys_getcwd, sys_getdents64, sys_link, sys_lseek, sys_lstat,
+    sys_mkdirat, sys_mount, sys_open, sys_openat, sys_readlink, sys_rename, sys_renameat2,
+    sys_stat, sys_statfs, sys_statx, sys_symlink, sys_sync, sys_sync_file_range, sys_syncfs,
+    sys_umount2, sys_unlinkat, sys_utimensat,
+};
+#[cfg(feature = "fs")]
+pub use imp::inotify::{sys_inotify_add_watch, sys_inotify_init1, sys_inotify_rm_watch};
+#[cfg(feature = "fd")]
+pub use imp::io::{sys_pread64, sys_preadv, sys_preadv2, sys_pwrite64, sys_pwritev, sys_pwritev2};
 #[cfg(feature = "select")]
 pub use imp::io_mpx::sys_select;
 #[cfg(feature = "epoll")]
 pub use imp::io_mpx::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
+#[cfg(feature = "fd")]
+pub use imp::io_ring::{sys_io_ring_setup, sys_io_ring_submit, sys_io_ring_wait};
 #[cfg(feature = "net")]
 pub use imp::net::{
     sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getpeername,
@@ -58,3 +70,9 @@ pub use imp::pthread::mutex::{
 };
 #[cfg(feature = "multitask")]
 pub use imp::pthread::{sys_pthread_create, sys_pthread_exit, sys_pthread_join, sys_pthread_self};
+#[cfg(feature = "multitask")]
+pub use imp::task::{
+    sys_sched_get_priority_max, sys_sched_get_priority_min, sys_sched_getaffinity,
+    sys_sched_getparam, sys_sched_getscheduler, sys_sched_setaffinity, sys_sched_setparam,
+    sys_sched_setscheduler, sys_setpriority,
+};
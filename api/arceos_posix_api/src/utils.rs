@@ -32,6 +32,8 @@ pub fn check_null_mut_ptr<T>(ptr: *mut T) -> LinuxResult {
 
 macro_rules! syscall_body {
     ($fn: ident, $($stmt: tt)*) => {{
+        #[cfg(feature = "multitask")]
+        let _guard = axtask::SystemTimeGuard::new();
         #[allow(clippy::redundant_closure_call)]
         let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
         match res {
@@ -49,6 +51,8 @@ macro_rules! syscall_body {
 
 macro_rules! syscall_body_no_debug {
     ($($stmt: tt)*) => {{
+        #[cfg(feature = "multitask")]
+        let _guard = axtask::SystemTimeGuard::new();
         #[allow(clippy::redundant_closure_call)]
         let res = (|| -> axerrno::LinuxResult<_> { $($stmt)* })();
         match res {
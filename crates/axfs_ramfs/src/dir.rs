@@ -0,0 +1,302 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{string::String, vec::Vec};
+
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsResult};
+use spin::Mutex;
+
+use super::file::{FileNode, SymlinkNode};
+use super::storage::{Storage, Usage};
+
+/// The directory node in the RAM filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct DirNode {
+    this: Weak<DirNode>,
+    ino: u64,
+    parent: Mutex<Weak<dyn VfsNodeOps>>,
+    children: Mutex<BTreeMap<String, VfsNodeRef>>,
+    storage: Arc<Storage>,
+}
+
+impl DirNode {
+    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>, storage: Arc<Storage>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            ino: storage.alloc_ino(),
+            parent: Mutex::new(parent.unwrap_or(Weak::<Self>::new())),
+            children: Mutex::new(BTreeMap::new()),
+            storage,
+        })
+    }
+
+    /// Returns the usage of the pages of the filesystem.
+    pub fn usage(&self) -> Usage {
+        self.storage.usage()
+    }
+
+    /// Returns the inode number, unique in the filesystem.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the number of hard links, which are its entry in the parent,
+    /// its `.`, and `..` of its subdirectories.
+    pub fn nlink(&self) -> u64 {
+        let children = self.children.lock();
+        let subdirs = children
+            .values()
+            .filter(|node| node.as_any().is::<DirNode>());
+        2 + subdirs.count() as u64
+    }
+
+    pub(super) fn set_parent(&self, parent: Option<&VfsNodeRef>) {
+        *self.parent.lock() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+    }
+
+    /// Returns a string list of all entries in this directory.
+    pub fn get_entries(&self) -> Vec<String> {
+        self.children.lock().keys().cloned().collect()
+    }
+
+    /// Checks whether a node with the given name exists in this directory.
+    pub fn exist(&self, name: &str) -> bool {
+        self.children.lock().contains_key(name)
+    }
+
+    /// Creates a new node with the given name and type in this directory.
+    pub fn create_node(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        if self.exist(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        let node: VfsNodeRef = match ty {
+            VfsNodeType::File => Arc::new(FileNode::new(&self.storage)),
+            VfsNodeType::Dir => Self::new(Some(self.this.clone()), self.storage.clone()),
+            VfsNodeType::SymLink => Arc::new(SymlinkNode::new(&self.storage)),
+            _ => return Err(VfsError::Unsupported),
+        };
+        self.children.lock().insert(name.into(), node);
+        Ok(())
+    }
+
+    /// Removes a node by the given name in this directory.
+    pub fn remove_node(&self, name: &str) -> VfsResult {
+        let mut children = self.children.lock();
+        let node = children.get(name).ok_or(VfsError::NotFound)?;
+        if node
+            .as_any()
+            .downcast_ref::<DirNode>()
+            .is_some_and(|dir| !dir.children.lock().is_empty())
+        {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        if let Some(file) = children.remove(name).as_ref().and_then(FileNode::of) {
+            file.add_link(-1);
+        }
+        Ok(())
+    }
+
+    /// Adds a hard link `name` to the existing `node` in this directory.
+    ///
+    /// The node must belong to the same filesystem. Directories can not be
+    /// linked.
+    pub fn link(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        if matches!(name, "" | "." | "..") || name.contains('/') {
+            return Err(VfsError::InvalidInput);
+        }
+        if node.get_attr()?.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        let file = FileNode::of(&node).ok_or(VfsError::InvalidInput)?;
+        let mut children = self.children.lock();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        file.add_link(1);
+        children.insert(name.into(), node);
+        Ok(())
+    }
+
+    /// Returns the directory containing the last component of `path`, along
+    /// with that component.
+    fn parent_dir_of<'a>(&self, path: &'a str) -> VfsResult<(Arc<DirNode>, &'a str)> {
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let path = path.trim_matches('/');
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir_path, name)) => (this.lookup(dir_path)?, name),
+            None => (this as VfsNodeRef, path),
+        };
+        if !dir.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        // `..` of the root is not in this filesystem
+        let dir = dir
+            .as_any()
+            .downcast_ref::<DirNode>()
+            .ok_or(VfsError::InvalidInput)?;
+        let dir = dir.this.upgrade().ok_or(VfsError::NotFound)?;
+        Ok((dir, name))
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new_dir(4096, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.lock().upgrade()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .lock()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let children = self.children.lock();
+        let mut children = children.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, node)) = children.next() {
+                        *ent = VfsDirEntry::new(name, node.get_attr()?.file_type());
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ramfs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => {
+                    let subdir = self
+                        .children
+                        .lock()
+                        .get(name)
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.create(rest, ty)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            self.create_node(name, ty)
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => {
+                    let subdir = self
+                        .children
+                        .lock()
+                        .get(name)
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.remove(rest)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::InvalidInput) // remove '.' or '..'
+        } else {
+            self.remove_node(name)
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at ramfs: {} -> {}", src_path, dst_path);
+        let (src_dir, src_name) = self.parent_dir_of(src_path)?;
+        let (dst_dir, dst_name) = self.parent_dir_of(dst_path)?;
+        if [src_name, dst_name]
+            .iter()
+            .any(|name| matches!(*name, "" | "." | ".."))
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        let node = src_dir
+            .children
+            .lock()
+            .get(src_name)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+
+        let moved_dir = node.as_any().downcast_ref::<DirNode>();
+        if let Some(moved_dir) = moved_dir {
+            // a directory can not be moved into itself
+            let mut ancestor = Some(dst_dir.clone() as VfsNodeRef);
+            while let Some(dir) = ancestor {
+                if core::ptr::addr_eq(Arc::as_ptr(&dir), moved_dir as *const DirNode) {
+                    return Err(VfsError::InvalidInput);
+                }
+                ancestor = dir.parent();
+            }
+        }
+        if let Some(old) = dst_dir.children.lock().get(dst_name) {
+            if Arc::ptr_eq(old, &node) {
+                return Ok(()); // links of the same file
+            }
+            let old_attr = old.get_attr()?;
+            if old_attr.is_dir() && moved_dir.is_none() {
+                return Err(VfsError::IsADirectory);
+            } else if !old_attr.is_dir() && moved_dir.is_some() {
+                return Err(VfsError::NotADirectory);
+            }
+        }
+        if dst_dir.exist(dst_name) {
+            dst_dir.remove_node(dst_name)?;
+        }
+
+        src_dir.children.lock().remove(src_name);
+        if let Some(moved_dir) = moved_dir {
+            moved_dir.set_parent(Some(&(dst_dir.clone() as VfsNodeRef)));
+        }
+        dst_dir.children.lock().insert(dst_name.into(), node);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
@@ -0,0 +1,131 @@
+use alloc::sync::Arc;
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::storage::{Content, PAGE_SIZE, Storage};
+
+/// The file node in the RAM filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct FileNode {
+    ino: u64,
+    /// Number of directory entries linking to it.
+    nlink: AtomicU64,
+    content: Arc<Content>,
+}
+
+impl FileNode {
+    pub(super) fn new(storage: &Arc<Storage>) -> Self {
+        Self {
+            ino: storage.alloc_ino(),
+            nlink: AtomicU64::new(1),
+            content: Content::new(storage),
+        }
+    }
+
+    /// Returns the file or symbolic link node `node`.
+    pub(super) fn of(node: &VfsNodeRef) -> Option<&Self> {
+        let node = node.as_any();
+        node.downcast_ref::<Self>()
+            .or_else(|| Some(&node.downcast_ref::<SymlinkNode>()?.0))
+    }
+
+    /// Returns the inode number, unique in the filesystem.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the number of hard links.
+    pub fn nlink(&self) -> u64 {
+        self.nlink.load(Ordering::Relaxed)
+    }
+
+    /// Counts a directory entry added (`1`) or removed (`-1`) for the node.
+    pub(super) fn add_link(&self, delta: i64) {
+        self.nlink
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                n.checked_add_signed(delta)
+            })
+            .ok();
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let blocks = self.content.num_pages() * (PAGE_SIZE / 512) as u64;
+        Ok(VfsNodeAttr::new_file(self.content.size(), blocks))
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.content.truncate(size)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.content.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.content.write_at(offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // nothing to write back
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The symbolic link node in the RAM filesystem, whose content is the path it
+/// links to.
+pub struct SymlinkNode(FileNode);
+
+impl SymlinkNode {
+    pub(super) fn new(storage: &Arc<Storage>) -> Self {
+        Self(FileNode::new(storage))
+    }
+
+    /// Returns the inode number, unique in the filesystem.
+    pub fn ino(&self) -> u64 {
+        self.0.ino()
+    }
+
+    /// Returns the number of hard links.
+    pub fn nlink(&self) -> u64 {
+        self.0.nlink()
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = self.0.get_attr()?;
+        let perm = VfsNodePerm::from_bits_truncate(0o777);
+        Ok(VfsNodeAttr::new(
+            perm,
+            VfsNodeType::SymLink,
+            attr.size(),
+            attr.blocks(),
+        ))
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.0.truncate(size)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.0.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.0.write_at(offset, buf)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,80 @@
+//! RAM filesystem used by [ArceOS](https://github.com/arceos-org/arceos), with
+//! symbolic and hard links.
+//!
+//! A symbolic link is created by [`VfsNodeOps::create`] with
+//! [`VfsNodeType::SymLink`](axfs_vfs::VfsNodeType::SymLink), and its target is
+//! written as its content. A hard link is created by [`DirNode::link`], which
+//! adds an existing node to the directory, so all links of a file share the
+//! same node.
+//!
+//! The content of files is kept in pages, which can be limited in number and
+//! spilled to a [`Swap`] volume when memory is low, see
+//! [`RamFileSystem::with_limits`].
+
+#![no_std]
+
+extern crate alloc;
+#[macro_use]
+extern crate log;
+
+mod dir;
+mod file;
+mod storage;
+
+pub use self::dir::DirNode;
+pub use self::file::{FileNode, SymlinkNode};
+pub use self::storage::{PAGE_SIZE, Swap, Usage};
+
+use alloc::{boxed::Box, sync::Arc};
+use axfs_vfs::{VfsNodeOps, VfsNodeRef, VfsOps, VfsResult};
+use spin::Mutex;
+
+use self::storage::Storage;
+
+/// A RAM filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct RamFileSystem {
+    /// The parent of the mount point, which the root only refers to weakly.
+    parent: Mutex<Option<VfsNodeRef>>,
+    root: Arc<DirNode>,
+}
+
+impl RamFileSystem {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::with_limits(None, None)
+    }
+
+    /// Create a new instance of at most `size` bytes of file contents, which
+    /// fails with [`StorageFull`](axfs_vfs::VfsError::StorageFull) beyond, and
+    /// spills cold pages to `swap` when memory is low.
+    pub fn with_limits(size: Option<u64>, swap: Option<Box<dyn Swap>>) -> Self {
+        Self {
+            parent: Mutex::new(None),
+            root: DirNode::new(None, Storage::new(size, swap)),
+        }
+    }
+
+    /// Returns the root directory node in [`Arc<DirNode>`](DirNode).
+    pub fn root_dir_node(&self) -> Arc<DirNode> {
+        self.root.clone()
+    }
+}
+
+impl Default for RamFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for RamFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        let parent = mount_point.parent();
+        self.root.set_parent(parent.as_ref());
+        *self.parent.lock() = parent;
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
@@ -0,0 +1,391 @@
+//! Pages of the files in the RAM filesystem, which can be spilled to a swap
+//! volume.
+//!
+//! The content of each file is kept in pages of [`PAGE_SIZE`] bytes, holes are
+//! not allocated. All pages of a filesystem are counted against its size
+//! limit, whether they are in memory or on the swap volume.
+//!
+//! When [`Swap::memory_low`] tells that memory is low, the least recently used
+//! pages are written to free slots of the swap volume before a new page is
+//! allocated. Pages on the swap volume are accessed in place while memory is
+//! low, and are read back into memory once it is not.
+
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axfs_vfs::{VfsError, VfsResult};
+use spin::Mutex;
+
+/// The size of the pages of file contents.
+pub const PAGE_SIZE: usize = 4096;
+
+/// Maximum number of pages spilled at a time.
+const SPILL_BATCH: usize = 16;
+
+/// A volume that pages are spilled to when memory is low.
+pub trait Swap: Send + Sync {
+    /// Returns the size of the volume in bytes.
+    fn size(&self) -> u64;
+
+    /// Reads `buf.len()` bytes at the byte offset `pos` of the volume.
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> VfsResult;
+
+    /// Writes `buf` at the byte offset `pos` of the volume.
+    fn write_at(&self, pos: u64, buf: &[u8]) -> VfsResult;
+
+    /// Whether memory is low, so that pages are spilled before new ones are
+    /// allocated.
+    fn memory_low(&self) -> bool;
+}
+
+/// The usage of the pages of a filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    /// Maximum number of pages, or `None` if not limited.
+    pub max_pages: Option<u64>,
+    /// Number of pages allocated, in memory or on the swap volume.
+    pub used_pages: u64,
+}
+
+/// The pages of all files of a filesystem.
+pub(crate) struct Storage {
+    /// Maximum number of pages, or `None` if not limited.
+    max_pages: Option<u64>,
+    used_pages: AtomicU64,
+    tick: AtomicU64,
+    swap: Option<SwapVolume>,
+    /// Contents that may have pages in memory, to be spilled.
+    contents: Mutex<Vec<Weak<Content>>>,
+    next_ino: AtomicU64,
+}
+
+/// Page-sized slots on the swap volume.
+struct SwapVolume {
+    volume: Box<dyn Swap>,
+    num_slots: u64,
+    slots: Mutex<Slots>,
+}
+
+struct Slots {
+    /// Slots below it have been used.
+    next: u64,
+    free: Vec<u64>,
+}
+
+enum Page {
+    Memory { data: Vec<u8>, tick: u64 },
+    Swapped(u64),
+}
+
+/// The content of a file, it releases its pages when dropped.
+pub(crate) struct Content {
+    storage: Arc<Storage>,
+    inner: Mutex<ContentInner>,
+}
+
+struct ContentInner {
+    size: u64,
+    pages: BTreeMap<u64, Page>,
+}
+
+impl SwapVolume {
+    fn alloc(&self) -> Option<u64> {
+        let mut slots = self.slots.lock();
+        slots.free.pop().or_else(|| {
+            (slots.next < self.num_slots).then(|| {
+                slots.next += 1;
+                slots.next - 1
+            })
+        })
+    }
+
+    fn free(&self, slot: u64) {
+        self.slots.lock().free.push(slot);
+    }
+
+    fn read(&self, slot: u64, offset: usize, buf: &mut [u8]) -> VfsResult {
+        let pos = slot * PAGE_SIZE as u64 + offset as u64;
+        self.volume.read_at(pos, buf)
+    }
+
+    fn write(&self, slot: u64, offset: usize, buf: &[u8]) -> VfsResult {
+        let pos = slot * PAGE_SIZE as u64 + offset as u64;
+        self.volume.write_at(pos, buf)
+    }
+}
+
+impl Storage {
+    /// Creates the storage of at most `max_size` bytes, which spills pages to
+    /// `swap` if given.
+    pub fn new(max_size: Option<u64>, swap: Option<Box<dyn Swap>>) -> Arc<Self> {
+        Arc::new(Self {
+            max_pages: max_size.map(|size| size / PAGE_SIZE as u64),
+            used_pages: AtomicU64::new(0),
+            tick: AtomicU64::new(0),
+            swap: swap.map(|volume| SwapVolume {
+                num_slots: volume.size() / PAGE_SIZE as u64,
+                volume,
+                slots: Mutex::new(Slots {
+                    next: 0,
+                    free: Vec::new(),
+                }),
+            }),
+            contents: Mutex::new(Vec::new()),
+            next_ino: AtomicU64::new(1),
+        })
+    }
+
+    /// Allocates an inode number for a new node, the root is the first.
+    pub fn alloc_ino(&self) -> u64 {
+        self.next_ino.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the usage of the storage, the number of files is not limited.
+    pub fn usage(&self) -> Usage {
+        Usage {
+            max_pages: self.max_pages,
+            used_pages: self.used_pages.load(Ordering::Relaxed),
+        }
+    }
+
+    fn memory_low(&self) -> bool {
+        self.swap
+            .as_ref()
+            .is_some_and(|swap| swap.volume.memory_low())
+    }
+
+    fn tick(&self) -> u64 {
+        self.tick.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn charge(&self) -> VfsResult {
+        self.used_pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                match self.max_pages {
+                    Some(max) if used >= max => None,
+                    _ => Some(used + 1),
+                }
+            })
+            .map(|_| ())
+            .map_err(|_| VfsError::StorageFull)
+    }
+
+    fn release(&self, page: Page) {
+        self.used_pages.fetch_sub(1, Ordering::Relaxed);
+        if let (Page::Swapped(slot), Some(swap)) = (page, &self.swap) {
+            swap.free(slot);
+        }
+    }
+
+    /// Allocates a zeroed page in memory.
+    fn alloc_page(&self) -> VfsResult<Vec<u8>> {
+        let mut data = Vec::new();
+        data.try_reserve_exact(PAGE_SIZE)
+            .map_err(|_| VfsError::NoMemory)?;
+        data.resize(PAGE_SIZE, 0);
+        Ok(data)
+    }
+
+    /// Spills the least recently used pages to the swap volume if memory is
+    /// low. Must not be called with any content locked.
+    fn spill(&self) {
+        let Some(swap) = &self.swap else {
+            return;
+        };
+        if !swap.volume.memory_low() {
+            return;
+        }
+        let contents = {
+            let mut contents = self.contents.lock();
+            contents.retain(|c| c.strong_count() > 0);
+            contents
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect::<Vec<_>>()
+        };
+        let mut victims = Vec::new();
+        for (i, content) in contents.iter().enumerate() {
+            for (&idx, page) in &content.inner.lock().pages {
+                if let Page::Memory { tick, .. } = page {
+                    victims.push((*tick, i, idx));
+                }
+            }
+        }
+        if victims.len() > SPILL_BATCH {
+            victims.select_nth_unstable(SPILL_BATCH);
+            victims.truncate(SPILL_BATCH);
+        }
+
+        for (_, i, idx) in victims {
+            let mut inner = contents[i].inner.lock();
+            let Some(Page::Memory { data, .. }) = inner.pages.get(&idx) else {
+                continue; // removed or spilled by others
+            };
+            let Some(slot) = swap.alloc() else {
+                return; // the swap volume is full
+            };
+            if let Err(e) = swap.write(slot, 0, data) {
+                warn!("ramfs: failed to spill a page: {:?}", e);
+                swap.free(slot);
+                return;
+            }
+            inner.pages.insert(idx, Page::Swapped(slot));
+        }
+    }
+}
+
+impl Content {
+    pub fn new(storage: &Arc<Storage>) -> Arc<Self> {
+        let content = Arc::new(Self {
+            storage: storage.clone(),
+            inner: Mutex::new(ContentInner {
+                size: 0,
+                pages: BTreeMap::new(),
+            }),
+        });
+        if storage.swap.is_some() {
+            let mut contents = storage.contents.lock();
+            if contents.len() == contents.capacity() {
+                // drop removed files before growing
+                contents.retain(|c| c.strong_count() > 0);
+            }
+            contents.push(Arc::downgrade(&content));
+        }
+        content
+    }
+
+    pub fn size(&self) -> u64 {
+        self.inner.lock().size
+    }
+
+    /// Returns the number of pages allocated.
+    pub fn num_pages(&self) -> u64 {
+        self.inner.lock().pages.len() as u64
+    }
+
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut inner = self.inner.lock();
+        if offset >= inner.size {
+            return Ok(0);
+        }
+        let len = buf.len().min((inner.size - offset) as usize);
+        let mut pos = 0;
+        while pos < len {
+            let file_pos = offset + pos as u64;
+            let page_off = file_pos as usize % PAGE_SIZE;
+            let count = (len - pos).min(PAGE_SIZE - page_off);
+            let dst = &mut buf[pos..pos + count];
+            match self.page(&mut inner, file_pos / PAGE_SIZE as u64)? {
+                Some(Page::Memory { data, .. }) => {
+                    dst.copy_from_slice(&data[page_off..page_off + count])
+                }
+                Some(Page::Swapped(slot)) => self.swap().read(*slot, page_off, dst)?,
+                None => dst.fill(0), // a hole
+            }
+            pos += count;
+        }
+        Ok(len)
+    }
+
+    /// Writes `buf` at `offset`, returns the number of bytes written, which is
+    /// less than `buf.len()` if the size limit is reached.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.storage.spill();
+        let mut inner = self.inner.lock();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let file_pos = offset + pos as u64;
+            let page_off = file_pos as usize % PAGE_SIZE;
+            let count = (buf.len() - pos).min(PAGE_SIZE - page_off);
+            let src = &buf[pos..pos + count];
+            let idx = file_pos / PAGE_SIZE as u64;
+            match self.page(&mut inner, idx)? {
+                Some(Page::Memory { data, .. }) => {
+                    data[page_off..page_off + count].copy_from_slice(src)
+                }
+                Some(Page::Swapped(slot)) => self.swap().write(*slot, page_off, src)?,
+                None => match self.new_page() {
+                    Ok(mut data) => {
+                        data[page_off..page_off + count].copy_from_slice(src);
+                        let tick = self.storage.tick();
+                        inner.pages.insert(idx, Page::Memory { data, tick });
+                    }
+                    Err(e) if pos == 0 => return Err(e),
+                    Err(_) => break,
+                },
+            }
+            pos += count;
+        }
+        inner.size = inner.size.max(offset + pos as u64);
+        Ok(pos)
+    }
+
+    pub fn truncate(&self, size: u64) -> VfsResult {
+        let mut inner = self.inner.lock();
+        if size < inner.size {
+            let removed = inner.pages.split_off(&size.div_ceil(PAGE_SIZE as u64));
+            for page in removed.into_values() {
+                self.storage.release(page);
+            }
+            // zero the rest of the last page, in case the file grows again
+            let page_off = size as usize % PAGE_SIZE;
+            if page_off != 0 {
+                match inner.pages.get_mut(&(size / PAGE_SIZE as u64)) {
+                    Some(Page::Memory { data, .. }) => data[page_off..].fill(0),
+                    Some(Page::Swapped(slot)) => {
+                        let zeros = [0; PAGE_SIZE];
+                        self.swap().write(*slot, page_off, &zeros[page_off..])?;
+                    }
+                    None => {}
+                }
+            }
+        }
+        inner.size = size;
+        Ok(())
+    }
+
+    /// Returns the page `idx`, read back from the swap volume if memory is
+    /// not low.
+    fn page<'a>(&self, inner: &'a mut ContentInner, idx: u64) -> VfsResult<Option<&'a mut Page>> {
+        if let Some(Page::Swapped(slot)) = inner.pages.get(&idx) {
+            let slot = *slot;
+            if !self.storage.memory_low() {
+                if let Ok(mut data) = self.storage.alloc_page() {
+                    let swap = self.swap();
+                    swap.read(slot, 0, &mut data)?;
+                    swap.free(slot);
+                    let tick = self.storage.tick();
+                    inner.pages.insert(idx, Page::Memory { data, tick });
+                }
+            }
+        }
+        if let Some(Page::Memory { tick, .. }) = inner.pages.get_mut(&idx) {
+            *tick = self.storage.tick();
+        }
+        Ok(inner.pages.get_mut(&idx))
+    }
+
+    /// Allocates a new page counted against the size limit.
+    fn new_page(&self) -> VfsResult<Vec<u8>> {
+        self.storage.charge()?;
+        self.storage.alloc_page().inspect_err(|_| {
+            self.storage.used_pages.fetch_sub(1, Ordering::Relaxed);
+        })
+    }
+
+    fn swap(&self) -> &SwapVolume {
+        // only swapped pages access the swap volume
+        self.storage.swap.as_ref().unwrap()
+    }
+}
+
+impl Drop for Content {
+    fn drop(&mut self) {
+        let pages = core::mem::take(&mut self.inner.get_mut().pages);
+        for page in pages.into_values() {
+            self.storage.release(page);
+        }
+    }
+}
@@ -5,7 +5,8 @@
 //! All detected devices are composed into a large struct [`AllDevices`]
 //! and returned by the [`init_drivers`] function. The upperlayer subsystems
 //! (e.g., the network stack) may unpack the struct to get the specified device
-//! driver they want.
+//! driver they want. What has been found can still be listed by [`devices`]
+//! afterwards, e.g. for sysfs.
 //!
 //! For each device category (i.e., net, block, display, etc.), an unified type
 //! is used to represent all devices in that category. Currently, there are 3
@@ -60,8 +61,6 @@
 
 #[macro_use]
 extern crate log;
-
-#[cfg(feature = "dyn")]
 extern crate alloc;
 
 #[macro_use]
@@ -80,6 +79,9 @@ mod ixgbe;
 
 pub mod prelude;
 
+use alloc::{string::String, vec::Vec};
+use lazyinit::LazyInit;
+
 #[allow(unused_imports)]
 use self::prelude::*;
 pub use self::structs::{AxDeviceContainer, AxDeviceEnum};
@@ -91,6 +93,42 @@ pub use self::structs::AxDisplayDevice;
 #[cfg(feature = "net")]
 pub use self::structs::AxNetDevice;
 
+static DEVICES: LazyInit<Vec<DeviceInfo>> = LazyInit::new();
+
+/// Where a device is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceLocation {
+    /// Not on a bus, e.g. a RAM disk.
+    Global,
+    /// A memory-mapped device at the physical address `base`.
+    Mmio {
+        /// The base physical address of the registers.
+        base: usize,
+        /// The size of the register region.
+        size: usize,
+    },
+    /// A PCI device function.
+    Pci {
+        /// The bus number.
+        bus: u8,
+        /// The device number on the bus.
+        device: u8,
+        /// The function number of the device.
+        function: u8,
+    },
+}
+
+/// A device found by [`init_drivers`].
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// The name of the driver.
+    pub name: String,
+    /// The category of the device.
+    pub device_type: DeviceType,
+    /// Where the device is found.
+    pub location: DeviceLocation,
+}
+
 /// A structure that contains all device drivers, organized by their category.
 #[derive(Default)]
 pub struct AllDevices {
@@ -103,6 +141,8 @@ pub struct AllDevices {
     /// All graphics device drivers.
     #[cfg(feature = "display")]
     pub display: AxDeviceContainer<AxDisplayDevice>,
+    /// Information of all devices, in the order of probing.
+    infos: Vec<DeviceInfo>,
 }
 
 impl AllDevices {
@@ -126,7 +166,7 @@ impl AllDevices {
                     dev.device_type(),
                     dev.device_name(),
                 );
-                self.add_device(dev);
+                self.add_device(dev, DeviceLocation::Global);
             }
         });
 
@@ -135,7 +175,12 @@ impl AllDevices {
 
     /// Adds one device into the corresponding container, according to its device category.
     #[allow(dead_code)]
-    fn add_device(&mut self, dev: AxDeviceEnum) {
+    fn add_device(&mut self, dev: AxDeviceEnum, location: DeviceLocation) {
+        self.infos.push(DeviceInfo {
+            name: dev.device_name().into(),
+            device_type: dev.device_type(),
+            location,
+        });
         match dev {
             #[cfg(feature = "net")]
             AxDeviceEnum::Net(dev) => self.net.push(dev),
@@ -180,5 +225,12 @@ pub fn init_drivers() -> AllDevices {
         }
     }
 
+    DEVICES.init_once(core::mem::take(&mut all_devs.infos));
     all_devs
 }
+
+/// Returns all devices found by [`init_drivers`], or an empty slice if it has
+/// not been called.
+pub fn devices() -> &'static [DeviceInfo] {
+    DEVICES.get().map_or(&[], Vec::as_slice)
+}
@@ -0,0 +1,10 @@
+fn main() {
+    println!("cargo:rerun-if-env-changed=AX_INITRAMFS");
+    println!("cargo::rustc-check-cfg=cfg(initramfs_image)");
+    if let Ok(path) = std::env::var("AX_INITRAMFS") {
+        if !path.is_empty() {
+            println!("cargo:rerun-if-changed={path}");
+            println!("cargo:rustc-cfg=initramfs_image");
+        }
+    }
+}
@@ -2,6 +2,8 @@
 
 # From https://github.com/rafalh/rust-fatfs/blob/master/scripts/create-test-img.sh
 
+set -e
+
 CUR_DIR=`dirname $0`
 
 echo $OUT_DIR
@@ -26,5 +28,32 @@ create_test_img() {
 	sudo umount mnt
 }
 
+create_ext4_img() {
+	local name=$1
+	local blkcount=$2
+	local root=$(mktemp -d)
+	for i in $(seq 1 1000); do
+	  echo "Rust is cool!" >>"$root/long.txt"
+	done
+	echo "Rust is cool!" >>"$root/short.txt"
+	mkdir -p "$root/very/long/path"
+	echo "Rust is cool!" >>"$root/very/long/path/test.txt"
+	mkdir -p "$root/very-long-dir-name"
+	echo "Rust is cool!" >>"$root/very-long-dir-name/very-long-file-name.txt"
+	# a sparse file, only the written blocks take space
+	rm -f "$name"
+	truncate -s $((blkcount * 1024)) "$name"
+	# small block groups to have more than one of them
+	mkfs.ext4 -F -b 1024 -g 1024 -L "Test!" -U 12345678-1234-5678-1234-567812345678 \
+		-E root_owner=$(id -u):$(id -g) -d "$root" "$name" $blkcount
+	rm -rf "$root"
+}
+
+# the ext4 image is created by the tests, which need no root
+if [ "$1" = "ext4" ]; then
+	create_ext4_img "${2:-$CUR_DIR/ext4.img}" 2560
+	exit
+fi
+
 create_test_img "$CUR_DIR/fat16.img" 2500 16
 create_test_img "$CUR_DIR/fat32.img" 34000 32
@@ -137,7 +137,11 @@ impl DirBuilder {
         if self.recursive {
             self.create_dir_all(path)
         } else {
-            crate::root::create_dir(None, path)
+            let abs_path = crate::root::real_path(path, false)?;
+            crate::statfs::create(&abs_path, || crate::root::create_dir(path))?;
+            crate::meta::created(&abs_path);
+            crate::notify::created(&abs_path, true);
+            Ok(())
         }
     }
 
@@ -1,5 +1,5 @@
 use axio::{Result, SeekFrom, prelude::*};
-use core::fmt;
+use core::{fmt, time::Duration};
 
 use crate::fops;
 
@@ -16,7 +16,18 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(fops::FileAttr);
+pub struct Metadata {
+    pub(super) attr: fops::FileAttr,
+    pub(super) meta: fops::FileMeta,
+}
+
+/// Representation of the various timestamps on a file, to be set by
+/// [`File::set_times`] or [`set_times`](super::set_times).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileTimes {
+    pub(super) accessed: Option<Duration>,
+    pub(super) modified: Option<Duration>,
+}
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -79,42 +90,108 @@ impl OpenOptions {
 impl Metadata {
     /// Returns the file type for this metadata.
     pub const fn file_type(&self) -> FileType {
-        self.0.file_type()
+        self.attr.file_type()
     }
 
     /// Returns `true` if this metadata is for a directory. The
     /// result is mutually exclusive to the result of
     /// [`Metadata::is_file`].
     pub const fn is_dir(&self) -> bool {
-        self.0.is_dir()
+        self.attr.is_dir()
     }
 
     /// Returns `true` if this metadata is for a regular file. The
     /// result is mutually exclusive to the result of
     /// [`Metadata::is_dir`].
     pub const fn is_file(&self) -> bool {
-        self.0.is_file()
+        self.attr.is_file()
+    }
+
+    /// Returns `true` if this metadata is for a symbolic link, which is only
+    /// possible for the one from [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        self.attr.file_type().is_symlink()
     }
 
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
-        self.0.size()
+        self.attr.size()
     }
 
     /// Returns the permissions of the file this metadata is for.
     pub const fn permissions(&self) -> Permissions {
-        self.0.perm()
+        self.attr.perm()
     }
 
     /// Returns the total size of this file in bytes.
     pub const fn size(&self) -> u64 {
-        self.0.size()
+        self.attr.size()
     }
 
     /// Returns the number of blocks allocated to the file, in 512-byte units.
     pub const fn blocks(&self) -> u64 {
-        self.0.blocks()
+        self.attr.blocks()
+    }
+
+    /// Returns the last access time, since the UNIX epoch.
+    pub const fn accessed(&self) -> Duration {
+        self.meta.atime
+    }
+
+    /// Returns the last modification time of the content, since the UNIX
+    /// epoch.
+    pub const fn modified(&self) -> Duration {
+        self.meta.mtime
+    }
+
+    /// Returns the last time the content or the metadata was changed, since
+    /// the UNIX epoch.
+    pub const fn changed(&self) -> Duration {
+        self.meta.ctime
+    }
+
+    /// Returns the user ID of the owner.
+    pub const fn uid(&self) -> u32 {
+        self.meta.uid
+    }
+
+    /// Returns the group ID of the owner.
+    pub const fn gid(&self) -> u32 {
+        self.meta.gid
+    }
+
+    /// Returns the inode number of the file.
+    pub const fn ino(&self) -> u64 {
+        self.meta.ino
+    }
+
+    /// Returns the number of hard links to the file.
+    pub const fn nlink(&self) -> u64 {
+        self.meta.nlink
+    }
+}
+
+impl FileTimes {
+    /// Creates a new `FileTimes` with no times set, which leaves them
+    /// unchanged.
+    pub const fn new() -> Self {
+        Self {
+            accessed: None,
+            modified: None,
+        }
+    }
+
+    /// Sets the last access time, since the UNIX epoch.
+    pub const fn set_accessed(mut self, t: Duration) -> Self {
+        self.accessed = Some(t);
+        self
+    }
+
+    /// Sets the last modification time, since the UNIX epoch.
+    pub const fn set_modified(mut self, t: Duration) -> Self {
+        self.modified = Some(t);
+        self
     }
 }
 
@@ -124,7 +201,11 @@ impl fmt::Debug for Metadata {
             .field("file_type", &self.file_type())
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
+            .field("is_symlink", &self.is_symlink())
             .field("permissions", &self.permissions())
+            .field("modified", &self.modified())
+            .field("uid", &self.uid())
+            .field("gid", &self.gid())
             .finish_non_exhaustive()
     }
 }
@@ -164,9 +245,39 @@ impl File {
         self.inner.truncate(size)
     }
 
+    /// Attempts to sync all OS-internal file content and metadata to disk.
+    pub fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+
+    /// Similar to [`sync_all`](Self::sync_all), except that it might not
+    /// synchronize file metadata to the filesystem.
+    pub fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
-        self.inner.get_attr().map(Metadata)
+        Ok(Metadata {
+            attr: self.inner.get_attr()?,
+            meta: self.inner.get_meta()?,
+        })
+    }
+
+    /// Changes the permissions on the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        self.inner.set_perm(perm)
+    }
+
+    /// Changes the timestamps of the underlying file.
+    pub fn set_times(&self, times: FileTimes) -> Result<()> {
+        self.inner.set_times(times.accessed, times.modified)
+    }
+
+    /// Changes the owner and the group of the underlying file, `None` leaves
+    /// it unchanged.
+    pub fn chown(&self, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.inner.set_owner(uid, gid)
     }
 }
 
@@ -4,20 +4,25 @@ mod dir;
 mod file;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
-pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use self::file::{File, FileTimes, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::notify::{Event, EventKind, EventMask, Watcher};
+pub use crate::statfs::{FileSystemStats, Quota};
 
-use alloc::{string::String, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use axio::{self as io, prelude::*};
 
+use crate::fops::VfsOps;
+use crate::meta::MetaNode;
+
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir<'_>> {
     ReadDir::new(path)
 }
 
 /// Returns the canonical, absolute form of a path with all intermediate
-/// components normalized.
+/// components normalized and symbolic links resolved.
 pub fn canonicalize(path: &str) -> io::Result<String> {
-    crate::root::absolute_path(path)
+    crate::root::real_path(path, true)
 }
 
 /// Returns the current working directory as a [`String`].
@@ -56,7 +61,79 @@ pub fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> io::Result<()> {
 /// Given a path, query the file system to get information about a file,
 /// directory, etc.
 pub fn metadata(path: &str) -> io::Result<Metadata> {
-    File::open(path)?.metadata()
+    with_meta(path, true, |meta| {
+        Ok(Metadata {
+            attr: meta.attr()?,
+            meta: meta.get(),
+        })
+    })
+}
+
+/// Query the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    with_meta(path, false, |meta| {
+        Ok(Metadata {
+            attr: meta.attr()?,
+            meta: meta.get(),
+        })
+    })
+}
+
+/// Changes the permissions found on a file or a directory.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    with_meta(path, true, |meta| meta.set_perm(perm))
+}
+
+/// Changes the timestamps of a file or a directory.
+pub fn set_times(path: &str, times: FileTimes) -> io::Result<()> {
+    with_meta(path, true, |meta| {
+        meta.set_times(times.accessed, times.modified)
+    })
+}
+
+/// Changes the timestamps of a file or a directory, without following
+/// symbolic links.
+pub fn set_symlink_times(path: &str, times: FileTimes) -> io::Result<()> {
+    with_meta(path, false, |meta| {
+        meta.set_times(times.accessed, times.modified)
+    })
+}
+
+/// Changes the owner and the group of a file or a directory, `None` leaves
+/// it unchanged.
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    with_meta(path, true, |meta| meta.set_owner(uid, gid))
+}
+
+/// Changes the owner and the group of a file or a directory, without
+/// following symbolic links.
+pub fn lchown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    with_meta(path, false, |meta| meta.set_owner(uid, gid))
+}
+
+/// Reads a symbolic link, returning the path it points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(path)
+}
+
+/// Creates a new symbolic link `link` which points to `original`.
+///
+/// `original` is not required to exist, and a relative one is resolved from
+/// the directory of `link` when the link is followed.
+pub fn soft_link(original: &str, link: &str) -> io::Result<()> {
+    let abs_path = crate::root::real_path(link, false)?;
+    crate::statfs::create(&abs_path, || crate::root::symlink(original, link))?;
+    crate::meta::created(&abs_path);
+    crate::notify::created(&abs_path, false);
+    Ok(())
+}
+
+/// Creates a new hard link `link` to the file `original`.
+///
+/// Both must be in the same filesystem, which supports hard links (e.g.
+/// ramfs).
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::link(original, link)
 }
 
 /// Creates a new, empty directory at the provided path.
@@ -72,12 +149,22 @@ pub fn create_dir_all(path: &str) -> io::Result<()> {
 
 /// Removes an empty directory.
 pub fn remove_dir(path: &str) -> io::Result<()> {
-    crate::root::remove_dir(None, path)
+    let abs_path = crate::root::real_path(path, false)?;
+    let node = crate::root::lookup_no_follow(&abs_path)?;
+    crate::statfs::remove(&abs_path, || crate::root::remove_dir(path))?;
+    crate::root::forget_removed(&abs_path, &node);
+    crate::notify::removed(&abs_path, true);
+    Ok(())
 }
 
 /// Removes a file from the filesystem.
 pub fn remove_file(path: &str) -> io::Result<()> {
-    crate::root::remove_file(None, path)
+    let abs_path = crate::root::real_path(path, false)?;
+    let node = crate::root::lookup_no_follow(&abs_path)?;
+    crate::statfs::remove(&abs_path, || crate::root::remove_file(path))?;
+    crate::root::forget_removed(&abs_path, &node);
+    crate::notify::removed(&abs_path, false);
+    Ok(())
 }
 
 /// Rename a file or directory to a new name.
@@ -87,3 +174,119 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
     crate::root::rename(old, new)
 }
+
+/// Writes all cached modifications of all filesystems back to their devices,
+/// and flushes the write caches of the devices, like `sync` on Linux.
+pub fn sync() -> io::Result<()> {
+    crate::root::sync(None)
+}
+
+/// Returns the statistics of the filesystem containing `path`, as `statfs`
+/// on Linux.
+///
+/// They are limited to the quota of the filesystem, if any.
+pub fn statfs(path: &str) -> io::Result<FileSystemStats> {
+    crate::root::mount_point_of(&crate::root::real_path(path, true)?).stats()
+}
+
+/// Sets the quota of the filesystem mounted at `path`, or removes it if
+/// `quota` is `None`.
+///
+/// The usage of the filesystem is counted from its statistics at this time,
+/// see [`statfs`]. Creating files or writing data beyond the quota fails with
+/// [`StorageFull`](io::Error::StorageFull).
+pub fn set_quota(path: &str, quota: Option<Quota>) -> io::Result<()> {
+    let path = crate::root::real_path(path, true)?;
+    let mount = crate::root::mount_point_of(&path);
+    if mount.path() != path {
+        return axerrno::ax_err!(InvalidInput, "not a mount point");
+    }
+    mount.set_quota(quota)
+}
+
+/// Mounts the filesystem `fs` at `path`.
+///
+/// The mount point is created in its parent filesystem if it does not exist.
+/// Filesystems can also be mounted under other mounted filesystems.
+///
+/// The type of `fs` is unknown, so it is listed as `none` in `/proc/mounts`.
+/// Use [`mount_virtual`] to mount the builtin virtual filesystems.
+pub fn mount(path: &str, fs: Arc<dyn VfsOps>) -> io::Result<()> {
+    crate::root::mount(path, fs, "none", "none")
+}
+
+/// Mounts a new instance of the virtual filesystem of type `fstype` at `path`,
+/// like `mount -t <fstype> <fstype> <path>` on Linux.
+///
+/// See [`new_filesystem`] for the supported types.
+pub fn mount_virtual(fstype: &str, path: &str) -> io::Result<()> {
+    mount_virtual_with_options(fstype, path, "")
+}
+
+/// Mounts a new instance of the virtual filesystem of type `fstype` at `path`
+/// with comma-separated `options`, like `mount -t <fstype> -o <options>` on
+/// Linux.
+///
+/// Only RAM filesystems take options, others fail with
+/// [`InvalidInput`](io::Error::InvalidInput) if `options` is not empty:
+///
+/// - `size=<n>[k|m|g|%]`: the maximum size of file contents, in bytes, KiB,
+///   MiB, GiB, or percent of the total memory. Writing beyond it fails with
+///   [`StorageFull`](io::Error::StorageFull).
+/// - `swap=<volume>`: spills the least recently used pages to the block
+///   volume (as in [`mount_volume`]) when memory is low. The content of the
+///   volume is overwritten, and it is busy until the filesystem is unmounted.
+pub fn mount_virtual_with_options(fstype: &str, path: &str, options: &str) -> io::Result<()> {
+    let (fs, fstype) = crate::mounts::new_by_type(fstype, options)?;
+    crate::root::mount(path, fs, fstype, fstype)
+}
+
+/// Unmounts the filesystem mounted at `path`.
+///
+/// It fails with [`ResourceBusy`](io::Error::ResourceBusy) if there are still
+/// opened files or directories in the filesystem, or other filesystems are
+/// mounted under it.
+pub fn umount(path: &str) -> io::Result<()> {
+    crate::root::umount(path)
+}
+
+/// Mounts the on-disk filesystem on a block volume at `path`.
+///
+/// The volume is given by its name, like `disk1` or `disk1p2` (optionally
+/// prefixed with `/dev/`), or its GPT partition label. A volume can only be
+/// mounted once at a time.
+pub fn mount_volume(volume: &str, path: &str) -> io::Result<()> {
+    let (fs, source, fstype) = crate::partition::open_volume(volume)?;
+    crate::root::mount(path, fs, &source, fstype)
+}
+
+/// Mounts the read-only tar image `image` in memory at `path`.
+#[cfg(all(feature = "tarfs", not(feature = "myfs")))]
+pub fn mount_tar_image(image: &'static [u8], path: &str) -> io::Result<()> {
+    let fs = crate::fs::tarfs::TarFileSystem::from_memory(image)?;
+    crate::root::mount(path, fs, "memory", "tar")
+}
+
+/// Creates a new instance of the virtual filesystem of the given type, which
+/// can be mounted by [`mount`].
+///
+/// Supported types are `ramfs` (or `tmpfs`), `devfs` (or `devtmpfs`), `proc`
+/// and `sysfs`, depending on the enabled cargo features.
+pub fn new_filesystem(fstype: &str) -> io::Result<Arc<dyn VfsOps>> {
+    Ok(crate::mounts::new_by_type(fstype, "")?.0)
+}
+
+/// Calls `f` with the metadata of the file at `path`, following symbolic
+/// links in the last component if `follow` is true.
+fn with_meta<T>(
+    path: &str,
+    follow: bool,
+    f: impl FnOnce(MetaNode<'_>) -> io::Result<T>,
+) -> io::Result<T> {
+    let (mount, abs_path, node) = crate::root::lookup_with_mount(path, follow)?;
+    f(MetaNode {
+        path: &abs_path,
+        node: &node?,
+        mount: &mount,
+    })
+}
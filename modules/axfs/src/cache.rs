@@ -0,0 +1,192 @@
+//! A write-back LRU cache of disk blocks.
+//!
+//! Each block device has its own cache shared by all [`Disk`]s (partitions)
+//! on it. Modified blocks are only written to the device when they are
+//! evicted or on [`BlockCache::sync`]. A miss right after the previous one
+//! is treated as a sequential access, and the following blocks are read ahead
+//! with the same device request.
+//!
+//! [`Disk`]: crate::dev::Disk
+
+use alloc::{boxed::Box, collections::BTreeMap, vec};
+use axdriver::prelude::*;
+use axsync::Mutex;
+
+const BLOCK_SIZE: usize = 512;
+
+/// Maximum number of cached blocks of each device.
+const CACHE_CAPACITY: usize = 1024;
+/// Maximum number of blocks read at once on sequential misses.
+const READ_AHEAD_BLOCKS: u64 = 16;
+
+struct CachedBlock {
+    data: Box<[u8; BLOCK_SIZE]>,
+    dirty: bool,
+    /// The time of the last access, the key in [`CacheInner::lru`].
+    tick: u64,
+}
+
+struct CacheInner {
+    dev: AxBlockDevice,
+    num_blocks: u64,
+    blocks: BTreeMap<u64, CachedBlock>,
+    /// Block IDs ordered by the time of the last access.
+    lru: BTreeMap<u64, u64>,
+    tick: u64,
+    /// The last block loaded by the previous miss.
+    last_loaded: Option<u64>,
+}
+
+/// A write-back LRU cache in front of a block device.
+pub(crate) struct BlockCache {
+    num_blocks: u64,
+    inner: Mutex<CacheInner>,
+}
+
+impl BlockCache {
+    pub fn new(dev: AxBlockDevice) -> Self {
+        assert_eq!(BLOCK_SIZE, dev.block_size());
+        let num_blocks = dev.num_blocks();
+        Self {
+            num_blocks,
+            inner: Mutex::new(CacheInner {
+                dev,
+                num_blocks,
+                blocks: BTreeMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+                last_loaded: None,
+            }),
+        }
+    }
+
+    /// The number of blocks of the underlying device.
+    pub const fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// Reads `buf.len()` bytes at `offset` within the block `block_id`.
+    pub fn read(&self, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+        let mut inner = self.inner.lock();
+        let block = inner.block(block_id, true)?;
+        buf.copy_from_slice(&block.data[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    /// Writes `buf` at `offset` within the block `block_id`.
+    ///
+    /// The block is not read from the device if it is overwritten entirely.
+    pub fn write(&self, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+        let mut inner = self.inner.lock();
+        let block = inner.block(block_id, buf.len() < BLOCK_SIZE)?;
+        block.data[offset..offset + buf.len()].copy_from_slice(buf);
+        block.dirty = true;
+        Ok(())
+    }
+
+    /// Writes all modified blocks back to the device, then flushes it.
+    pub fn sync(&self) -> DevResult {
+        let mut inner = self.inner.lock();
+        let CacheInner { dev, blocks, .. } = &mut *inner;
+        for (&block_id, block) in blocks.iter_mut().filter(|(_, b)| b.dirty) {
+            dev.write_block(block_id, &block.data[..])?;
+            block.dirty = false;
+        }
+        match dev.flush() {
+            Err(DevError::Unsupported) => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Reads the whole device, bypassing the cache, as it would be found
+    /// after a crash.
+    #[cfg(test)]
+    pub fn device_image(&self) -> alloc::vec::Vec<u8> {
+        let mut inner = self.inner.lock();
+        let mut image = vec![0; self.num_blocks as usize * BLOCK_SIZE];
+        inner.dev.read_block(0, &mut image).unwrap();
+        image
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync() {
+            warn!("failed to write back the block cache: {:?}", e);
+        }
+    }
+}
+
+impl CacheInner {
+    /// Returns the cached block `block_id`, it is loaded from the device on a
+    /// miss if `fill` is true, or zero-filled otherwise.
+    fn block(&mut self, block_id: u64, fill: bool) -> DevResult<&mut CachedBlock> {
+        if block_id >= self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        if !self.blocks.contains_key(&block_id) {
+            if fill {
+                self.load(block_id)?;
+            } else {
+                self.insert(block_id, Box::new([0; BLOCK_SIZE]))?;
+            }
+        }
+
+        self.tick += 1;
+        let block = self.blocks.get_mut(&block_id).unwrap();
+        self.lru.remove(&block.tick);
+        self.lru.insert(self.tick, block_id);
+        block.tick = self.tick;
+        Ok(block)
+    }
+
+    /// Loads the block `block_id`, and the following uncached blocks if the
+    /// access looks sequential.
+    fn load(&mut self, block_id: u64) -> DevResult {
+        let sequential = self.last_loaded.is_some_and(|last| last + 1 == block_id);
+        let max_count = if sequential {
+            READ_AHEAD_BLOCKS.min(self.num_blocks - block_id)
+        } else {
+            1
+        };
+        let count = (1..max_count)
+            .take_while(|i| !self.blocks.contains_key(&(block_id + i)))
+            .count() as u64
+            + 1;
+
+        let mut buf = vec![0; count as usize * BLOCK_SIZE];
+        self.dev.read_block(block_id, &mut buf)?;
+        for (id, data) in (block_id..).zip(buf.chunks_exact(BLOCK_SIZE)) {
+            self.insert(id, Box::new(data.try_into().unwrap()))?;
+        }
+        self.last_loaded = Some(block_id + count - 1);
+        Ok(())
+    }
+
+    fn insert(&mut self, block_id: u64, data: Box<[u8; BLOCK_SIZE]>) -> DevResult {
+        while self.blocks.len() >= CACHE_CAPACITY {
+            self.evict()?;
+        }
+        self.tick += 1;
+        self.lru.insert(self.tick, block_id);
+        let block = CachedBlock {
+            data,
+            dirty: false,
+            tick: self.tick,
+        };
+        self.blocks.insert(block_id, block);
+        Ok(())
+    }
+
+    /// Removes the least recently used block, writes it back if modified.
+    fn evict(&mut self) -> DevResult {
+        let (&tick, &block_id) = self.lru.first_key_value().unwrap();
+        let block = &self.blocks[&block_id];
+        if block.dirty {
+            self.dev.write_block(block_id, &block.data[..])?;
+        }
+        self.lru.remove(&tick);
+        self.blocks.remove(&block_id);
+        Ok(())
+    }
+}
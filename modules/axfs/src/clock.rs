@@ -0,0 +1,37 @@
+//! Clocks provided by the upper layers, so that filesystems do not depend on
+//! the platform for their times.
+
+use core::time::Duration;
+
+use lazyinit::LazyInit;
+
+struct Clocks {
+    wall: fn() -> Duration,
+    monotonic: fn() -> Duration,
+}
+
+static CLOCKS: LazyInit<Clocks> = LazyInit::new();
+
+/// Sets the clocks of filesystems: `wall` returns the time since the Unix
+/// epoch, and `monotonic` the time since boot.
+///
+/// It should be called once before [`init_filesystems`](crate::init_filesystems).
+/// Until then, both clocks read as zero.
+pub fn set_clocks(wall: fn() -> Duration, monotonic: fn() -> Duration) {
+    CLOCKS.init_once(Clocks { wall, monotonic });
+}
+
+/// The time since the Unix epoch, for the times of files.
+pub(crate) fn wall_time() -> Duration {
+    CLOCKS
+        .get()
+        .map_or(Duration::ZERO, |clocks| (clocks.wall)())
+}
+
+/// The time since boot.
+#[cfg(feature = "procfs")]
+pub(crate) fn monotonic_time() -> Duration {
+    CLOCKS
+        .get()
+        .map_or(Duration::ZERO, |clocks| (clocks.monotonic)())
+}
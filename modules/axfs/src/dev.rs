@@ -1,28 +1,62 @@
+use alloc::sync::Arc;
 use axdriver::prelude::*;
 
+use crate::cache::BlockCache;
+
 const BLOCK_SIZE: usize = 512;
 
 /// A disk device with a cursor.
+///
+/// A disk can be either a whole block device, or a partition on it. In the
+/// latter case, block IDs are relative to the beginning of the partition, and
+/// accesses beyond the end of the partition are rejected. All accesses go
+/// through the block cache of the device, call [`Disk::sync`] to write the
+/// modifications back.
+#[derive(Clone)]
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    start_block: u64,
+    num_blocks: u64,
+    cache: Arc<BlockCache>,
 }
 
 impl Disk {
     /// Create a new disk.
     pub fn new(dev: AxBlockDevice) -> Self {
-        assert_eq!(BLOCK_SIZE, dev.block_size());
+        let cache = BlockCache::new(dev);
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            start_block: 0,
+            num_blocks: cache.num_blocks(),
+            cache: Arc::new(cache),
+        }
+    }
+
+    /// Create a view of the `num_blocks` blocks starting at `start_block` on
+    /// this disk, e.g. a partition.
+    pub(crate) fn sub_disk(&self, start_block: u64, num_blocks: u64) -> Option<Self> {
+        if start_block.checked_add(num_blocks)? > self.num_blocks {
+            return None;
         }
+        Some(Self {
+            block_id: 0,
+            offset: 0,
+            start_block: self.start_block + start_block,
+            num_blocks,
+            cache: self.cache.clone(),
+        })
     }
 
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.num_blocks * BLOCK_SIZE as u64
+    }
+
+    /// Get the number of blocks of the disk.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
     }
 
     /// Get the position of the cursor.
@@ -36,57 +70,85 @@ impl Disk {
         self.offset = pos as usize % BLOCK_SIZE;
     }
 
+    /// Read a whole block at `block_id`, regardless of the cursor.
+    pub(crate) fn read_block(&self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.read_in_block(block_id, 0, &mut buf[..BLOCK_SIZE])
+    }
+
+    /// Read `buf.len()` bytes at `offset` within the block `block_id`.
+    fn read_in_block(&self, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+        if block_id >= self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        self.cache.read(self.start_block + block_id, offset, buf)
+    }
+
+    /// Write `buf` at `offset` within the block `block_id`.
+    fn write_in_block(&self, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+        if block_id >= self.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        self.cache.write(self.start_block + block_id, offset, buf)
+    }
+
+    /// Read `buf.len()` bytes at the byte offset `pos`, regardless of the
+    /// cursor.
+    pub(crate) fn read_at(&self, mut pos: u64, mut buf: &mut [u8]) -> DevResult {
+        while !buf.is_empty() {
+            let offset = pos as usize % BLOCK_SIZE;
+            let count = buf.len().min(BLOCK_SIZE - offset);
+            let (head, rest) = buf.split_at_mut(count);
+            self.read_in_block(pos / BLOCK_SIZE as u64, offset, head)?;
+            pos += count as u64;
+            buf = rest;
+        }
+        Ok(())
+    }
+
+    /// Write `buf` at the byte offset `pos`, regardless of the cursor.
+    pub(crate) fn write_at(&self, mut pos: u64, mut buf: &[u8]) -> DevResult {
+        while !buf.is_empty() {
+            let offset = pos as usize % BLOCK_SIZE;
+            let count = buf.len().min(BLOCK_SIZE - offset);
+            self.write_in_block(pos / BLOCK_SIZE as u64, offset, &buf[..count])?;
+            pos += count as u64;
+            buf = &buf[count..];
+        }
+        Ok(())
+    }
+
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(read_size)
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        self.read_in_block(self.block_id, self.offset, &mut buf[..count])?;
+        self.advance(count);
+        Ok(count)
     }
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        self.write_in_block(self.block_id, self.offset, &buf[..count])?;
+        self.advance(count);
+        Ok(count)
+    }
+
+    /// Write all cached modifications of the whole device back to it.
+    pub fn sync(&self) -> DevResult {
+        self.cache.sync()
+    }
+
+    /// Reads the whole device, bypassing the block cache.
+    #[cfg(test)]
+    pub(crate) fn device_image(&self) -> alloc::vec::Vec<u8> {
+        self.cache.device_image()
+    }
+
+    fn advance(&mut self, count: usize) {
+        self.offset += count;
+        if self.offset >= BLOCK_SIZE {
             self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(write_size)
+            self.offset -= BLOCK_SIZE;
+        }
     }
 }
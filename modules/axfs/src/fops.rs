@@ -1,16 +1,33 @@
 //! Low-level filesystem operations.
 
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
 use axerrno::{AxError, AxResult, ax_err, ax_err_type};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
+use axsync::{Mutex, spin::SpinNoIrq};
 use cap_access::{Cap, WithCap};
-use core::fmt;
+use core::{fmt, time::Duration};
+
+use crate::meta::{self, MetaNode};
+use crate::root::MountPoint;
+use crate::{lock, notify, page_cache, statfs};
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
 #[cfg(feature = "myfs")]
 pub use crate::fs::myfs::MyFileSystemIf;
 
+pub use crate::lock::{FileLocker, LockKind, RecordLock};
+pub use crate::meta::FileMeta;
+pub use crate::page_cache::PageCache;
+pub use crate::statfs::{FileSystemStats, Quota};
+pub use axfs_vfs::VfsOps;
+
 /// Alias of [`axfs_vfs::VfsNodeType`].
 pub type FileType = axfs_vfs::VfsNodeType;
 /// Alias of [`axfs_vfs::VfsDirEntry`].
@@ -21,8 +38,14 @@ pub type FileAttr = axfs_vfs::VfsNodeAttr;
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
 /// An opened file object, with open permissions and a cursor.
+///
+/// Regular files are read through their [`PageCache`].
 pub struct File {
     node: WithCap<VfsNodeRef>,
+    cache: Option<Arc<PageCache>>,
+    locker: FileLocker,
+    mount: Arc<MountPoint>,
+    path: OpenedPath,
     is_append: bool,
     offset: u64,
 }
@@ -31,9 +54,18 @@ pub struct File {
 /// [`read_dir`](Directory::read_dir).
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
+    locker: FileLocker,
+    mount: Arc<MountPoint>,
+    path: OpenedPath,
     entry_idx: usize,
 }
 
+/// The canonical absolute path of an opened file or directory, which follows
+/// it when it or any of its ancestors is renamed.
+struct OpenedPath(Arc<SpinNoIrq<String>>);
+
+static OPENED_PATHS: Mutex<Vec<Weak<SpinNoIrq<String>>>> = Mutex::new(Vec::new());
+
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone)]
 pub struct OpenOptions {
@@ -122,13 +154,13 @@ impl File {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
 
-        let node_option = crate::root::lookup(dir, path);
+        let (mount, abs_path, node_option) = crate::root::lookup_with_mount(path, true)?;
         let node = if opts.create || opts.create_new {
             match node_option {
                 Ok(node) => {
@@ -139,7 +171,12 @@ impl File {
                     node
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => {
+                    let node = mount.charged(1, 0, || crate::root::create_file(path))?;
+                    meta_of(&abs_path, &node, &mount).created();
+                    notify::created(&abs_path, false);
+                    node
+                }
                 Err(e) => return Err(e),
             }
         } else {
@@ -147,7 +184,7 @@ impl File {
             node_option?
         };
 
-        let attr = node.get_attr()?;
+        let attr = meta_of(&abs_path, &node, &mount).attr()?;
         if attr.is_dir()
             && (opts.create || opts.create_new || opts.write || opts.append || opts.truncate)
         {
@@ -159,11 +196,23 @@ impl File {
         }
 
         node.open()?;
+        let cache = (attr.is_file() && mount.node_ext(&node).page_cached())
+            .then(|| page_cache::open(&abs_path, &node, &mount));
         if opts.truncate {
-            node.truncate(0)?;
+            match &cache {
+                Some(cache) => cache.truncate(0)?,
+                None => node.truncate(0)?,
+            }
+            meta_of(&abs_path, &node, &mount).modified();
+            notify::modified(&abs_path);
         }
+        let locker = lock::open(&abs_path, &node, &mount);
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            cache,
+            locker,
+            mount,
+            path: OpenedPath::new(abs_path),
             is_append: opts.append,
             offset: 0,
         })
@@ -172,12 +221,59 @@ impl File {
     /// Opens a file at the path relative to the current directory. Returns a
     /// [`File`] object.
     pub fn open(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_at(None, path, opts)
+        Self::_open_at(path, opts)
+    }
+
+    /// Returns the page cache of the file, or `None` if it is not a regular
+    /// file, or a file of a pseudo filesystem like procfs.
+    ///
+    /// With the `mmap` feature, it implements `axmm::FileMapping` and can back
+    /// file mappings.
+    pub fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.cache.clone()
+    }
+
+    /// Returns the locker of the file, to set advisory locks on it.
+    pub fn locker(&self) -> FileLocker {
+        self.locker.clone()
+    }
+
+    fn meta<T>(&self, f: impl FnOnce(MetaNode<'_>) -> AxResult<T>) -> AxResult<T> {
+        let path = self.path.get();
+        f(meta_of(&path, self.access_node(Cap::empty())?, &self.mount))
+    }
+
+    fn node_read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let node = self.access_node(Cap::READ)?;
+        match &self.cache {
+            Some(cache) => cache.read_at(offset, buf),
+            None => node.read_at(offset, buf),
+        }
+    }
+
+    fn node_write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let node = self.access_node(Cap::WRITE)?;
+        let end = offset.saturating_add(buf.len() as u64);
+        let write_len = statfs::resize(&self.mount, node, end, || match &self.cache {
+            Some(cache) => cache.write_at(offset, buf),
+            None => node.write_at(offset, buf),
+        })?;
+        let path = self.path.get();
+        meta_of(&path, node, &self.mount).modified();
+        notify::modified(&path);
+        Ok(write_len)
     }
 
     /// Truncates the file to the specified size.
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.access_node(Cap::WRITE)?.truncate(size)?;
+        let node = self.access_node(Cap::WRITE)?;
+        statfs::resize(&self.mount, node, size, || match &self.cache {
+            Some(cache) => cache.truncate(size),
+            None => node.truncate(size),
+        })?;
+        let path = self.path.get();
+        meta_of(&path, node, &self.mount).modified();
+        notify::modified(&path);
         Ok(())
     }
 
@@ -186,8 +282,7 @@ impl File {
     ///
     /// After the read, the cursor will be advanced by the number of bytes read.
     pub fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf)?;
+        let read_len = self.node_read_at(self.offset, buf)?;
         self.offset += read_len as u64;
         Ok(read_len)
     }
@@ -196,9 +291,7 @@ impl File {
     ///
     /// It does not update the file cursor.
     pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::READ)?;
-        let read_len = node.read_at(offset, buf)?;
-        Ok(read_len)
+        self.node_read_at(offset, buf)
     }
 
     /// Writes the file at the current position. Returns the number of bytes
@@ -212,8 +305,7 @@ impl File {
         } else {
             self.offset
         };
-        let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        let write_len = self.node_write_at(offset, buf)?;
         self.offset = offset + write_len as u64;
         Ok(write_len)
     }
@@ -223,15 +315,135 @@ impl File {
     ///
     /// It does not update the file cursor.
     pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
-        let node = self.access_node(Cap::WRITE)?;
-        let write_len = node.write_at(offset, buf)?;
+        self.node_write_at(offset, buf)
+    }
+
+    /// Reads the file at the current position into the buffers in order.
+    /// Returns the number of bytes read.
+    ///
+    /// It stops at the first buffer not filled, and the cursor will be
+    /// advanced by the number of bytes read.
+    pub fn read_vectored(&mut self, bufs: &mut [&mut [u8]]) -> AxResult<usize> {
+        let read_len = self.read_vectored_at(self.offset, bufs)?;
+        self.offset += read_len as u64;
+        Ok(read_len)
+    }
+
+    /// Reads the file at the given position into the buffers in order.
+    /// Returns the number of bytes read.
+    ///
+    /// It does not update the file cursor.
+    pub fn read_vectored_at(&self, offset: u64, bufs: &mut [&mut [u8]]) -> AxResult<usize> {
+        let mut read_len = 0;
+        for buf in bufs.iter_mut() {
+            let n = match self.node_read_at(offset + read_len as u64, buf) {
+                Ok(n) => n,
+                Err(_) if read_len > 0 => break,
+                Err(e) => return Err(e),
+            };
+            read_len += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(read_len)
+    }
+
+    /// Writes the buffers in order to the file at the current position.
+    /// Returns the number of bytes written.
+    ///
+    /// In the append mode, all buffers are written contiguously at the end of
+    /// the file. After the write, the cursor will be advanced by the number
+    /// of bytes written.
+    pub fn write_vectored(&mut self, bufs: &[&[u8]]) -> AxResult<usize> {
+        let offset = if self.is_append {
+            self.get_attr()?.size()
+        } else {
+            self.offset
+        };
+        let write_len = self.write_vectored_at(offset, bufs)?;
+        self.offset = offset + write_len as u64;
+        Ok(write_len)
+    }
+
+    /// Writes the buffers in order to the file at the given position. Returns
+    /// the number of bytes written.
+    ///
+    /// It does not update the file cursor.
+    pub fn write_vectored_at(&self, offset: u64, bufs: &[&[u8]]) -> AxResult<usize> {
+        let mut write_len = 0;
+        for buf in bufs {
+            let n = match self.node_write_at(offset + write_len as u64, buf) {
+                Ok(n) => n,
+                Err(_) if write_len > 0 => break,
+                Err(e) => return Err(e),
+            };
+            write_len += n;
+            if n < buf.len() {
+                break;
+            }
+        }
         Ok(write_len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
+    ///
+    /// Unlike [`File::sync_all`], the rest of the filesystem and the write
+    /// cache of the device are not flushed.
     pub fn flush(&self) -> AxResult {
-        self.access_node(Cap::WRITE)?.fsync()?;
-        Ok(())
+        let node = self.access_node(Cap::WRITE)?;
+        self.sync_file(node)
+    }
+
+    fn sync_file(&self, node: &VfsNodeRef) -> AxResult {
+        match &self.cache {
+            Some(cache) => cache.sync(),
+            None => Ok(node.fsync()?),
+        }
+    }
+
+    /// Synchronizes the data and metadata of the file to the device, and
+    /// flushes the write cache of the device, as `fsync`.
+    ///
+    /// Unlike [`File::flush`], it also works on read-only files. Only the
+    /// metadata stored by the filesystem is synchronized, see
+    /// [the crate documentation](crate#metadata).
+    pub fn sync_all(&self) -> AxResult {
+        self.sync_file(self.access_node(Cap::empty())?)?;
+        // the metadata kept by the filesystem, and the write cache of the device
+        self.mount.sync()
+    }
+
+    /// Synchronizes the data of the file to the device, as `fdatasync`.
+    ///
+    /// The metadata needed to read the data back, like the file size, is
+    /// synchronized as well. Filesystems update it along with the data, so
+    /// this is the same as [`File::sync_all`].
+    pub fn sync_data(&self) -> AxResult {
+        self.sync_all()
+    }
+
+    /// Writes the modifications of the `len` bytes at `offset` back to the
+    /// filesystem, without flushing the filesystem or the device, as
+    /// `sync_file_range`. A `len` of 0 means until the end of the file.
+    pub fn sync_range(&self, offset: u64, len: u64) -> AxResult {
+        self.access_node(Cap::empty())?;
+        let len = if len == 0 { u64::MAX } else { len };
+        match &self.cache {
+            Some(cache) => cache.write_back_range(offset, len),
+            None => Ok(()), // writes go to the filesystem directly
+        }
+    }
+
+    /// Synchronizes the whole filesystem containing the file, as `syncfs`.
+    pub fn sync_fs(&self) -> AxResult {
+        crate::root::sync(Some(&self.mount))
+    }
+
+    /// Returns the statistics of the filesystem containing the file, as
+    /// `fstatfs`.
+    pub fn statfs(&self) -> AxResult<FileSystemStats> {
+        self.mount.stats()
     }
 
     /// Sets the cursor of the file to the specified offset. Returns the new
@@ -250,7 +462,29 @@ impl File {
 
     /// Gets the file attributes.
     pub fn get_attr(&self) -> AxResult<FileAttr> {
-        self.access_node(Cap::empty())?.get_attr()
+        self.meta(|meta| meta.attr())
+    }
+
+    /// Gets the timestamps and the owner of the file.
+    pub fn get_meta(&self) -> AxResult<FileMeta> {
+        self.meta(|meta| Ok(meta.get()))
+    }
+
+    /// Changes the permissions of the file.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        self.meta(|meta| meta.set_perm(perm))
+    }
+
+    /// Changes the owner and the group of the file, `None` leaves it
+    /// unchanged.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        self.meta(|meta| meta.set_owner(uid, gid))
+    }
+
+    /// Changes the access and the modification times of the file, since the
+    /// UNIX epoch. `None` leaves it unchanged.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> AxResult {
+        self.meta(|meta| meta.set_times(atime, mtime))
     }
 }
 
@@ -259,7 +493,7 @@ impl Directory {
         self.node.access_or_err(cap, AxError::PermissionDenied)
     }
 
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -268,8 +502,9 @@ impl Directory {
             return ax_err!(InvalidInput);
         }
 
-        let node = crate::root::lookup(dir, path)?;
-        let attr = node.get_attr()?;
+        let (mount, abs_path, node) = crate::root::lookup_with_mount(path, true)?;
+        let node = node?;
+        let attr = meta_of(&abs_path, &node, &mount).attr()?;
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
         }
@@ -279,56 +514,188 @@ impl Directory {
         }
 
         node.open()?;
+        let locker = lock::open(&abs_path, &node, &mount);
         Ok(Self {
             node: WithCap::new(node, access_cap),
+            locker,
+            mount,
+            path: OpenedPath::new(abs_path),
             entry_idx: 0,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
-        if path.starts_with('/') {
-            Ok(None)
+    /// Returns the locker of the directory, to set `flock` locks on it.
+    pub fn locker(&self) -> FileLocker {
+        self.locker.clone()
+    }
+
+    /// Returns `path` relative to this directory as a path from the root, if
+    /// it may be looked up in this directory.
+    fn path_at(&self, path: &str) -> AxResult<String> {
+        if path.is_empty() || path.starts_with('/') {
+            Ok(path.into())
         } else {
-            Ok(Some(self.access_node(Cap::EXECUTE)?))
+            self.access_node(Cap::EXECUTE)?;
+            Ok(format!("{}/{}", self.path.get(), path))
         }
     }
 
+    /// Returns the canonical absolute path of `path` relative to this
+    /// directory, with symbolic links resolved.
+    ///
+    /// The last component is followed only if `follow` is true or `path` ends
+    /// with '/', and it does not need to exist.
+    pub fn real_path_at(&self, path: &str, follow: bool) -> AxResult<String> {
+        crate::root::real_path(&self.path_at(path)?, follow)
+    }
+
     /// Opens a directory at the path relative to the current directory.
     /// Returns a [`Directory`] object.
     pub fn open_dir(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(None, path, opts)
+        Self::_open_dir_at(path, opts)
     }
 
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        Self::_open_dir_at(&self.path_at(path)?, opts)
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        File::_open_at(&self.path_at(path)?, opts)
     }
 
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        let path = self.path_at(path)?;
+        let abs_path = crate::root::real_path(&path, true)?;
+        let node = statfs::create(&abs_path, || crate::root::create_file(&path))?;
+        meta::created(&abs_path);
+        notify::created(&abs_path, false);
+        Ok(node)
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+        let path = self.path_at(path)?;
+        let abs_path = crate::root::real_path(&path, false)?;
+        statfs::create(&abs_path, || crate::root::create_dir(&path))?;
+        meta::created(&abs_path);
+        notify::created(&abs_path, true);
+        Ok(())
     }
 
     /// Removes a file at the path relative to this directory.
     pub fn remove_file(&self, path: &str) -> AxResult {
-        crate::root::remove_file(self.access_at(path)?, path)
+        let path = self.path_at(path)?;
+        let abs_path = crate::root::real_path(&path, false)?;
+        let node = crate::root::lookup_no_follow(&abs_path)?;
+        statfs::remove(&abs_path, || crate::root::remove_file(&path))?;
+        crate::root::forget_removed(&abs_path, &node);
+        notify::removed(&abs_path, false);
+        Ok(())
     }
 
     /// Removes a directory at the path relative to this directory.
     pub fn remove_dir(&self, path: &str) -> AxResult {
-        crate::root::remove_dir(self.access_at(path)?, path)
+        let path = self.path_at(path)?;
+        let abs_path = crate::root::real_path(&path, false)?;
+        let node = crate::root::lookup_no_follow(&abs_path)?;
+        statfs::remove(&abs_path, || crate::root::remove_dir(&path))?;
+        crate::root::forget_removed(&abs_path, &node);
+        notify::removed(&abs_path, true);
+        Ok(())
+    }
+
+    /// Creates a symbolic link at the path relative to this directory, which
+    /// links to `target`.
+    pub fn symlink(&self, target: &str, path: &str) -> AxResult {
+        let path = self.path_at(path)?;
+        let abs_path = crate::root::real_path(&path, false)?;
+        statfs::create(&abs_path, || crate::root::symlink(target, &path))?;
+        meta::created(&abs_path);
+        notify::created(&abs_path, false);
+        Ok(())
+    }
+
+    /// Returns the target of the symbolic link at the path relative to this
+    /// directory.
+    pub fn read_link(&self, path: &str) -> AxResult<String> {
+        crate::root::read_link(&self.path_at(path)?)
+    }
+
+    fn meta<T>(&self, f: impl FnOnce(MetaNode<'_>) -> AxResult<T>) -> AxResult<T> {
+        let path = self.path.get();
+        f(meta_of(&path, self.access_node(Cap::empty())?, &self.mount))
+    }
+
+    /// Get the attributes of the directory.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        self.meta(|meta| meta.attr())
+    }
+
+    /// Gets the timestamps and the owner of the directory.
+    pub fn get_meta(&self) -> AxResult<FileMeta> {
+        self.meta(|meta| Ok(meta.get()))
+    }
+
+    /// Changes the permissions of the directory.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        self.meta(|meta| meta.set_perm(perm))
+    }
+
+    /// Changes the owner and the group of the directory, `None` leaves it
+    /// unchanged.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        self.meta(|meta| meta.set_owner(uid, gid))
+    }
+
+    /// Changes the access and the modification times of the directory, since
+    /// the UNIX epoch. `None` leaves it unchanged.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> AxResult {
+        self.meta(|meta| meta.set_times(atime, mtime))
+    }
+
+    /// Synchronizes the entries of the directory to the device, as `fsync`.
+    ///
+    /// Directory entries are not tracked separately, so the whole filesystem
+    /// containing the directory is synchronized.
+    pub fn sync_all(&self) -> AxResult {
+        self.sync_fs()
+    }
+
+    /// Synchronizes the whole filesystem containing the directory, as
+    /// `syncfs`.
+    pub fn sync_fs(&self) -> AxResult {
+        crate::root::sync(Some(&self.mount))
+    }
+
+    /// Returns the statistics of the filesystem containing the directory, as
+    /// `fstatfs`.
+    pub fn statfs(&self) -> AxResult<FileSystemStats> {
+        self.mount.stats()
+    }
+
+    /// Returns the index of the next entry to read by
+    /// [`read_dir`](Self::read_dir), including `.` and `..`.
+    pub fn entry_idx(&self) -> usize {
+        self.entry_idx
+    }
+
+    /// Sets the index of the next entry to read by
+    /// [`read_dir`](Self::read_dir), e.g. `0` to read from the beginning.
+    pub fn set_entry_idx(&mut self, idx: usize) {
+        self.entry_idx = idx;
+    }
+
+    /// Returns the inode number of the entry `name` of this directory, see
+    /// [`FileMeta::ino`].
+    pub fn entry_ino(&self, name: &str) -> AxResult<u64> {
+        self.access_node(Cap::READ)?;
+        let (mount, path, node) = crate::root::lookup_with_mount(&self.path_at(name)?, false)?;
+        Ok(meta_of(&path, &node?, &mount).ino())
     }
 
     /// Reads directory entries starts from the current position into the
@@ -344,23 +711,43 @@ impl Directory {
         Ok(n)
     }
 
-    /// Rename a file or directory to a new name.
-    /// Delete the original file if `old` already exists.
+    /// Rename a file or directory to a new name, both relative to this
+    /// directory. Delete the original file if `old` already exists.
     ///
     /// This only works then the new path is in the same mounted fs.
     pub fn rename(&self, old: &str, new: &str) -> AxResult {
-        crate::root::rename(old, new)
+        crate::root::rename(
+            &self.real_path_at(old, false)?,
+            &self.real_path_at(new, false)?,
+        )
+    }
+
+    /// Creates a hard link `new` to the file at `old`, both relative to this
+    /// directory.
+    ///
+    /// This only works then both paths are in the same mounted fs, which
+    /// supports hard links.
+    pub fn link(&self, old: &str, new: &str) -> AxResult {
+        crate::root::link(
+            &self.real_path_at(old, false)?,
+            &self.real_path_at(new, false)?,
+        )
     }
 }
 
 impl Drop for File {
     fn drop(&mut self) {
+        self.locker.release_file();
+        if self.access_node(Cap::WRITE).is_ok() {
+            notify::closed_write(&self.path.get());
+        }
         unsafe { self.node.access_unchecked().release().ok() };
     }
 }
 
 impl Drop for Directory {
     fn drop(&mut self) {
+        self.locker.release_file();
         unsafe { self.node.access_unchecked().release().ok() };
     }
 }
@@ -403,6 +790,39 @@ impl From<&OpenOptions> for Cap {
     }
 }
 
+impl OpenedPath {
+    fn new(path: String) -> Self {
+        let path = Arc::new(SpinNoIrq::new(path));
+        let mut paths = OPENED_PATHS.lock();
+        paths.retain(|path| path.strong_count() > 0);
+        paths.push(Arc::downgrade(&path));
+        Self(path)
+    }
+
+    fn get(&self) -> String {
+        self.0.lock().clone()
+    }
+}
+
+/// Updates the paths of opened files and directories renamed from `old` to
+/// `new`, including those in the renamed directory.
+pub(crate) fn rename(old: &str, new: &str) {
+    for path in OPENED_PATHS.lock().iter().filter_map(Weak::upgrade) {
+        let mut path = path.lock();
+        let moved = path
+            .strip_prefix(old)
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            .map(|rest| String::from(new) + rest);
+        if let Some(moved) = moved {
+            *path = moved;
+        }
+    }
+}
+
+fn meta_of<'a>(path: &'a str, node: &'a VfsNodeRef, mount: &'a MountPoint) -> MetaNode<'a> {
+    MetaNode { path, node, mount }
+}
+
 fn perm_to_cap(perm: FilePerm) -> Cap {
     let mut cap = Cap::empty();
     if perm.owner_readable() {
@@ -0,0 +1,318 @@
+//! Linear directories.
+//!
+//! Hash tree indexes are not maintained, directories with them are converted
+//! to linear ones on the first modification. Linux still reads them.
+
+use alloc::{string::String, vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use super::crc::crc32c;
+use super::layout::*;
+use super::volume::Ext4Volume;
+
+const DIRENT_HEADER_SIZE: usize = 8;
+const DIR_TAIL_SIZE: usize = 12;
+const DIR_TAIL_FILE_TYPE: u8 = 0xDE;
+pub const MAX_NAME_LEN: usize = 255;
+
+/// An entry read from a directory.
+pub struct DirEntry {
+    pub ino: u32,
+    pub name: String,
+    /// The type from the entry, `None` if the filesystem doesn't record it.
+    pub ty: Option<VfsNodeType>,
+}
+
+/// The location of an entry in a directory.
+struct EntryPos {
+    lblk: u32,
+    offset: usize,
+    /// The offset of the previous entry in the same block.
+    prev: Option<usize>,
+    ino: u32,
+}
+
+const fn rec_len_of(name_len: usize) -> usize {
+    (DIRENT_HEADER_SIZE + name_len).next_multiple_of(4)
+}
+
+impl Ext4Volume {
+    /// The size of the checksum tail at the end of directory blocks.
+    fn dir_tail_size(&self) -> usize {
+        if self.sb.csum_seed.is_some() {
+            DIR_TAIL_SIZE
+        } else {
+            0
+        }
+    }
+
+    fn read_dir_block(&self, dir: &Inode, lblk: u32) -> VfsResult<Vec<u8>> {
+        let mut block = vec![0; self.block_size()];
+        self.read_data(dir, lblk as u64 * self.block_size() as u64, &mut block)?;
+        Ok(block)
+    }
+
+    fn write_dir_block(&self, dir: &Inode, lblk: u32, block: &mut [u8]) -> VfsResult {
+        if let Some(seed) = self.inode_csum_seed(dir) {
+            let tail = block.len() - DIR_TAIL_SIZE;
+            write_u32(block, tail, 0);
+            write_u16(block, tail + 4, DIR_TAIL_SIZE as u16);
+            block[tail + 6] = 0;
+            block[tail + 7] = DIR_TAIL_FILE_TYPE;
+            let csum = crc32c(seed, &block[..tail]);
+            write_u32(block, tail + 8, csum);
+        }
+        let mapping = self.map_block(dir, lblk)?.ok_or(VfsError::InvalidData)?;
+        self.write_block(mapping.pblk, 0, block)
+    }
+
+    /// Calls `f` with each entry of the directory `dir`, until it returns
+    /// `true`.
+    fn scan_dir(
+        &self,
+        dir: &Inode,
+        mut f: impl FnMut(&[u8], &EntryPos) -> bool,
+    ) -> VfsResult<Option<EntryPos>> {
+        let bs = self.block_size();
+        let blocks = dir.size().div_ceil(bs as u64) as u32;
+        for lblk in 0..blocks {
+            let block = self.read_dir_block(dir, lblk)?;
+            let mut offset = 0;
+            let mut prev = None;
+            while offset + DIRENT_HEADER_SIZE <= bs {
+                let ino = read_u32(&block, offset);
+                let rec_len = read_u16(&block, offset + 4) as usize;
+                let name_len = block[offset + 6] as usize;
+                if rec_len < DIRENT_HEADER_SIZE
+                    || offset + rec_len > bs
+                    || DIRENT_HEADER_SIZE + name_len > rec_len
+                {
+                    warn!("ext4: corrupted directory block {} of {}", lblk, dir.ino);
+                    return Err(VfsError::InvalidData);
+                }
+                let pos = EntryPos {
+                    lblk,
+                    offset,
+                    prev,
+                    ino,
+                };
+                if f(&block[offset..offset + rec_len], &pos) {
+                    return Ok(Some(pos));
+                }
+                prev = Some(offset);
+                offset += rec_len;
+            }
+        }
+        Ok(None)
+    }
+
+    fn find_entry(&self, dir: &Inode, name: &str) -> VfsResult<Option<EntryPos>> {
+        self.scan_dir(dir, |entry, pos| {
+            pos.ino != 0 && &entry[DIRENT_HEADER_SIZE..][..entry[6] as usize] == name.as_bytes()
+        })
+    }
+
+    /// Finds `name` in the directory `dir`, returns its inode number.
+    pub fn lookup(&self, dir: &Inode, name: &str) -> VfsResult<Option<u32>> {
+        Ok(self.find_entry(dir, name)?.map(|pos| pos.ino))
+    }
+
+    /// Reads up to `max` entries of `dir`, skipping the first `start`.
+    pub fn read_dir(&self, dir: &Inode, start: usize, max: usize) -> VfsResult<Vec<DirEntry>> {
+        let has_type = self.sb.feature_incompat() & INCOMPAT_FILETYPE != 0;
+        let mut idx = 0;
+        let mut entries = Vec::new();
+        self.scan_dir(dir, |entry, pos| {
+            if pos.ino == 0 || entries.len() >= max {
+                return entries.len() >= max;
+            }
+            idx += 1;
+            if idx > start {
+                let name = &entry[DIRENT_HEADER_SIZE..][..entry[6] as usize];
+                entries.push(DirEntry {
+                    ino: pos.ino,
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    ty: has_type.then(|| dirent_type_to_type(entry[7])).flatten(),
+                });
+            }
+            false
+        })?;
+        Ok(entries)
+    }
+
+    /// Whether the directory `dir` has no entries other than `.` and `..`.
+    pub fn dir_is_empty(&self, dir: &Inode) -> VfsResult<bool> {
+        let found = self.scan_dir(dir, |entry, pos| {
+            let name = &entry[DIRENT_HEADER_SIZE..][..entry[6] as usize];
+            pos.ino != 0 && name != b"." && name != b".."
+        })?;
+        Ok(found.is_none())
+    }
+
+    /// Adds an entry `name` of inode `ino` to the directory `dir`.
+    ///
+    /// The inode of `dir` is written back if it grows.
+    pub fn add_entry(
+        &mut self,
+        dir: &mut Inode,
+        name: &str,
+        ino: u32,
+        ty: VfsNodeType,
+    ) -> VfsResult {
+        if name.len() > MAX_NAME_LEN {
+            return Err(VfsError::InvalidInput);
+        }
+        self.drop_dir_index(dir)?;
+        let bs = self.block_size();
+        let limit = bs - self.dir_tail_size();
+        let needed = rec_len_of(name.len());
+
+        // find an entry with enough space after it
+        let found = self.scan_dir(dir, |entry, pos| {
+            if pos.offset >= limit {
+                return false; // the checksum tail
+            }
+            let used = if pos.ino == 0 {
+                0
+            } else {
+                rec_len_of(entry[6] as usize)
+            };
+            entry.len() - used >= needed
+        })?;
+
+        let (lblk, mut block, offset) = match found {
+            Some(pos) => {
+                let mut block = self.read_dir_block(dir, pos.lblk)?;
+                let offset = if pos.ino == 0 {
+                    pos.offset
+                } else {
+                    // split the entry
+                    let old_len = read_u16(&block, pos.offset + 4) as usize;
+                    let used = rec_len_of(block[pos.offset + 6] as usize);
+                    write_u16(&mut block, pos.offset + 4, used as u16);
+                    let offset = pos.offset + used;
+                    write_u16(&mut block, offset + 4, (old_len - used) as u16);
+                    offset
+                };
+                (pos.lblk, block, offset)
+            }
+            None => {
+                // append a new block
+                let lblk = (dir.size() / bs as u64) as u32;
+                let mut block = vec![0; bs];
+                write_u16(&mut block, 4, limit as u16);
+                self.write_data(dir, lblk as u64 * bs as u64, &block)?;
+                (lblk, block, 0)
+            }
+        };
+        write_u32(&mut block, offset, ino);
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = if self.sb.feature_incompat() & INCOMPAT_FILETYPE != 0 {
+            type_to_dirent_type(ty)
+        } else {
+            0
+        };
+        block[offset + DIRENT_HEADER_SIZE..][..name.len()].copy_from_slice(name.as_bytes());
+        self.write_dir_block(dir, lblk, &mut block)
+    }
+
+    /// Removes the entry `name` from the directory `dir`, returns the inode
+    /// number of it.
+    pub fn remove_entry(&mut self, dir: &mut Inode, name: &str) -> VfsResult<u32> {
+        self.drop_dir_index(dir)?;
+        let pos = self.find_entry(dir, name)?.ok_or(VfsError::NotFound)?;
+        let mut block = self.read_dir_block(dir, pos.lblk)?;
+        match pos.prev {
+            Some(prev) => {
+                // merge into the previous entry
+                let len = read_u16(&block, prev + 4) + read_u16(&block, pos.offset + 4);
+                write_u16(&mut block, prev + 4, len);
+            }
+            None => write_u32(&mut block, pos.offset, 0),
+        }
+        self.write_dir_block(dir, pos.lblk, &mut block)?;
+        Ok(pos.ino)
+    }
+
+    /// Changes the inode of the entry `name` in `dir` to `ino`.
+    pub fn set_entry(&mut self, dir: &mut Inode, name: &str, ino: u32) -> VfsResult {
+        self.drop_dir_index(dir)?;
+        let pos = self.find_entry(dir, name)?.ok_or(VfsError::NotFound)?;
+        let mut block = self.read_dir_block(dir, pos.lblk)?;
+        write_u32(&mut block, pos.offset, ino);
+        self.write_dir_block(dir, pos.lblk, &mut block)
+    }
+
+    /// Writes the first block of a new directory `dir`, with `.` and `..`.
+    pub fn init_dir(&mut self, dir: &mut Inode, parent: u32) -> VfsResult {
+        let bs = self.block_size();
+        let mut block = vec![0; bs];
+        let ty = if self.sb.feature_incompat() & INCOMPAT_FILETYPE != 0 {
+            type_to_dirent_type(VfsNodeType::Dir)
+        } else {
+            0
+        };
+        let dot_len = rec_len_of(1);
+        write_u32(&mut block, 0, dir.ino);
+        write_u16(&mut block, 4, dot_len as u16);
+        block[6] = 1;
+        block[7] = ty;
+        block[8] = b'.';
+        write_u32(&mut block, dot_len, parent);
+        write_u16(
+            &mut block,
+            dot_len + 4,
+            (bs - self.dir_tail_size() - dot_len) as u16,
+        );
+        block[dot_len + 6] = 2;
+        block[dot_len + 7] = ty;
+        block[dot_len + 8..dot_len + 10].copy_from_slice(b"..");
+        self.write_data(dir, 0, &block)?;
+        self.write_dir_block(dir, 0, &mut block)
+    }
+
+    /// Turns a directory with a hash tree index into a linear one, since the
+    /// index is not updated on modifications.
+    fn drop_dir_index(&mut self, dir: &mut Inode) -> VfsResult {
+        if dir.flags() & INODE_FLAG_INDEX == 0 {
+            return Ok(());
+        }
+        let bs = self.block_size();
+        let limit = bs - self.dir_tail_size();
+        let mut root = self.read_dir_block(dir, 0)?;
+
+        // collect the interior nodes, the root is the first block
+        let levels = root[0x1E];
+        let mut nodes = Vec::new();
+        let mut current = vec![dx_children(&root, 0x20)];
+        for _ in 0..levels {
+            let mut next = Vec::new();
+            for block in current.into_iter().flatten() {
+                nodes.push(block);
+                next.push(dx_children(&self.read_dir_block(dir, block)?, 0x8));
+            }
+            current = next;
+        }
+
+        // `..` covers the whole block, shrink it for the checksum tail
+        let dotdot = read_u16(&root, 4) as usize;
+        write_u16(&mut root, dotdot + 4, (limit - dotdot) as u16);
+        self.write_dir_block(dir, 0, &mut root)?;
+        for lblk in nodes {
+            let mut block = vec![0; bs];
+            write_u16(&mut block, 4, limit as u16);
+            self.write_dir_block(dir, lblk, &mut block)?;
+        }
+        dir.set_flags(dir.flags() & !INODE_FLAG_INDEX);
+        self.write_inode(dir)
+    }
+}
+
+/// The child blocks of a hash tree node, with entries starting at `offset`.
+fn dx_children(block: &[u8], offset: usize) -> Vec<u32> {
+    let count = read_u16(block, offset + 2) as usize;
+    (0..count)
+        .filter_map(|i| block.get(offset + i * 8 + 4..offset + i * 8 + 8))
+        .map(|raw| u32::from_le_bytes(raw.try_into().unwrap()))
+        .collect()
+}
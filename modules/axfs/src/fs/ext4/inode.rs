@@ -0,0 +1,539 @@
+//! Data of inodes, mapped by extent trees.
+//!
+//! Lookups walk the tree on disk. Modifications are done on the flattened
+//! list of extents, then the whole tree is rebuilt, reusing its blocks. This
+//! is simple and fast enough, since files written sequentially have only a
+//! few extents.
+
+use alloc::{vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::crc::crc32c;
+use super::layout::*;
+use super::volume::Ext4Volume;
+
+const EXTENT_MAGIC: u16 = 0xF30A;
+const EXTENT_HEADER_SIZE: usize = 12;
+const EXTENT_SIZE: usize = 12;
+/// The maximum length of an initialized extent.
+const MAX_INIT_LEN: u32 = 32768;
+/// The maximum depth of extent trees accepted.
+const MAX_DEPTH: u16 = 5;
+
+/// A range of contiguous logical blocks mapped to contiguous physical blocks.
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    lblk: u32,
+    len: u32,
+    pblk: u64,
+    /// Uninitialized extents are allocated but read as zeros.
+    uninit: bool,
+}
+
+impl Extent {
+    fn parse(raw: &[u8]) -> Self {
+        let len = read_u16(raw, 4) as u32;
+        let (len, uninit) = if len > MAX_INIT_LEN {
+            (len - MAX_INIT_LEN, true)
+        } else {
+            (len, false)
+        };
+        Self {
+            lblk: read_u32(raw, 0),
+            len,
+            pblk: read_u32(raw, 8) as u64 | (read_u16(raw, 6) as u64) << 32,
+            uninit,
+        }
+    }
+
+    fn write(&self, raw: &mut [u8]) {
+        let len = if self.uninit {
+            self.len + MAX_INIT_LEN
+        } else {
+            self.len
+        };
+        write_u32(raw, 0, self.lblk);
+        write_u16(raw, 4, len as u16);
+        write_u16(raw, 6, (self.pblk >> 32) as u16);
+        write_u32(raw, 8, self.pblk as u32);
+    }
+
+    fn end(&self) -> u32 {
+        self.lblk + self.len
+    }
+
+    fn contains(&self, lblk: u32) -> bool {
+        (self.lblk..self.end()).contains(&lblk)
+    }
+}
+
+/// The physical location of a logical block.
+#[derive(Debug, Clone, Copy)]
+pub struct Mapping {
+    pub pblk: u64,
+    /// The number of following blocks contiguous on disk, including this one.
+    pub len: u32,
+    pub uninit: bool,
+}
+
+struct NodeHeader {
+    entries: usize,
+    depth: u16,
+}
+
+fn parse_header(raw: &[u8], max: usize) -> VfsResult<NodeHeader> {
+    let entries = read_u16(raw, 2) as usize;
+    let depth = read_u16(raw, 6);
+    if read_u16(raw, 0) != EXTENT_MAGIC || entries > max || depth > MAX_DEPTH {
+        return Err(VfsError::InvalidData);
+    }
+    Ok(NodeHeader { entries, depth })
+}
+
+fn write_header(raw: &mut [u8], entries: usize, max: usize, depth: u16) {
+    write_u16(raw, 0, EXTENT_MAGIC);
+    write_u16(raw, 2, entries as u16);
+    write_u16(raw, 4, max as u16);
+    write_u16(raw, 6, depth);
+    write_u32(raw, 8, 0);
+}
+
+/// Initializes the extent tree of a new inode.
+pub fn init_extents(inode: &mut Inode) {
+    inode.set_flags(inode.flags() | INODE_FLAG_EXTENTS);
+    write_header(inode.block_area_mut(), 0, 4, 0);
+}
+
+impl Ext4Volume {
+    fn node_max_entries(&self) -> usize {
+        (self.block_size() - EXTENT_HEADER_SIZE) / EXTENT_SIZE
+    }
+
+    fn read_tree_node(&self, block: u64) -> VfsResult<Vec<u8>> {
+        let mut raw = vec![0; self.block_size()];
+        self.read_block(block, 0, &mut raw)?;
+        Ok(raw)
+    }
+
+    /// Finds the physical block of the logical block `lblk` of `inode`,
+    /// returns `None` for holes.
+    pub fn map_block(&self, inode: &Inode, lblk: u32) -> VfsResult<Option<Mapping>> {
+        if inode.flags() & INODE_FLAG_EXTENTS == 0 {
+            return self.map_block_indirect(inode, lblk);
+        }
+        let mut node = inode.block_area().to_vec();
+        let mut max = 4;
+        loop {
+            let header = parse_header(&node, max)?;
+            let entries = &node[EXTENT_HEADER_SIZE..][..header.entries * EXTENT_SIZE];
+            // the last entry starting at or before `lblk`
+            let Some(entry) = entries
+                .chunks_exact(EXTENT_SIZE)
+                .take_while(|e| read_u32(e, 0) <= lblk)
+                .last()
+            else {
+                return Ok(None);
+            };
+            if header.depth == 0 {
+                let extent = Extent::parse(entry);
+                return Ok(extent.contains(lblk).then(|| Mapping {
+                    pblk: extent.pblk + (lblk - extent.lblk) as u64,
+                    len: extent.end() - lblk,
+                    uninit: extent.uninit,
+                }));
+            }
+            let child = read_u32(entry, 4) as u64 | (read_u16(entry, 8) as u64) << 32;
+            node = self.read_tree_node(child)?;
+            max = self.node_max_entries();
+        }
+    }
+
+    /// Finds the physical block with the ext2/3 block map.
+    fn map_block_indirect(&self, inode: &Inode, lblk: u32) -> VfsResult<Option<Mapping>> {
+        let per_block = (self.block_size() / 4) as u64;
+        let mut lblk = lblk as u64;
+        let single = |idx: usize| read_u32(inode.block_area(), idx * 4) as u64;
+        let (mut block, mut levels) = if lblk < 12 {
+            (single(lblk as usize), 0)
+        } else {
+            lblk -= 12;
+            let mut levels = 1;
+            let mut span = per_block;
+            while lblk >= span {
+                lblk -= span;
+                levels += 1;
+                span *= per_block;
+                if levels > 3 {
+                    return Ok(None);
+                }
+            }
+            (single(11 + levels), levels)
+        };
+        while levels > 0 && block != 0 {
+            levels -= 1;
+            let idx = (lblk / per_block.pow(levels as u32)) % per_block;
+            let mut raw = [0; 4];
+            self.read_block(block, idx as usize * 4, &mut raw)?;
+            block = u32::from_le_bytes(raw) as u64;
+        }
+        Ok((block != 0).then_some(Mapping {
+            pblk: block,
+            len: 1,
+            uninit: false,
+        }))
+    }
+
+    /// Collects all extents of `inode`, and the blocks of the tree nodes.
+    fn load_extents(&self, inode: &Inode) -> VfsResult<(Vec<Extent>, Vec<u64>)> {
+        if inode.flags() & INODE_FLAG_EXTENTS == 0 {
+            return Err(VfsError::Unsupported); // block maps are read-only
+        }
+        let mut extents = Vec::new();
+        let mut tree_blocks = Vec::new();
+        self.collect_extents(inode.block_area(), 4, &mut extents, &mut tree_blocks)?;
+        Ok((extents, tree_blocks))
+    }
+
+    fn collect_extents(
+        &self,
+        node: &[u8],
+        max: usize,
+        extents: &mut Vec<Extent>,
+        tree_blocks: &mut Vec<u64>,
+    ) -> VfsResult {
+        let header = parse_header(node, max)?;
+        let entries = &node[EXTENT_HEADER_SIZE..][..header.entries * EXTENT_SIZE];
+        for entry in entries.chunks_exact(EXTENT_SIZE) {
+            if header.depth == 0 {
+                extents.push(Extent::parse(entry));
+            } else {
+                let child = read_u32(entry, 4) as u64 | (read_u16(entry, 8) as u64) << 32;
+                tree_blocks.push(child);
+                let raw = self.read_tree_node(child)?;
+                self.collect_extents(&raw, self.node_max_entries(), extents, tree_blocks)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the extent tree of `inode` with `extents`, reusing the old
+    /// tree blocks. The inode is not written.
+    fn store_extents(
+        &mut self,
+        inode: &mut Inode,
+        extents: &[Extent],
+        mut old_blocks: Vec<u64>,
+    ) -> VfsResult {
+        let bs = self.block_size();
+        let max = self.node_max_entries();
+        let csum_seed = self.inode_csum_seed(inode);
+
+        // entries of the current level, as (first logical block, raw entry)
+        let mut level = extents
+            .iter()
+            .map(|e| {
+                let mut raw = [0; EXTENT_SIZE];
+                e.write(&mut raw);
+                (e.lblk, raw)
+            })
+            .collect::<Vec<_>>();
+        let mut depth = 0;
+        let mut new_blocks = Vec::new();
+        let result = (|| {
+            while level.len() > 4 {
+                let mut upper = Vec::new();
+                for chunk in level.chunks(max) {
+                    let block = match old_blocks.pop() {
+                        Some(block) => block,
+                        None => {
+                            let block = self.alloc_blocks(self.inode_goal(inode.ino), 1)?.0;
+                            new_blocks.push(block);
+                            block
+                        }
+                    };
+                    let mut raw = vec![0; bs];
+                    write_header(&mut raw, chunk.len(), max, depth);
+                    for (i, (_, entry)) in chunk.iter().enumerate() {
+                        raw[EXTENT_HEADER_SIZE + i * EXTENT_SIZE..][..EXTENT_SIZE]
+                            .copy_from_slice(entry);
+                    }
+                    if let Some(seed) = csum_seed {
+                        let tail = EXTENT_HEADER_SIZE + max * EXTENT_SIZE;
+                        let csum = crc32c(seed, &raw[..tail]);
+                        write_u32(&mut raw, tail, csum);
+                    }
+                    self.write_block(block, 0, &raw)?;
+
+                    let mut index = [0; EXTENT_SIZE];
+                    write_u32(&mut index, 0, chunk[0].0);
+                    write_u32(&mut index, 4, block as u32);
+                    write_u16(&mut index, 8, (block >> 32) as u16);
+                    upper.push((chunk[0].0, index));
+                }
+                level = upper;
+                depth += 1;
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            for block in new_blocks {
+                self.free_blocks(block, 1)?;
+            }
+            return Err(e);
+        }
+
+        let root = inode.block_area_mut();
+        root.fill(0);
+        write_header(root, level.len(), 4, depth);
+        for (i, (_, entry)) in level.iter().enumerate() {
+            root[EXTENT_HEADER_SIZE + i * EXTENT_SIZE..][..EXTENT_SIZE].copy_from_slice(entry);
+        }
+
+        let sectors_per_block = (bs / 512) as u64;
+        let mut sectors = inode.sectors(bs) + new_blocks.len() as u64 * sectors_per_block;
+        for block in old_blocks {
+            self.free_blocks(block, 1)?;
+            sectors -= sectors_per_block;
+        }
+        inode.set_sectors(sectors, bs);
+        Ok(())
+    }
+
+    /// Reads the data of `inode` at `offset`.
+    pub fn read_data(&self, inode: &Inode, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        if inode.flags() & INODE_FLAG_INLINE_DATA != 0 {
+            return Err(VfsError::Unsupported);
+        }
+        let bs = self.block_size() as u64;
+        let len = buf.len().min((size - offset) as usize);
+        let mut pos = 0;
+        while pos < len {
+            let file_pos = offset + pos as u64;
+            let lblk = u32::try_from(file_pos / bs).map_err(|_| VfsError::InvalidData)?;
+            let in_block = (file_pos % bs) as usize;
+            match self.map_block(inode, lblk)? {
+                Some(m) if !m.uninit => {
+                    let count = (len - pos).min(m.len as usize * bs as usize - in_block);
+                    self.read_blocks(m.pblk, in_block, &mut buf[pos..pos + count])?;
+                    pos += count;
+                }
+                _ => {
+                    let count = (len - pos).min(bs as usize - in_block);
+                    buf[pos..pos + count].fill(0);
+                    pos += count;
+                }
+            }
+        }
+        Ok(len)
+    }
+
+    /// Writes `buf` to the data of `inode` at `offset`, allocates blocks for
+    /// holes, and extends the size if needed. The inode is written back.
+    pub fn write_data(&mut self, inode: &mut Inode, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.check_writable()?;
+        let bs = self.block_size();
+        if offset
+            .checked_add(buf.len() as u64)
+            .is_none_or(|end| end.div_ceil(bs as u64) > u32::MAX as u64)
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        // loaded on the first allocation
+        let mut tree: Option<(Vec<Extent>, Vec<u64>)> = None;
+        let mut new_blocks = 0;
+        let mut pos = 0;
+        let mut result = Ok(());
+        while pos < buf.len() {
+            let file_pos = offset + pos as u64;
+            let lblk = (file_pos / bs as u64) as u32;
+            let in_block = (file_pos % bs as u64) as usize;
+            let mapping = match &tree {
+                Some((extents, _)) => lookup_extent(extents, lblk),
+                None => self.map_block(inode, lblk)?,
+            };
+            if let Some(m) = mapping.filter(|m| !m.uninit) {
+                let count = (buf.len() - pos).min(m.len as usize * bs - in_block);
+                self.write_blocks(m.pblk, in_block, &buf[pos..pos + count])?;
+                pos += count;
+                continue;
+            }
+
+            if tree.is_none() {
+                tree = Some(self.load_extents(inode)?);
+            }
+            let (extents, _) = tree.as_mut().unwrap();
+            let blocks = ((in_block + buf.len() - pos).div_ceil(bs) as u32).min(MAX_INIT_LEN);
+            let (pblk, count) = match mapping {
+                Some(m) => (m.pblk, 1), // uninitialized, converted block by block
+                None => {
+                    let limit = next_extent_start(extents, lblk)
+                        .map_or(blocks, |next| blocks.min(next - lblk));
+                    match self.alloc_blocks(goal_of(extents, lblk, inode.ino, self), limit) {
+                        Ok(allocated) => {
+                            new_blocks += allocated.1 as u64;
+                            allocated
+                        }
+                        Err(e) => {
+                            result = Err(e);
+                            break;
+                        }
+                    }
+                }
+            };
+            insert_extent(
+                extents,
+                Extent {
+                    lblk,
+                    len: count,
+                    pblk,
+                    uninit: false,
+                },
+            );
+
+            // new blocks are zeroed, except the parts written
+            let count = (buf.len() - pos).min(count as usize * bs - in_block);
+            let mut data = vec![0; (in_block + count).next_multiple_of(bs)];
+            data[in_block..in_block + count].copy_from_slice(&buf[pos..pos + count]);
+            self.write_blocks(pblk, 0, &data)?;
+            pos += count;
+        }
+
+        if let Some((extents, tree_blocks)) = tree {
+            let sectors = inode.sectors(bs) + new_blocks * (bs / 512) as u64;
+            inode.set_sectors(sectors, bs);
+            if let Err(e) = self.store_extents(inode, &extents, tree_blocks) {
+                // drop the data written to the new blocks
+                warn!(
+                    "ext4: failed to update extents of inode {}: {:?}",
+                    inode.ino, e
+                );
+                return Err(e);
+            }
+        }
+        if offset + pos as u64 > inode.size() {
+            inode.set_size(offset + pos as u64);
+        }
+        self.write_inode(inode)?;
+        match result {
+            Err(e) if pos == 0 => Err(e),
+            _ => Ok(pos),
+        }
+    }
+
+    /// Truncates or extends the data of `inode` to `size`, and frees the
+    /// blocks beyond it. The inode is written back.
+    pub fn truncate_data(&mut self, inode: &mut Inode, size: u64) -> VfsResult {
+        self.check_writable()?;
+        let bs = self.block_size() as u64;
+        if size.div_ceil(bs) > u32::MAX as u64 {
+            return Err(VfsError::InvalidInput);
+        }
+        if size < inode.size() {
+            let first_freed = size.div_ceil(bs) as u32;
+            let (mut extents, tree_blocks) = self.load_extents(inode)?;
+            let mut freed = 0;
+            let mut kept = Vec::with_capacity(extents.len());
+            for mut extent in extents.drain(..) {
+                if extent.end() <= first_freed {
+                    kept.push(extent);
+                    continue;
+                }
+                let keep = first_freed.saturating_sub(extent.lblk);
+                self.free_blocks(extent.pblk + keep as u64, (extent.len - keep) as u64)?;
+                freed += (extent.len - keep) as u64;
+                if keep > 0 {
+                    extent.len = keep;
+                    kept.push(extent);
+                }
+            }
+            let sectors = inode.sectors(bs as usize) - freed * (bs / 512);
+            inode.set_sectors(sectors, bs as usize);
+            self.store_extents(inode, &kept, tree_blocks)?;
+
+            // zero the rest of the last block, in case of extending later
+            if size % bs != 0 {
+                if let Some(m) = lookup_extent(&kept, (size / bs) as u32).filter(|m| !m.uninit) {
+                    let zeros = vec![0; (bs - size % bs) as usize];
+                    self.write_block(m.pblk, (size % bs) as usize, &zeros)?;
+                }
+            }
+        }
+        inode.set_size(size);
+        self.write_inode(inode)
+    }
+}
+
+/// Finds the mapping of `lblk` in the sorted `extents`.
+fn lookup_extent(extents: &[Extent], lblk: u32) -> Option<Mapping> {
+    let idx = extents.partition_point(|e| e.lblk <= lblk).checked_sub(1)?;
+    let extent = &extents[idx];
+    extent.contains(lblk).then(|| Mapping {
+        pblk: extent.pblk + (lblk - extent.lblk) as u64,
+        len: extent.end() - lblk,
+        uninit: extent.uninit,
+    })
+}
+
+/// The first logical block of the next extent after `lblk`.
+fn next_extent_start(extents: &[Extent], lblk: u32) -> Option<u32> {
+    extents.iter().map(|e| e.lblk).find(|&start| start > lblk)
+}
+
+/// A good physical block to allocate for `lblk`, right after the block of the
+/// previous logical block.
+fn goal_of(extents: &[Extent], lblk: u32, ino: u32, vol: &Ext4Volume) -> u64 {
+    let idx = extents.partition_point(|e| e.lblk <= lblk);
+    match idx.checked_sub(1).map(|i| &extents[i]) {
+        Some(prev) => prev.pblk + (lblk - prev.lblk) as u64,
+        None => vol.inode_goal(ino),
+    }
+}
+
+/// Inserts `new` into the sorted `extents`, replacing the range it covers and
+/// merging with adjacent extents.
+fn insert_extent(extents: &mut Vec<Extent>, new: Extent) {
+    // split the extents overlapping `new`
+    let mut result = Vec::with_capacity(extents.len() + 2);
+    for e in extents.drain(..) {
+        if e.end() <= new.lblk || e.lblk >= new.end() {
+            result.push(e);
+            continue;
+        }
+        if e.lblk < new.lblk {
+            result.push(Extent {
+                len: new.lblk - e.lblk,
+                ..e
+            });
+        }
+        if e.end() > new.end() {
+            let skip = new.end() - e.lblk;
+            result.push(Extent {
+                lblk: new.end(),
+                len: e.len - skip,
+                pblk: e.pblk + skip as u64,
+                uninit: e.uninit,
+            });
+        }
+    }
+    let idx = result.partition_point(|e| e.lblk < new.lblk);
+    result.insert(idx, new);
+
+    // merge adjacent extents
+    for e in result {
+        match extents.last_mut() {
+            Some(last)
+                if last.end() == e.lblk
+                    && last.pblk + last.len as u64 == e.pblk
+                    && last.uninit == e.uninit
+                    && last.len + e.len <= MAX_INIT_LEN - if e.uninit { 1 } else { 0 } =>
+            {
+                last.len += e.len;
+            }
+            _ => extents.push(e),
+        }
+    }
+}
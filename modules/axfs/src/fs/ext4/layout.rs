@@ -0,0 +1,627 @@
+//! On-disk structures of ext4.
+
+use alloc::{vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use core::time::Duration;
+
+use super::crc::crc32c;
+
+pub const SUPER_BLOCK_OFFSET: u64 = 1024;
+pub const SUPER_BLOCK_SIZE: usize = 1024;
+pub const EXT4_MAGIC: u16 = 0xEF53;
+pub const ROOT_INO: u32 = 2;
+pub const GOOD_OLD_INODE_SIZE: usize = 128;
+
+/// Offsets of the seconds and the extra field of the times of an inode.
+pub const ATIME: (usize, usize) = (0x8, 0x8C);
+pub const CTIME: (usize, usize) = (0xC, 0x84);
+pub const MTIME: (usize, usize) = (0x10, 0x88);
+
+pub const COMPAT_SPARSE_SUPER2: u32 = 0x200;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+pub const INCOMPAT_LARGEDIR: u32 = 0x4000;
+/// Incompatible features we understand, others are refused.
+pub const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+pub const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+pub const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+pub const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+pub const RO_COMPAT_ORPHAN_PRESENT: u32 = 0x10000;
+/// Read-only compatible features we can keep consistent on writes, others
+/// make the filesystem read-only.
+pub const RO_COMPAT_SUPPORTED: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_GDT_CSUM
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE
+    | RO_COMPAT_METADATA_CSUM
+    | RO_COMPAT_ORPHAN_PRESENT;
+
+pub const BG_INODE_UNINIT: u16 = 0x1;
+pub const BG_BLOCK_UNINIT: u16 = 0x2;
+
+pub const INODE_FLAG_INDEX: u32 = 0x1000;
+pub const INODE_FLAG_HUGE_FILE: u32 = 0x40000;
+pub const INODE_FLAG_EXTENTS: u32 = 0x80000;
+pub const INODE_FLAG_INLINE_DATA: u32 = 0x1000_0000;
+
+pub const S_IFMT: u16 = 0o170000;
+
+pub fn read_u16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+pub fn read_u32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+pub fn write_u16(buf: &mut [u8], off: usize, val: u16) {
+    buf[off..off + 2].copy_from_slice(&val.to_le_bytes());
+}
+
+pub fn write_u32(buf: &mut [u8], off: usize, val: u32) {
+    buf[off..off + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+/// Converts the file type bits of an inode mode to the node type.
+pub fn mode_to_type(mode: u16) -> Option<VfsNodeType> {
+    Some(match mode & S_IFMT {
+        0o010000 => VfsNodeType::Fifo,
+        0o020000 => VfsNodeType::CharDevice,
+        0o040000 => VfsNodeType::Dir,
+        0o060000 => VfsNodeType::BlockDevice,
+        0o100000 => VfsNodeType::File,
+        0o120000 => VfsNodeType::SymLink,
+        0o140000 => VfsNodeType::Socket,
+        _ => return None,
+    })
+}
+
+/// Converts a node type to the file type bits of an inode mode.
+pub fn type_to_mode(ty: VfsNodeType) -> u16 {
+    match ty {
+        VfsNodeType::Fifo => 0o010000,
+        VfsNodeType::CharDevice => 0o020000,
+        VfsNodeType::Dir => 0o040000,
+        VfsNodeType::BlockDevice => 0o060000,
+        VfsNodeType::File => 0o100000,
+        VfsNodeType::SymLink => 0o120000,
+        VfsNodeType::Socket => 0o140000,
+    }
+}
+
+/// Converts a node type to the file type in directory entries.
+pub fn type_to_dirent_type(ty: VfsNodeType) -> u8 {
+    match ty {
+        VfsNodeType::File => 1,
+        VfsNodeType::Dir => 2,
+        VfsNodeType::CharDevice => 3,
+        VfsNodeType::BlockDevice => 4,
+        VfsNodeType::Fifo => 5,
+        VfsNodeType::Socket => 6,
+        VfsNodeType::SymLink => 7,
+    }
+}
+
+/// Converts the file type in directory entries to the node type.
+pub fn dirent_type_to_type(ty: u8) -> Option<VfsNodeType> {
+    Some(match ty {
+        1 => VfsNodeType::File,
+        2 => VfsNodeType::Dir,
+        3 => VfsNodeType::CharDevice,
+        4 => VfsNodeType::BlockDevice,
+        5 => VfsNodeType::Fifo,
+        6 => VfsNodeType::Socket,
+        7 => VfsNodeType::SymLink,
+        _ => return None,
+    })
+}
+
+/// The superblock, with frequently used fields decoded.
+pub struct SuperBlock {
+    pub raw: Vec<u8>,
+    pub block_size: usize,
+    pub blocks_count: u64,
+    pub first_data_block: u64,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: usize,
+    pub desc_size: usize,
+    pub group_count: u32,
+    pub first_ino: u32,
+    /// The seed of all metadata checksums, if `metadata_csum` is enabled.
+    pub csum_seed: Option<u32>,
+}
+
+impl SuperBlock {
+    pub fn parse(raw: Vec<u8>) -> VfsResult<Self> {
+        if read_u16(&raw, 0x38) != EXT4_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let log_block_size = read_u32(&raw, 0x18);
+        if log_block_size > 6 || read_u32(&raw, 0x1C) != log_block_size {
+            // bigalloc is not supported
+            return Err(VfsError::Unsupported);
+        }
+        let block_size = 1024 << log_block_size;
+        let incompat = read_u32(&raw, 0x60);
+        let mut blocks_count = read_u32(&raw, 0x4) as u64;
+        let mut desc_size = 32;
+        if incompat & INCOMPAT_64BIT != 0 {
+            blocks_count |= (read_u32(&raw, 0x150) as u64) << 32;
+            desc_size = read_u16(&raw, 0xFE) as usize;
+        }
+        let rev_level = read_u32(&raw, 0x4C);
+        let (inode_size, first_ino) = if rev_level == 0 {
+            (GOOD_OLD_INODE_SIZE, 11)
+        } else {
+            (read_u16(&raw, 0x58) as usize, read_u32(&raw, 0x54))
+        };
+        let first_data_block = read_u32(&raw, 0x14) as u64;
+        let blocks_per_group = read_u32(&raw, 0x20);
+        let inodes_per_group = read_u32(&raw, 0x28);
+        if blocks_per_group == 0
+            || blocks_per_group as usize > block_size * 8
+            || inodes_per_group == 0
+            || inodes_per_group as usize > block_size * 8
+            || inode_size < GOOD_OLD_INODE_SIZE
+            || inode_size > block_size
+            || !(32..=block_size).contains(&desc_size)
+            || blocks_count <= first_data_block
+        {
+            return Err(VfsError::InvalidData);
+        }
+        let group_count = (blocks_count - first_data_block).div_ceil(blocks_per_group as u64);
+
+        let csum_seed = if read_u32(&raw, 0x64) & RO_COMPAT_METADATA_CSUM != 0 {
+            Some(if incompat & INCOMPAT_CSUM_SEED != 0 {
+                read_u32(&raw, 0x270)
+            } else {
+                crc32c(!0, &raw[0x68..0x78])
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            raw,
+            block_size,
+            blocks_count,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            desc_size,
+            group_count: group_count as u32,
+            first_ino,
+            csum_seed,
+        })
+    }
+
+    pub fn feature_compat(&self) -> u32 {
+        read_u32(&self.raw, 0x5C)
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        read_u32(&self.raw, 0x60)
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        read_u32(&self.raw, 0x64)
+    }
+
+    pub fn set_feature_incompat(&mut self, feature: u32) {
+        let val = self.feature_incompat() | feature;
+        write_u32(&mut self.raw, 0x60, val);
+    }
+
+    pub fn set_feature_ro_compat(&mut self, feature: u32) {
+        let val = self.feature_ro_compat() | feature;
+        write_u32(&mut self.raw, 0x64, val);
+    }
+
+    pub fn has_gdt_csum(&self) -> bool {
+        self.feature_ro_compat() & (RO_COMPAT_GDT_CSUM | RO_COMPAT_METADATA_CSUM) != 0
+    }
+
+    pub fn reserved_gdt_blocks(&self) -> u64 {
+        read_u16(&self.raw, 0xCE) as u64
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0x0)
+    }
+
+    /// Number of blocks reserved for root.
+    pub fn reserved_blocks_count(&self) -> u64 {
+        let mut count = read_u32(&self.raw, 0x8) as u64;
+        if self.feature_incompat() & INCOMPAT_64BIT != 0 {
+            count |= (read_u32(&self.raw, 0x154) as u64) << 32;
+        }
+        count
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        let mut count = read_u32(&self.raw, 0xC) as u64;
+        if self.feature_incompat() & INCOMPAT_64BIT != 0 {
+            count |= (read_u32(&self.raw, 0x158) as u64) << 32;
+        }
+        count
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        write_u32(&mut self.raw, 0xC, count as u32);
+        if self.feature_incompat() & INCOMPAT_64BIT != 0 {
+            write_u32(&mut self.raw, 0x158, (count >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        read_u32(&self.raw, 0x10)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        write_u32(&mut self.raw, 0x10, count);
+    }
+
+    /// Updates the checksum, returns the raw bytes to write.
+    pub fn to_disk(&mut self) -> &[u8] {
+        if self.csum_seed.is_some() {
+            let csum = crc32c(!0, &self.raw[..0x3FC]);
+            write_u32(&mut self.raw, 0x3FC, csum);
+        }
+        &self.raw
+    }
+
+    /// Whether the group `group` has a backup of the superblock and group
+    /// descriptors.
+    pub fn group_has_super(&self, group: u32) -> bool {
+        if group == 0 {
+            return true;
+        }
+        if self.feature_compat() & COMPAT_SPARSE_SUPER2 != 0 {
+            return group == read_u32(&self.raw, 0x24C) || group == read_u32(&self.raw, 0x250);
+        }
+        if self.feature_ro_compat() & RO_COMPAT_SPARSE_SUPER == 0 || group == 1 {
+            return true;
+        }
+        [3, 5, 7].into_iter().any(|base| {
+            let mut n = base;
+            while n < group {
+                n *= base;
+            }
+            n == group
+        })
+    }
+
+    /// The first block of the group `group`.
+    pub fn group_first_block(&self, group: u32) -> u64 {
+        self.first_data_block + group as u64 * self.blocks_per_group as u64
+    }
+
+    /// The number of blocks in the group `group`, only the last group may have
+    /// fewer blocks than others.
+    pub fn blocks_in_group(&self, group: u32) -> u32 {
+        (self.blocks_count - self.group_first_block(group)).min(self.blocks_per_group as u64) as u32
+    }
+
+    /// The number of blocks occupied by the group descriptor table.
+    pub fn gdt_blocks(&self) -> u64 {
+        (self.group_count as u64 * self.desc_size as u64).div_ceil(self.block_size as u64)
+    }
+
+    /// The number of blocks occupied by the inode table of each group.
+    pub fn inode_table_blocks(&self) -> u64 {
+        (self.inodes_per_group as u64 * self.inode_size as u64).div_ceil(self.block_size as u64)
+    }
+}
+
+/// A group descriptor.
+pub struct GroupDesc {
+    pub raw: Vec<u8>,
+}
+
+impl GroupDesc {
+    fn get_lo_hi(&self, lo: usize, hi: usize) -> u64 {
+        let mut val = read_u32(&self.raw, lo) as u64;
+        if self.raw.len() >= 64 {
+            val |= (read_u32(&self.raw, hi) as u64) << 32;
+        }
+        val
+    }
+
+    fn get_lo_hi16(&self, lo: usize, hi: usize) -> u32 {
+        let mut val = read_u16(&self.raw, lo) as u32;
+        if self.raw.len() >= 64 {
+            val |= (read_u16(&self.raw, hi) as u32) << 16;
+        }
+        val
+    }
+
+    fn set_lo_hi16(&mut self, lo: usize, hi: usize, val: u32) {
+        write_u16(&mut self.raw, lo, val as u16);
+        if self.raw.len() >= 64 {
+            write_u16(&mut self.raw, hi, (val >> 16) as u16);
+        }
+    }
+
+    pub fn block_bitmap(&self) -> u64 {
+        self.get_lo_hi(0x0, 0x20)
+    }
+
+    pub fn inode_bitmap(&self) -> u64 {
+        self.get_lo_hi(0x4, 0x24)
+    }
+
+    pub fn inode_table(&self) -> u64 {
+        self.get_lo_hi(0x8, 0x28)
+    }
+
+    pub fn free_blocks_count(&self) -> u32 {
+        self.get_lo_hi16(0xC, 0x2C)
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u32) {
+        self.set_lo_hi16(0xC, 0x2C, count)
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        self.get_lo_hi16(0xE, 0x2E)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        self.set_lo_hi16(0xE, 0x2E, count)
+    }
+
+    pub fn used_dirs_count(&self) -> u32 {
+        self.get_lo_hi16(0x10, 0x30)
+    }
+
+    pub fn set_used_dirs_count(&mut self, count: u32) {
+        self.set_lo_hi16(0x10, 0x30, count)
+    }
+
+    pub fn flags(&self) -> u16 {
+        read_u16(&self.raw, 0x12)
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        write_u16(&mut self.raw, 0x12, flags)
+    }
+
+    pub fn itable_unused(&self) -> u32 {
+        self.get_lo_hi16(0x1C, 0x32)
+    }
+
+    pub fn set_itable_unused(&mut self, count: u32) {
+        self.set_lo_hi16(0x1C, 0x32, count)
+    }
+
+    pub fn set_block_bitmap_csum(&mut self, csum: u32) {
+        write_u16(&mut self.raw, 0x18, csum as u16);
+        if self.raw.len() >= 0x3A {
+            write_u16(&mut self.raw, 0x38, (csum >> 16) as u16);
+        }
+    }
+
+    pub fn set_inode_bitmap_csum(&mut self, csum: u32) {
+        write_u16(&mut self.raw, 0x1A, csum as u16);
+        if self.raw.len() >= 0x3C {
+            write_u16(&mut self.raw, 0x3A, (csum >> 16) as u16);
+        }
+    }
+}
+
+/// An inode, kept in its on-disk format.
+pub struct Inode {
+    pub ino: u32,
+    pub raw: Vec<u8>,
+}
+
+impl Inode {
+    /// Creates an empty inode of the given size.
+    pub fn empty(ino: u32, inode_size: usize) -> Self {
+        Self {
+            ino,
+            raw: vec![0; inode_size],
+        }
+    }
+
+    pub fn mode(&self) -> u16 {
+        read_u16(&self.raw, 0x0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        write_u16(&mut self.raw, 0x0, mode)
+    }
+
+    pub fn node_type(&self) -> Option<VfsNodeType> {
+        mode_to_type(self.mode())
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode() & S_IFMT == 0o040000
+    }
+
+    pub fn size(&self) -> u64 {
+        read_u32(&self.raw, 0x4) as u64 | (read_u32(&self.raw, 0x6C) as u64) << 32
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        write_u32(&mut self.raw, 0x4, size as u32);
+        write_u32(&mut self.raw, 0x6C, (size >> 32) as u32);
+    }
+
+    pub fn uid(&self) -> u32 {
+        read_u16(&self.raw, 0x2) as u32 | (read_u16(&self.raw, 0x78) as u32) << 16
+    }
+
+    pub fn set_uid(&mut self, uid: u32) {
+        write_u16(&mut self.raw, 0x2, uid as u16);
+        write_u16(&mut self.raw, 0x78, (uid >> 16) as u16);
+    }
+
+    pub fn gid(&self) -> u32 {
+        read_u16(&self.raw, 0x18) as u32 | (read_u16(&self.raw, 0x7A) as u32) << 16
+    }
+
+    pub fn set_gid(&mut self, gid: u32) {
+        write_u16(&mut self.raw, 0x18, gid as u16);
+        write_u16(&mut self.raw, 0x7A, (gid >> 16) as u16);
+    }
+
+    /// Returns the time at `field`, one of [`ATIME`], [`CTIME`] and
+    /// [`MTIME`], since the UNIX epoch. Times before it are returned as the
+    /// epoch.
+    ///
+    /// Without the extra field, times are in seconds and end in 2038.
+    pub fn time(&self, (secs_off, extra_off): (usize, usize)) -> Duration {
+        let mut secs = read_u32(&self.raw, secs_off) as i32 as i64;
+        let mut nanos = 0;
+        if self.has_extra(extra_off) {
+            let extra = read_u32(&self.raw, extra_off);
+            secs += ((extra & 3) as i64) << 32;
+            nanos = (extra >> 2).min(999_999_999);
+        }
+        Duration::new(secs.max(0) as u64, nanos)
+    }
+
+    pub fn set_time(&mut self, (secs_off, extra_off): (usize, usize), time: Duration) {
+        let secs = time.as_secs() as i64;
+        write_u32(&mut self.raw, secs_off, secs as u32);
+        if self.has_extra(extra_off) {
+            let epoch = ((secs - secs as i32 as i64) >> 32) as u32 & 3;
+            write_u32(&mut self.raw, extra_off, time.subsec_nanos() << 2 | epoch);
+        }
+    }
+
+    /// Sets the access, change and modification times to `time`.
+    pub fn set_all_times(&mut self, time: Duration) {
+        for field in [ATIME, CTIME, MTIME] {
+            self.set_time(field, time);
+        }
+    }
+
+    /// Sets the modification and change times to `time`.
+    pub fn set_modified(&mut self, time: Duration) {
+        self.set_time(MTIME, time);
+        self.set_time(CTIME, time);
+    }
+
+    /// Whether the extra field at `off` is within the extra space in use.
+    fn has_extra(&self, off: usize) -> bool {
+        off + 4 <= (GOOD_OLD_INODE_SIZE + self.extra_isize()).min(self.raw.len())
+    }
+
+    pub fn links_count(&self) -> u16 {
+        read_u16(&self.raw, 0x1A)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        write_u16(&mut self.raw, 0x1A, count)
+    }
+
+    pub fn flags(&self) -> u32 {
+        read_u32(&self.raw, 0x20)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        write_u32(&mut self.raw, 0x20, flags)
+    }
+
+    pub fn generation(&self) -> u32 {
+        read_u32(&self.raw, 0x64)
+    }
+
+    pub fn set_generation(&mut self, generation: u32) {
+        write_u32(&mut self.raw, 0x64, generation)
+    }
+
+    /// The number of 512-byte sectors occupied by the inode.
+    pub fn sectors(&self, block_size: usize) -> u64 {
+        let count = read_u32(&self.raw, 0x1C) as u64 | (read_u16(&self.raw, 0x74) as u64) << 32;
+        if self.flags() & INODE_FLAG_HUGE_FILE != 0 {
+            count * (block_size as u64 / 512)
+        } else {
+            count
+        }
+    }
+
+    pub fn set_sectors(&mut self, count: u64, block_size: usize) {
+        let count = if self.flags() & INODE_FLAG_HUGE_FILE != 0 {
+            count / (block_size as u64 / 512)
+        } else {
+            count
+        };
+        write_u32(&mut self.raw, 0x1C, count as u32);
+        write_u16(&mut self.raw, 0x74, (count >> 32) as u16);
+    }
+
+    /// The 60 bytes of `i_block`, the root of the extent tree, block map, or
+    /// the target of a fast symlink.
+    pub fn block_area(&self) -> &[u8] {
+        &self.raw[0x28..0x64]
+    }
+
+    pub fn block_area_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[0x28..0x64]
+    }
+
+    pub fn extra_isize(&self) -> usize {
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            read_u16(&self.raw, 0x80) as usize
+        } else {
+            0
+        }
+    }
+
+    pub fn set_extra_isize(&mut self, size: u16) {
+        if self.raw.len() > GOOD_OLD_INODE_SIZE {
+            write_u16(&mut self.raw, 0x80, size)
+        }
+    }
+
+    /// The seed of the checksums of the inode and its metadata blocks.
+    pub fn csum_seed(&self, fs_seed: u32) -> u32 {
+        let crc = crc32c(fs_seed, &self.ino.to_le_bytes());
+        crc32c(crc, &self.generation().to_le_bytes())
+    }
+
+    /// Updates the checksum of the inode.
+    pub fn update_csum(&mut self, fs_seed: u32) {
+        let has_hi = self.extra_isize() >= 4;
+        let raw = &self.raw;
+        let mut crc = crc32c(self.csum_seed(fs_seed), &raw[..0x7C]);
+        crc = crc32c(crc, &[0; 2]);
+        crc = crc32c(crc, &raw[0x7E..GOOD_OLD_INODE_SIZE]);
+        if raw.len() > GOOD_OLD_INODE_SIZE {
+            crc = crc32c(crc, &raw[GOOD_OLD_INODE_SIZE..0x82]);
+            let mut offset = 0x82;
+            if has_hi {
+                crc = crc32c(crc, &[0; 2]);
+                offset += 2;
+            }
+            crc = crc32c(crc, &raw[offset..]);
+        }
+        write_u16(&mut self.raw, 0x7C, crc as u16);
+        if has_hi {
+            write_u16(&mut self.raw, 0x82, (crc >> 16) as u16);
+        }
+    }
+}
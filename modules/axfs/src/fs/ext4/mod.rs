@@ -0,0 +1,273 @@
+//! A read-write [ext4] filesystem.
+//!
+//! Supported are extent-mapped files, linear directories (hash tree indexes
+//! are dropped on modification), `flex_bg`, `64bit`, and metadata checksums.
+//! The journal is not used, so it must be clean, or the filesystem is mounted
+//! read-only. Files with the ext2/3 block map can be read but not extended.
+//!
+//! [ext4]: https://docs.kernel.org/filesystems/ext4/index.html
+
+mod crc;
+mod dir;
+mod inode;
+mod layout;
+mod namei;
+mod volume;
+
+use alloc::sync::Arc;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+use axsync::Mutex;
+use core::time::Duration;
+
+use self::layout::{ATIME, CTIME, Inode, MTIME, ROOT_INO};
+use self::volume::Ext4Volume;
+use crate::dev::Disk;
+use crate::statfs::FileSystemStats;
+
+/// An ext4 filesystem on a disk.
+pub struct Ext4FileSystem {
+    vol: Arc<Mutex<Ext4Volume>>,
+}
+
+/// A file or directory of [`Ext4FileSystem`].
+pub struct Ext4Node {
+    vol: Arc<Mutex<Ext4Volume>>,
+    ino: u32,
+}
+
+impl Ext4FileSystem {
+    /// Whether there is an ext4 (or ext2/3) filesystem on the disk.
+    pub fn probe(disk: &Disk) -> bool {
+        Ext4Volume::probe(disk)
+    }
+
+    /// Opens the ext4 filesystem on the disk.
+    pub fn open(disk: Disk) -> VfsResult<Arc<Self>> {
+        let vol = Ext4Volume::open(disk)?;
+        Ok(Arc::new(Self {
+            vol: Arc::new(Mutex::new(vol)),
+        }))
+    }
+
+    fn new_node(&self, ino: u32) -> VfsNodeRef {
+        Arc::new(Ext4Node {
+            vol: self.vol.clone(),
+            ino,
+        })
+    }
+}
+
+impl VfsOps for Ext4FileSystem {
+    fn umount(&self) -> VfsResult {
+        self.vol.lock().sync()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.new_node(ROOT_INO)
+    }
+}
+
+impl Ext4Node {
+    /// Returns the usage of the filesystem, from the counters of the
+    /// superblock. Blocks reserved for root are not available to others.
+    pub fn stats(&self) -> VfsResult<FileSystemStats> {
+        let vol = self.vol.lock();
+        let sb = &vol.sb;
+        let free = sb.free_blocks_count();
+        Ok(FileSystemStats {
+            block_size: sb.block_size as u64,
+            blocks: sb.blocks_count - sb.first_data_block,
+            blocks_free: free,
+            blocks_avail: free.saturating_sub(sb.reserved_blocks_count()),
+            files: sb.inodes_count() as u64,
+            files_free: sb.free_inodes_count() as u64,
+            read_only: vol.read_only,
+            ..Default::default()
+        })
+    }
+
+    /// Changes the metadata of the inode by `f`, and sets its change time.
+    fn change_inode(&self, f: impl FnOnce(&mut Inode)) -> VfsResult {
+        let mut vol = self.vol.lock();
+        vol.check_writable()?;
+        let mut inode = vol.read_inode(self.ino)?;
+        f(&mut inode);
+        inode.set_time(CTIME, crate::clock::wall_time());
+        vol.write_inode(&mut inode)?;
+        vol.flush_metadata()
+    }
+
+    fn new_node(&self, ino: u32) -> VfsNodeRef {
+        Arc::new(Self {
+            vol: self.vol.clone(),
+            ino,
+        })
+    }
+}
+
+impl VfsNodeOps for Ext4Node {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        let ty = inode.node_type().ok_or(VfsError::InvalidData)?;
+        let perm = VfsNodePerm::from_bits_truncate(inode.mode() & 0o777);
+        let blocks = inode.sectors(vol.block_size());
+        Ok(VfsNodeAttr::new(perm, ty, inode.size(), blocks))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let vol = self.vol.lock();
+        let inode = vol.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        vol.read_data(&inode, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut vol = self.vol.lock();
+        let mut inode = vol.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        inode.set_modified(crate::clock::wall_time());
+        let res = vol.write_data(&mut inode, offset, buf);
+        vol.flush_metadata()?;
+        res
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.vol.lock().sync()
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut vol = self.vol.lock();
+        let mut inode = vol.read_inode(self.ino)?;
+        if inode.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+        inode.set_modified(crate::clock::wall_time());
+        let res = vol.truncate_data(&mut inode, size);
+        vol.flush_metadata()?;
+        res
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.ino == ROOT_INO {
+            return None;
+        }
+        let ino = self.vol.lock().resolve(self.ino, "..").ok()?;
+        Some(self.new_node(ino))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at ext4: {}", path);
+        let ino = self.vol.lock().resolve(self.ino, path)?;
+        Ok(self.new_node(ino))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at ext4: {}", ty, path);
+        let perm = match ty {
+            VfsNodeType::File => 0o644,
+            VfsNodeType::Dir => 0o755,
+            _ => return Err(VfsError::Unsupported),
+        };
+        let mut vol = self.vol.lock();
+        let (mut parent, name) = match vol.resolve_parent(self.ino, path) {
+            Err(VfsError::InvalidInput) => return Ok(()), // `.` or `..`
+            res => res?,
+        };
+        if let Some(ino) = vol.lookup(&parent, name)? {
+            return if vol.read_inode(ino)?.node_type() == Some(ty) {
+                Ok(())
+            } else {
+                Err(VfsError::AlreadyExists)
+            };
+        }
+        vol.create(&mut parent, name, ty, perm)?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at ext4: {}", path);
+        let mut vol = self.vol.lock();
+        let (mut parent, name) = vol.resolve_parent(self.ino, path)?;
+        vol.remove(&mut parent, name)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let vol = self.vol.lock();
+        let dir = vol.read_inode(self.ino)?;
+        if !dir.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let entries = vol.read_dir(&dir, start_idx, dirents.len())?;
+        for (entry, out_entry) in entries.iter().zip(dirents.iter_mut()) {
+            let ty = match entry.ty {
+                Some(ty) => ty,
+                None => vol
+                    .read_inode(entry.ino)?
+                    .node_type()
+                    .ok_or(VfsError::InvalidData)?,
+            };
+            *out_entry = VfsDirEntry::new(&entry.name, ty);
+        }
+        Ok(entries.len())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!(
+            "rename at ext4, src_path: {}, dst_path: {}",
+            src_path, dst_path
+        );
+        let mut vol = self.vol.lock();
+        let (src_dir, src_name) = vol.resolve_parent(self.ino, src_path)?;
+        let (dst_dir, dst_name) = vol.resolve_parent(self.ino, dst_path)?;
+        vol.rename(src_dir.ino, src_name, dst_dir.ino, dst_name)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl crate::fs::NodeExt for Ext4Node {
+    fn ino(&self) -> Option<u64> {
+        Some(self.ino as u64)
+    }
+
+    fn nlink(&self) -> Option<u64> {
+        let inode = self.vol.lock().read_inode(self.ino).ok()?;
+        Some(inode.links_count() as u64)
+    }
+
+    fn owner(&self) -> Option<(u32, u32)> {
+        let inode = self.vol.lock().read_inode(self.ino).ok()?;
+        Some((inode.uid(), inode.gid()))
+    }
+
+    fn times(&self) -> Option<(Duration, Duration, Duration)> {
+        let inode = self.vol.lock().read_inode(self.ino).ok()?;
+        Some((inode.time(ATIME), inode.time(MTIME), inode.time(CTIME)))
+    }
+
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        self.change_inode(|inode| inode.set_mode(inode.mode() & !0o777 | perm.bits()))
+    }
+
+    fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult {
+        self.change_inode(|inode| {
+            inode.set_uid(uid.unwrap_or(inode.uid()));
+            inode.set_gid(gid.unwrap_or(inode.gid()));
+        })
+    }
+
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        self.change_inode(|inode| {
+            inode.set_time(ATIME, atime.unwrap_or(inode.time(ATIME)));
+            inode.set_time(MTIME, mtime.unwrap_or(inode.time(MTIME)));
+        })
+    }
+}
@@ -0,0 +1,240 @@
+//! Path resolution and namespace operations.
+
+use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+
+use super::dir::MAX_NAME_LEN;
+use super::inode::init_extents;
+use super::layout::*;
+use super::volume::Ext4Volume;
+
+/// The maximum number of links of an inode, directories with more
+/// subdirectories have the link count 1 with `dir_nlink`.
+const MAX_LINKS: u16 = 65000;
+
+impl Ext4Volume {
+    /// Resolves `path` relative to the directory `dir`.
+    pub fn resolve(&self, mut ino: u32, path: &str) -> VfsResult<u32> {
+        for name in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+            let dir = self.read_inode(ino)?;
+            if !dir.is_dir() {
+                return Err(VfsError::NotADirectory);
+            }
+            ino = self.lookup(&dir, name)?.ok_or(VfsError::NotFound)?;
+        }
+        Ok(ino)
+    }
+
+    /// Resolves the parent directory of `path` relative to `dir`, returns it
+    /// with the last component of `path`.
+    pub fn resolve_parent<'a>(&self, dir: u32, path: &'a str) -> VfsResult<(Inode, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let parent = self.read_inode(self.resolve(dir, parent)?)?;
+        if !parent.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok((parent, name))
+    }
+
+    /// Creates a node `name` of type `ty` in the directory `parent`.
+    pub fn create(
+        &mut self,
+        parent: &mut Inode,
+        name: &str,
+        ty: VfsNodeType,
+        perm: u16,
+    ) -> VfsResult<u32> {
+        self.check_writable()?;
+        if name.len() > MAX_NAME_LEN {
+            return Err(VfsError::InvalidInput);
+        }
+        if self.lookup(parent, name)?.is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+        let is_dir = ty == VfsNodeType::Dir;
+        let ino = self.alloc_inode(parent.ino, is_dir)?;
+
+        let mut inode = self.read_inode(ino)?;
+        let generation = inode.generation().wrapping_add(1);
+        inode.raw.fill(0);
+        inode.set_mode(type_to_mode(ty) | perm);
+        inode.set_links_count(if is_dir { 2 } else { 1 });
+        inode.set_generation(generation);
+        inode.set_all_times(crate::clock::wall_time());
+        if inode.raw.len() > GOOD_OLD_INODE_SIZE {
+            let extra = read_u16(&self.sb.raw, 0x15E).max(32) as usize;
+            inode.set_extra_isize(extra.min(inode.raw.len() - GOOD_OLD_INODE_SIZE) as u16);
+        }
+        if matches!(ty, VfsNodeType::File | VfsNodeType::Dir) {
+            init_extents(&mut inode);
+            self.require_feature(INCOMPAT_EXTENTS, 0);
+        }
+
+        let result = (|| {
+            if is_dir {
+                self.init_dir(&mut inode, parent.ino)?;
+            } else {
+                self.write_inode(&mut inode)?;
+            }
+            self.add_entry(parent, name, ino, ty)
+        })();
+        if let Err(e) = result {
+            self.release_inode(&mut inode)?;
+            self.flush_metadata()?;
+            return Err(e);
+        }
+        if is_dir {
+            self.inc_links(parent)?;
+        }
+        self.flush_metadata()?;
+        Ok(ino)
+    }
+
+    /// Removes the entry `name` from the directory `parent`, and releases the
+    /// inode if it has no other links.
+    pub fn remove(&mut self, parent: &mut Inode, name: &str) -> VfsResult {
+        self.check_writable()?;
+        let ino = self.lookup(parent, name)?.ok_or(VfsError::NotFound)?;
+        let mut inode = self.read_inode(ino)?;
+        if inode.is_dir() {
+            if !self.dir_is_empty(&inode)? {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+            self.remove_entry(parent, name)?;
+            self.dec_links(parent)?;
+            self.release_inode(&mut inode)?;
+        } else {
+            self.remove_entry(parent, name)?;
+            self.dec_links(&mut inode)?;
+        }
+        self.flush_metadata()
+    }
+
+    /// Moves the entry `src_name` in `src_dir` to `dst_name` in `dst_dir`,
+    /// replaces the destination if it exists.
+    pub fn rename(
+        &mut self,
+        src_dir: u32,
+        src_name: &str,
+        dst_dir: u32,
+        dst_name: &str,
+    ) -> VfsResult {
+        self.check_writable()?;
+        if dst_name.len() > MAX_NAME_LEN {
+            return Err(VfsError::InvalidInput);
+        }
+        let src_parent = self.read_inode(src_dir)?;
+        let ino = self
+            .lookup(&src_parent, src_name)?
+            .ok_or(VfsError::NotFound)?;
+        if src_dir == dst_dir && src_name == dst_name {
+            return Ok(());
+        }
+        let mut inode = self.read_inode(ino)?;
+        let ty = inode.node_type().ok_or(VfsError::InvalidData)?;
+        if inode.is_dir() && self.is_ancestor(ino, dst_dir)? {
+            return Err(VfsError::InvalidInput); // move a directory into itself
+        }
+
+        let mut dst_parent = self.read_inode(dst_dir)?;
+        if let Some(old) = self.lookup(&dst_parent, dst_name)? {
+            if old == ino {
+                return Ok(()); // hard links of the same inode
+            }
+            let mut old_inode = self.read_inode(old)?;
+            match (inode.is_dir(), old_inode.is_dir()) {
+                (false, true) => return Err(VfsError::IsADirectory),
+                (true, false) => return Err(VfsError::NotADirectory),
+                (true, true) if !self.dir_is_empty(&old_inode)? => {
+                    return Err(VfsError::DirectoryNotEmpty);
+                }
+                _ => {}
+            }
+            self.set_entry(&mut dst_parent, dst_name, ino)?;
+            if old_inode.is_dir() {
+                self.dec_links(&mut dst_parent)?;
+                self.release_inode(&mut old_inode)?;
+            } else {
+                self.dec_links(&mut old_inode)?;
+            }
+        } else {
+            self.add_entry(&mut dst_parent, dst_name, ino, ty)?;
+        }
+
+        // re-read, in case it is the same directory as `dst_parent`
+        let mut src_parent = self.read_inode(src_dir)?;
+        self.remove_entry(&mut src_parent, src_name)?;
+        if inode.is_dir() && src_dir != dst_dir {
+            self.set_entry(&mut inode, "..", dst_dir)?;
+            self.dec_links(&mut src_parent)?;
+            self.inc_links(&mut self.read_inode(dst_dir)?)?;
+        }
+        self.flush_metadata()
+    }
+
+    /// Whether the directory `ancestor` is `dir` or one of its ancestors.
+    fn is_ancestor(&self, ancestor: u32, mut dir: u32) -> VfsResult<bool> {
+        loop {
+            if dir == ancestor {
+                return Ok(true);
+            }
+            if dir == ROOT_INO {
+                return Ok(false);
+            }
+            dir = self.resolve(dir, "..")?;
+        }
+    }
+
+    fn inc_links(&mut self, inode: &mut Inode) -> VfsResult {
+        let links = inode.links_count();
+        if inode.is_dir() && (links == 1 || links >= MAX_LINKS - 1) {
+            // too many subdirectories to count
+            inode.set_links_count(1);
+            self.require_feature(0, RO_COMPAT_DIR_NLINK);
+        } else if links >= MAX_LINKS {
+            return Err(VfsError::InvalidInput);
+        } else {
+            inode.set_links_count(links + 1);
+        }
+        self.write_inode(inode)
+    }
+
+    /// Decreases the link count of `inode`, and releases it if no links
+    /// remain. Directories are never released here.
+    fn dec_links(&mut self, inode: &mut Inode) -> VfsResult {
+        let links = inode.links_count();
+        if inode.is_dir() {
+            if links > 2 {
+                inode.set_links_count(links - 1);
+            }
+            self.write_inode(inode)
+        } else if links > 1 {
+            inode.set_links_count(links - 1);
+            self.write_inode(inode)
+        } else {
+            self.release_inode(inode)
+        }
+    }
+
+    /// Frees the data and the inode itself.
+    fn release_inode(&mut self, inode: &mut Inode) -> VfsResult {
+        if inode.flags() & INODE_FLAG_EXTENTS != 0 {
+            self.truncate_data(inode, 0)?;
+        } else if inode.sectors(self.block_size()) != 0 {
+            warn!(
+                "ext4: leaking blocks of inode {} with a block map",
+                inode.ino
+            );
+        }
+        let is_dir = inode.is_dir();
+        inode.set_links_count(0);
+        // no deletion time is recorded, clear the mode instead to mark it
+        // as deleted
+        inode.set_mode(0);
+        self.write_inode(inode)?;
+        self.free_inode(inode.ino, is_dir)
+    }
+}
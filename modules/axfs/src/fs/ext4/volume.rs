@@ -0,0 +1,443 @@
+//! The superblock, group descriptors, inodes and allocation bitmaps.
+
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsResult};
+
+use super::crc::{crc16, crc32c};
+use super::layout::*;
+use crate::dev::Disk;
+
+/// An opened ext4 filesystem, all operations are done with inode numbers.
+pub struct Ext4Volume {
+    pub(super) disk: Disk,
+    pub(super) sb: SuperBlock,
+    groups: Vec<GroupDesc>,
+    dirty_groups: BTreeSet<u32>,
+    sb_dirty: bool,
+    /// Writes are refused if there are unknown features or an unrecovered
+    /// journal.
+    pub(super) read_only: bool,
+}
+
+impl Ext4Volume {
+    /// Whether there is an ext4 (or ext2/3) filesystem on the disk.
+    pub fn probe(disk: &Disk) -> bool {
+        let mut magic = [0; 2];
+        disk.read_at(SUPER_BLOCK_OFFSET + 0x38, &mut magic).is_ok()
+            && u16::from_le_bytes(magic) == EXT4_MAGIC
+    }
+
+    pub fn open(disk: Disk) -> VfsResult<Self> {
+        let mut raw = vec![0; SUPER_BLOCK_SIZE];
+        disk.read_at(SUPER_BLOCK_OFFSET, &mut raw)
+            .map_err(|_| VfsError::Io)?;
+        let sb = SuperBlock::parse(raw)?;
+        if sb.blocks_count * sb.block_size as u64 > disk.size() {
+            warn!("ext4: the filesystem is larger than the disk");
+            return Err(VfsError::InvalidData);
+        }
+
+        let incompat = sb.feature_incompat();
+        if incompat & !INCOMPAT_SUPPORTED != 0 {
+            warn!(
+                "ext4: unsupported incompatible features {:#x}",
+                incompat & !INCOMPAT_SUPPORTED
+            );
+            return Err(VfsError::Unsupported);
+        }
+        let mut read_only = false;
+        if incompat & INCOMPAT_RECOVER != 0 {
+            warn!("ext4: the journal needs recovery, mounting read-only");
+            read_only = true;
+        }
+        let ro_compat = sb.feature_ro_compat();
+        if ro_compat & !RO_COMPAT_SUPPORTED != 0 {
+            warn!(
+                "ext4: unsupported read-only compatible features {:#x}, mounting read-only",
+                ro_compat & !RO_COMPAT_SUPPORTED
+            );
+            read_only = true;
+        }
+
+        let gdt_pos = (sb.first_data_block + 1) * sb.block_size as u64;
+        let mut gdt = vec![0; sb.group_count as usize * sb.desc_size];
+        disk.read_at(gdt_pos, &mut gdt).map_err(|_| VfsError::Io)?;
+        let groups = gdt
+            .chunks_exact(sb.desc_size)
+            .map(|raw| GroupDesc { raw: raw.to_vec() })
+            .collect();
+        debug!(
+            "ext4: block size {}, {} blocks, {} groups",
+            sb.block_size, sb.blocks_count, sb.group_count
+        );
+        Ok(Self {
+            disk,
+            sb,
+            groups,
+            dirty_groups: BTreeSet::new(),
+            sb_dirty: false,
+            read_only,
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.sb.block_size
+    }
+
+    pub fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            Err(VfsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads `buf.len()` bytes at `offset` within the block `block`.
+    pub fn read_block(&self, block: u64, offset: usize, buf: &mut [u8]) -> VfsResult {
+        debug_assert!(offset + buf.len() <= self.sb.block_size);
+        self.disk
+            .read_at(block * self.sb.block_size as u64 + offset as u64, buf)
+            .map_err(|_| VfsError::Io)
+    }
+
+    /// Writes `buf` at `offset` within the block `block`.
+    pub fn write_block(&self, block: u64, offset: usize, buf: &[u8]) -> VfsResult {
+        debug_assert!(offset + buf.len() <= self.sb.block_size);
+        self.disk
+            .write_at(block * self.sb.block_size as u64 + offset as u64, buf)
+            .map_err(|_| VfsError::Io)
+    }
+
+    /// Reads `buf.len()` bytes starting at the block `block`, across blocks.
+    pub fn read_blocks(&self, block: u64, offset: usize, buf: &mut [u8]) -> VfsResult {
+        self.disk
+            .read_at(block * self.sb.block_size as u64 + offset as u64, buf)
+            .map_err(|_| VfsError::Io)
+    }
+
+    /// Writes `buf` starting at the block `block`, across blocks.
+    pub fn write_blocks(&self, block: u64, offset: usize, buf: &[u8]) -> VfsResult {
+        self.disk
+            .write_at(block * self.sb.block_size as u64 + offset as u64, buf)
+            .map_err(|_| VfsError::Io)
+    }
+
+    /// Writes modified group descriptors and the superblock back.
+    pub fn flush_metadata(&mut self) -> VfsResult {
+        let gdt_pos = (self.sb.first_data_block + 1) * self.sb.block_size as u64;
+        for group in core::mem::take(&mut self.dirty_groups) {
+            self.update_group_csum(group);
+            let pos = gdt_pos + group as u64 * self.sb.desc_size as u64;
+            self.disk
+                .write_at(pos, &self.groups[group as usize].raw)
+                .map_err(|_| VfsError::Io)?;
+        }
+        if self.sb_dirty {
+            self.sb_dirty = false;
+            let raw = self.sb.to_disk();
+            self.disk
+                .write_at(SUPER_BLOCK_OFFSET, raw)
+                .map_err(|_| VfsError::Io)?;
+        }
+        Ok(())
+    }
+
+    /// Writes all modifications back to the disk.
+    pub fn sync(&mut self) -> VfsResult {
+        self.flush_metadata()?;
+        self.disk.sync().map_err(|_| VfsError::Io)
+    }
+
+    /// Sets a feature flag that is required by new metadata.
+    pub fn require_feature(&mut self, incompat: u32, ro_compat: u32) {
+        if self.sb.feature_incompat() & incompat != incompat {
+            self.sb.set_feature_incompat(incompat);
+            self.sb_dirty = true;
+        }
+        if self.sb.feature_ro_compat() & ro_compat != ro_compat {
+            self.sb.set_feature_ro_compat(ro_compat);
+            self.sb_dirty = true;
+        }
+    }
+
+    fn update_group_csum(&mut self, group: u32) {
+        let sb = &self.sb;
+        let desc = &mut self.groups[group as usize];
+        let csum = if let Some(seed) = sb.csum_seed {
+            let mut crc = crc32c(seed, &group.to_le_bytes());
+            crc = crc32c(crc, &desc.raw[..0x1E]);
+            crc = crc32c(crc, &[0; 2]);
+            crc = crc32c(crc, &desc.raw[0x20..]);
+            crc as u16
+        } else if sb.has_gdt_csum() {
+            let mut crc = crc16(!0, &sb.raw[0x68..0x78]);
+            crc = crc16(crc, &group.to_le_bytes());
+            crc = crc16(crc, &desc.raw[..0x1E]);
+            crc16(crc, &desc.raw[0x20..])
+        } else {
+            return;
+        };
+        write_u16(&mut desc.raw, 0x1E, csum);
+    }
+
+    fn inode_pos(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.sb.inodes_per_group * self.sb.group_count {
+            return Err(VfsError::InvalidData);
+        }
+        let group = (ino - 1) / self.sb.inodes_per_group;
+        let index = (ino - 1) % self.sb.inodes_per_group;
+        let table = self.groups[group as usize].inode_table();
+        Ok(table * self.sb.block_size as u64 + index as u64 * self.sb.inode_size as u64)
+    }
+
+    pub fn read_inode(&self, ino: u32) -> VfsResult<Inode> {
+        let mut inode = Inode::empty(ino, self.sb.inode_size);
+        self.disk
+            .read_at(self.inode_pos(ino)?, &mut inode.raw)
+            .map_err(|_| VfsError::Io)?;
+        Ok(inode)
+    }
+
+    pub fn write_inode(&mut self, inode: &mut Inode) -> VfsResult {
+        if inode.size() >= 1 << 31 {
+            self.require_feature(0, RO_COMPAT_LARGE_FILE);
+        }
+        if let Some(seed) = self.sb.csum_seed {
+            inode.update_csum(seed);
+        }
+        self.disk
+            .write_at(self.inode_pos(inode.ino)?, &inode.raw)
+            .map_err(|_| VfsError::Io)
+    }
+
+    /// The seed of the checksums of metadata blocks of `inode`.
+    pub fn inode_csum_seed(&self, inode: &Inode) -> Option<u32> {
+        self.sb.csum_seed.map(|seed| inode.csum_seed(seed))
+    }
+
+    /// Reads the block bitmap of `group`, builds it if uninitialized.
+    fn read_block_bitmap(&self, group: u32) -> VfsResult<Vec<u8>> {
+        let bs = self.sb.block_size;
+        let desc = &self.groups[group as usize];
+        let mut bitmap = vec![0; bs];
+        if desc.flags() & BG_BLOCK_UNINIT == 0 {
+            self.read_block(desc.block_bitmap(), 0, &mut bitmap)?;
+            return Ok(bitmap);
+        }
+
+        // Only metadata blocks are in use, they may also belong to other
+        // groups with `flex_bg`.
+        let first = self.sb.group_first_block(group);
+        let count = self.sb.blocks_in_group(group) as u64;
+        let mut mark = |start: u64, len: u64| {
+            for block in start.max(first)..(start + len).min(first + count) {
+                let bit = (block - first) as usize;
+                bitmap[bit / 8] |= 1 << (bit % 8);
+            }
+        };
+        if self.sb.group_has_super(group) {
+            mark(
+                first,
+                1 + self.sb.gdt_blocks() + self.sb.reserved_gdt_blocks(),
+            );
+        }
+        let itable_blocks = self.sb.inode_table_blocks();
+        for desc in &self.groups {
+            mark(desc.block_bitmap(), 1);
+            mark(desc.inode_bitmap(), 1);
+            mark(desc.inode_table(), itable_blocks);
+        }
+        mark_bitmap_end(&mut bitmap, count as usize);
+        Ok(bitmap)
+    }
+
+    fn write_block_bitmap(&mut self, group: u32, bitmap: &[u8]) -> VfsResult {
+        let desc = &mut self.groups[group as usize];
+        desc.set_flags(desc.flags() & !BG_BLOCK_UNINIT);
+        if let Some(seed) = self.sb.csum_seed {
+            let len = self.sb.blocks_per_group as usize / 8;
+            desc.set_block_bitmap_csum(crc32c(seed, &bitmap[..len]));
+        }
+        let block = desc.block_bitmap();
+        self.dirty_groups.insert(group);
+        self.write_block(block, 0, bitmap)
+    }
+
+    /// Reads the inode bitmap of `group`, builds it if uninitialized.
+    fn read_inode_bitmap(&self, group: u32) -> VfsResult<Vec<u8>> {
+        let desc = &self.groups[group as usize];
+        let mut bitmap = vec![0; self.sb.block_size];
+        if desc.flags() & BG_INODE_UNINIT == 0 {
+            self.read_block(desc.inode_bitmap(), 0, &mut bitmap)?;
+        } else {
+            mark_bitmap_end(&mut bitmap, self.sb.inodes_per_group as usize);
+        }
+        Ok(bitmap)
+    }
+
+    fn write_inode_bitmap(&mut self, group: u32, bitmap: &[u8]) -> VfsResult {
+        let desc = &mut self.groups[group as usize];
+        desc.set_flags(desc.flags() & !BG_INODE_UNINIT);
+        if let Some(seed) = self.sb.csum_seed {
+            let len = self.sb.inodes_per_group as usize / 8;
+            desc.set_inode_bitmap_csum(crc32c(seed, &bitmap[..len]));
+        }
+        let block = desc.inode_bitmap();
+        self.dirty_groups.insert(group);
+        self.write_block(block, 0, bitmap)
+    }
+
+    /// Allocates up to `max_count` contiguous blocks, near `goal` if possible.
+    ///
+    /// Returns the first block and the number of blocks allocated.
+    pub fn alloc_blocks(&mut self, goal: u64, max_count: u32) -> VfsResult<(u64, u32)> {
+        self.check_writable()?;
+        let goal = goal.clamp(self.sb.first_data_block, self.sb.blocks_count - 1);
+        let goal_group =
+            ((goal - self.sb.first_data_block) / self.sb.blocks_per_group as u64) as u32;
+        for i in 0..self.sb.group_count {
+            let group = (goal_group + i) % self.sb.group_count;
+            if self.groups[group as usize].free_blocks_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_block_bitmap(group)?;
+            let count = self.sb.blocks_in_group(group) as usize;
+            let start = if i == 0 {
+                (goal - self.sb.group_first_block(group)) as usize
+            } else {
+                0
+            };
+            let Some(first) = (start..count)
+                .chain(0..start)
+                .find(|&bit| bitmap[bit / 8] & (1 << (bit % 8)) == 0)
+            else {
+                continue;
+            };
+            let mut len = 0;
+            while len < max_count as usize
+                && first + len < count
+                && bitmap[(first + len) / 8] & (1 << ((first + len) % 8)) == 0
+            {
+                let bit = first + len;
+                bitmap[bit / 8] |= 1 << (bit % 8);
+                len += 1;
+            }
+            self.write_block_bitmap(group, &bitmap)?;
+            let desc = &mut self.groups[group as usize];
+            desc.set_free_blocks_count(desc.free_blocks_count() - len as u32);
+            let free = self.sb.free_blocks_count();
+            self.sb
+                .set_free_blocks_count(free.saturating_sub(len as u64));
+            self.sb_dirty = true;
+            return Ok((self.sb.group_first_block(group) + first as u64, len as u32));
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    /// Frees `count` blocks starting at `start`.
+    pub fn free_blocks(&mut self, start: u64, count: u64) -> VfsResult {
+        let end = start + count;
+        if start < self.sb.first_data_block || end > self.sb.blocks_count {
+            return Err(VfsError::InvalidData);
+        }
+        let mut block = start;
+        while block < end {
+            let group =
+                ((block - self.sb.first_data_block) / self.sb.blocks_per_group as u64) as u32;
+            let first = self.sb.group_first_block(group);
+            let group_end = end.min(first + self.sb.blocks_in_group(group) as u64);
+            let mut bitmap = self.read_block_bitmap(group)?;
+            let mut freed = 0;
+            for bit in (block - first) as usize..(group_end - first) as usize {
+                if bitmap[bit / 8] & (1 << (bit % 8)) != 0 {
+                    bitmap[bit / 8] &= !(1 << (bit % 8));
+                    freed += 1;
+                } else {
+                    warn!("ext4: freeing free block {}", first + bit as u64);
+                }
+            }
+            self.write_block_bitmap(group, &bitmap)?;
+            let desc = &mut self.groups[group as usize];
+            desc.set_free_blocks_count(desc.free_blocks_count() + freed);
+            let free = self.sb.free_blocks_count();
+            self.sb.set_free_blocks_count(free + freed as u64);
+            self.sb_dirty = true;
+            block = group_end;
+        }
+        Ok(())
+    }
+
+    /// Allocates an inode, preferably in the same group as `parent`.
+    pub fn alloc_inode(&mut self, parent: u32, is_dir: bool) -> VfsResult<u32> {
+        self.check_writable()?;
+        let ipg = self.sb.inodes_per_group;
+        let parent_group = (parent - 1) / ipg;
+        for i in 0..self.sb.group_count {
+            let group = (parent_group + i) % self.sb.group_count;
+            if self.groups[group as usize].free_inodes_count() == 0 {
+                continue;
+            }
+            let mut bitmap = self.read_inode_bitmap(group)?;
+            let first = if group == 0 { self.sb.first_ino - 1 } else { 0 };
+            let Some(index) =
+                (first as usize..ipg as usize).find(|&bit| bitmap[bit / 8] & (1 << (bit % 8)) == 0)
+            else {
+                continue;
+            };
+            bitmap[index / 8] |= 1 << (index % 8);
+            self.write_inode_bitmap(group, &bitmap)?;
+
+            let has_gdt_csum = self.sb.has_gdt_csum();
+            let desc = &mut self.groups[group as usize];
+            desc.set_free_inodes_count(desc.free_inodes_count() - 1);
+            if is_dir {
+                desc.set_used_dirs_count(desc.used_dirs_count() + 1);
+            }
+            if has_gdt_csum && index as u32 >= ipg - desc.itable_unused() {
+                desc.set_itable_unused(ipg - index as u32 - 1);
+            }
+            let free = self.sb.free_inodes_count();
+            self.sb.set_free_inodes_count(free - 1);
+            self.sb_dirty = true;
+            return Ok(group * ipg + index as u32 + 1);
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    /// Frees the inode `ino`.
+    pub fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult {
+        let ipg = self.sb.inodes_per_group;
+        let group = (ino - 1) / ipg;
+        let index = ((ino - 1) % ipg) as usize;
+        let mut bitmap = self.read_inode_bitmap(group)?;
+        if bitmap[index / 8] & (1 << (index % 8)) == 0 {
+            warn!("ext4: freeing free inode {}", ino);
+            return Ok(());
+        }
+        bitmap[index / 8] &= !(1 << (index % 8));
+        self.write_inode_bitmap(group, &bitmap)?;
+        let desc = &mut self.groups[group as usize];
+        desc.set_free_inodes_count(desc.free_inodes_count() + 1);
+        if is_dir {
+            desc.set_used_dirs_count(desc.used_dirs_count().saturating_sub(1));
+        }
+        let free = self.sb.free_inodes_count();
+        self.sb.set_free_inodes_count(free + 1);
+        self.sb_dirty = true;
+        Ok(())
+    }
+
+    /// The first block of the group of the inode `ino`, a good goal for
+    /// allocating its data blocks.
+    pub fn inode_goal(&self, ino: u32) -> u64 {
+        self.sb
+            .group_first_block((ino - 1) / self.sb.inodes_per_group)
+    }
+}
+
+/// Marks the bits from `count` to the end of the bitmap block as in use.
+fn mark_bitmap_end(bitmap: &mut [u8], count: usize) {
+    for bit in count..bitmap.len() * 8 {
+        bitmap[bit / 8] |= 1 << (bit % 8);
+    }
+}
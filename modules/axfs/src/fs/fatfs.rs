@@ -1,22 +1,42 @@
 use alloc::sync::Arc;
-use core::cell::UnsafeCell;
+use core::{cell::UnsafeCell, time::Duration};
 
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Dir, File, LossyOemCpConverter, Read, Seek, SeekFrom, Time, Write};
 
 use crate::dev::Disk;
+use crate::statfs::FileSystemStats;
 
 const BLOCK_SIZE: usize = 512;
 
+/// Days from the UNIX epoch to 1980-01-01 and 2108-01-01, FAT dates are in
+/// between.
+const FAT_DAYS: core::ops::Range<u64> = 3652..50403;
+const SECS_PER_DAY: u64 = 86400;
+
+/// Provides the wall time for timestamps of FAT files, which are in UTC.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WallTimeProvider;
+
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    // drop the root directory before the filesystem it borrows
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    inner: FatFs,
+    /// Shares the block cache with `inner`, to write it back on unmount.
+    disk: Disk,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+type FatFs = fatfs::FileSystem<Disk, WallTimeProvider, LossyOemCpConverter>;
+
+pub struct FileWrapper<'a>(Mutex<File<'a, Disk, WallTimeProvider, LossyOemCpConverter>>);
+/// A directory, the root one also refers to the filesystem for
+/// [`DirWrapper::stats`].
+pub struct DirWrapper<'a>(
+    Dir<'a, Disk, WallTimeProvider, LossyOemCpConverter>,
+    Option<&'a FatFs>,
+);
 
 unsafe impl Sync for FatFileSystem {}
 unsafe impl Send for FatFileSystem {}
@@ -30,41 +50,111 @@ impl FatFileSystem {
     pub fn new(mut disk: Disk) -> Self {
         let opts = fatfs::FormatVolumeOptions::new();
         fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(disk.clone(), fs_options())
             .expect("failed to initialize FAT filesystem");
         Self {
-            inner,
             root_dir: UnsafeCell::new(None),
+            inner,
+            disk,
         }
     }
 
     #[cfg(not(feature = "use-ramdisk"))]
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(disk.clone(), fs_options())
             .expect("failed to initialize FAT filesystem");
         Self {
-            inner,
             root_dir: UnsafeCell::new(None),
+            inner,
+            disk,
         }
     }
 
+    /// Opens an existing FAT filesystem on the disk, used to mount other
+    /// volumes at runtime.
+    pub fn open(disk: Disk) -> VfsResult<Arc<Self>> {
+        let inner = fatfs::FileSystem::new(disk.clone(), fs_options()).map_err(as_vfs_err)?;
+        let fs = Arc::new(Self {
+            root_dir: UnsafeCell::new(None),
+            inner,
+            disk,
+        });
+        // SAFETY: nodes of this filesystem are only reachable from its mount
+        // point, which keeps `fs` alive.
+        let this: &'static Self = unsafe { &*Arc::as_ptr(&fs) };
+        this.init();
+        Ok(fs)
+    }
+
     pub fn init(&'static self) {
         // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+        let root_dir = Arc::new(DirWrapper(self.inner.root_dir(), Some(&self.inner)));
+        unsafe { *self.root_dir.get() = Some(root_dir) }
     }
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
+    fn new_file(file: File<'_, Disk, WallTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
         Arc::new(FileWrapper(Mutex::new(file)))
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
-        Arc::new(DirWrapper(dir))
+    fn new_dir(dir: Dir<'_, Disk, WallTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
+        Arc::new(DirWrapper(dir, None))
+    }
+}
+
+impl crate::fs::NodeExt for FileWrapper<'_> {
+    /// Sets the access and the modification times in the directory entry.
+    /// FAT only stores the date of the last access.
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        let mut file = self.0.lock();
+        if let Some(atime) = atime {
+            file.set_accessed(to_fat_time(atime).date);
+        }
+        if let Some(mtime) = mtime {
+            file.set_modified(to_fat_time(mtime));
+        }
+        file.flush().map_err(as_vfs_err)
+    }
+}
+
+impl DirWrapper<'_> {
+    /// Returns the access, modification and change times of the entry `name`
+    /// in this directory. FAT has no change time, the modification time is
+    /// returned instead.
+    pub fn entry_times(&self, name: &str) -> Option<(Duration, Duration, Duration)> {
+        let entry = self.0.iter().filter_map(Result::ok).find(|entry| {
+            entry.file_name().eq_ignore_ascii_case(name)
+                || entry.short_file_name().eq_ignore_ascii_case(name)
+        })?;
+        let accessed = from_fat_time(DateTime::new(entry.accessed(), Time::new(0, 0, 0, 0)));
+        let modified = from_fat_time(entry.modified());
+        Some((accessed, modified, modified))
+    }
+
+    /// Returns the usage of the filesystem in clusters, only known by the
+    /// root directory. FAT has no inode table.
+    pub fn stats(&self) -> VfsResult<FileSystemStats> {
+        let stats = self
+            .1
+            .ok_or(VfsError::Unsupported)?
+            .stats()
+            .map_err(as_vfs_err)?;
+        Ok(FileSystemStats {
+            block_size: stats.cluster_size() as u64,
+            blocks: stats.total_clusters() as u64,
+            blocks_free: stats.free_clusters() as u64,
+            blocks_avail: stats.free_clusters() as u64,
+            ..Default::default()
+        })
     }
 }
 
 impl VfsNodeOps for FileWrapper<'static> {
     axfs_vfs::impl_vfs_non_dir_default! {}
 
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         let size = self.0.lock().seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
         let blocks = size.div_ceil(BLOCK_SIZE as u64);
@@ -75,16 +165,21 @@ impl VfsNodeOps for FileWrapper<'static> {
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
-        file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
+        seek_to(&mut file, offset)?;
         file.read(buf).map_err(as_vfs_err)
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
-        file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
+        seek_to(&mut file, offset)?;
         file.write(buf).map_err(as_vfs_err)
     }
 
+    fn fsync(&self) -> VfsResult {
+        // also flushes the disk, see `Write for Disk`
+        self.0.lock().flush().map_err(as_vfs_err)
+    }
+
     fn truncate(&self, size: u64) -> VfsResult {
         let mut file = self.0.lock();
         let current_size = file.seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
@@ -92,7 +187,7 @@ impl VfsNodeOps for FileWrapper<'static> {
         if size <= current_size {
             // If the target size is smaller than the current size,
             // perform a standard truncation operation
-            file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
+            seek_to(&mut file, size)?;
             file.truncate().map_err(as_vfs_err)
         } else {
             // Calculate the number of bytes to fill
@@ -113,6 +208,10 @@ impl VfsNodeOps for FileWrapper<'static> {
 impl VfsNodeOps for DirWrapper<'static> {
     axfs_vfs::impl_vfs_dir_default! {}
 
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         // FAT fs doesn't support permissions, we just set everything to 755
         Ok(VfsNodeAttr::new(
@@ -217,12 +316,26 @@ impl VfsNodeOps for DirWrapper<'static> {
 }
 
 impl VfsOps for FatFileSystem {
+    fn umount(&self) -> VfsResult {
+        self.disk.sync().map_err(|_| VfsError::Io)
+    }
+
     fn root_dir(&self) -> VfsNodeRef {
         let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
         root_dir.clone()
     }
 }
 
+impl fatfs::TimeProvider for WallTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        to_fat_time(crate::clock::wall_time())
+    }
+}
+
 impl fatfs::IoBase for Disk {
     type Error = ();
 }
@@ -261,7 +374,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        self.sync().map_err(|_| ())
     }
 }
 
@@ -282,6 +395,74 @@ impl Seek for Disk {
     }
 }
 
+fn fs_options() -> fatfs::FsOptions<WallTimeProvider, LossyOemCpConverter> {
+    fatfs::FsOptions::new().time_provider(WallTimeProvider)
+}
+
+/// Converts the time since the UNIX epoch to a FAT timestamp, clamped to the
+/// range of FAT dates.
+fn to_fat_time(time: Duration) -> DateTime {
+    let secs = time.as_secs().clamp(
+        FAT_DAYS.start * SECS_PER_DAY,
+        FAT_DAYS.end * SECS_PER_DAY - 1,
+    );
+    let (year, month, day) = civil_from_days(secs / SECS_PER_DAY);
+    let secs = secs % SECS_PER_DAY;
+    DateTime::new(
+        Date::new(year, month, day),
+        Time::new(
+            (secs / 3600) as u16,
+            (secs / 60 % 60) as u16,
+            (secs % 60) as u16,
+            time.subsec_millis() as u16,
+        ),
+    )
+}
+
+/// Converts a FAT timestamp to the time since the UNIX epoch.
+fn from_fat_time(time: DateTime) -> Duration {
+    let days = days_from_civil(time.date.year, time.date.month, time.date.day);
+    let secs = time.time.hour as u64 * 3600 + time.time.min as u64 * 60 + time.time.sec as u64;
+    Duration::from_secs(days * SECS_PER_DAY + secs) + Duration::from_millis(time.time.millis as u64)
+}
+
+/// Converts days since the UNIX epoch to a date in the Gregorian calendar,
+/// see <http://howardhinnant.github.io/date_algorithms.html>.
+fn civil_from_days(days: u64) -> (u16, u16, u16) {
+    let z = days + 719468;
+    let (era, doe) = (z / 146097, z % 146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + (month <= 2) as u64;
+    (year as u16, month as u16, day as u16)
+}
+
+/// Converts a date in the Gregorian calendar after the UNIX epoch to days
+/// since then.
+fn days_from_civil(year: u16, month: u16, day: u16) -> u64 {
+    let (year, month, day) = (year as u64, month as u64, day as u64);
+    let year = if month <= 2 { year - 1 } else { year };
+    let (era, yoe) = (year / 400, year % 400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Moves the cursor of `file` to `offset`, sequential accesses don't need to
+/// walk the cluster chain again from the beginning of the file.
+fn seek_to(
+    file: &mut File<'_, Disk, WallTimeProvider, LossyOemCpConverter>,
+    offset: u64,
+) -> VfsResult {
+    if file.seek(SeekFrom::Current(0)).map_err(as_vfs_err)? != offset {
+        file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?;
+    }
+    Ok(())
+}
+
 const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
     use fatfs::Error::*;
     match err {
@@ -1,8 +1,16 @@
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeRef, VfsResult};
+use core::time::Duration;
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "myfs")] {
         pub mod myfs;
-    } else if #[cfg(feature = "fatfs")] {
+    } else {
+        #[cfg(feature = "fatfs")]
         pub mod fatfs;
+        #[cfg(feature = "ext4")]
+        pub mod ext4;
+        #[cfg(feature = "tarfs")]
+        pub mod tarfs;
     }
 }
 
@@ -10,4 +18,125 @@ cfg_if::cfg_if! {
 pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
-pub use axfs_ramfs as ramfs;
+pub mod ramfs;
+
+#[cfg(feature = "overlay")]
+pub mod overlayfs;
+
+#[cfg(feature = "procfs")]
+pub mod procfs;
+#[cfg(any(feature = "procfs", feature = "sysfs"))]
+pub mod pseudo;
+#[cfg(feature = "sysfs")]
+pub mod sysfs;
+
+/// Operations of the nodes of the filesystems in this crate, besides those of
+/// [`axfs_vfs::VfsNodeOps`].
+///
+/// Nodes get them by [`MountPoint::node_ext`](crate::root::MountPoint::node_ext),
+/// or the defaults if their filesystems don't implement them.
+pub(crate) trait NodeExt {
+    /// Returns the inode number, or `None` if the filesystem has none.
+    fn ino(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the number of hard links, or `None` if the filesystem doesn't
+    /// count them.
+    fn nlink(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether hard links can be created in the filesystem.
+    ///
+    /// Such filesystems always return the same node for a file, whatever path
+    /// it is looked up by.
+    fn supports_links(&self) -> bool {
+        false
+    }
+
+    /// Creates a hard link `name` in this directory to the file `node` of the
+    /// same filesystem.
+    fn link(&self, _name: &str, _node: VfsNodeRef) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Returns the owner and the group stored by the filesystem, or `None` if
+    /// it stores none.
+    fn owner(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    /// Returns the access, modification and change times stored by the
+    /// filesystem, since the UNIX epoch, or `None` if it stores none.
+    fn times(&self) -> Option<(Duration, Duration, Duration)> {
+        None
+    }
+
+    /// Stores the permissions in the filesystem.
+    ///
+    /// This and the other setters fail with `Unsupported` if the filesystem
+    /// doesn't store the metadata, which is then kept in memory instead.
+    fn set_perm(&self, _perm: VfsNodePerm) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Stores the owner and the group in the filesystem, `None` leaves it
+    /// unchanged.
+    fn set_owner(&self, _uid: Option<u32>, _gid: Option<u32>) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Stores the access and the modification times in the filesystem, `None`
+    /// leaves it unchanged.
+    fn set_times(&self, _atime: Option<Duration>, _mtime: Option<Duration>) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Whether the content of this regular file can be kept in the page
+    /// cache, which is not the case if it is generated on each read.
+    fn page_cached(&self) -> bool {
+        true
+    }
+}
+
+/// The defaults of [`NodeExt`].
+pub(crate) struct NoExt;
+
+impl NodeExt for NoExt {}
+
+/// Returns the [`NodeExt`] operations of `node`, which must be of a filesystem
+/// in this crate, whose nodes implement `as_any`.
+pub(crate) fn node_ext(node: &VfsNodeRef) -> &dyn NodeExt {
+    #[allow(unused_variables)]
+    let node = node.as_any();
+    #[cfg(all(feature = "ext4", not(feature = "myfs")))]
+    if let Some(node) = node.downcast_ref::<ext4::Ext4Node>() {
+        return node;
+    }
+    #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+    if let Some(node) = node.downcast_ref::<fatfs::FileWrapper<'static>>() {
+        return node;
+    }
+    #[cfg(all(feature = "tarfs", not(feature = "myfs")))]
+    if let Some(node) = node.downcast_ref::<tarfs::TarNode>() {
+        return node;
+    }
+    #[cfg(feature = "ramfs")]
+    if let Some(node) = node.downcast_ref::<ramfs::DirNode>() {
+        return node;
+    }
+    #[cfg(feature = "ramfs")]
+    if let Some(node) = node.downcast_ref::<ramfs::FileNode>() {
+        return node;
+    }
+    #[cfg(feature = "ramfs")]
+    if let Some(node) = node.downcast_ref::<ramfs::SymlinkNode>() {
+        return node;
+    }
+    #[cfg(any(feature = "procfs", feature = "sysfs"))]
+    if let Some(node) = node.downcast_ref::<pseudo::PseudoFile>() {
+        return node;
+    }
+    &NoExt
+}
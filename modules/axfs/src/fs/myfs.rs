@@ -7,6 +7,8 @@ use axfs_vfs::VfsOps;
 pub trait MyFileSystemIf {
     /// Creates a new instance of the filesystem with initialization.
     ///
+    /// Accesses to `disk` are cached, call [`Disk::sync`] to write them back.
+    ///
     /// TODO: use generic disk type
     fn new_myfs(disk: Disk) -> Arc<dyn VfsOps>;
 }
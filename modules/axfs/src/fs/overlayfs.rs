@@ -0,0 +1,633 @@
+//! Overlay filesystem, which layers a writable upper filesystem over a lower
+//! one that is never modified, like the overlayfs of Linux.
+//!
+//! Files are looked up in the upper filesystem first, and then in the lower
+//! one, and a directory in both lists the entries of both. A file only in the
+//! lower filesystem is copied up to the upper one, along with its parent
+//! directories, before it is modified. Removing a file that is in the lower
+//! filesystem leaves a whiteout in the upper directory to hide it, and a
+//! directory created in place of a removed one is made opaque, so that the
+//! lower entries under it are hidden.
+//!
+//! Whiteouts are empty files named `.wh.<name>`, and an opaque directory has
+//! an empty file named `.wh..wh..opq`, as those of AUFS, so that any
+//! filesystem can be the upper one. Names with the `.wh.` prefix can not be
+//! used in the overlay.
+//!
+//! Directories in the lower filesystem can not be renamed, as Linux does
+//! without the `redirect_dir` feature. Hard links are not supported.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsOps, VfsResult};
+use axsync::Mutex;
+
+/// The prefix of the names of whiteouts.
+const WHITEOUT_PREFIX: &str = ".wh.";
+/// The name of the file that makes an upper directory opaque.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+/// The size of the buffer to copy up files.
+const COPY_BUF_SIZE: usize = 4096;
+
+/// An overlay filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct OverlayFileSystem {
+    root: Arc<OverlayNode>,
+}
+
+struct Layers {
+    upper: Arc<dyn VfsOps>,
+    lower: Arc<dyn VfsOps>,
+    /// The parent of the mount point, which the root only refers to weakly.
+    parent: Mutex<Option<VfsNodeRef>>,
+    /// The nodes in use by their paths, so that a file has a single node,
+    /// which is updated when the file is copied up or renamed.
+    nodes: Mutex<BTreeMap<String, Weak<OverlayNode>>>,
+}
+
+/// A file or directory of [`OverlayFileSystem`].
+pub struct OverlayNode {
+    fs: Arc<Layers>,
+    is_dir: bool,
+    state: Mutex<NodeState>,
+}
+
+struct NodeState {
+    /// The path relative to the root, without the leading '/', or `None` if
+    /// the file has been removed.
+    path: Option<String>,
+    upper: Option<VfsNodeRef>,
+    lower: Option<VfsNodeRef>,
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay of the writable `upper` filesystem on `lower`.
+    pub fn new(upper: Arc<dyn VfsOps>, lower: Arc<dyn VfsOps>) -> Self {
+        let fs = Arc::new(Layers {
+            parent: Mutex::new(None),
+            nodes: Mutex::new(BTreeMap::new()),
+            upper: upper.clone(),
+            lower: lower.clone(),
+        });
+        let root = Arc::new(OverlayNode {
+            fs: fs.clone(),
+            is_dir: true,
+            state: Mutex::new(NodeState {
+                path: Some(String::new()),
+                upper: Some(upper.root_dir()),
+                lower: Some(lower.root_dir()),
+            }),
+        });
+        fs.nodes.lock().insert(String::new(), Arc::downgrade(&root));
+        Self { root }
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        *self.root.fs.parent.lock() = mount_point.parent();
+        Ok(())
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.root.fs.upper.umount()?;
+        self.root.fs.lower.umount()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl Layers {
+    /// Returns the node of the file at `path`.
+    fn node(self: &Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let mut nodes = self.nodes.lock();
+        if let Some(node) = nodes.get(path).and_then(Weak::upgrade) {
+            return Ok(node);
+        }
+        let (upper, lower) = self.find(path)?;
+        let is_dir = top_of(&upper, &lower).get_attr()?.is_dir();
+        nodes.retain(|_, node| node.strong_count() > 0);
+        let node = Arc::new(OverlayNode {
+            fs: self.clone(),
+            is_dir,
+            state: Mutex::new(NodeState {
+                path: Some(path.into()),
+                upper,
+                lower,
+            }),
+        });
+        nodes.insert(path.into(), Arc::downgrade(&node));
+        Ok(node)
+    }
+
+    /// Finds the file at `path` in both layers, at least one of the returned
+    /// nodes is `Some`.
+    ///
+    /// The lower node is `None` if it is hidden by a whiteout, an opaque
+    /// directory, or an upper file of another type.
+    fn find(&self, path: &str) -> VfsResult<(Option<VfsNodeRef>, Option<VfsNodeRef>)> {
+        let mut upper = Some(self.upper.root_dir());
+        let mut lower = Some(self.lower.root_dir());
+        for name in components(path) {
+            if name.starts_with(WHITEOUT_PREFIX) {
+                return Err(VfsError::NotFound);
+            }
+            if !top_of(&upper, &lower).get_attr()?.is_dir() {
+                return Err(VfsError::NotADirectory);
+            }
+            let mut lower_child = match &lower {
+                Some(dir) if !has_child(upper.as_ref(), &whiteout_of(name))? => child(dir, name)?,
+                _ => None,
+            };
+            let upper_child = match &upper {
+                Some(dir) => child(dir, name)?,
+                None => None,
+            };
+            if let Some(upper_child) = &upper_child {
+                let is_dir = upper_child.get_attr()?.is_dir();
+                if !is_dir || has_child(Some(upper_child), OPAQUE_MARKER)? {
+                    lower_child = None;
+                } else if let Some(lower_dir) = &lower_child {
+                    if !lower_dir.get_attr()?.is_dir() {
+                        lower_child = None;
+                    }
+                }
+            }
+            if upper_child.is_none() && lower_child.is_none() {
+                return Err(VfsError::NotFound);
+            }
+            (upper, lower) = (upper_child, lower_child);
+        }
+        Ok((upper, lower))
+    }
+
+    /// Returns the directory at `path` in the upper filesystem, creating it
+    /// and its ancestors if they are only in the lower one.
+    fn upper_dir(&self, path: &str) -> VfsResult<VfsNodeRef> {
+        let mut dir = self.upper.root_dir();
+        for name in components(path) {
+            dir = match child(&dir, name)? {
+                Some(child) => child,
+                None => {
+                    dir.create(name, VfsNodeType::Dir)?;
+                    dir.clone().lookup(name)?
+                }
+            };
+        }
+        Ok(dir)
+    }
+
+    /// Copies the file `lower` at `path` up to the upper filesystem, returns
+    /// the upper node.
+    fn copy_up(&self, path: &str, lower: &VfsNodeRef) -> VfsResult<VfsNodeRef> {
+        let (parent, name) = split_parent(path);
+        let dir = self.upper_dir(parent)?;
+        let ty = lower.get_attr()?.file_type();
+        dir.create(name, ty)?;
+        let upper = dir.clone().lookup(name)?;
+        if ty != VfsNodeType::Dir {
+            copy_data(lower, &upper).inspect_err(|_| {
+                dir.remove(name).ok();
+            })?;
+        }
+        Ok(upper)
+    }
+
+    /// Removes the file at `path`, leaving a whiteout if it is in the lower
+    /// filesystem.
+    fn remove(&self, path: &str) -> VfsResult {
+        let (upper, lower) = self.find(path)?;
+        if top_of(&upper, &lower).get_attr()?.is_dir()
+            && !list(upper.as_ref(), lower.as_ref())?.is_empty()
+        {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        let (parent, name) = split_parent(path);
+        if let Some(upper) = &upper {
+            if upper.get_attr()?.is_dir() {
+                // only whiteouts are left in it
+                for (name, _) in read_all(upper)? {
+                    upper.remove(&name)?;
+                }
+            }
+            self.upper.root_dir().lookup(parent)?.remove(name)?;
+        }
+        // the lower file is also hidden by an opaque upper directory
+        if self.find(path).is_ok() {
+            self.upper_dir(parent)?
+                .create(&whiteout_of(name), VfsNodeType::File)?;
+        }
+        self.forget(path);
+        Ok(())
+    }
+
+    /// Removes the whiteout at `path` that the upper file `upper` is placed
+    /// on, and makes it opaque if it is a directory.
+    fn cover_whiteout(&self, path: &str, upper: &VfsNodeRef) -> VfsResult {
+        let (parent, name) = split_parent(path);
+        let dir = self.upper.root_dir().lookup(parent)?;
+        let whiteout = whiteout_of(name);
+        if child(&dir, &whiteout)?.is_some() {
+            if upper.get_attr()?.is_dir() {
+                upper.create(OPAQUE_MARKER, VfsNodeType::File)?;
+            }
+            dir.remove(&whiteout)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the nodes of the removed file at `path` and its descendants.
+    fn forget(&self, path: &str) {
+        let mut nodes = self.nodes.lock();
+        nodes.retain(|node_path, node| {
+            if !is_descendant(node_path, path) {
+                return true;
+            }
+            if let Some(node) = node.upgrade() {
+                node.state.lock().path = None;
+            }
+            false
+        });
+    }
+
+    /// Moves the nodes of the file renamed from `src` to `dst`, and its
+    /// descendants.
+    fn rename_nodes(&self, src: &str, dst: &str) {
+        let mut nodes = self.nodes.lock();
+        let moved = nodes
+            .keys()
+            .filter(|path| is_descendant(path, src))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            let Some(node) = nodes.remove(&path).and_then(|node| node.upgrade()) else {
+                continue;
+            };
+            let new_path = String::from(dst) + &path[src.len()..];
+            let mut state = node.state.lock();
+            // it is only in the upper filesystem now
+            state.upper = self.upper.root_dir().lookup(&new_path).ok();
+            state.lower = None;
+            state.path = Some(new_path.clone());
+            drop(state);
+            nodes.insert(new_path, Arc::downgrade(&node));
+        }
+    }
+}
+
+impl OverlayNode {
+    /// Returns the path of the file relative to the root.
+    fn path(&self) -> VfsResult<String> {
+        self.state.lock().path.clone().ok_or(VfsError::NotFound)
+    }
+
+    /// Resolves `path` relative to this node, see [`resolve`].
+    fn resolve(&self, path: &str) -> VfsResult<Result<String, String>> {
+        Ok(resolve(&self.path()?, path))
+    }
+
+    /// Returns the upper and lower nodes of the file.
+    fn layers(&self) -> (Option<VfsNodeRef>, Option<VfsNodeRef>) {
+        let mut state = self.state.lock();
+        if state.upper.is_none() && self.is_dir {
+            // the directory may have been copied up with its descendants
+            if let Some(path) = state.path.clone() {
+                state.upper = self.fs.upper.root_dir().lookup(&path).ok();
+            }
+        }
+        (state.upper.clone(), state.lower.clone())
+    }
+
+    /// Returns the node of the file in the upper filesystem if it is there,
+    /// or in the lower one.
+    fn top(&self) -> VfsResult<VfsNodeRef> {
+        let (upper, lower) = self.layers();
+        upper.or(lower).ok_or(VfsError::NotFound)
+    }
+
+    /// Returns the upper node of the file, copying it up first if it is only
+    /// in the lower filesystem.
+    fn copy_up(&self) -> VfsResult<VfsNodeRef> {
+        let (upper, lower) = self.layers();
+        if let Some(upper) = upper {
+            return Ok(upper);
+        }
+        let mut state = self.state.lock();
+        if let Some(upper) = &state.upper {
+            return Ok(upper.clone()); // copied up by others
+        }
+        let path = state.path.as_deref().ok_or(VfsError::NotFound)?;
+        let lower = lower.ok_or(VfsError::NotFound)?;
+        let upper = self.fs.copy_up(path, &lower)?;
+        state.upper = Some(upper.clone());
+        Ok(upper)
+    }
+
+    /// Returns the root directory of the upper filesystem, where all changes
+    /// are written.
+    pub fn upper_root(&self) -> VfsNodeRef {
+        self.fs.upper.root_dir()
+    }
+
+    fn parent_fs(&self) -> VfsResult<VfsNodeRef> {
+        self.fs.parent.lock().clone().ok_or(VfsError::NotFound)
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.top()?.get_attr()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.top()?.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.copy_up()?.write_at(offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        match self.layers().0 {
+            Some(upper) => upper.fsync(),
+            None => Ok(()),
+        }
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.copy_up()?.truncate(size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let path = self.path().ok()?;
+        if path.is_empty() {
+            self.parent_fs().ok()
+        } else {
+            self.fs.node(split_parent(&path).0).ok()
+        }
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at overlayfs: {}", path);
+        match self.resolve(path)? {
+            Ok(path) => self.fs.node(&path),
+            Err(rest) => self.parent_fs()?.lookup(&rest),
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at overlayfs: {}", ty, path);
+        let path = match self.resolve(path)? {
+            Ok(path) => path,
+            Err(rest) => return self.parent_fs()?.create(&rest, ty),
+        };
+        match self.fs.find(&path) {
+            Ok((upper, lower)) => {
+                return if top_of(&upper, &lower).get_attr()?.file_type() == ty {
+                    Ok(()) // already exists
+                } else {
+                    Err(VfsError::AlreadyExists)
+                };
+            }
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        let (parent, name) = split_parent(&path);
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::InvalidInput);
+        }
+        let (upper, lower) = self.fs.find(parent)?;
+        if !top_of(&upper, &lower).get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let dir = self.fs.upper_dir(parent)?;
+        dir.create(name, ty)?;
+        self.fs.cover_whiteout(&path, &dir.lookup(name)?)
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at overlayfs: {}", path);
+        match self.resolve(path)? {
+            Ok(path) if path.is_empty() => Err(VfsError::InvalidInput),
+            Ok(path) => self.fs.remove(&path),
+            Err(rest) => self.parent_fs()?.remove(&rest),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let (upper, lower) = self.layers();
+        let entries = list(upper.as_ref(), lower.as_ref())?;
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match entries.next() {
+                    Some((name, ty)) => *ent = VfsDirEntry::new(name, *ty),
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at overlayfs: {} -> {}", src_path, dst_path);
+        let (Ok(src), Ok(dst)) = (self.resolve(src_path)?, self.resolve(dst_path)?) else {
+            return Err(VfsError::Unsupported); // out of the filesystem
+        };
+        if src.is_empty() || dst.is_empty() || (is_descendant(&dst, &src) && dst != src) {
+            return Err(VfsError::InvalidInput);
+        }
+        let (src_upper, src_lower) = self.fs.find(&src)?;
+        let src_is_dir = top_of(&src_upper, &src_lower).get_attr()?.is_dir();
+        if src_is_dir && src_lower.is_some() {
+            return Err(VfsError::Unsupported);
+        }
+        let (parent, name) = split_parent(&dst);
+        if name.starts_with(WHITEOUT_PREFIX) {
+            return Err(VfsError::InvalidInput);
+        }
+        let (upper, lower) = self.fs.find(parent)?;
+        if !top_of(&upper, &lower).get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        if dst == src {
+            return Ok(());
+        }
+        let dst_exists = match self.fs.find(&dst) {
+            Ok((upper, lower)) => {
+                let dst_is_dir = top_of(&upper, &lower).get_attr()?.is_dir();
+                if dst_is_dir && !src_is_dir {
+                    return Err(VfsError::IsADirectory);
+                } else if !dst_is_dir && src_is_dir {
+                    return Err(VfsError::NotADirectory);
+                } else if dst_is_dir && !list(upper.as_ref(), lower.as_ref())?.is_empty() {
+                    return Err(VfsError::DirectoryNotEmpty);
+                }
+                true
+            }
+            Err(VfsError::NotFound) => false,
+            Err(e) => return Err(e),
+        };
+
+        // the destination is only replaced once the source can be moved
+        if src_upper.is_none() {
+            self.fs.copy_up(&src, src_lower.as_ref().unwrap())?;
+        }
+        self.fs.upper_dir(parent)?;
+        if dst_exists {
+            self.fs.remove(&dst)?;
+        }
+        self.fs.upper.root_dir().rename(&src, &dst)?;
+        let moved = self.fs.upper.root_dir().lookup(&dst)?;
+        self.fs.cover_whiteout(&dst, &moved)?;
+        if self.fs.find(&src).is_ok() {
+            let (parent, name) = split_parent(&src);
+            self.fs
+                .upper_dir(parent)?
+                .create(&whiteout_of(name), VfsNodeType::File)?;
+        }
+        self.fs.rename_nodes(&src, &dst);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// Returns the upper node of a file found by [`Layers::find`] if it is
+/// there, or the lower one.
+fn top_of<'a>(upper: &'a Option<VfsNodeRef>, lower: &'a Option<VfsNodeRef>) -> &'a VfsNodeRef {
+    upper.as_ref().or(lower.as_ref()).unwrap()
+}
+
+/// Lists the entries of the directory in both layers, sorted by name,
+/// without `.`, `..` and those hidden.
+fn list(
+    upper: Option<&VfsNodeRef>,
+    lower: Option<&VfsNodeRef>,
+) -> VfsResult<Vec<(String, VfsNodeType)>> {
+    let mut entries = BTreeMap::new();
+    let mut hidden = BTreeSet::new();
+    let mut opaque = false;
+    for (name, ty) in upper.map(read_all).transpose()?.into_iter().flatten() {
+        if name == OPAQUE_MARKER {
+            opaque = true;
+        } else if let Some(name) = name.strip_prefix(WHITEOUT_PREFIX) {
+            hidden.insert(String::from(name));
+        } else {
+            entries.insert(name, ty);
+        }
+    }
+    if let Some(lower) = lower.filter(|_| !opaque) {
+        for (name, ty) in read_all(lower)? {
+            if !hidden.contains(&name) {
+                entries.entry(name).or_insert(ty);
+            }
+        }
+    }
+    Ok(entries.into_iter().collect())
+}
+
+/// Reads all entries of the directory `dir`, without `.` and `..`.
+fn read_all(dir: &VfsNodeRef) -> VfsResult<Vec<(String, VfsNodeType)>> {
+    let mut entries = Vec::new();
+    let mut buf = [const { VfsDirEntry::default() }; 16];
+    let mut idx = 0;
+    loop {
+        let n = dir.read_dir(idx, &mut buf)?;
+        if n == 0 {
+            return Ok(entries);
+        }
+        idx += n;
+        for ent in &buf[..n] {
+            let name =
+                core::str::from_utf8(ent.name_as_bytes()).map_err(|_| VfsError::InvalidData)?;
+            if name != "." && name != ".." {
+                entries.push((name.into(), ent.entry_type()));
+            }
+        }
+    }
+}
+
+/// Copies the content of the file `src` to the empty file `dst`.
+fn copy_data(src: &VfsNodeRef, dst: &VfsNodeRef) -> VfsResult {
+    let mut buf = vec![0; COPY_BUF_SIZE];
+    let mut offset = 0;
+    loop {
+        let n = src.read_at(offset, &mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        dst.write_at(offset, &buf[..n])?;
+        offset += n as u64;
+    }
+}
+
+/// Looks up the entry `name` of the directory `dir`, returns `None` if it
+/// does not exist.
+fn child(dir: &VfsNodeRef, name: &str) -> VfsResult<Option<VfsNodeRef>> {
+    match dir.clone().lookup(name) {
+        Ok(node) => Ok(Some(node)),
+        Err(VfsError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn has_child(dir: Option<&VfsNodeRef>, name: &str) -> VfsResult<bool> {
+    match dir {
+        Some(dir) => Ok(child(dir, name)?.is_some()),
+        None => Ok(false),
+    }
+}
+
+fn whiteout_of(name: &str) -> String {
+    String::from(WHITEOUT_PREFIX) + name
+}
+
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|name| !name.is_empty())
+}
+
+/// Splits a path relative to the root into its parent and its last
+/// component.
+fn split_parent(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Whether `path` is `ancestor` or in it, both relative to the root.
+fn is_descendant(path: &str, ancestor: &str) -> bool {
+    ancestor.is_empty()
+        || path
+            .strip_prefix(ancestor)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Resolves `path` relative to the directory at `base`, returns the path
+/// relative to the root, or `Err` with the rest of `path` relative to the
+/// parent of the root if it goes out of the filesystem by `..`.
+fn resolve(base: &str, path: &str) -> Result<String, String> {
+    let mut resolved = components(base).collect::<Vec<_>>();
+    let mut names = path.split('/');
+    while let Some(name) = names.next() {
+        match name {
+            "" | "." => {}
+            ".." => {
+                if resolved.pop().is_none() {
+                    return Err(names.collect::<Vec<_>>().join("/"));
+                }
+            }
+            _ => resolved.push(name),
+        }
+    }
+    Ok(resolved.join("/"))
+}
@@ -0,0 +1,96 @@
+//! The proc filesystem, whose files are generated from the kernel state on
+//! each read.
+//!
+//! - `/proc/meminfo`: memory usage of the global allocator.
+//! - `/proc/cpuinfo`: the CPUs of the platform.
+//! - `/proc/uptime`: time since boot.
+//! - `/proc/mounts`: mounted filesystems.
+//!
+//! Other entries, like those of tasks or sockets, are added by the upper layers
+//! through [`register_provider`].
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::fmt::Write;
+
+use axsync::Mutex;
+
+use super::pseudo::{PseudoDir, PseudoFileSystem};
+
+static PROVIDERS: Mutex<Vec<fn(&Arc<PseudoDir>)>> = Mutex::new(Vec::new());
+
+/// Registers `provider` to add entries to the root of every proc filesystem
+/// created afterwards, e.g. `/proc/<tid>` of tasks by the runtime.
+///
+/// Providers should be registered before
+/// [`init_filesystems`](crate::init_filesystems), which mounts `/proc`.
+pub fn register_provider(provider: fn(&Arc<PseudoDir>)) {
+    PROVIDERS.lock().push(provider);
+}
+
+/// Create a new proc filesystem.
+pub fn new_procfs() -> Arc<PseudoFileSystem> {
+    let procfs = PseudoFileSystem::new();
+    let root = procfs.root();
+    root.add_file("meminfo", meminfo);
+    root.add_file("cpuinfo", cpuinfo);
+    root.add_file("uptime", uptime);
+    root.add_file("mounts", mounts);
+
+    let sys = root.add_dir("sys");
+    sys.add_dir("net")
+        .add_dir("core")
+        .add_file("somaxconn", || "4096\n".into());
+    sys.add_dir("vm")
+        .add_file("overcommit_memory", || "0\n".into());
+
+    for provider in PROVIDERS.lock().iter() {
+        provider(root);
+    }
+
+    Arc::new(procfs)
+}
+
+fn meminfo() -> String {
+    const PAGE_SIZE: usize = 0x1000;
+    let allocator = axalloc::global_allocator();
+    let free = allocator.available_pages() * PAGE_SIZE;
+    let total = allocator.used_pages() * PAGE_SIZE + free;
+    format!(
+        "MemTotal:       {:8} kB\nMemFree:        {:8} kB\nMemAvailable:   {:8} kB\n",
+        total / 1024,
+        free / 1024,
+        free / 1024
+    )
+}
+
+fn cpuinfo() -> String {
+    let mut info = String::new();
+    for cpu in 0..axconfig::plat::CPU_NUM {
+        writeln!(info, "processor\t: {}", cpu).unwrap();
+        writeln!(info, "arch\t\t: {}", axconfig::ARCH).unwrap();
+        writeln!(info, "platform\t: {}", axconfig::PLATFORM).unwrap();
+        writeln!(info).unwrap();
+    }
+    info
+}
+
+fn uptime() -> String {
+    let now = crate::clock::monotonic_time();
+    // idle time is not accounted
+    format!("{}.{:02} 0.00\n", now.as_secs(), now.subsec_millis() / 10)
+}
+
+fn mounts() -> String {
+    let mut mounts = String::new();
+    for mp in crate::root::mount_points() {
+        writeln!(
+            mounts,
+            "{} {} {} rw 0 0",
+            mp.source(),
+            mp.path(),
+            mp.fstype()
+        )
+        .unwrap();
+    }
+    mounts
+}
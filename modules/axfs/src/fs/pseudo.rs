@@ -0,0 +1,276 @@
+//! Building blocks of pseudo filesystems like procfs and sysfs, whose files do not store
+//! data but are generated on each read.
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, sync::Weak, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axsync::Mutex;
+
+type ReadFn = Box<dyn Fn() -> String + Send + Sync>;
+type WriteFn = Box<dyn Fn(&[u8]) -> VfsResult + Send + Sync>;
+type ListFn = Box<dyn Fn(&Arc<PseudoDir>) -> Vec<(String, VfsNodeRef)> + Send + Sync>;
+
+/// A pseudo filesystem made of [`PseudoDir`]s and [`PseudoFile`]s.
+pub struct PseudoFileSystem {
+    root: Arc<PseudoDir>,
+}
+
+impl PseudoFileSystem {
+    /// Create a new pseudo filesystem with an empty root directory.
+    pub fn new() -> Self {
+        Self {
+            root: PseudoDir::new(None),
+        }
+    }
+
+    /// Returns the root directory, to add entries to it.
+    pub fn root(&self) -> &Arc<PseudoDir> {
+        &self.root
+    }
+}
+
+impl Default for PseudoFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for PseudoFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+/// A file whose content is generated on each read.
+///
+/// Its size is reported as 0, as Linux does, so it must be read until the end
+/// instead of by its size. A writable file passes each write as a whole to its
+/// handler, regardless of the offset.
+pub struct PseudoFile {
+    read: ReadFn,
+    write: Option<WriteFn>,
+}
+
+impl PseudoFile {
+    /// Create a read-only file with the content generated by `read`.
+    pub fn new(read: impl Fn() -> String + Send + Sync + 'static) -> Arc<Self> {
+        Arc::new(Self {
+            read: Box::new(read),
+            write: None,
+        })
+    }
+
+    /// Create a file with the content generated by `read`, and writes handled
+    /// by `write`.
+    pub fn new_writable(
+        read: impl Fn() -> String + Send + Sync + 'static,
+        write: impl Fn(&[u8]) -> VfsResult + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            read: Box::new(read),
+            write: Some(Box::new(write)),
+        })
+    }
+}
+
+impl VfsNodeOps for PseudoFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mode = if self.write.is_some() { 0o644 } else { 0o444 };
+        let perm = VfsNodePerm::from_bits_truncate(mode);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, 0, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let src = content
+            .as_bytes()
+            .get(offset as usize..)
+            .unwrap_or_default();
+        let len = src.len().min(buf.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        write(buf)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        // opening with `O_TRUNC` for writing is allowed, but does nothing
+        match self.write {
+            Some(_) => Ok(()),
+            None => Err(VfsError::PermissionDenied),
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+impl crate::fs::NodeExt for PseudoFile {
+    fn page_cached(&self) -> bool {
+        false // generated on each read
+    }
+}
+
+/// A read-only directory of pseudo files.
+///
+/// Besides the fixed entries added by [`add`](Self::add), a directory can have
+/// entries generated on each lookup, e.g. one directory per task.
+pub struct PseudoDir {
+    this: Weak<PseudoDir>,
+    parent: Option<Weak<dyn VfsNodeOps>>,
+    entries: Mutex<BTreeMap<String, VfsNodeRef>>,
+    generators: Mutex<Vec<ListFn>>,
+}
+
+impl PseudoDir {
+    /// Create an empty directory.
+    pub fn new(parent: Option<Weak<dyn VfsNodeOps>>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent,
+            entries: Mutex::new(BTreeMap::new()),
+            generators: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Returns a weak reference to the directory, to be the parent of others.
+    pub fn as_parent(&self) -> Weak<dyn VfsNodeOps> {
+        self.this.clone()
+    }
+
+    /// Add an entry `name` to the directory.
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
+        self.entries.lock().insert(name.into(), node);
+    }
+
+    /// Add a read-only file `name` with the content generated by `read`.
+    pub fn add_file(&self, name: &str, read: impl Fn() -> String + Send + Sync + 'static) {
+        self.add(name, PseudoFile::new(read));
+    }
+
+    /// Add a writable file `name`, see [`PseudoFile::new_writable`].
+    pub fn add_writable_file(
+        &self,
+        name: &str,
+        read: impl Fn() -> String + Send + Sync + 'static,
+        write: impl Fn(&[u8]) -> VfsResult + Send + Sync + 'static,
+    ) {
+        self.add(name, PseudoFile::new_writable(read, write));
+    }
+
+    /// Add an empty subdirectory `name` and returns it.
+    pub fn add_dir(&self, name: &str) -> Arc<PseudoDir> {
+        let dir = PseudoDir::new(Some(self.as_parent()));
+        self.add(name, dir.clone());
+        dir
+    }
+
+    /// Add the entries generated by `list` on each lookup. It is called with
+    /// the directory itself, to be the parent of the generated entries.
+    pub fn add_generated(
+        &self,
+        list: impl Fn(&Arc<PseudoDir>) -> Vec<(String, VfsNodeRef)> + Send + Sync + 'static,
+    ) {
+        self.generators.lock().push(Box::new(list));
+    }
+
+    /// Returns all entries, the fixed ones first.
+    fn all_entries(&self) -> Vec<(String, VfsNodeRef)> {
+        let mut entries: Vec<_> = self
+            .entries
+            .lock()
+            .iter()
+            .map(|(name, node)| (name.clone(), node.clone()))
+            .collect();
+        if let Some(this) = self.this.upgrade() {
+            for list in self.generators.lock().iter() {
+                entries.extend(list(&this));
+            }
+        }
+        entries
+    }
+
+    fn child(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Some(node) = self.entries.lock().get(name) {
+            return Some(node.clone());
+        }
+        self.all_entries()
+            .into_iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, node)| node)
+    }
+}
+
+impl VfsNodeOps for PseudoDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let perm = VfsNodePerm::from_bits_truncate(0o555);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::Dir, 0, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.as_ref().and_then(Weak::upgrade)
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.child(name).ok_or(VfsError::NotFound),
+        }?;
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.all_entries();
+        let mut count = 0;
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            let idx = start_idx + i;
+            *ent = match idx {
+                0 => VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match entries.get(idx - 2) {
+                    Some((name, node)) => VfsDirEntry::new(name, node.get_attr()?.file_type()),
+                    None => break,
+                },
+            };
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn create(&self, path: &str, _ty: VfsNodeType) -> VfsResult {
+        match self.this.upgrade().map(|this| this.lookup(path)) {
+            Some(Ok(_)) => Err(VfsError::AlreadyExists),
+            _ => Err(VfsError::PermissionDenied),
+        }
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
@@ -0,0 +1,106 @@
+//! The RAM filesystem of [`axfs_ramfs`], with symbolic and hard links.
+//!
+//! Its nodes get inode numbers and link counts through [`NodeExt`], and its
+//! pages are spilled to a [`Disk`] when the free memory of `axalloc` drops
+//! below [`LOW_MEMORY_PAGES`].
+
+pub use axfs_ramfs::*;
+
+use alloc::boxed::Box;
+use axfs_vfs::{VfsError, VfsNodeRef, VfsResult};
+
+use super::NodeExt;
+use crate::dev::Disk;
+use crate::statfs::FileSystemStats;
+
+/// Pages are spilled when fewer pages than this are free in `axalloc`.
+const LOW_MEMORY_PAGES: usize = 256;
+
+/// A swap volume of the RAM filesystem on a disk or partition.
+struct DiskSwap(Disk);
+
+impl Swap for DiskSwap {
+    fn size(&self) -> u64 {
+        self.0.size()
+    }
+
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> VfsResult {
+        self.0.read_at(pos, buf).map_err(|_| VfsError::Io)
+    }
+
+    fn write_at(&self, pos: u64, buf: &[u8]) -> VfsResult {
+        self.0.write_at(pos, buf).map_err(|_| VfsError::Io)
+    }
+
+    fn memory_low(&self) -> bool {
+        axalloc::global_allocator().available_pages() < LOW_MEMORY_PAGES
+    }
+}
+
+/// Creates a RAM filesystem of at most `size` bytes of file contents, which
+/// spills cold pages to `swap` when memory is low.
+pub(crate) fn with_limits(size: Option<u64>, swap: Option<Disk>) -> RamFileSystem {
+    let swap = swap.map(|disk| Box::new(DiskSwap(disk)) as Box<dyn Swap>);
+    RamFileSystem::with_limits(size, swap)
+}
+
+/// Returns the usage of the RAM filesystem of `root`, the number of files is
+/// not limited.
+pub(crate) fn stats(root: &DirNode) -> FileSystemStats {
+    let usage = root.usage();
+    let blocks = usage.max_pages.unwrap_or(0);
+    let free = blocks.saturating_sub(usage.used_pages);
+    FileSystemStats {
+        block_size: PAGE_SIZE as u64,
+        blocks,
+        blocks_free: free,
+        blocks_avail: free,
+        ..Default::default()
+    }
+}
+
+impl NodeExt for DirNode {
+    fn ino(&self) -> Option<u64> {
+        Some(DirNode::ino(self))
+    }
+
+    fn nlink(&self) -> Option<u64> {
+        Some(DirNode::nlink(self))
+    }
+
+    fn supports_links(&self) -> bool {
+        true
+    }
+
+    fn link(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        DirNode::link(self, name, node)
+    }
+}
+
+impl NodeExt for FileNode {
+    fn ino(&self) -> Option<u64> {
+        Some(FileNode::ino(self))
+    }
+
+    fn nlink(&self) -> Option<u64> {
+        Some(FileNode::nlink(self))
+    }
+
+    fn supports_links(&self) -> bool {
+        true
+    }
+}
+
+impl NodeExt for SymlinkNode {
+    fn ino(&self) -> Option<u64> {
+        Some(SymlinkNode::ino(self))
+    }
+
+    fn nlink(&self) -> Option<u64> {
+        Some(SymlinkNode::nlink(self))
+    }
+
+    fn supports_links(&self) -> bool {
+        true
+    }
+}
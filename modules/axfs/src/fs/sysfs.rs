@@ -0,0 +1,139 @@
+//! The sys filesystem, which exposes devices, CPUs and kernel tunables.
+//!
+//! - `/sys/class/<type>/<name>/`: every device found by [`axdriver`], with its
+//!   driver `name`, device `type` and `location` (a PCI BDF or an MMIO range).
+//! - `/sys/devices/system/cpu/`: `online` and `possible` CPUs, and `cpuN/online`
+//!   of each.
+//! - `/sys/devices/system/clocksource/clocksource0/current_clocksource`: the
+//!   timer of the architecture.
+//! - `/sys/kernel/mm/transparent_hugepage/enabled`: always `never`.
+//! - `/sys/kernel/debug/log_level`: the max log level, writable.
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+};
+use axdriver::{DeviceInfo, DeviceLocation, prelude::DeviceType};
+use axfs_vfs::{VfsError, VfsResult};
+use core::str::FromStr;
+use log::LevelFilter;
+
+use super::pseudo::{PseudoDir, PseudoFileSystem};
+
+/// Create a new sys filesystem.
+pub fn new_sysfs() -> Arc<PseudoFileSystem> {
+    let sysfs = PseudoFileSystem::new();
+    let root = sysfs.root();
+
+    add_devices(&root.add_dir("class"));
+
+    let system = root.add_dir("devices").add_dir("system");
+    add_cpus(&system.add_dir("cpu"));
+    system
+        .add_dir("clocksource")
+        .add_dir("clocksource0")
+        .add_file("current_clocksource", || clocksource().into());
+
+    let kernel = root.add_dir("kernel");
+    kernel
+        .add_dir("mm")
+        .add_dir("transparent_hugepage")
+        .add_file("enabled", || "always madvise [never]\n".into());
+    kernel
+        .add_dir("debug")
+        .add_writable_file("log_level", log_level, set_log_level);
+
+    Arc::new(sysfs)
+}
+
+/// Adds `<class>/<name>` for every device, named as `diskN`, `ethN`, etc.
+fn add_devices(class: &Arc<PseudoDir>) {
+    let mut dirs = BTreeMap::new();
+    let mut counts = BTreeMap::new();
+    for dev in axdriver::devices() {
+        let (class_name, prefix) = device_class(dev.device_type);
+        let dir = dirs
+            .entry(class_name)
+            .or_insert_with(|| class.add_dir(class_name));
+        let idx = counts.entry(class_name).or_insert(0);
+        add_device(&dir.add_dir(&format!("{}{}", prefix, idx)), dev);
+        *idx += 1;
+    }
+}
+
+fn add_device(dir: &Arc<PseudoDir>, dev: &'static DeviceInfo) {
+    dir.add_file("name", || format!("{}\n", dev.name));
+    dir.add_file("type", || format!("{}\n", device_class(dev.device_type).0));
+    dir.add_file("location", || match dev.location {
+        DeviceLocation::Global => "global\n".into(),
+        DeviceLocation::Mmio { base, size } => {
+            format!("mmio {:#x}-{:#x}\n", base, base + size - 1)
+        }
+        DeviceLocation::Pci {
+            bus,
+            device,
+            function,
+        } => format!("pci 0000:{:02x}:{:02x}.{:x}\n", bus, device, function),
+    });
+}
+
+/// Returns the class directory of the device type, and the prefix of device
+/// names in it.
+fn device_class(ty: DeviceType) -> (&'static str, &'static str) {
+    match ty {
+        DeviceType::Block => ("block", "disk"),
+        DeviceType::Net => ("net", "eth"),
+        DeviceType::Display => ("graphics", "fb"),
+        _ => ("misc", "dev"),
+    }
+}
+
+fn add_cpus(cpu: &Arc<PseudoDir>) {
+    // all CPUs are brought up at boot and never go offline
+    cpu.add_file("online", cpu_range);
+    cpu.add_file("possible", cpu_range);
+    for i in 0..axconfig::plat::CPU_NUM {
+        cpu.add_dir(&format!("cpu{}", i))
+            .add_file("online", || "1\n".into());
+    }
+}
+
+fn cpu_range() -> String {
+    match axconfig::plat::CPU_NUM {
+        1 => "0\n".into(),
+        n => format!("0-{}\n", n - 1),
+    }
+}
+
+fn clocksource() -> &'static str {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            "tsc\n"
+        } else if #[cfg(target_arch = "aarch64")] {
+            "arch_sys_counter\n"
+        } else if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
+            "riscv_clocksource\n"
+        } else if #[cfg(target_arch = "loongarch64")] {
+            "Constant\n"
+        } else {
+            "unknown\n"
+        }
+    }
+}
+
+fn log_level() -> String {
+    let mut level = log::max_level().to_string().to_lowercase();
+    level.push('\n');
+    level
+}
+
+/// Sets the max log level to one of `off`, `error`, `warn`, `info`, `debug`
+/// and `trace`. It has no effect on levels disabled at compile time.
+fn set_log_level(buf: &[u8]) -> VfsResult {
+    let level = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+    let level = LevelFilter::from_str(level.trim()).map_err(|_| VfsError::InvalidInput)?;
+    log::set_max_level(level);
+    Ok(())
+}
@@ -0,0 +1,549 @@
+//! A read-only filesystem of a [tar] image.
+//!
+//! The image is scanned once when opened, to build an index of its entries in
+//! memory. The content of files is read from the image on each access, so it
+//! is never copied into memory. POSIX ustar, GNU long names and PAX extended
+//! headers (`path`, `linkpath` and `size`) are supported. Hard links share the
+//! node of their target, and device files, FIFOs and sockets are skipped.
+//!
+//! The image can not be changed, so mount points on it must be directories of
+//! the image, unless it is the lower layer of an overlay.
+//!
+//! [tar]: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/pax.html
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use core::time::Duration;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+
+use crate::dev::Disk;
+use crate::statfs::FileSystemStats;
+
+const BLOCK_SIZE: u64 = 512;
+const ROOT: usize = 0;
+
+/// Where the image is stored.
+enum Source {
+    Disk(Disk),
+    Memory(&'static [u8]),
+}
+
+/// An entry of the image, with its children if it is a directory.
+struct Entry {
+    ty: VfsNodeType,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u64,
+    size: u64,
+    /// The offset of the content in the image.
+    offset: u64,
+    /// The target of a symbolic link.
+    target: String,
+    parent: usize,
+    children: BTreeMap<String, usize>,
+}
+
+struct Image {
+    source: Source,
+    entries: Vec<Entry>,
+}
+
+/// A read-only tar image on a disk or in memory.
+pub struct TarFileSystem {
+    image: Arc<Image>,
+}
+
+/// A file or directory of [`TarFileSystem`].
+pub struct TarNode {
+    image: Arc<Image>,
+    idx: usize,
+}
+
+/// The fields of a tar header, overridden by the extended headers before it.
+struct Header {
+    name: String,
+    link: String,
+    ty: u8,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u64,
+    size: u64,
+}
+
+/// Names and sizes given by the GNU or PAX extended headers, for the next
+/// entry.
+#[derive(Default)]
+struct Extension {
+    name: Option<String>,
+    link: Option<String>,
+    size: Option<u64>,
+}
+
+impl Source {
+    fn size(&self) -> u64 {
+        match self {
+            Self::Disk(disk) => disk.size(),
+            Self::Memory(data) => data.len() as u64,
+        }
+    }
+
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> VfsResult {
+        match self {
+            Self::Disk(disk) => disk.read_at(pos, buf).map_err(|_| VfsError::Io),
+            Self::Memory(data) => {
+                let data = usize::try_from(pos)
+                    .ok()
+                    .and_then(|pos| data.get(pos..pos.checked_add(buf.len())?))
+                    .ok_or(VfsError::InvalidData)?;
+                buf.copy_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+
+    fn read_string(&self, pos: u64, len: u64) -> VfsResult<String> {
+        let mut buf = vec![0; len as usize];
+        self.read_at(pos, &mut buf)?;
+        Ok(String::from_utf8_lossy(field_bytes(&buf)).into_owned())
+    }
+}
+
+impl Entry {
+    fn dir(parent: usize) -> Self {
+        Self {
+            ty: VfsNodeType::Dir,
+            mode: 0o755,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+            size: 0,
+            offset: 0,
+            target: String::new(),
+            parent,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+impl Header {
+    /// Parses a header block, returns `None` for an end-of-archive block.
+    fn parse(block: &[u8; BLOCK_SIZE as usize]) -> VfsResult<Option<Self>> {
+        if block.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+        // the checksum is computed with its own field as spaces
+        let sum: u32 = block
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| {
+                if (148..156).contains(&i) {
+                    b' ' as u32
+                } else {
+                    b as u32
+                }
+            })
+            .sum();
+        if parse_number(&block[148..156])? != sum as u64 {
+            return Err(VfsError::InvalidData);
+        }
+        let mut name = String::from_utf8_lossy(field_bytes(&block[0..100])).into_owned();
+        if &block[257..263] == b"ustar\0" {
+            let prefix = field_bytes(&block[345..500]);
+            if !prefix.is_empty() {
+                name = alloc::format!("{}/{}", String::from_utf8_lossy(prefix), name);
+            }
+        }
+        Ok(Some(Self {
+            name,
+            link: String::from_utf8_lossy(field_bytes(&block[157..257])).into_owned(),
+            ty: block[156],
+            mode: parse_number(&block[100..108])? as u32,
+            uid: parse_number(&block[108..116])? as u32,
+            gid: parse_number(&block[116..124])? as u32,
+            mtime: parse_number(&block[136..148])?,
+            size: parse_number(&block[124..136])?,
+        }))
+    }
+}
+
+impl Image {
+    /// Scans the image and builds the index of its entries.
+    fn open(source: Source) -> VfsResult<Self> {
+        let mut image = Self {
+            source,
+            entries: vec![Entry::dir(ROOT)],
+        };
+        let mut pos = 0;
+        let mut ext = Extension::default();
+        let mut block = [0; BLOCK_SIZE as usize];
+        while pos + BLOCK_SIZE <= image.source.size() {
+            image.source.read_at(pos, &mut block)?;
+            let Some(mut header) = Header::parse(&block)? else {
+                break;
+            };
+            let data = pos + BLOCK_SIZE;
+            match header.ty {
+                b'L' => ext.name = Some(image.source.read_string(data, header.size)?),
+                b'K' => ext.link = Some(image.source.read_string(data, header.size)?),
+                b'x' => image.read_pax(data, header.size, &mut ext)?,
+                b'g' => {} // global PAX headers are ignored
+                _ => {
+                    let ext = core::mem::take(&mut ext);
+                    header.name = ext.name.unwrap_or(header.name);
+                    header.link = ext.link.unwrap_or(header.link);
+                    header.size = ext.size.unwrap_or(header.size);
+                    image.add(&header, data);
+                }
+            }
+            // links and directories have no content, whatever their size
+            let size = match header.ty {
+                b'1' | b'2' | b'5' => 0,
+                _ => header.size,
+            };
+            pos = data + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        }
+        Ok(image)
+    }
+
+    /// Reads the records of a PAX extended header, like `30 path=a/b\n`.
+    fn read_pax(&self, pos: u64, size: u64, ext: &mut Extension) -> VfsResult {
+        let mut buf = vec![0; size as usize];
+        self.source.read_at(pos, &mut buf)?;
+        let mut rest = buf.as_slice();
+        while let Some(space) = rest.iter().position(|&b| b == b' ') {
+            let len: usize = core::str::from_utf8(&rest[..space])
+                .ok()
+                .and_then(|len| len.parse().ok())
+                .filter(|&len| len > space && len <= rest.len())
+                .ok_or(VfsError::InvalidData)?;
+            let record = String::from_utf8_lossy(&rest[space + 1..len - 1]);
+            if let Some((key, value)) = record.split_once('=') {
+                match key {
+                    "path" => ext.name = Some(value.into()),
+                    "linkpath" => ext.link = Some(value.into()),
+                    "size" => ext.size = value.parse().ok(),
+                    _ => {}
+                }
+            }
+            rest = &rest[len..];
+        }
+        Ok(())
+    }
+
+    /// Adds the entry of `header` with the content at `offset`, along with
+    /// its missing parent directories.
+    fn add(&mut self, header: &Header, offset: u64) {
+        let ty = match header.ty {
+            b'0' | b'\0' | b'7' => VfsNodeType::File,
+            b'2' => VfsNodeType::SymLink,
+            b'5' => VfsNodeType::Dir,
+            b'1' => {
+                match self.find(&header.link) {
+                    Some(target) if self.entries[target].ty != VfsNodeType::Dir => {
+                        self.link(&header.name, target);
+                    }
+                    _ => warn!(
+                        "tarfs: skip {:?}, bad link to {:?}",
+                        header.name, header.link
+                    ),
+                }
+                return;
+            }
+            _ => {
+                warn!(
+                    "tarfs: skip {:?} of type {:?}",
+                    header.name, header.ty as char
+                );
+                return;
+            }
+        };
+        let Some((parent, name)) = self.parent_of(&header.name) else {
+            // the root directory itself
+            if ty == VfsNodeType::Dir {
+                self.set_meta(ROOT, header);
+            }
+            return;
+        };
+        if let Some(&idx) = self.entries[parent].children.get(name) {
+            if ty == VfsNodeType::Dir && self.entries[idx].ty == VfsNodeType::Dir {
+                self.set_meta(idx, header);
+                return;
+            }
+        }
+        let idx = self.entries.len();
+        self.entries.push(Entry {
+            ty,
+            size: match ty {
+                VfsNodeType::File => header.size,
+                VfsNodeType::SymLink => header.link.len() as u64,
+                _ => 0,
+            },
+            offset,
+            target: match ty {
+                VfsNodeType::SymLink => header.link.clone(),
+                _ => String::new(),
+            },
+            ..Entry::dir(parent)
+        });
+        self.set_meta(idx, header);
+        // a later entry replaces an earlier one of the same path
+        self.entries[parent].children.insert(name.into(), idx);
+    }
+
+    fn set_meta(&mut self, idx: usize, header: &Header) {
+        let entry = &mut self.entries[idx];
+        entry.mode = header.mode & 0o7777;
+        entry.uid = header.uid;
+        entry.gid = header.gid;
+        entry.mtime = header.mtime;
+    }
+
+    fn link(&mut self, path: &str, target: usize) {
+        if let Some((parent, name)) = self.parent_of(path) {
+            self.entries[parent].children.insert(name.into(), target);
+        }
+    }
+
+    /// Returns the directory of the last component of `path` and the name
+    /// of it, creating the missing directories. Returns `None` for the root.
+    fn parent_of<'a>(&mut self, path: &'a str) -> Option<(usize, &'a str)> {
+        let mut names = path
+            .split('/')
+            .filter(|name| !name.is_empty() && *name != "." && *name != "..");
+        let mut name = names.next()?;
+        let mut dir = ROOT;
+        for next in names {
+            dir = match self.entries[dir].children.get(name) {
+                Some(&idx) if self.entries[idx].ty == VfsNodeType::Dir => idx,
+                _ => {
+                    let idx = self.entries.len();
+                    self.entries.push(Entry::dir(dir));
+                    self.entries[dir].children.insert(name.into(), idx);
+                    idx
+                }
+            };
+            name = next;
+        }
+        Some((dir, name))
+    }
+
+    /// Finds the entry at `path` relative to the root.
+    fn find(&self, path: &str) -> Option<usize> {
+        path.split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+            .try_fold(ROOT, |dir, name| {
+                self.entries[dir].children.get(name).copied()
+            })
+    }
+}
+
+impl TarFileSystem {
+    /// Whether there is a tar image on the disk.
+    pub fn probe(disk: &Disk) -> bool {
+        let mut block = [0; BLOCK_SIZE as usize];
+        disk.read_at(0, &mut block).is_ok()
+            && &block[257..262] == b"ustar"
+            && Header::parse(&block).is_ok_and(|h| h.is_some())
+    }
+
+    /// Opens the tar image on the disk.
+    pub fn open(disk: Disk) -> VfsResult<Arc<Self>> {
+        Self::new(Source::Disk(disk))
+    }
+
+    /// Opens the tar image in memory.
+    pub fn from_memory(data: &'static [u8]) -> VfsResult<Arc<Self>> {
+        Self::new(Source::Memory(data))
+    }
+
+    fn new(source: Source) -> VfsResult<Arc<Self>> {
+        let image = Image::open(source)?;
+        info!("tarfs: {} entries", image.entries.len());
+        Ok(Arc::new(Self {
+            image: Arc::new(image),
+        }))
+    }
+}
+
+impl VfsOps for TarFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        Arc::new(TarNode {
+            image: self.image.clone(),
+            idx: ROOT,
+        })
+    }
+}
+
+impl TarNode {
+    fn entry(&self) -> &Entry {
+        &self.image.entries[self.idx]
+    }
+
+    fn new_node(&self, idx: usize) -> VfsNodeRef {
+        Arc::new(Self {
+            image: self.image.clone(),
+            idx,
+        })
+    }
+
+    /// Finds the entry at `path` relative to this directory.
+    fn resolve(&self, path: &str) -> VfsResult<usize> {
+        let mut idx = self.idx;
+        for name in path.split('/') {
+            let entry = &self.image.entries[idx];
+            if entry.ty != VfsNodeType::Dir {
+                return Err(VfsError::NotADirectory);
+            }
+            idx = match name {
+                "" | "." => idx,
+                ".." if idx == ROOT => return Err(VfsError::NotFound),
+                ".." => entry.parent,
+                _ => *entry.children.get(name).ok_or(VfsError::NotFound)?,
+            };
+        }
+        Ok(idx)
+    }
+
+    /// Returns the size of the image and the number of its entries, nothing
+    /// is free in it.
+    pub fn stats(&self) -> FileSystemStats {
+        FileSystemStats {
+            block_size: BLOCK_SIZE,
+            blocks: self.image.source.size() / BLOCK_SIZE,
+            files: self.image.entries.len() as u64,
+            read_only: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl crate::fs::NodeExt for TarNode {
+    fn owner(&self) -> Option<(u32, u32)> {
+        Some((self.entry().uid, self.entry().gid))
+    }
+
+    /// Only the modification time is stored in the image.
+    fn times(&self) -> Option<(Duration, Duration, Duration)> {
+        let mtime = Duration::from_secs(self.entry().mtime);
+        Some((mtime, mtime, mtime))
+    }
+}
+
+impl VfsNodeOps for TarNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let entry = self.entry();
+        let perm = VfsNodePerm::from_bits_truncate(entry.mode as u16);
+        let blocks = entry.size.div_ceil(BLOCK_SIZE);
+        Ok(VfsNodeAttr::new(perm, entry.ty, entry.size, blocks))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let entry = self.entry();
+        if entry.ty == VfsNodeType::Dir {
+            return Err(VfsError::IsADirectory);
+        }
+        if offset >= entry.size {
+            return Ok(0);
+        }
+        let len = buf.len().min((entry.size - offset) as usize);
+        match entry.ty {
+            VfsNodeType::SymLink => {
+                buf[..len].copy_from_slice(&entry.target.as_bytes()[offset as usize..][..len])
+            }
+            _ => (self.image.source).read_at(entry.offset + offset, &mut buf[..len])?,
+        }
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        Ok(()) // read-only
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.idx == ROOT {
+            return None;
+        }
+        Some(self.new_node(self.entry().parent))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        debug!("lookup at tarfs: {}", path);
+        Ok(self.new_node(self.resolve(path)?))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        // existing entries are accepted, like other filesystems do
+        match self.resolve(path) {
+            Ok(idx) if self.image.entries[idx].ty == ty => Ok(()),
+            Ok(_) => Err(VfsError::AlreadyExists),
+            Err(_) => Err(VfsError::PermissionDenied),
+        }
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entry = self.entry();
+        if entry.ty != VfsNodeType::Dir {
+            return Err(VfsError::NotADirectory);
+        }
+        let mut children = entry.children.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match children.next() {
+                    Some((name, &idx)) => {
+                        *ent = VfsDirEntry::new(name, self.image.entries[idx].ty);
+                    }
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// Returns the bytes of a field before the first NUL.
+fn field_bytes(field: &[u8]) -> &[u8] {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..len]
+}
+
+/// Parses a numeric field, in octal ASCII or in the GNU base-256 encoding.
+fn parse_number(field: &[u8]) -> VfsResult<u64> {
+    if field[0] & 0x80 != 0 {
+        // big-endian binary, without the marker bit
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7F) as u64, |n, &b| (n << 8) | b as u64));
+    }
+    let digits = field_bytes(field);
+    let digits = core::str::from_utf8(digits).map_err(|_| VfsError::InvalidData)?;
+    let digits = digits.trim_matches(|c| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| VfsError::InvalidData)
+}
@@ -0,0 +1,183 @@
+//! Unpacking of the initramfs, a cpio archive in the "newc" format, into the
+//! root directory.
+//!
+//! Each entry is a header of ASCII hexadecimal fields, followed by the path and
+//! the data, both padded to 4 bytes. The data of a file with hard links is in
+//! its last entry, and the archive ends with an entry named `TRAILER!!!`.
+
+use alloc::{collections::BTreeMap, format, vec::Vec};
+use axerrno::{AxError, AxResult, ax_err};
+use core::time::Duration;
+
+use crate::api::{self, FileTimes, Permissions};
+
+/// The archive embedded in the kernel image, or an empty one.
+#[cfg(initramfs_image)]
+pub(crate) const EMBEDDED: &[u8] = include_bytes!(env!("AX_INITRAMFS"));
+#[cfg(not(initramfs_image))]
+pub(crate) const EMBEDDED: &[u8] = &[];
+
+const MAGIC: &[u8] = b"070701";
+const MAGIC_CRC: &[u8] = b"070702";
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// The header of an entry of the archive.
+struct Header {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    file_size: usize,
+    dev_major: u32,
+    dev_minor: u32,
+    name_size: usize,
+}
+
+/// An entry of the archive, with the path relative to the root.
+struct Entry<'a> {
+    header: Header,
+    path: &'a str,
+    data: &'a [u8],
+}
+
+impl Header {
+    fn parse(buf: &[u8]) -> AxResult<Self> {
+        if buf.len() < HEADER_SIZE || !matches!(&buf[..6], MAGIC | MAGIC_CRC) {
+            return ax_err!(InvalidData, "bad cpio header");
+        }
+        // 13 fields of 8 hexadecimal digits follow the magic
+        let field = |i: usize| -> AxResult<u32> {
+            let digits = core::str::from_utf8(&buf[6 + i * 8..14 + i * 8])
+                .map_err(|_| AxError::InvalidData)?;
+            u32::from_str_radix(digits, 16).map_err(|_| AxError::InvalidData)
+        };
+        Ok(Self {
+            ino: field(0)?,
+            mode: field(1)?,
+            uid: field(2)?,
+            gid: field(3)?,
+            nlink: field(4)?,
+            mtime: field(5)?,
+            file_size: field(6)? as usize,
+            dev_major: field(7)?,
+            dev_minor: field(8)?,
+            name_size: field(11)? as usize,
+        })
+    }
+}
+
+/// Returns the entries of the archive in order, until the trailer.
+fn entries(archive: &[u8]) -> AxResult<Vec<Entry<'_>>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    loop {
+        let header = Header::parse(&archive[offset.min(archive.len())..])?;
+        // the name and the data are padded to 4 bytes
+        let name_start = offset + HEADER_SIZE;
+        let data_start = (name_start + header.name_size).next_multiple_of(4);
+        let data_end = data_start + header.file_size;
+        if header.name_size == 0 || data_end > archive.len() {
+            return ax_err!(InvalidData, "truncated cpio archive");
+        }
+        let name = &archive[name_start..name_start + header.name_size - 1];
+        let name = core::str::from_utf8(name).map_err(|_| AxError::InvalidData)?;
+        if name == TRAILER {
+            return Ok(entries);
+        }
+        entries.push(Entry {
+            path: name.trim_start_matches("./").trim_start_matches('/'),
+            data: &archive[data_start..data_end],
+            header,
+        });
+        offset = data_end.next_multiple_of(4);
+    }
+}
+
+/// Unpacks the cpio `archive` into the root directory. An empty archive
+/// leaves the root empty.
+pub(crate) fn unpack(archive: &[u8]) -> AxResult {
+    if archive.is_empty() {
+        return Ok(());
+    }
+    let entries = entries(archive)?;
+    info!("  unpack initramfs: {} entries", entries.len());
+    // files with more than one link, by device and inode numbers, and the
+    // path where they were first unpacked
+    let mut links = BTreeMap::new();
+    let mut dirs = Vec::new();
+    for entry in &entries {
+        let header = &entry.header;
+        if entry.path.is_empty() || entry.path == "." {
+            continue;
+        }
+        let path = format!("/{}", entry.path);
+        let (parent, _) = path.rsplit_once('/').unwrap();
+        if !parent.is_empty() {
+            api::create_dir_all(parent)?;
+        }
+        match header.mode & S_IFMT {
+            S_IFDIR => {
+                if api::metadata(&path).is_err() {
+                    api::create_dir(&path)?;
+                }
+                // set after the entries in it are unpacked, which change the
+                // modification time
+                dirs.push(entry);
+                continue;
+            }
+            S_IFREG => {
+                let key = (header.dev_major, header.dev_minor, header.ino);
+                match links.get(&key) {
+                    Some(original) if header.nlink > 1 => {
+                        api::hard_link(original, &path)?;
+                        // the data is only in the last entry of the file
+                        if !entry.data.is_empty() {
+                            api::write(&path, entry.data)?;
+                        }
+                    }
+                    _ => {
+                        api::write(&path, entry.data)?;
+                        if header.nlink > 1 {
+                            links.insert(key, path.clone());
+                        }
+                    }
+                }
+            }
+            S_IFLNK => {
+                let target = core::str::from_utf8(entry.data).map_err(|_| AxError::InvalidData)?;
+                api::soft_link(target, &path)?;
+                api::lchown(&path, Some(header.uid), Some(header.gid))?;
+                api::set_symlink_times(&path, times_of(header))?;
+                continue;
+            }
+            _ => {
+                warn!("initramfs: skip {:?} of mode {:#o}", path, header.mode);
+                continue;
+            }
+        }
+        set_meta(&path, header)?;
+    }
+    for entry in dirs.into_iter().rev() {
+        set_meta(&format!("/{}", entry.path), &entry.header)?;
+    }
+    Ok(())
+}
+
+fn times_of(header: &Header) -> FileTimes {
+    let mtime = Duration::from_secs(header.mtime as u64);
+    FileTimes::new().set_accessed(mtime).set_modified(mtime)
+}
+
+fn set_meta(path: &str, header: &Header) -> AxResult {
+    api::set_permissions(path, Permissions::from_bits_truncate(header.mode as u16))?;
+    api::chown(path, Some(header.uid), Some(header.gid))?;
+    api::set_times(path, times_of(header))
+}
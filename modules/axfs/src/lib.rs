@@ -6,17 +6,110 @@
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!   is **enabled** by default.
+//! - `ext4`: Use [ext4] as the main filesystem if the root volume has one, and
+//!   allow mounting other ext4 volumes. It takes precedence over `fatfs`, which
+//!   is still used for FAT volumes if both are enabled. This feature is
+//!   **disabled** by default.
+//! - `tarfs`: Use a read-only [tar] image as the main filesystem if the root
+//!   volume has one, and allow mounting other tar images, on volumes or in
+//!   memory by [`api::mount_tar_image`]. It takes precedence over `ext4` and
+//!   `fatfs`. This feature is **disabled** by default.
 //! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
 //!   **enabled** by default.
-//! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
+//! - `ramfs`: Mount a RAM filesystem on `/tmp`, which supports symbolic and
+//!   hard links. Its size limit and swap volume can be given by the
+//!   `AX_TMPFS_OPTIONS` environment variable at build time, as the options of
+//!   [`api::mount_virtual_with_options`] (e.g. `size=50%,swap=disk1`). This
+//!   feature is **enabled** by default.
+//! - `overlay`: Use an overlay of a writable filesystem on the main filesystem
+//!   as the root, so that a read-only root can be written. The writable layer
+//!   is a RAM filesystem, or the volume given by the `AX_OVERLAY_UPPER`
+//!   environment variable at build time. This feature is **disabled** by
+//!   default.
+//! - `initramfs`: Use a RAM filesystem as the root, into which a cpio archive
+//!   is unpacked at boot, see [Initramfs](#initramfs). This feature is
+//!   **disabled** by default.
+//! - `procfs`: Mount a proc filesystem on `/proc`, whose files are generated
+//!   from the kernel state on each read. Upper layers can add their own files
+//!   by [`register_procfs_provider`]. This feature is **enabled** by default.
+//! - `sysfs`: Mount a sys filesystem on `/sys`, which lists devices and CPUs,
+//!   and allows tuning the kernel by writing its files. This feature is
 //!   **enabled** by default.
+//! - `multitask`: Allow waiting for file locks by blocking the task, see
+//!   [Locks](#locks). This feature is **disabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!   default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!   to create and initialize other filesystems. This feature is **disabled** by
 //!   by default, but it will override other filesystem selection features if
 //!   both are enabled.
+//! - `mmap`: Allow page caches of regular files to back file mappings of
+//!   `axmm`, see [`fops::File::page_cache`]. This feature is **disabled** by
+//!   default.
+//!
+//! # Block devices
+//!
+//! Every block device is named `diskN`, and each partition in its MBR or GPT
+//! `diskNpM`. The root filesystem is placed on the volume given by the
+//! `AX_ROOT_PART` environment variable at build time (a name or a GPT label),
+//! which defaults to the first partition of `disk0`. Others can be mounted at
+//! runtime by [`api::mount_volume`].
+//!
+//! # Initramfs
+//!
+//! With the `initramfs` feature, a cpio archive in the "newc" format (created
+//! by `cpio -H newc`, as the initramfs of Linux) can be unpacked into a RAM
+//! filesystem as the root. The archive is embedded in the kernel image from the
+//! path given by the `AX_INITRAMFS` environment variable at build time, or
+//! passed to [`init_filesystems_with_initramfs`]. Directories, regular files,
+//! symbolic and hard links are unpacked with their permissions, owners and
+//! modification times. Device files, FIFOs and sockets are skipped.
+//!
+//! # Links
+//!
+//! Symbolic links are followed in all paths, except the last component of
+//! those given to [`api::symlink_metadata`], [`api::read_link`] and functions
+//! that remove or rename the link itself. They can be created on filesystems
+//! whose nodes accept [`VfsNodeType::SymLink`](axfs_vfs::VfsNodeType::SymLink)
+//! in `create`, with the target as the content. Hard links are only supported
+//! by the RAM filesystem.
+//!
+//! Paths are resolved component by component, so `..` leads to the parent of
+//! the directory that a symbolic link resolves to, and fails if the component
+//! before it does not exist or is not a directory.
+//!
+//! # Locks
+//!
+//! Files support advisory locks, see [`fops::File::locker`]: whole-file locks
+//! as `flock`, and byte-range record locks of processes as `fcntl`. Waiting
+//! for a lock requires the `multitask` feature.
+//!
+//! # Metadata
+//!
+//! Files have access, modification and change times, an owner and a group,
+//! which can be changed by [`api::set_times`] and [`api::chown`], along with
+//! the permissions by [`api::set_permissions`]. They are stored in the inodes
+//! of ext4, the times also in FAT directory entries, and are otherwise kept in
+//! memory until the file is removed or unmounted. Files without stored owners
+//! are owned by root, and those without stored times have the time of
+//! mounting. The times are from the wall clock given by [`set_clocks`].
+//!
+//! # Notifications
+//!
+//! Changes made through this crate can be watched by [`api::Watcher`], like
+//! `inotify` on Linux: the creation, modification, removal and renaming of
+//! files in watched directories, and of watched files themselves. Changes made
+//! by the filesystems themselves, like those of procfs, are not reported.
+//!
+//! # Quotas
+//!
+//! The usage of a filesystem is reported by [`api::statfs`], as far as the
+//! filesystem knows it. Each mounted filesystem can be given a quota of bytes
+//! and files by [`api::set_quota`], enforced on changes made through this
+//! crate, which then fail with [`StorageFull`](axerrno::AxError::StorageFull).
 //!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [ext4]: https://en.wikipedia.org/wiki/Ext4
+//! [tar]: https://en.wikipedia.org/wiki/Tar_(computing)
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
@@ -26,21 +119,79 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
+mod clock;
 mod dev;
 mod fs;
+#[cfg(feature = "initramfs")]
+mod initramfs;
+mod lock;
+mod meta;
 mod mounts;
+mod notify;
+mod page_cache;
+mod partition;
 mod root;
+mod statfs;
+#[cfg(all(
+    test,
+    feature = "fatfs",
+    not(any(feature = "myfs", feature = "overlay"))
+))]
+mod tests;
 
 pub mod api;
 pub mod fops;
 
+pub use self::clock::set_clocks;
+#[cfg(feature = "procfs")]
+pub use self::fs::procfs::register_provider as register_procfs_provider;
+#[cfg(any(feature = "procfs", feature = "sysfs"))]
+pub use self::fs::pseudo;
+
 use axdriver::{AxDeviceContainer, prelude::*};
 
 /// Initializes filesystems by block devices.
-pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
+///
+/// All block devices and partitions on them are registered, see the
+/// [crate-level documentation](crate) for which one holds the root filesystem.
+/// With the `initramfs` feature, the root filesystem is the initramfs instead
+/// if an archive is embedded in the kernel image, or if there is no block
+/// device.
+pub fn init_filesystems(blk_devs: AxDeviceContainer<AxBlockDevice>) {
+    #[cfg(feature = "initramfs")]
+    if !initramfs::EMBEDDED.is_empty() || blk_devs.is_empty() {
+        return init_filesystems_with_initramfs(blk_devs, initramfs::EMBEDDED);
+    }
+
     info!("Initialize filesystems...");
+    let num_disks = add_disks(blk_devs);
+    assert!(num_disks > 0, "No block device found!");
+    let (root_volume, root_disk) = self::partition::take_root_volume();
+    self::root::init_rootfs(&root_volume, root_disk);
+}
+
+/// Initializes filesystems with the cpio `archive` unpacked into a RAM
+/// filesystem as the root, e.g. an initramfs passed by the bootloader.
+///
+/// All block devices and partitions on them are registered, and can be
+/// mounted by [`api::mount_volume`]. See the [crate-level
+/// documentation](crate#initramfs) for the format of the archive.
+#[cfg(feature = "initramfs")]
+pub fn init_filesystems_with_initramfs(blk_devs: AxDeviceContainer<AxBlockDevice>, archive: &[u8]) {
+    info!("Initialize filesystems...");
+    add_disks(blk_devs);
+    self::root::init_initramfs();
+    self::initramfs::unpack(archive).expect("failed to unpack initramfs");
+}
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+/// Registers all block devices, returns the number of them.
+fn add_disks(mut blk_devs: AxDeviceContainer<AxBlockDevice>) -> usize {
+    let mut num_disks = 0;
+    while let Some(dev) = blk_devs.take_one() {
+        info!("  use block device {}: {:?}", num_disks, dev.device_name());
+        self::partition::add_disk(num_disks, self::dev::Disk::new(dev));
+        num_disks += 1;
+    }
+    num_disks
 }
@@ -0,0 +1,291 @@
+//! Advisory locks of files.
+//!
+//! Files are identified as in the [page cache](crate::page_cache), and all
+//! opened [`File`](crate::fops::File)s of a file share a table of its locks.
+//! There are two kinds of locks, which never conflict with each other, as on
+//! Linux:
+//!
+//! - Whole-file locks of `flock`, owned by an opened file, and released when
+//!   it is dropped.
+//! - Byte-range record locks of `fcntl`, owned by a process, and released by
+//!   [`FileLocker::release_records`], which POSIX requires when the process
+//!   closes any descriptor of the file.
+//!
+//! A conflicting lock is waited for until it is released, if the `multitask`
+//! feature is enabled. Otherwise it fails with `WouldBlock`, as no other task
+//! can release it.
+
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerrno::{AxResult, ax_err};
+use axfs_vfs::VfsNodeRef;
+use axsync::{Mutex, spin::SpinNoIrq};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::root::MountPoint;
+
+static LOCK_TABLES: Mutex<BTreeMap<String, Weak<LockTable>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_FILE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The type of a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// A read lock, which can be held by multiple owners at the same time.
+    Shared,
+    /// A write lock, which can only be held by one owner.
+    Exclusive,
+}
+
+/// A byte-range record lock of a file, as `struct flock` of `fcntl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLock {
+    /// The type of the lock.
+    pub kind: LockKind,
+    /// The offset of the first locked byte.
+    pub start: u64,
+    /// The offset after the last locked byte, or `u64::MAX` to lock to the
+    /// end of the file however it grows.
+    pub end: u64,
+    /// The process holding the lock.
+    pub pid: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Owner {
+    /// An opened file, by its ID.
+    File(u64),
+    /// A process, by its ID.
+    Process(u64),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lock {
+    kind: LockKind,
+    start: u64,
+    end: u64,
+    owner: Owner,
+}
+
+struct LockTable {
+    locks: SpinNoIrq<Vec<Lock>>,
+    #[cfg(feature = "multitask")]
+    wq: axtask::WaitQueue,
+}
+
+/// Locks an opened file.
+///
+/// It is obtained by [`File::locker`](crate::fops::File::locker), and can be
+/// used to wait for a lock without borrowing the file.
+#[derive(Clone)]
+pub struct FileLocker {
+    table: Arc<LockTable>,
+    file_id: u64,
+}
+
+/// Returns a locker for a newly opened file `node` at the absolute path
+/// `path`, which shares the lock table with other opened files of it.
+pub(crate) fn open(path: &str, node: &VfsNodeRef, mount: &MountPoint) -> FileLocker {
+    let key = mount.file_key(path, node);
+    let mut tables = LOCK_TABLES.lock();
+    let table = match tables.get(&key).and_then(Weak::upgrade) {
+        Some(table) => table,
+        None => {
+            tables.retain(|_, table| table.strong_count() > 0);
+            let table = Arc::new(LockTable {
+                locks: SpinNoIrq::new(Vec::new()),
+                #[cfg(feature = "multitask")]
+                wq: axtask::WaitQueue::new(),
+            });
+            tables.insert(key, Arc::downgrade(&table));
+            table
+        }
+    };
+    FileLocker {
+        table,
+        file_id: NEXT_FILE_ID.fetch_add(1, Ordering::Relaxed),
+    }
+}
+
+/// Forgets the locks of the removed file identified by `key`, see
+/// [`MountPoint::file_key`]. Opened files keep using them.
+pub(crate) fn remove(key: &str) {
+    LOCK_TABLES.lock().remove(key);
+}
+
+/// Moves the locks of the file or directory renamed from `old` to `new`.
+pub(crate) fn rename(old: &str, new: &str) {
+    crate::root::rename_keys(&mut LOCK_TABLES.lock(), old, new);
+}
+
+impl Lock {
+    fn conflicts_with(&self, other: &Lock) -> bool {
+        let same_kind = matches!(
+            (self.owner, other.owner),
+            (Owner::File(_), Owner::File(_)) | (Owner::Process(_), Owner::Process(_))
+        );
+        same_kind
+            && self.owner != other.owner
+            && self.start < other.end
+            && other.start < self.end
+            && (self.kind == LockKind::Exclusive || other.kind == LockKind::Exclusive)
+    }
+}
+
+impl LockTable {
+    fn conflict(&self, lock: &Lock) -> Option<Lock> {
+        let locks = self.locks.lock();
+        locks.iter().find(|l| l.conflicts_with(lock)).copied()
+    }
+
+    /// Adds `lock`, replacing the locks of the same owner in its range.
+    fn acquire(&self, lock: Lock, wait: bool) -> AxResult {
+        loop {
+            let mut locks = self.locks.lock();
+            if !locks.iter().any(|l| l.conflicts_with(&lock)) {
+                remove_range(&mut locks, lock.owner, lock.start, lock.end);
+                locks.push(lock);
+                drop(locks);
+                // others may wait for a lock that has been downgraded
+                self.notify();
+                return Ok(());
+            }
+            drop(locks);
+            if !wait {
+                return ax_err!(WouldBlock);
+            }
+            self.wait_for(&lock)?;
+        }
+    }
+
+    /// Removes the locks of `owner` in the range from `start` to `end`.
+    fn release(&self, owner: Owner, start: u64, end: u64) {
+        remove_range(&mut self.locks.lock(), owner, start, end);
+        self.notify();
+    }
+
+    #[cfg(feature = "multitask")]
+    fn wait_for(&self, lock: &Lock) -> AxResult {
+        self.wq.wait_until(|| self.conflict(lock).is_none());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "multitask"))]
+    fn wait_for(&self, _lock: &Lock) -> AxResult {
+        ax_err!(WouldBlock)
+    }
+
+    fn notify(&self) {
+        #[cfg(feature = "multitask")]
+        self.wq.notify_all(false);
+    }
+}
+
+impl FileLocker {
+    /// Locks the whole file as `flock`, or unlocks it if `kind` is `None`.
+    ///
+    /// The lock is owned by the opened file, and any lock it already holds is
+    /// converted to `kind`. If `wait` is false, it fails with `WouldBlock`
+    /// instead of waiting for a conflicting lock.
+    pub fn flock(&self, kind: Option<LockKind>, wait: bool) -> AxResult {
+        let owner = Owner::File(self.file_id);
+        let Some(kind) = kind else {
+            self.table.release(owner, 0, u64::MAX);
+            return Ok(());
+        };
+        let lock = Lock {
+            kind,
+            start: 0,
+            end: u64::MAX,
+            owner,
+        };
+        if wait && self.table.conflict(&lock).is_some() {
+            // release the lock being converted before waiting, as Linux does,
+            // so that two owners converting their locks do not deadlock
+            self.table.release(owner, 0, u64::MAX);
+        }
+        self.table.acquire(lock, wait)
+    }
+
+    /// Sets the record lock `lock`, replacing the locks of the same process
+    /// in its range.
+    ///
+    /// If `wait` is false, it fails with `WouldBlock` instead of waiting for a
+    /// conflicting lock.
+    pub fn lock_record(&self, lock: RecordLock, wait: bool) -> AxResult {
+        let lock = Lock {
+            kind: lock.kind,
+            start: lock.start,
+            end: lock.end,
+            owner: Owner::Process(lock.pid),
+        };
+        self.table.acquire(lock, wait)
+    }
+
+    /// Removes the record locks of the process `pid` in the range from
+    /// `start` to `end`.
+    pub fn unlock_record(&self, pid: u64, start: u64, end: u64) {
+        self.table.release(Owner::Process(pid), start, end);
+    }
+
+    /// Returns a record lock of another process that conflicts with `lock`,
+    /// or `None` if `lock` can be set.
+    pub fn test_record(&self, lock: RecordLock) -> Option<RecordLock> {
+        let conflict = self.table.conflict(&Lock {
+            kind: lock.kind,
+            start: lock.start,
+            end: lock.end,
+            owner: Owner::Process(lock.pid),
+        })?;
+        match conflict.owner {
+            Owner::Process(pid) => Some(RecordLock {
+                kind: conflict.kind,
+                start: conflict.start,
+                end: conflict.end,
+                pid,
+            }),
+            Owner::File(_) => None,
+        }
+    }
+
+    /// Removes all the record locks of the process `pid`.
+    pub fn release_records(&self, pid: u64) {
+        self.unlock_record(pid, 0, u64::MAX);
+    }
+
+    /// Removes the lock owned by the opened file, when it is dropped.
+    pub(crate) fn release_file(&self) {
+        self.table.release(Owner::File(self.file_id), 0, u64::MAX);
+    }
+}
+
+/// Removes the locks of `owner` in the range from `start` to `end`, splitting
+/// those partially in the range.
+fn remove_range(locks: &mut Vec<Lock>, owner: Owner, start: u64, end: u64) {
+    let mut tails = Vec::new();
+    locks.retain_mut(|lock| {
+        if lock.owner != owner || lock.end <= start || end <= lock.start {
+            return true;
+        }
+        if lock.start < start && end < lock.end {
+            tails.push(Lock {
+                start: end,
+                ..*lock
+            });
+        }
+        if lock.start < start {
+            lock.end = start;
+            true
+        } else if end < lock.end {
+            lock.start = end;
+            true
+        } else {
+            false
+        }
+    });
+    locks.extend(tails);
+}
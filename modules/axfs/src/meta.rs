@@ -0,0 +1,278 @@
+//! Timestamps, owners and permissions of files.
+//!
+//! Nodes of [`axfs_vfs`] only have a type, permissions and a size. The rest of
+//! the metadata is read from the filesystems that store it: ext4 stores all of
+//! it in the inode, FAT only the times, and tar images the owner and the
+//! modification time. Otherwise the file is owned by root (`0:0`), with the
+//! time of mounting.
+//!
+//! Changes that the filesystem can't store, and the times of files created in
+//! filesystems without times, are kept here in memory, until the file is
+//! removed or its filesystem unmounted. Files are identified as in the
+//! [page cache](crate::page_cache), see [`MountPoint::file_key`].
+
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+};
+use axerrno::{AxError, AxResult};
+use axfs_vfs::{VfsNodeOps, VfsNodeRef, VfsResult};
+use axsync::Mutex;
+use core::time::Duration;
+
+use crate::fops::{FileAttr, FilePerm};
+use crate::fs::NodeExt;
+use crate::root::MountPoint;
+
+static METAS: Mutex<BTreeMap<String, Entry>> = Mutex::new(BTreeMap::new());
+
+/// Timestamps and owner of a file, in addition to its [`FileAttr`].
+///
+/// Times are durations since the UNIX epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileMeta {
+    /// User ID of the owner.
+    pub uid: u32,
+    /// Group ID of the owner.
+    pub gid: u32,
+    /// Time of the last access.
+    pub atime: Duration,
+    /// Time of the last modification of the content.
+    pub mtime: Duration,
+    /// Time of the last change of the content or the metadata.
+    pub ctime: Duration,
+    /// Inode number, unique in the filesystem.
+    pub ino: u64,
+    /// Number of hard links.
+    pub nlink: u64,
+}
+
+struct Entry {
+    meta: FileMeta,
+    /// Permissions set by `chmod`, or `None` to use those of the filesystem.
+    perm: Option<FilePerm>,
+    /// The node if the file is identified by it, it also keeps the address of
+    /// the node from being reused by others.
+    node: Option<Weak<dyn VfsNodeOps>>,
+}
+
+/// A file whose metadata is queried or changed.
+pub(crate) struct MetaNode<'a> {
+    /// The canonical absolute path.
+    pub path: &'a str,
+    pub node: &'a VfsNodeRef,
+    pub mount: &'a MountPoint,
+}
+
+impl MetaNode<'_> {
+    fn key(&self) -> String {
+        self.mount.file_key(self.path, self.node)
+    }
+
+    /// Returns the metadata of the file.
+    pub fn get(&self) -> FileMeta {
+        let meta = match METAS.lock().get(&self.key()) {
+            Some(entry) => entry.meta,
+            None => self.initial(),
+        };
+        FileMeta {
+            ino: self.ino(),
+            nlink: self.mount.node_ext(self.node).nlink().unwrap_or(1),
+            ..meta
+        }
+    }
+
+    /// Returns the inode number of the file. Files of filesystems without
+    /// inode numbers are numbered by the hash of their keys.
+    pub fn ino(&self) -> u64 {
+        self.mount
+            .node_ext(self.node)
+            .ino()
+            .unwrap_or_else(|| fnv1a(self.key().as_bytes()))
+    }
+
+    /// Returns the attributes of the file, with the permissions set by
+    /// [`set_perm`](Self::set_perm).
+    pub fn attr(&self) -> AxResult<FileAttr> {
+        let attr = self.node.get_attr()?;
+        match METAS.lock().get(&self.key()).and_then(|entry| entry.perm) {
+            Some(perm) => Ok(FileAttr::new(
+                perm,
+                attr.file_type(),
+                attr.size(),
+                attr.blocks(),
+            )),
+            None => Ok(attr),
+        }
+    }
+
+    /// Records that the file has just been created, if its filesystem doesn't
+    /// store times.
+    pub fn created(&self) {
+        if self.stored_times().is_some() {
+            return;
+        }
+        let now = crate::clock::wall_time();
+        let entry = self.new_entry(FileMeta {
+            atime: now,
+            mtime: now,
+            ctime: now,
+            ..Default::default()
+        });
+        METAS.lock().insert(self.key(), entry);
+    }
+
+    /// Records that the content of the file has just been changed, if its
+    /// metadata is kept in memory. Filesystems that store times update them
+    /// on their own.
+    pub fn modified(&self) {
+        if let Some(entry) = METAS.lock().get_mut(&self.key()) {
+            entry.meta.mtime = crate::clock::wall_time();
+            entry.meta.ctime = entry.meta.mtime;
+        }
+    }
+
+    /// Changes the permissions of the file.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        self.change(
+            |ext| ext.set_perm(perm),
+            |_, old_perm| *old_perm = Some(perm),
+        )
+    }
+
+    /// Changes the owner and the group of the file, `None` leaves it
+    /// unchanged.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        self.change(
+            |ext| ext.set_owner(uid, gid),
+            |meta, _| {
+                meta.uid = uid.unwrap_or(meta.uid);
+                meta.gid = gid.unwrap_or(meta.gid);
+            },
+        )
+    }
+
+    /// Changes the access and the modification times of the file, `None`
+    /// leaves it unchanged.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> AxResult {
+        self.change(
+            |ext| ext.set_times(atime, mtime),
+            |meta, _| {
+                meta.atime = atime.unwrap_or(meta.atime);
+                meta.mtime = mtime.unwrap_or(meta.mtime);
+            },
+        )
+    }
+
+    /// Stores a change of the metadata in the filesystem by `store`, and
+    /// applies it by `f` to the metadata in memory, which is recorded first if
+    /// the filesystem can't store the change.
+    fn change(
+        &self,
+        store: impl FnOnce(&dyn NodeExt) -> VfsResult,
+        f: impl FnOnce(&mut FileMeta, &mut Option<FilePerm>),
+    ) -> AxResult {
+        let stored = match store(self.mount.node_ext(self.node)) {
+            Ok(()) => true,
+            Err(AxError::Unsupported) => false,
+            Err(e) => return Err(e),
+        };
+        let key = self.key();
+        if !stored && !METAS.lock().contains_key(&key) {
+            // the filesystem may be accessed, not holding the lock
+            let entry = self.new_entry(self.initial());
+            let mut metas = METAS.lock();
+            metas.retain(|_, entry| entry.node.as_ref().is_none_or(|n| n.strong_count() > 0));
+            metas.entry(key.clone()).or_insert(entry);
+        }
+        if let Some(entry) = METAS.lock().get_mut(&key) {
+            f(&mut entry.meta, &mut entry.perm);
+            entry.meta.ctime = crate::clock::wall_time();
+        }
+        Ok(())
+    }
+
+    fn new_entry(&self, meta: FileMeta) -> Entry {
+        Entry {
+            meta,
+            perm: None,
+            node: self
+                .mount
+                .node_ext(self.node)
+                .supports_links()
+                .then(|| Arc::downgrade(self.node)),
+        }
+    }
+
+    /// The metadata of a file that has not been changed in memory.
+    fn initial(&self) -> FileMeta {
+        let mounted = self.mount.mount_time();
+        let (atime, mtime, ctime) = self.stored_times().unwrap_or((mounted, mounted, mounted));
+        let (uid, gid) = self.mount.node_ext(self.node).owner().unwrap_or((0, 0));
+        FileMeta {
+            uid,
+            gid,
+            atime,
+            mtime,
+            ctime,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the times stored by the filesystem. FAT stores them in the
+    /// directory entry of the file, not in the node.
+    fn stored_times(&self) -> Option<(Duration, Duration, Duration)> {
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        if self.mount.fstype() == "vfat" && self.path != self.mount.path() {
+            let (dir, name) = self.path.rsplit_once('/')?;
+            let dir = crate::root::lookup_no_follow(if dir.is_empty() { "/" } else { dir }).ok()?;
+            return dir
+                .as_any()
+                .downcast_ref::<crate::fs::fatfs::DirWrapper<'static>>()?
+                .entry_times(name);
+        }
+        self.mount.node_ext(self.node).times()
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Forgets the metadata of the removed file identified by `key`, see
+/// [`MountPoint::file_key`].
+pub(crate) fn remove(key: &str) {
+    METAS.lock().remove(key);
+}
+
+/// Forgets the metadata of the files of the filesystem unmounted from the
+/// canonical absolute `path`.
+pub(crate) fn umount(path: &str) {
+    METAS.lock().retain(|key, entry| match &entry.node {
+        Some(node) => node.strong_count() > 0,
+        None => !(key == path || key.strip_prefix(path).is_some_and(|p| p.starts_with('/'))),
+    });
+}
+
+/// Moves the metadata of the file or directory renamed from `old` to `new`.
+pub(crate) fn rename(old: &str, new: &str) {
+    crate::root::rename_keys(&mut METAS.lock(), old, new);
+}
+
+/// Records that the file at the canonical absolute `path` has just been
+/// created.
+pub(crate) fn created(path: &str) {
+    let Ok((mount, _, Ok(node))) = crate::root::lookup_with_mount(path, false) else {
+        return;
+    };
+    MetaNode {
+        path,
+        node: &node,
+        mount: &mount,
+    }
+    .created();
+}
@@ -1,6 +1,8 @@
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axerrno::{AxError, AxResult, ax_err};
+use axfs_vfs::VfsOps;
 
+use crate::dev::Disk;
 use crate::fs;
 
 #[cfg(feature = "devfs")]
@@ -23,60 +25,145 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
     Arc::new(fs::ramfs::RamFileSystem::new())
 }
 
-#[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
+/// Options of the RAM filesystem on `/tmp`, given by the `AX_TMPFS_OPTIONS`
+/// environment variable at build time.
+#[cfg(feature = "ramfs")]
+const TMPFS_OPTIONS: &str = match option_env!("AX_TMPFS_OPTIONS") {
+    Some(options) => options,
+    None => "",
+};
 
-    // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
+/// Creates the RAM filesystem mounted on `/tmp`.
+#[cfg(feature = "ramfs")]
+pub(crate) fn tmpfs() -> Arc<dyn VfsOps> {
+    ramfs_with_options(TMPFS_OPTIONS)
+        .unwrap_or_else(|e| panic!("invalid AX_TMPFS_OPTIONS {:?}: {:?}", TMPFS_OPTIONS, e))
+}
+
+/// Creates a RAM filesystem with comma-separated `options`:
+///
+/// - `size=<n>[k|m|g|%]`: the maximum size of file contents, in bytes, KiB,
+///   MiB, GiB, or percent of the total memory. Not limited by default.
+/// - `swap=<volume>`: spills cold pages to the block volume when memory is
+///   low. The volume is overwritten, and busy until the filesystem is
+///   released.
+#[cfg(feature = "ramfs")]
+fn ramfs_with_options(options: &str) -> AxResult<Arc<dyn VfsOps>> {
+    let mut size = None;
+    let mut swap = None;
+    for option in options.split(',').filter(|o| !o.is_empty()) {
+        match option.split_once('=') {
+            Some(("size", value)) => size = Some(parse_size(value)?),
+            Some(("swap", value)) => swap = Some(value),
+            _ => return ax_err!(InvalidInput, "unknown ramfs option"),
+        }
+    }
+    match swap {
+        Some(volume) => {
+            let (fs, source) = crate::partition::open_volume_with(volume, |disk| {
+                Ok(Arc::new(fs::ramfs::with_limits(size, Some(disk))))
+            })?;
+            info!("  ramfs spills to {}", source);
+            Ok(fs)
+        }
+        None => Ok(Arc::new(fs::ramfs::with_limits(size, None))),
+    }
+}
 
-    // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
+#[cfg(feature = "ramfs")]
+fn parse_size(value: &str) -> AxResult<u64> {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(end);
+    let num = num.parse::<u64>().map_err(|_| AxError::InvalidInput)?;
+    let size = match unit {
+        "" => Some(num),
+        "k" | "K" => num.checked_mul(1 << 10),
+        "m" | "M" => num.checked_mul(1 << 20),
+        "g" | "G" => num.checked_mul(1 << 30),
+        "%" => {
+            let alloc = axalloc::global_allocator();
+            let total = (alloc.used_pages() + alloc.available_pages()) as u64 * 0x1000;
+            Some(total / 100 * num)
+        }
+        _ => None,
+    };
+    size.ok_or(AxError::InvalidInput)
+}
 
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
+/// Creates the overlay of the root filesystem `lower`, whose upper layer is
+/// the volume given by `AX_OVERLAY_UPPER`, or a RAM filesystem if it is not
+/// set.
+#[cfg(feature = "overlay")]
+pub(crate) fn overlay(lower: Arc<dyn VfsOps>) -> Arc<fs::overlayfs::OverlayFileSystem> {
+    let upper: Arc<dyn VfsOps> = match crate::partition::open_overlay_upper() {
+        Some(upper) => upper,
+        None => ramfs(),
+    };
+    Arc::new(fs::overlayfs::OverlayFileSystem::new(upper, lower))
+}
 
-    Ok(Arc::new(procfs))
+#[cfg(feature = "procfs")]
+pub(crate) fn procfs() -> Arc<fs::pseudo::PseudoFileSystem> {
+    fs::procfs::new_procfs()
 }
 
 #[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let sysfs = fs::ramfs::RamFileSystem::new();
-    let sys_root = sysfs.root_dir();
-
-    // Create /sys/kernel/mm/transparent_hugepage/enabled
-    sys_root.create("kernel", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage/enabled", VfsNodeType::File)?;
-    let file_hp = sys_root
-        .clone()
-        .lookup("./kernel/mm/transparent_hugepage/enabled")?;
-    file_hp.write_at(0, b"always [madvise] never\n")?;
+pub(crate) fn sysfs() -> Arc<fs::pseudo::PseudoFileSystem> {
+    fs::sysfs::new_sysfs()
+}
 
-    // Create /sys/devices/system/clocksource/clocksource0/current_clocksource
-    sys_root.create("devices", VfsNodeType::Dir)?;
-    sys_root.create("devices/system", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource/clocksource0", VfsNodeType::Dir)?;
-    sys_root.create(
-        "devices/system/clocksource/clocksource0/current_clocksource",
-        VfsNodeType::File,
-    )?;
-    let file_cc = sys_root
-        .clone()
-        .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
-    file_cc.write_at(0, b"tsc\n")?;
+/// Creates a new instance of the virtual filesystem by its type name with
+/// comma-separated `options`, returns it along with the canonical type name.
+///
+/// Only RAM filesystems take options, see [`ramfs_with_options`].
+pub(crate) fn new_by_type(
+    fstype: &str,
+    options: &str,
+) -> AxResult<(Arc<dyn VfsOps>, &'static str)> {
+    let (fs, fstype): (Arc<dyn VfsOps>, _) = match fstype {
+        #[cfg(feature = "devfs")]
+        "devfs" | "devtmpfs" => (devfs(), "devfs"),
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => return Ok((ramfs_with_options(options)?, "ramfs")),
+        #[cfg(feature = "procfs")]
+        "proc" | "procfs" => (procfs(), "proc"),
+        #[cfg(feature = "sysfs")]
+        "sysfs" => (sysfs(), "sysfs"),
+        _ => return ax_err!(Unsupported, "unknown filesystem type"),
+    };
+    if !options.is_empty() {
+        return ax_err!(InvalidInput, "unsupported mount options");
+    }
+    Ok((fs, fstype))
+}
 
-    Ok(Arc::new(sysfs))
+/// Opens the on-disk filesystem on the given disk or partition, returns it
+/// along with its type name.
+pub(crate) fn diskfs(disk: Disk) -> AxResult<(Arc<dyn VfsOps>, &'static str)> {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "myfs")] {
+            Ok((fs::myfs::new_myfs(disk), "myfs"))
+        } else if #[cfg(any(feature = "ext4", feature = "fatfs", feature = "tarfs"))] {
+            #[cfg(feature = "tarfs")]
+            if fs::tarfs::TarFileSystem::probe(&disk) {
+                return Ok((fs::tarfs::TarFileSystem::open(disk)?, "tar"));
+            }
+            #[cfg(feature = "ext4")]
+            if fs::ext4::Ext4FileSystem::probe(&disk) {
+                return Ok((fs::ext4::Ext4FileSystem::open(disk)?, "ext4"));
+            }
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "fatfs")] {
+                    Ok((fs::fatfs::FatFileSystem::open(disk)?, "vfat"))
+                } else {
+                    ax_err!(Unsupported, "unknown filesystem on the volume")
+                }
+            }
+        } else {
+            let _ = disk;
+            ax_err!(Unsupported, "no on-disk filesystem is enabled")
+        }
+    }
 }
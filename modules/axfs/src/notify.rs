@@ -0,0 +1,343 @@
+//! Notification of changes of files, like `inotify` on Linux.
+//!
+//! A [`Watcher`] watches files and directories by their canonical absolute
+//! paths, and queues the events of the changes made through [`crate::api`] and
+//! [`crate::fops`]. A watched directory reports the changes of its entries,
+//! and a watched file or directory reports the changes of itself.
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerrno::{AxError, AxResult, ax_err};
+use axsync::Mutex;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Maximum number of queued events of each watcher, an
+/// [`EventKind::Overflow`] is queued instead of those after it.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+static WATCHES: Mutex<BTreeMap<String, Vec<Watch>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// A kind of change of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// An entry was created in the watched directory.
+    Create,
+    /// The file was written or truncated.
+    Modify,
+    /// The file opened for writing was closed.
+    CloseWrite,
+    /// An entry was removed from the watched directory.
+    Delete,
+    /// An entry was renamed out of the watched directory.
+    MovedFrom,
+    /// An entry was renamed into the watched directory.
+    MovedTo,
+    /// The watched file or directory itself was removed.
+    DeleteSelf,
+    /// The watched file or directory itself was renamed.
+    MoveSelf,
+    /// The watch was removed, explicitly or because the watched file was
+    /// removed.
+    Ignored,
+    /// Events were dropped since the queue was full.
+    Overflow,
+}
+
+/// A set of [`EventKind`]s that a watch reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventMask(u32);
+
+/// An event of a change, read from a [`Watcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The watch descriptor returned by [`Watcher::add_watch`], or `None` for
+    /// [`EventKind::Overflow`].
+    pub wd: Option<u32>,
+    /// The kind of the change.
+    pub kind: EventKind,
+    /// Whether the changed file is a directory.
+    pub is_dir: bool,
+    /// Relates the [`EventKind::MovedFrom`] and [`EventKind::MovedTo`] events
+    /// of the same rename, 0 for other events.
+    pub cookie: u32,
+    /// The name of the changed entry in the watched directory, or `None` if
+    /// the watched file itself is changed.
+    pub name: Option<String>,
+}
+
+/// Watches files and directories, and queues the events of their changes.
+pub struct Watcher {
+    inner: Arc<WatcherInner>,
+}
+
+struct WatcherInner {
+    events: Mutex<VecDeque<Event>>,
+    next_wd: AtomicU32,
+}
+
+struct Watch {
+    watcher: Weak<WatcherInner>,
+    wd: u32,
+    mask: EventMask,
+}
+
+impl EventKind {
+    const fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl EventMask {
+    /// All kinds of events.
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Returns an empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the set with `kind` added.
+    pub const fn with(self, kind: EventKind) -> Self {
+        Self(self.0 | kind.bit())
+    }
+
+    /// Returns the union of the two sets.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns whether `kind` is in the set.
+    pub const fn contains(self, kind: EventKind) -> bool {
+        self.0 & kind.bit() != 0
+    }
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watcher {
+    /// Creates a watcher with no watches.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(WatcherInner {
+                events: Mutex::new(VecDeque::new()),
+                next_wd: AtomicU32::new(1),
+            }),
+        }
+    }
+
+    /// Watches the file or directory at `path` for events in `mask`, and
+    /// returns the watch descriptor.
+    ///
+    /// Symbolic links in the last component of `path` are followed if
+    /// `follow` is true. If the file is already watched by this watcher, the
+    /// mask of the watch is replaced and its descriptor is returned.
+    pub fn add_watch(&self, path: &str, mask: EventMask, follow: bool) -> AxResult<u32> {
+        let path = crate::root::real_path(path, follow)?;
+        crate::root::lookup_no_follow(&path)?;
+        let mut watches = WATCHES.lock();
+        for watches in watches.values_mut() {
+            watches.retain(|w| w.watcher.strong_count() > 0);
+        }
+        watches.retain(|_, watches| !watches.is_empty());
+        let watches = watches.entry(path).or_default();
+        if let Some(watch) = watches.iter_mut().find(|w| self.owns(w)) {
+            watch.mask = mask;
+            return Ok(watch.wd);
+        }
+        let wd = self.inner.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.push(Watch {
+            watcher: Arc::downgrade(&self.inner),
+            wd,
+            mask,
+        });
+        Ok(wd)
+    }
+
+    /// Returns the descriptor of the watch of the file or directory at
+    /// `path`, or `None` if it is not watched by this watcher.
+    pub fn find_watch(&self, path: &str, follow: bool) -> AxResult<Option<u32>> {
+        let path = crate::root::real_path(path, follow)?;
+        let watches = WATCHES.lock();
+        Ok(watches
+            .get(&path)
+            .into_iter()
+            .flatten()
+            .find(|w| self.owns(w))
+            .map(|w| w.wd))
+    }
+
+    /// Returns the mask of the watch `wd`.
+    pub fn watch_mask(&self, wd: u32) -> AxResult<EventMask> {
+        let watches = WATCHES.lock();
+        watches
+            .values()
+            .flatten()
+            .find(|w| w.wd == wd && self.owns(w))
+            .map(|w| w.mask)
+            .ok_or(AxError::InvalidInput)
+    }
+
+    /// Removes the watch `wd`, an [`EventKind::Ignored`] event is queued for
+    /// it.
+    pub fn remove_watch(&self, wd: u32) -> AxResult {
+        let mut watches = WATCHES.lock();
+        let mut found = false;
+        for watches in watches.values_mut() {
+            watches.retain(|w| {
+                let matched = w.wd == wd && self.owns(w);
+                found |= matched;
+                !matched
+            });
+        }
+        watches.retain(|_, watches| !watches.is_empty());
+        if !found {
+            return ax_err!(InvalidInput);
+        }
+        self.inner.push(Event {
+            wd: Some(wd),
+            kind: EventKind::Ignored,
+            is_dir: false,
+            cookie: 0,
+            name: None,
+        });
+        Ok(())
+    }
+
+    /// Returns whether there are queued events.
+    pub fn has_events(&self) -> bool {
+        !self.inner.events.lock().is_empty()
+    }
+
+    /// Returns the next queued event without removing it.
+    pub fn peek_event(&self) -> Option<Event> {
+        self.inner.events.lock().front().cloned()
+    }
+
+    /// Removes and returns the next queued event.
+    pub fn next_event(&self) -> Option<Event> {
+        self.inner.events.lock().pop_front()
+    }
+
+    fn owns(&self, watch: &Watch) -> bool {
+        core::ptr::eq(watch.watcher.as_ptr(), Arc::as_ptr(&self.inner))
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let mut watches = WATCHES.lock();
+        for watches in watches.values_mut() {
+            watches.retain(|w| !self.owns(w));
+        }
+        watches.retain(|_, watches| !watches.is_empty());
+    }
+}
+
+impl WatcherInner {
+    fn push(&self, event: Event) {
+        let mut events = self.events.lock();
+        // identical events in a row are merged, as on Linux
+        if events.back() == Some(&event) {
+            return;
+        }
+        if events.len() >= MAX_QUEUED_EVENTS {
+            if events.back().is_none_or(|e| e.kind != EventKind::Overflow) {
+                events.push_back(Event {
+                    wd: None,
+                    kind: EventKind::Overflow,
+                    is_dir: false,
+                    cookie: 0,
+                    name: None,
+                });
+            }
+            return;
+        }
+        events.push_back(event);
+    }
+}
+
+/// Queues the event `kind` of the file at the canonical absolute `path` to
+/// the watches of its parent directory, and to those of itself.
+fn emit(path: &str, kind: EventKind, is_dir: bool, cookie: u32) {
+    let mut watches = WATCHES.lock();
+    if watches.is_empty() {
+        return;
+    }
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let dir = if dir.is_empty() { "/" } else { dir };
+    let push = |watch: &Watch, kind: EventKind, name: Option<&str>| {
+        if let Some(watcher) = watch.watcher.upgrade() {
+            watcher.push(Event {
+                wd: Some(watch.wd),
+                kind,
+                is_dir,
+                cookie,
+                name: name.map(String::from),
+            });
+        }
+    };
+
+    if path != "/" {
+        for watch in watches.get(dir).into_iter().flatten() {
+            if watch.mask.contains(kind) {
+                push(watch, kind, Some(name));
+            }
+        }
+    }
+    let self_kind = match kind {
+        EventKind::Modify | EventKind::CloseWrite => kind,
+        EventKind::Delete => EventKind::DeleteSelf,
+        EventKind::MovedFrom => EventKind::MoveSelf,
+        _ => return,
+    };
+    for watch in watches.get(path).into_iter().flatten() {
+        if watch.mask.contains(self_kind) {
+            push(watch, self_kind, None);
+        }
+    }
+    if kind == EventKind::Delete {
+        // the watches of a removed file are removed
+        for watch in watches.remove(path).into_iter().flatten() {
+            push(&watch, EventKind::Ignored, None);
+        }
+    }
+}
+
+/// Reports that the file or directory at `path` has just been created.
+pub(crate) fn created(path: &str, is_dir: bool) {
+    emit(path, EventKind::Create, is_dir, 0);
+}
+
+/// Reports that the content of the file at `path` has just been changed.
+pub(crate) fn modified(path: &str) {
+    emit(path, EventKind::Modify, false, 0);
+}
+
+/// Reports that the file at `path` opened for writing has just been closed.
+pub(crate) fn closed_write(path: &str) {
+    emit(path, EventKind::CloseWrite, false, 0);
+}
+
+/// Reports that the file or directory at `path` has just been removed.
+pub(crate) fn removed(path: &str, is_dir: bool) {
+    emit(path, EventKind::Delete, is_dir, 0);
+}
+
+/// Reports that the file or directory at `old` has just been renamed to
+/// `new`, and moves the watches of it and its descendants.
+pub(crate) fn renamed(old: &str, new: &str, is_dir: bool) {
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    emit(old, EventKind::MovedFrom, is_dir, cookie);
+    emit(new, EventKind::MovedTo, is_dir, cookie);
+    crate::root::rename_keys(&mut WATCHES.lock(), old, new);
+}
@@ -0,0 +1,317 @@
+//! Page cache of regular files.
+//!
+//! Each regular file has at most one [`PageCache`], shared by all opened
+//! [`File`](crate::fops::File)s of it and by its memory mappings. Files are
+//! identified by their canonical absolute paths with symbolic links resolved,
+//! since not all filesystems return the same node for the same file. Files of
+//! filesystems with hard links are identified by their nodes instead.
+//!
+//! Writes go through to the filesystem, so the file size reported by the node
+//! is always up to date. Only pages modified via shared writable mappings are
+//! dirty, they are written back on [`PageCache::sync`] or when evicted.
+
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+};
+use axalloc::GlobalPage;
+use axerrno::{AxError, AxResult};
+use axfs_vfs::VfsNodeRef;
+use axsync::Mutex;
+use memory_addr::VirtAddr;
+
+use crate::root::MountPoint;
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Maximum number of cached pages of each file.
+const MAX_PAGES_PER_FILE: usize = 1024;
+
+static PAGE_CACHES: Mutex<BTreeMap<String, Weak<PageCache>>> = Mutex::new(BTreeMap::new());
+
+struct CachedPage {
+    page: GlobalPage,
+    dirty: bool,
+    /// Number of mappings of the page, mapped pages are never evicted.
+    map_count: usize,
+    /// Whether the page has been mapped writable since it was unmapped by all,
+    /// it keeps dirty until then.
+    mapped_writable: bool,
+    /// The time of the last access.
+    tick: u64,
+}
+
+struct PageCacheInner {
+    pages: BTreeMap<u64, CachedPage>,
+    tick: u64,
+}
+
+/// The cached pages of a regular file.
+pub struct PageCache {
+    node: VfsNodeRef,
+    mount: Arc<MountPoint>,
+    inner: Mutex<PageCacheInner>,
+}
+
+/// Returns the page cache of the file at the absolute path `path`, or creates
+/// one for `node` if it doesn't exist.
+pub(crate) fn open(path: &str, node: &VfsNodeRef, mount: &Arc<MountPoint>) -> Arc<PageCache> {
+    let path = mount.file_key(path, node);
+    let mut caches = PAGE_CACHES.lock();
+    if let Some(cache) = caches.get(&path).and_then(Weak::upgrade) {
+        return cache;
+    }
+    caches.retain(|_, cache| cache.strong_count() > 0);
+    let cache = Arc::new(PageCache {
+        node: node.clone(),
+        mount: mount.clone(),
+        inner: Mutex::new(PageCacheInner {
+            pages: BTreeMap::new(),
+            tick: 0,
+        }),
+    });
+    caches.insert(path, Arc::downgrade(&cache));
+    cache
+}
+
+/// Forgets the page cache of the removed file identified by `key`, see
+/// [`MountPoint::file_key`]. Opened files keep using the old one.
+pub(crate) fn remove(key: &str) {
+    PAGE_CACHES.lock().remove(key);
+}
+
+/// Syncs the page caches of all opened files on the filesystem `mount`, or on
+/// all filesystems if it is `None`.
+pub(crate) fn sync_all(mount: Option<&Arc<MountPoint>>) -> AxResult {
+    let caches = PAGE_CACHES
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .filter(|cache| mount.is_none_or(|mount| Arc::ptr_eq(&cache.mount, mount)))
+        .collect::<alloc::vec::Vec<_>>();
+    for cache in caches {
+        cache.sync()?;
+    }
+    Ok(())
+}
+
+/// Moves page caches of the file or directory renamed from `old` to `new`.
+pub(crate) fn rename(old: &str, new: &str) {
+    crate::root::rename_keys(&mut PAGE_CACHES.lock(), old, new);
+}
+
+impl PageCache {
+    /// Reads the file at `offset` through the cache.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let size = self.node.get_attr()?.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+
+        let mut inner = self.inner.lock();
+        let mut read_len = 0;
+        while read_len < len {
+            let pos = offset + read_len as u64;
+            let page_off = pos as usize % PAGE_SIZE;
+            let count = (len - read_len).min(PAGE_SIZE - page_off);
+            let dst = &mut buf[read_len..read_len + count];
+            match inner.page(&self.node, pos / PAGE_SIZE as u64) {
+                Ok(page) => dst.copy_from_slice(&page.as_slice()[page_off..page_off + count]),
+                // fall back to uncached reads if no page can be allocated
+                Err(AxError::NoMemory) => {
+                    let n = self.node.read_at(pos, dst)?;
+                    if n < count {
+                        return Ok(read_len + n);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+            read_len += count;
+        }
+        Ok(read_len)
+    }
+
+    /// Writes to the file at `offset`, and updates the cached pages.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let mut inner = self.inner.lock();
+        let write_len = self.node.write_at(offset, buf)?;
+        let mut pos = 0;
+        while pos < write_len {
+            let file_pos = offset + pos as u64;
+            let page_off = file_pos as usize % PAGE_SIZE;
+            let count = (write_len - pos).min(PAGE_SIZE - page_off);
+            if let Some(cached) = inner.pages.get_mut(&(file_pos / PAGE_SIZE as u64)) {
+                cached.page.as_slice_mut()[page_off..page_off + count]
+                    .copy_from_slice(&buf[pos..pos + count]);
+            }
+            pos += count;
+        }
+        Ok(write_len)
+    }
+
+    /// Truncates the file to `size`, and drops the cached data beyond it.
+    pub fn truncate(&self, size: u64) -> AxResult {
+        let mut inner = self.inner.lock();
+        self.node.truncate(size)?;
+        let first = size.div_ceil(PAGE_SIZE as u64);
+        inner
+            .pages
+            .retain(|&idx, cached| idx < first || cached.map_count > 0);
+        for (&idx, cached) in inner.pages.range_mut(size / PAGE_SIZE as u64..) {
+            // zero the data beyond `size`, for the partial and mapped pages
+            let start = size.saturating_sub(idx * PAGE_SIZE as u64) as usize;
+            cached.page.as_slice_mut()[start..].fill(0);
+        }
+        Ok(())
+    }
+
+    /// Writes dirty pages back to the file, then flushes the file.
+    pub fn sync(&self) -> AxResult {
+        self.write_back_all()?;
+        self.node.fsync()?;
+        Ok(())
+    }
+
+    /// Writes dirty pages overlapping the `len` bytes at `offset` back to the
+    /// file, without flushing it.
+    pub fn write_back_range(&self, offset: u64, len: u64) -> AxResult {
+        let first = offset / PAGE_SIZE as u64;
+        let end = offset.saturating_add(len).div_ceil(PAGE_SIZE as u64);
+        if first >= end {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock();
+        let size = self.node.get_attr()?.size();
+        for (&idx, cached) in inner.pages.range_mut(first..end).filter(|(_, p)| p.dirty) {
+            write_back(&self.node, size, idx, cached)?;
+        }
+        Ok(())
+    }
+
+    fn write_back_all(&self) -> AxResult {
+        self.write_back_range(0, u64::MAX)
+    }
+
+    /// Gets the cached page at the page-aligned `offset` for a memory mapping,
+    /// returns its kernel virtual address.
+    ///
+    /// The page is kept in the cache until [`PageCache::put_page`] is called.
+    /// If `writable` is true, the page is considered as dirty until then.
+    pub fn get_page(&self, offset: u64, writable: bool) -> AxResult<VirtAddr> {
+        if offset
+            >= self
+                .node
+                .get_attr()?
+                .size()
+                .next_multiple_of(PAGE_SIZE as u64)
+        {
+            return Err(AxError::InvalidInput);
+        }
+        let mut inner = self.inner.lock();
+        inner.page(&self.node, offset / PAGE_SIZE as u64)?;
+        let cached = inner.pages.get_mut(&(offset / PAGE_SIZE as u64)).unwrap();
+        cached.map_count += 1;
+        if writable {
+            cached.mapped_writable = true;
+            cached.dirty = true;
+        }
+        Ok(cached.page.start_vaddr())
+    }
+
+    /// Releases a page got by [`PageCache::get_page`].
+    pub fn put_page(&self, offset: u64) {
+        if let Some(cached) = self
+            .inner
+            .lock()
+            .pages
+            .get_mut(&(offset / PAGE_SIZE as u64))
+        {
+            cached.map_count -= 1;
+            if cached.map_count == 0 {
+                cached.mapped_writable = false;
+            }
+        }
+    }
+}
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_back_all() {
+            warn!("failed to write back the page cache: {:?}", e);
+        }
+    }
+}
+
+impl PageCacheInner {
+    /// Returns the cached page with index `idx`, loads it from the file on a
+    /// miss.
+    fn page(&mut self, node: &VfsNodeRef, idx: u64) -> AxResult<&GlobalPage> {
+        self.tick += 1;
+        if !self.pages.contains_key(&idx) {
+            if self.pages.len() >= MAX_PAGES_PER_FILE {
+                self.evict(node)?;
+            }
+            let mut page = GlobalPage::alloc_zero()?;
+            let buf = page.as_slice_mut();
+            let mut len = 0;
+            while len < PAGE_SIZE {
+                match node.read_at(idx * PAGE_SIZE as u64 + len as u64, &mut buf[len..])? {
+                    0 => break,
+                    n => len += n,
+                }
+            }
+            let cached = CachedPage {
+                page,
+                dirty: false,
+                map_count: 0,
+                mapped_writable: false,
+                tick: self.tick,
+            };
+            self.pages.insert(idx, cached);
+        }
+        let cached = self.pages.get_mut(&idx).unwrap();
+        cached.tick = self.tick;
+        Ok(&cached.page)
+    }
+
+    /// Removes the least recently used page that is not mapped.
+    fn evict(&mut self, node: &VfsNodeRef) -> AxResult {
+        let Some((&idx, _)) = self
+            .pages
+            .iter()
+            .filter(|(_, p)| p.map_count == 0)
+            .min_by_key(|(_, p)| p.tick)
+        else {
+            return Ok(()); // all pages are mapped
+        };
+        let mut cached = self.pages.remove(&idx).unwrap();
+        if cached.dirty {
+            write_back(node, node.get_attr()?.size(), idx, &mut cached)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl axmm::FileMapping for PageCache {
+    fn get_page(&self, offset: u64, writable: bool) -> Option<VirtAddr> {
+        PageCache::get_page(self, offset, writable).ok()
+    }
+
+    fn put_page(&self, offset: u64) {
+        PageCache::put_page(self, offset)
+    }
+}
+
+/// Writes the page with index `idx` back to the file of size `size`.
+fn write_back(node: &VfsNodeRef, size: u64, idx: u64, cached: &mut CachedPage) -> AxResult {
+    let offset = idx * PAGE_SIZE as u64;
+    if offset < size {
+        let len = PAGE_SIZE.min((size - offset) as usize);
+        node.write_at(offset, &cached.page.as_slice()[..len])?;
+    }
+    cached.dirty = cached.mapped_writable;
+    Ok(())
+}
@@ -0,0 +1,374 @@
+//! Partition table parsing and the registry of block volumes.
+//!
+//! Every block device is registered as a volume named `diskN`, and each
+//! partition found in its MBR or GPT as `diskNpM` (`M` starts from 1, logical
+//! partitions in an MBR extended partition start from 5, like Linux). GPT
+//! partition names are used as volume labels.
+//!
+//! The root filesystem is placed on the volume given by the `AX_ROOT_PART`
+//! environment variable at build time (a volume name or a label). If it is not
+//! set, the first partition of `disk0` is used, or `disk0` itself if it has no
+//! partition table. Other volumes can be mounted at runtime with
+//! [`crate::api::mount_volume`].
+//!
+//! All volumes on a disk share its block cache, so syncing a volume writes
+//! back the modifications of the whole disk.
+
+use alloc::{
+    format,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerrno::{AxError, AxResult, ax_err};
+use axfs_vfs::VfsOps;
+use axsync::Mutex;
+
+use crate::dev::Disk;
+
+macro_rules! env_or_default {
+    ($key:literal) => {
+        match option_env!($key) {
+            Some(val) => val,
+            None => "",
+        }
+    };
+}
+
+const ROOT_PART: &str = env_or_default!("AX_ROOT_PART");
+#[cfg(feature = "overlay")]
+const OVERLAY_UPPER: &str = env_or_default!("AX_OVERLAY_UPPER");
+
+const BLOCK_SIZE: usize = 512;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_ENTRIES_OFFSET: usize = 446;
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+const MAX_LOGICAL_PARTS: usize = 128;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MAX_ENTRIES: u32 = 256;
+
+/// A partition found in the partition table of a disk.
+struct Partition {
+    index: usize,
+    start_block: u64,
+    num_blocks: u64,
+    label: Option<String>,
+}
+
+/// A primary entry of an MBR.
+struct MbrEntry {
+    ty: u8,
+    start_block: u64,
+    num_blocks: u64,
+}
+
+/// A whole disk or a partition on it that can hold a filesystem.
+struct Volume {
+    name: String,
+    label: Option<String>,
+    disk: Disk,
+    fs: Option<Weak<dyn VfsOps>>,
+}
+
+static VOLUMES: Mutex<Vec<Volume>> = Mutex::new(Vec::new());
+/// Whole disks, by their indices.
+static DISKS: Mutex<Vec<Disk>> = Mutex::new(Vec::new());
+
+/// Registers the disk with index `disk_idx` and all partitions on it.
+pub(crate) fn add_disk(disk_idx: usize, disk: Disk) {
+    assert_eq!(disk_idx, DISKS.lock().len());
+    DISKS.lock().push(disk.clone());
+    let parts = scan_partitions(&disk);
+    let mut volumes = VOLUMES.lock();
+    for part in parts {
+        let Some(part_disk) = disk.sub_disk(part.start_block, part.num_blocks) else {
+            warn!(
+                "partition {} of disk{} is out of the disk, ignored",
+                part.index, disk_idx
+            );
+            continue;
+        };
+        let name = format!("disk{}p{}", disk_idx, part.index);
+        info!(
+            "  found partition {}: start = {}, blocks = {}, label = {:?}",
+            name, part.start_block, part.num_blocks, part.label
+        );
+        volumes.push(Volume {
+            name,
+            label: part.label,
+            disk: part_disk,
+            fs: None,
+        });
+    }
+    volumes.push(Volume {
+        name: format!("disk{}", disk_idx),
+        label: None,
+        disk,
+        fs: None,
+    });
+}
+
+/// Removes the volume of the root filesystem from the registry and returns
+/// its name and disk, see the [module-level documentation](self) for how it is
+/// chosen.
+pub(crate) fn take_root_volume() -> (String, Disk) {
+    let mut volumes = VOLUMES.lock();
+    let idx = if ROOT_PART.is_empty() {
+        volumes
+            .iter()
+            .position(|v| v.name.starts_with("disk0p"))
+            .or_else(|| volumes.iter().position(|v| v.name == "disk0"))
+    } else {
+        find_volume(&volumes, ROOT_PART)
+    };
+    let Some(idx) = idx else {
+        panic!("root partition {:?} not found!", ROOT_PART);
+    };
+    let root = volumes.remove(idx);
+    info!("  use {} as the root filesystem", root.name);
+    (root.name, root.disk)
+}
+
+/// Opens the filesystem on the volume `source`, which is a volume name (with
+/// an optional `/dev/` prefix) or a label.
+///
+/// A volume can only be opened once until its filesystem is released. Returns
+/// the filesystem along with the device path of the volume and the type name
+/// of the filesystem.
+pub(crate) fn open_volume(source: &str) -> AxResult<(Arc<dyn VfsOps>, String, &'static str)> {
+    let mut fstype = "";
+    let (fs, source) = open_volume_with(source, |disk| {
+        let (fs, name) = crate::mounts::diskfs(disk)?;
+        fstype = name;
+        Ok(fs)
+    })?;
+    Ok((fs, source, fstype))
+}
+
+/// Creates the filesystem `f` on the volume `source`, which keeps the volume
+/// busy as [`open_volume`] until it is released. Returns the filesystem along
+/// with the device path of the volume.
+pub(crate) fn open_volume_with(
+    source: &str,
+    f: impl FnOnce(Disk) -> AxResult<Arc<dyn VfsOps>>,
+) -> AxResult<(Arc<dyn VfsOps>, String)> {
+    let mut volumes = VOLUMES.lock();
+    let Some(idx) = find_volume(&volumes, source) else {
+        return ax_err!(NotFound, "no such volume");
+    };
+    let volume = &mut volumes[idx];
+    if volume.fs.as_ref().and_then(Weak::upgrade).is_some() {
+        return ax_err!(ResourceBusy, "volume is already mounted");
+    }
+    let fs = f(volume.disk.clone())?;
+    volume.fs = Some(Arc::downgrade(&fs));
+    Ok((fs, format!("/dev/{}", volume.name)))
+}
+
+/// Writes the cached modifications of all disks back to them, and flushes
+/// their write caches.
+pub(crate) fn sync_disks() -> AxResult {
+    let disks = DISKS.lock().clone();
+    for disk in disks {
+        disk.sync().map_err(|_| AxError::Io)?;
+    }
+    Ok(())
+}
+
+/// Writes the cached modifications of the disk holding the volume at the
+/// device path `source` back to it, and flushes its write cache. Does nothing
+/// if `source` is not a volume (e.g. for in-memory filesystems).
+pub(crate) fn sync_volume(source: &str) -> AxResult {
+    let Some(name) = source.strip_prefix("/dev/disk") else {
+        return Ok(());
+    };
+    let end = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let disk = name[..end]
+        .parse::<usize>()
+        .ok()
+        .and_then(|idx| DISKS.lock().get(idx).cloned());
+    match disk {
+        Some(disk) => disk.sync().map_err(|_| AxError::Io),
+        None => Ok(()),
+    }
+}
+
+/// Opens the filesystem on the volume given by the `AX_OVERLAY_UPPER`
+/// environment variable at build time, which is the upper layer of the
+/// overlay root filesystem. Returns `None` if it is not set.
+#[cfg(feature = "overlay")]
+pub(crate) fn open_overlay_upper() -> Option<Arc<dyn VfsOps>> {
+    if OVERLAY_UPPER.is_empty() {
+        return None;
+    }
+    let (fs, source, fstype) = open_volume(OVERLAY_UPPER)
+        .unwrap_or_else(|e| panic!("overlay upper volume {:?}: {:?}", OVERLAY_UPPER, e));
+    info!(
+        "  use {} ({}) as the upper layer of the root",
+        source, fstype
+    );
+    Some(fs)
+}
+
+fn find_volume(volumes: &[Volume], source: &str) -> Option<usize> {
+    let name = source.strip_prefix("/dev/").unwrap_or(source);
+    volumes.iter().position(|v| v.name == name).or_else(|| {
+        volumes
+            .iter()
+            .position(|v| v.label.as_deref() == Some(source))
+    })
+}
+
+fn scan_partitions(disk: &Disk) -> Vec<Partition> {
+    let mut mbr = [0u8; BLOCK_SIZE];
+    if disk.read_block(0, &mut mbr).is_err() || mbr[510..] != MBR_SIGNATURE {
+        return Vec::new();
+    }
+    if is_boot_sector(&mbr) {
+        // a filesystem on the whole disk
+        return Vec::new();
+    }
+    let Some(entries) = parse_mbr(&mbr) else {
+        return Vec::new();
+    };
+    if entries.iter().any(|e| e.ty == MBR_TYPE_GPT_PROTECTIVE) {
+        return scan_gpt(disk).unwrap_or_else(|| {
+            warn!("invalid GPT, no partitions are used");
+            Vec::new()
+        });
+    }
+
+    let mut parts = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.ty == MBR_TYPE_EMPTY || entry.num_blocks == 0 {
+            continue;
+        }
+        if MBR_TYPES_EXTENDED.contains(&entry.ty) {
+            scan_logical(disk, entry.start_block, &mut parts);
+        } else {
+            parts.push(Partition {
+                index: i + 1,
+                start_block: entry.start_block,
+                num_blocks: entry.num_blocks,
+                label: None,
+            });
+        }
+    }
+    parts
+}
+
+/// Returns whether the sector looks like the boot sector of FAT, exFAT or NTFS
+/// rather than an MBR, they have the same signature.
+fn is_boot_sector(sector: &[u8; BLOCK_SIZE]) -> bool {
+    &sector[0x36..0x39] == b"FAT"
+        || &sector[0x52..0x55] == b"FAT"
+        || &sector[3..8] == b"EXFAT"
+        || &sector[3..7] == b"NTFS"
+}
+
+fn parse_mbr(sector: &[u8; BLOCK_SIZE]) -> Option<[MbrEntry; 4]> {
+    let entry = |i: usize| {
+        let raw = &sector[MBR_ENTRIES_OFFSET + i * 16..][..16];
+        if raw[0] != 0 && raw[0] != 0x80 {
+            return None; // invalid boot indicator
+        }
+        Some(MbrEntry {
+            ty: raw[4],
+            start_block: read_u32(raw, 8) as u64,
+            num_blocks: read_u32(raw, 12) as u64,
+        })
+    };
+    Some([entry(0)?, entry(1)?, entry(2)?, entry(3)?])
+}
+
+/// Follows the chain of EBRs in the extended partition starting at
+/// `ext_start`, logical partitions are numbered from 5.
+fn scan_logical(disk: &Disk, ext_start: u64, parts: &mut Vec<Partition>) {
+    let mut ebr_block = ext_start;
+    let mut sector = [0u8; BLOCK_SIZE];
+    for i in 0..MAX_LOGICAL_PARTS {
+        if disk.read_block(ebr_block, &mut sector).is_err() || sector[510..] != MBR_SIGNATURE {
+            warn!("invalid EBR at block {}", ebr_block);
+            return;
+        }
+        let Some([logical, next, ..]) = parse_mbr(&sector) else {
+            warn!("invalid EBR at block {}", ebr_block);
+            return;
+        };
+        if logical.ty != MBR_TYPE_EMPTY && logical.num_blocks != 0 {
+            parts.push(Partition {
+                index: i + 5,
+                start_block: ebr_block + logical.start_block,
+                num_blocks: logical.num_blocks,
+                label: None,
+            });
+        }
+        if next.ty == MBR_TYPE_EMPTY || next.start_block == 0 {
+            return;
+        }
+        // the next EBR is relative to the start of the extended partition
+        ebr_block = ext_start + next.start_block;
+    }
+}
+
+fn scan_gpt(disk: &Disk) -> Option<Vec<Partition>> {
+    let mut header = [0u8; BLOCK_SIZE];
+    disk.read_block(1, &mut header).ok()?;
+    if &header[..8] != GPT_SIGNATURE {
+        return None;
+    }
+    let entries_block = read_u64(&header, 72);
+    let num_entries = read_u32(&header, 80).min(GPT_MAX_ENTRIES) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    if entry_size < 128 || entry_size > BLOCK_SIZE || BLOCK_SIZE % entry_size != 0 {
+        return None;
+    }
+
+    let per_block = BLOCK_SIZE / entry_size;
+    let mut parts = Vec::new();
+    let mut sector = [0u8; BLOCK_SIZE];
+    for i in 0..num_entries {
+        if i % per_block == 0 {
+            disk.read_block(entries_block + (i / per_block) as u64, &mut sector)
+                .ok()?;
+        }
+        let raw = &sector[(i % per_block) * entry_size..][..entry_size];
+        if raw[..16].iter().all(|&b| b == 0) {
+            continue; // unused entry
+        }
+        let first = read_u64(raw, 32);
+        let last = read_u64(raw, 40);
+        if last < first {
+            continue;
+        }
+        let name = char::decode_utf16(
+            raw[56..128]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect::<String>();
+        parts.push(Partition {
+            index: i + 1,
+            start_block: first,
+            num_blocks: last - first + 1,
+            label: (!name.is_empty()).then_some(name),
+        });
+    }
+    Some(parts)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
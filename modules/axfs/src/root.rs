@@ -1,106 +1,356 @@
 //! Root directory of the filesystem
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Mount points are organized as a tree keyed by path components, so that
+//! nested mounts (e.g. `/tmp` and `/tmp/sub`) and sibling mounts sharing a
+//! common prefix (e.g. `/mnt` and `/mntx`) can be resolved correctly.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
-use axerrno::{AxError, AxResult, ax_err};
+use alloc::{borrow::Cow, collections::BTreeMap, format, string::String, sync::Arc, vec, vec::Vec};
+use axerrno::{AxError, AxResult, ax_err, ax_err_type};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axns::{ResArc, def_resource};
 use axsync::Mutex;
+use core::time::Duration;
 use lazyinit::LazyInit;
 
+use crate::fs::{NoExt, NodeExt};
+use crate::statfs::{FileSystemStats, Quota, QuotaUsage};
 use crate::{api::FileType, fs, mounts};
 
 def_resource! {
     static CURRENT_DIR_PATH: ResArc<Mutex<String>> = ResArc::new();
-    static CURRENT_DIR: ResArc<Mutex<VfsNodeRef>> = ResArc::new();
 }
 
-struct MountPoint {
-    path: &'static str,
+/// A filesystem mounted at some path.
+///
+/// Opened files and directories hold a reference to the mount point they
+/// belong to, so that a filesystem in use can not be unmounted.
+pub(crate) struct MountPoint {
+    path: String,
     fs: Arc<dyn VfsOps>,
+    /// The device or the name of the filesystem, as in `/proc/mounts`.
+    source: String,
+    fstype: String,
+    /// The wall time when it was mounted.
+    mount_time: Duration,
+    quota: Mutex<Option<QuotaUsage>>,
+    /// Whether the filesystem is implemented in this crate, whose nodes can
+    /// be downcast for their [`NodeExt`] operations. Those of devfs and
+    /// `myfs` are not.
+    native: bool,
+}
+
+/// A node of the mount tree.
+///
+/// Each node corresponds to a path component. Intermediate nodes that are not
+/// mount points themselves only exist to lead to deeper mount points.
+#[derive(Default)]
+struct MountNode {
+    mount: Option<Arc<MountPoint>>,
+    children: BTreeMap<String, MountNode>,
 }
 
 struct RootDirectory {
-    main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    main_fs: Arc<MountPoint>,
+    mounts: Mutex<MountNode>,
 }
 
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
+/// Maximum number of symbolic links followed in a path, as `MAXSYMLINKS` of
+/// Linux.
+const MAX_SYMLINKS: usize = 40;
+
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(path: String, fs: Arc<dyn VfsOps>, source: &str, fstype: &str) -> Self {
+        Self {
+            path,
+            fs,
+            source: source.into(),
+            fstype: fstype.into(),
+            mount_time: crate::clock::wall_time(),
+            quota: Mutex::new(None),
+            native: !matches!(fstype, "devfs" | "myfs"),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn fstype(&self) -> &str {
+        &self.fstype
+    }
+
+    pub fn mount_time(&self) -> Duration {
+        self.mount_time
+    }
+
+    pub fn root_dir(&self) -> VfsNodeRef {
+        self.fs.root_dir()
+    }
+
+    /// Returns the statistics of the filesystem, limited to its quota.
+    pub fn stats(&self) -> AxResult<FileSystemStats> {
+        let mut stats = crate::statfs::fs_stats(self)?;
+        if let Some(usage) = &*self.quota.lock() {
+            usage.apply(&mut stats);
+        }
+        Ok(stats)
+    }
+
+    /// Sets the quota of the filesystem, or removes it if `quota` is `None`.
+    ///
+    /// The usage is counted from the statistics of the filesystem.
+    pub fn set_quota(&self, quota: Option<Quota>) -> AxResult {
+        let usage = match quota {
+            Some(quota) => Some(QuotaUsage::new(quota, &crate::statfs::fs_stats(self)?)),
+            None => None,
+        };
+        *self.quota.lock() = usage;
+        Ok(())
+    }
+
+    pub fn has_quota(&self) -> bool {
+        self.quota.lock().is_some()
+    }
+
+    /// Counts `files` and `bytes` against the quota while `f` uses them, and
+    /// releases them if it fails.
+    pub fn charged<T>(
+        &self,
+        files: u64,
+        bytes: u64,
+        f: impl FnOnce() -> AxResult<T>,
+    ) -> AxResult<T> {
+        if let Some(usage) = &mut *self.quota.lock() {
+            usage.charge(files, bytes)?;
+        }
+        let ret = f();
+        if ret.is_err() {
+            self.release(files, bytes);
+        }
+        ret
+    }
+
+    /// Releases `files` and `bytes` from the quota.
+    pub fn release(&self, files: u64, bytes: u64) {
+        if let Some(usage) = &mut *self.quota.lock() {
+            usage.release(files, bytes);
+        }
+    }
+
+    /// Writes the cached modifications of the filesystem back to its device,
+    /// and flushes the write cache of the device.
+    ///
+    /// Filesystems that keep metadata in memory write it back on `fsync` of
+    /// the root directory, others don't support it on directories.
+    pub fn sync(&self) -> AxResult {
+        match self.fs.root_dir().fsync() {
+            Ok(()) | Err(AxError::InvalidInput) => {}
+            Err(e) => return Err(e),
+        }
+        if self.fstype == "overlay" {
+            // the upper layer can be on any disk
+            crate::partition::sync_disks()
+        } else {
+            crate::partition::sync_volume(&self.source)
+        }
+    }
+
+    /// Returns the [`NodeExt`] operations of `node` of the filesystem.
+    pub fn node_ext<'a>(&self, node: &'a VfsNodeRef) -> &'a dyn NodeExt {
+        if self.native {
+            fs::node_ext(node)
+        } else {
+            &NoExt
+        }
+    }
+
+    /// Identifies the file `node` at the canonical absolute `path` in the
+    /// filesystem.
+    ///
+    /// Files are identified by their paths, since not all filesystems return
+    /// the same node for the same file, or by their nodes if the filesystem
+    /// supports hard links.
+    pub fn file_key(&self, path: &str, node: &VfsNodeRef) -> String {
+        if self.node_ext(node).supports_links() {
+            // never conflicts with paths, which start with '/'
+            format!("{:p}", Arc::as_ptr(node))
+        } else {
+            path.into()
+        }
     }
 }
 
 impl Drop for MountPoint {
     fn drop(&mut self) {
+        debug!("umount filesystem at {}", self.path);
         self.fs.umount().ok();
     }
 }
 
+impl MountNode {
+    /// Collects the mount points in this subtree, parents before children.
+    fn collect(&self, mount_points: &mut Vec<Arc<MountPoint>>) {
+        if let Some(mp) = &self.mount {
+            mount_points.push(mp.clone());
+        }
+        for child in self.children.values() {
+            child.collect(mount_points);
+        }
+    }
+
+    /// Finds the node at the given canonical path (without the leading '/').
+    fn get(&self, path: &str) -> Option<&MountNode> {
+        components(path).try_fold(self, |node, name| node.children.get(name))
+    }
+
+    /// Removes the mount point at the given canonical path (without the
+    /// leading '/'), and prunes the nodes that no longer lead to any mount
+    /// point.
+    fn remove(&mut self, path: &str) -> Option<Arc<MountPoint>> {
+        let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+        let child = self.children.get_mut(name)?;
+        let removed = if rest.is_empty() {
+            child.mount.take()
+        } else {
+            child.remove(rest)
+        };
+        if child.mount.is_none() && child.children.is_empty() {
+            self.children.remove(name);
+        }
+        removed
+    }
+}
+
 impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub fn new(main_fs: Arc<dyn VfsOps>, source: &str, fstype: &str) -> Self {
         Self {
-            main_fs,
-            mounts: Vec::new(),
+            main_fs: Arc::new(MountPoint::new("/".into(), main_fs, source, fstype)),
+            mounts: Mutex::new(MountNode::default()),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
+    pub fn mount(&self, path: &str, fs: Arc<dyn VfsOps>, source: &str, fstype: &str) -> AxResult {
+        if !path.starts_with('/') {
+            return ax_err!(InvalidInput, "mount path must start with '/'");
+        }
         if path == "/" {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
-        if !path.starts_with('/') {
-            return ax_err!(InvalidInput, "mount path must start with '/'");
+        if self.contains(path) {
+            return ax_err!(InvalidInput, "mount point already exists");
+        }
+        // create the mount point in its parent filesystem if it does not exist
+        let mount_point = self.lookup_mounted_fs(path, |parent_fs, rest_path| {
+            let parent_root = parent_fs.root_dir();
+            match parent_root.create(rest_path, FileType::Dir) {
+                Ok(()) | Err(AxError::AlreadyExists) => {}
+                Err(e) => return Err(e),
+            }
+            parent_root.lookup(rest_path)
+        })?;
+        if !mount_point.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
+
+        let mut mounts = self.mounts.lock();
+        let mounted = mounts.get(path.trim_matches('/'));
+        if mounted.is_some_and(|node| node.mount.is_some()) {
             return ax_err!(InvalidInput, "mount point already exists");
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        fs.mount(path, mount_point)?;
+        let node = components(path).fold(&mut *mounts, |node, name| {
+            node.children.entry(name.into()).or_default()
+        });
+        node.mount = Some(Arc::new(MountPoint::new(path.into(), fs, source, fstype)));
+        Ok(())
+    }
+
+    pub fn umount(&self, path: &str) -> AxResult {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return ax_err!(ResourceBusy, "cannot unmount root filesystem");
+        }
+
+        let mut mounts = self.mounts.lock();
+        let node = mounts
+            .get(path)
+            .filter(|node| node.mount.is_some())
+            .ok_or_else(|| ax_err_type!(InvalidInput, "not a mount point"))?;
+        if !node.children.is_empty() {
+            return ax_err!(ResourceBusy, "other filesystems are mounted under it");
+        }
+        if node
+            .mount
+            .as_ref()
+            .is_some_and(|mp| Arc::strong_count(mp) > 1)
+        {
+            return ax_err!(ResourceBusy, "filesystem is in use");
+        }
+        let mount_point = mounts.remove(path);
+        drop(mounts);
+        drop(mount_point); // `VfsOps::umount` is called here
+        crate::meta::umount(&format!("/{}", path));
         Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    /// Returns all mount points, the root first.
+    pub fn mount_points(&self) -> Vec<Arc<MountPoint>> {
+        let mut mount_points = Vec::from([self.main_fs.clone()]);
+        self.mounts.lock().collect(&mut mount_points);
+        mount_points
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        self.mounts
+            .lock()
+            .get(path.trim_matches('/'))
+            .is_some_and(|node| node.mount.is_some())
     }
 
-    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
-    where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
-    {
-        debug!("lookup at root: {}", path);
+    /// Finds the deepest mount point along the canonical absolute `path`,
+    /// returns it along with the remaining path relative to the root of its
+    /// filesystem.
+    ///
+    /// `.` and `..` are resolved before, see [`real_path`], so that `..`
+    /// can cross the boundaries of mount points.
+    fn find_mount(&self, path: &str) -> (Arc<MountPoint>, String) {
         let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
-        }
-
-        let mut idx = 0;
-        let mut max_len = 0;
 
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
+        let mounts = self.mounts.lock();
+        let mut mount_point = &self.main_fs;
+        let mut rest_path = path;
+        let mut node = &*mounts;
+        let mut remaining = path;
+        while !remaining.is_empty() {
+            let (name, next) = remaining.split_once('/').unwrap_or((remaining, ""));
+            match node.children.get(name) {
+                Some(child) => node = child,
+                None => break,
+            }
+            remaining = next;
+            if let Some(mp) = &node.mount {
+                mount_point = mp;
+                rest_path = remaining;
             }
         }
+        (mount_point.clone(), rest_path.into())
+    }
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
-        }
+    /// Finds the filesystem mounted at the deepest mount point along `path`,
+    /// and calls `f` with it and the remaining path relative to its root.
+    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
+    where
+        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
+    {
+        debug!("lookup at root: {}", path);
+        let (mount_point, rest_path) = self.find_mount(path);
+        f(mount_point.fs.clone(), &rest_path)
     }
 }
 
@@ -108,7 +358,7 @@ impl VfsNodeOps for RootDirectory {
     axfs_vfs::impl_vfs_dir_default! {}
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        self.main_fs.root_dir().get_attr()
+        self.main_fs.fs.root_dir().get_attr()
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -136,79 +386,218 @@ impl VfsNodeOps for RootDirectory {
     }
 
     fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot rename mount points
-            } else {
-                fs.root_dir().rename(rest_path, dst_path)
+        self.lookup_mounted_fs(src_path, |src_fs, src_rest| {
+            if src_rest.is_empty() {
+                return ax_err!(PermissionDenied); // cannot rename mount points
             }
+            self.lookup_mounted_fs(dst_path, |dst_fs, dst_rest| {
+                if dst_rest.is_empty() {
+                    ax_err!(PermissionDenied) // cannot replace mount points
+                } else if !Arc::ptr_eq(&src_fs, &dst_fs) {
+                    ax_err!(Unsupported, "cannot rename across mount points")
+                } else {
+                    src_fs.root_dir().rename(src_rest, dst_rest)
+                }
+            })
         })
     }
 }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+pub(crate) fn init_rootfs(volume: &str, disk: crate::dev::Disk) {
+    #[cfg(all(feature = "tarfs", not(feature = "myfs")))]
+    if fs::tarfs::TarFileSystem::probe(&disk) {
+        let main_fs =
+            fs::tarfs::TarFileSystem::open(disk).expect("failed to initialize tar filesystem");
+        return init_main_fs(volume, main_fs, "tar");
+    }
+
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
+            let (main_fs, fstype) = (fs::myfs::new_myfs(disk), "myfs");
+        } else if #[cfg(feature = "ext4")] {
+            let (main_fs, fstype): (Arc<dyn VfsOps>, _) =
+                match fs::ext4::Ext4FileSystem::probe(&disk) {
+                    true => (
+                        fs::ext4::Ext4FileSystem::open(disk)
+                            .expect("failed to initialize ext4 filesystem"),
+                        "ext4",
+                    ),
+                    #[cfg(feature = "fatfs")]
+                    false => (fat_rootfs(disk), "vfat"),
+                    #[cfg(not(feature = "fatfs"))]
+                    false => panic!("no ext4 filesystem found on the root volume"),
+                };
         } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
+            let (main_fs, fstype) = (fat_rootfs(disk), "vfat");
+        } else if #[cfg(feature = "tarfs")] {
+            let (main_fs, fstype) = (
+                fs::tarfs::TarFileSystem::open(disk).expect("no tar image found on the root volume"),
+                "tar",
+            );
         }
     }
+    init_main_fs(volume, main_fs, fstype);
+}
+
+/// Initializes the root directory with the main filesystem `main_fs` on the
+/// root volume, or the overlay of it.
+fn init_main_fs(volume: &str, main_fs: Arc<dyn VfsOps>, fstype: &str) {
+    #[cfg(feature = "overlay")]
+    let (main_fs, fstype) = (mounts::overlay(main_fs), "overlay");
+
+    init_root_dir(main_fs, &alloc::format!("/dev/{}", volume), fstype);
+}
+
+/// Initializes the root directory with an empty RAM filesystem, into which
+/// the initramfs is unpacked.
+#[cfg(feature = "initramfs")]
+pub(crate) fn init_initramfs() {
+    init_root_dir(mounts::ramfs(), "rootfs", "ramfs");
+}
 
-    let mut root_dir = RootDirectory::new(main_fs);
+/// Initializes the root directory with the main filesystem `main_fs`, and
+/// mounts the virtual filesystems on it.
+fn init_root_dir(main_fs: Arc<dyn VfsOps>, source: &str, fstype: &str) {
+    let root_dir = RootDirectory::new(main_fs, source, fstype);
 
     #[cfg(feature = "devfs")]
     root_dir
-        .mount("/dev", mounts::devfs())
+        .mount("/dev", mounts::devfs(), "devfs", "devfs")
         .expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
     root_dir
-        .mount("/tmp", mounts::ramfs())
+        .mount("/tmp", mounts::tmpfs(), "ramfs", "ramfs")
         .expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
-    root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
+    root_dir
+        .mount("/proc", mounts::procfs(), "proc", "proc")
         .expect("fail to mount procfs at /proc");
 
-    // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
     root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
+        .mount("/sys", mounts::sysfs(), "sysfs", "sysfs")
         .expect("fail to mount sysfs at /sys");
 
     ROOT_DIR.init_once(Arc::new(root_dir));
-    CURRENT_DIR.init_new(Mutex::new(ROOT_DIR.clone()));
     CURRENT_DIR_PATH.init_new(Mutex::new("/".into()));
 }
 
-fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
-    if path.starts_with('/') {
-        ROOT_DIR.clone()
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+fn fat_rootfs(disk: crate::dev::Disk) -> Arc<fs::fatfs::FatFileSystem> {
+    static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
+    FAT_FS.init_once(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
+    FAT_FS.init();
+    FAT_FS.clone()
+}
+
+/// Resolves `path` component by component in the mounted tree, returns its
+/// canonical absolute path with symbolic links resolved.
+///
+/// Relative paths are resolved from the current directory. The last component
+/// is followed only if `follow` is true or `path` ends with '/'. It does not
+/// need to exist, so that the result can be used to create it. Each other
+/// component must be an existing directory, or a symbolic link to one, so `..`
+/// leads to the parent of the directory a symbolic link resolves to, as Linux
+/// does, and crosses the boundaries of mount points.
+pub(crate) fn real_path(path: &str, follow: bool) -> AxResult<String> {
+    let follow = follow || path.ends_with('/');
+    let path = if path.starts_with('/') {
+        Cow::Borrowed(path)
     } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
+        Cow::Owned(CURRENT_DIR_PATH.lock().clone() + path)
+    };
+    let mut resolved = String::from("/");
+    let mut pending: Vec<String> = components(&path).rev().map(String::from).collect();
+    let mut num_links = 0;
+    while let Some(name) = pending.pop() {
+        match name.as_str() {
+            "." => continue,
+            ".." => {
+                // '/..' is '/' itself
+                resolved.truncate(resolved.rfind('/').unwrap_or_default().max(1));
+                continue;
+            }
+            _ => {}
+        }
+
+        let is_last = pending.is_empty();
+        let dir_len = resolved.len();
+        push_component(&mut resolved, &name);
+        if is_last && !follow {
+            break;
+        }
+        let node = match ROOT_DIR.clone().lookup(&resolved) {
+            Ok(node) => node,
+            Err(AxError::NotFound) if is_last => break,
+            Err(e) => return Err(e),
+        };
+        let ty = node.get_attr()?.file_type();
+        if ty != VfsNodeType::SymLink {
+            if !is_last && ty != VfsNodeType::Dir {
+                return ax_err!(NotADirectory);
+            }
+            continue;
+        }
+
+        num_links += 1;
+        if num_links > MAX_SYMLINKS {
+            return ax_err!(InvalidData, "too many levels of symbolic links");
+        }
+        let target = read_link_node(&node)?;
+        if target.starts_with('/') {
+            resolved = "/".into();
+        } else {
+            resolved.truncate(dir_len);
+        }
+        pending.extend(components(&target).rev().map(String::from));
     }
+    Ok(resolved)
 }
 
-pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
-    if path.starts_with('/') {
-        Ok(axfs_vfs::path::canonicalize(path))
-    } else {
-        let path = CURRENT_DIR_PATH.lock().clone() + path;
-        Ok(axfs_vfs::path::canonicalize(&path))
+fn push_component(path: &mut String, name: &str) {
+    if !path.ends_with('/') {
+        path.push('/');
     }
+    path.push_str(name);
+}
+
+/// Reads the target of the symbolic link `node`.
+fn read_link_node(node: &VfsNodeRef) -> AxResult<String> {
+    let size = node.get_attr()?.size() as usize;
+    let mut buf = vec![0; size];
+    let mut len = 0;
+    while len < size {
+        match node.read_at(len as u64, &mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| ax_err_type!(InvalidData, "invalid symbolic link"))
+}
+
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|name| !name.is_empty())
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+/// Looks up `path`, following symbolic links.
+pub(crate) fn lookup(path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(path, true)
+}
+
+/// Looks up `path`, but returns the symbolic link itself if it is the last
+/// component.
+pub(crate) fn lookup_no_follow(path: &str) -> AxResult<VfsNodeRef> {
+    lookup_at(path, false)
+}
+
+fn lookup_at(path: &str, follow: bool) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
-    let node = parent_node_of(dir, path).lookup(path)?;
+    let node = ROOT_DIR.clone().lookup(&real_path(path, follow)?)?;
     if path.ends_with('/') && !node.get_attr()?.is_dir() {
         ax_err!(NotADirectory)
     } else {
@@ -216,38 +605,38 @@ pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRe
     }
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+pub(crate) fn create_file(path: &str) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let path = real_path(path, true)?;
+    ROOT_DIR.create(&path, VfsNodeType::File)?;
+    ROOT_DIR.clone().lookup(&path)
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+pub(crate) fn create_dir(path: &str) -> AxResult {
+    match lookup_no_follow(path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => ROOT_DIR.create(&real_path(path, false)?, VfsNodeType::Dir),
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
+pub(crate) fn remove_file(path: &str) -> AxResult {
+    let node = lookup_no_follow(path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        ROOT_DIR.remove(&real_path(path, false)?)
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -261,53 +650,222 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
+    let abs_path = real_path(path, false)?;
+    if ROOT_DIR.contains(&abs_path) {
         return ax_err!(PermissionDenied);
     }
 
-    let node = lookup(dir, path)?;
+    let node = lookup_no_follow(path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        ROOT_DIR.remove(&abs_path)
     }
 }
 
+/// Creates a symbolic link at `path` which links to `target`.
+///
+/// `target` is stored as is, it is resolved only when the link is followed.
+pub(crate) fn symlink(target: &str, path: &str) -> AxResult {
+    if target.is_empty() || path.is_empty() {
+        return ax_err!(NotFound);
+    } else if path.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    if lookup_no_follow(path).is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    let path = real_path(path, false)?;
+    ROOT_DIR.create(&path, VfsNodeType::SymLink)?;
+    let node = ROOT_DIR.clone().lookup(&path)?;
+    if let Err(e) = node.write_at(0, target.as_bytes()) {
+        ROOT_DIR.remove(&path).ok();
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Returns the target of the symbolic link at `path`.
+pub(crate) fn read_link(path: &str) -> AxResult<String> {
+    let node = lookup_no_follow(path)?;
+    if node.get_attr()?.file_type() != VfsNodeType::SymLink {
+        return ax_err!(InvalidInput, "not a symbolic link");
+    }
+    read_link_node(&node)
+}
+
+/// Creates a hard link `new` to the file at `old`.
+///
+/// Both must be in the same filesystem, and the filesystem must support hard
+/// links, see [`NodeExt::link`]. `old` is not followed if it is a symbolic
+/// link.
+pub(crate) fn link(old: &str, new: &str) -> AxResult {
+    let node = lookup_no_follow(old)?;
+    if node.get_attr()?.is_dir() {
+        return ax_err!(PermissionDenied, "cannot link directories");
+    }
+    if new.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    if lookup_no_follow(new).is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    let (old, new) = (real_path(old, false)?, real_path(new, false)?);
+    let (mount, _) = ROOT_DIR.find_mount(&new);
+    if !Arc::ptr_eq(&ROOT_DIR.find_mount(&old).0, &mount) {
+        return ax_err!(Unsupported, "cannot link across mount points");
+    }
+
+    let (dir_path, name) = new.rsplit_once('/').unwrap();
+    let dir = ROOT_DIR
+        .clone()
+        .lookup(if dir_path.is_empty() { "/" } else { dir_path })?;
+    // the directory is in the same filesystem, as it is not a mount point
+    let size = node.get_attr()?.size();
+    mount.charged(1, size, || mount.node_ext(&dir).link(name, node))?;
+    crate::notify::created(&new, false);
+    Ok(())
+}
+
 pub(crate) fn current_dir() -> AxResult<String> {
     Ok(CURRENT_DIR_PATH.lock().clone())
 }
 
 pub(crate) fn set_current_dir(path: &str) -> AxResult {
-    let mut abs_path = absolute_path(path)?;
+    let mut abs_path = real_path(path, true)?;
     if !abs_path.ends_with('/') {
         abs_path += "/";
     }
     if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
         *CURRENT_DIR_PATH.lock() = "/".into();
         return Ok(());
     }
 
-    let node = lookup(None, &abs_path)?;
+    let node = lookup(&abs_path)?;
     let attr = node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
     } else if !attr.perm().owner_executable() {
         ax_err!(PermissionDenied)
     } else {
-        *CURRENT_DIR.lock() = node;
         *CURRENT_DIR_PATH.lock() = abs_path;
         Ok(())
     }
 }
 
 pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+    if lookup_no_follow(new).is_ok() {
         warn!("dst file already exist, now remove it");
-        remove_file(None, new)?;
+        crate::statfs::remove(&real_path(new, false)?, || remove_file(new))?;
+    }
+    let (old, new) = (real_path(old, false)?, real_path(new, false)?);
+    let is_dir = lookup_no_follow(&old)?.get_attr()?.is_dir();
+    ROOT_DIR.rename(&old, &new)?;
+    crate::page_cache::rename(&old, &new);
+    crate::meta::rename(&old, &new);
+    crate::lock::rename(&old, &new);
+    crate::fops::rename(&old, &new);
+    crate::notify::renamed(&old, &new, is_dir);
+    Ok(())
+}
+
+/// Forgets the page cache, the metadata and the locks of the file or
+/// directory `node` just removed from the canonical absolute `path`.
+///
+/// Files identified by their nodes are kept while they have other links.
+pub(crate) fn forget_removed(path: &str, node: &VfsNodeRef) {
+    let mount = mount_point_of(path);
+    let ext = mount.node_ext(node);
+    if ext.supports_links()
+        && ext.nlink().is_some_and(|nlink| nlink > 0)
+        && node.get_attr().is_ok_and(|attr| !attr.is_dir())
+    {
+        return;
+    }
+    let key = mount.file_key(path, node);
+    crate::page_cache::remove(&key);
+    crate::meta::remove(&key);
+    crate::lock::remove(&key);
+}
+
+/// Moves the entries of `map` keyed by the paths of the file or directory
+/// renamed from `old` to `new`, including those of its descendants.
+pub(crate) fn rename_keys<V>(map: &mut BTreeMap<String, V>, old: &str, new: &str) {
+    let moved = map
+        .keys()
+        .filter(|path| *path == old || path.strip_prefix(old).is_some_and(|p| p.starts_with('/')))
+        .cloned()
+        .collect::<Vec<_>>();
+    map.remove(new);
+    for path in moved {
+        let value = map.remove(&path).unwrap();
+        map.insert(String::from(new) + &path[old.len()..], value);
+    }
+}
+
+pub(crate) fn mount(path: &str, fs: Arc<dyn VfsOps>, source: &str, fstype: &str) -> AxResult {
+    ROOT_DIR.mount(&real_path(path, true)?, fs, source, fstype)
+}
+
+/// Returns all mount points, the root first.
+pub(crate) fn mount_points() -> Vec<Arc<MountPoint>> {
+    if ROOT_DIR.is_inited() {
+        ROOT_DIR.mount_points()
+    } else {
+        Vec::new()
     }
-    parent_node_of(None, old).rename(old, new)
+}
+
+pub(crate) fn umount(path: &str) -> AxResult {
+    ROOT_DIR.umount(&real_path(path, true)?)
+}
+
+/// Writes all cached modifications of the filesystem `mount`, or of all
+/// filesystems if it is `None`, back to their devices.
+pub(crate) fn sync(mount: Option<&Arc<MountPoint>>) -> AxResult {
+    crate::page_cache::sync_all(mount)?;
+    match mount {
+        Some(mount) => mount.sync(),
+        None => {
+            for mount in mount_points() {
+                mount.sync()?;
+            }
+            crate::partition::sync_disks()
+        }
+    }
+}
+
+/// Looks up `path` like [`lookup`], or [`lookup_no_follow`] if `follow` is
+/// false, along with its canonical absolute path and the mount point that the
+/// node is in, found by the same lookup.
+///
+/// The mount point is returned even if the node is not found, e.g. to create
+/// the node in it. The filesystem is regarded as busy while it is held.
+pub(crate) fn lookup_with_mount(
+    path: &str,
+    follow: bool,
+) -> AxResult<(Arc<MountPoint>, String, AxResult<VfsNodeRef>)> {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let abs_path = real_path(path, follow)?;
+    let (mount, rest_path) = ROOT_DIR.find_mount(&abs_path);
+    let node = mount.root_dir().lookup(&rest_path).and_then(|node| {
+        if path.ends_with('/') && !node.get_attr()?.is_dir() {
+            ax_err!(NotADirectory)
+        } else {
+            Ok(node)
+        }
+    });
+    Ok((mount, abs_path, node))
+}
+
+/// Returns the mount point that the canonical absolute `path` is in.
+///
+/// The filesystem is regarded as busy while the returned reference is held.
+pub(crate) fn mount_point_of(path: &str) -> Arc<MountPoint> {
+    ROOT_DIR.find_mount(path).0
 }
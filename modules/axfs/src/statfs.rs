@@ -0,0 +1,209 @@
+//! Statistics and quotas of filesystems.
+//!
+//! [`VfsOps`](axfs_vfs::VfsOps) can not report the usage of a filesystem, so
+//! it is taken from the root directory of the filesystems that know it (FAT,
+//! ext4 and tar images, and RAM filesystems with a size limit), downcast as in
+//! [`meta`](crate::meta). The others report no blocks and no files.
+//!
+//! A mount point can be given a [`Quota`] of bytes and files. The usage is
+//! counted from the statistics when the quota is set, and then updated as files
+//! are created, written, truncated and removed through this crate, so changes
+//! made by the filesystems themselves are not counted. Changes that would
+//! exceed the quota fail with [`AxError::StorageFull`], and the statistics are
+//! reported within the quota. Hard links are counted as copies of the file.
+
+use axerrno::{AxError, AxResult};
+use axfs_vfs::VfsNodeRef;
+
+use crate::root::MountPoint;
+
+/// Statistics of a mounted filesystem, as `struct statfs`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileSystemStats {
+    /// The type of the filesystem, as `f_type` of Linux.
+    pub magic: u64,
+    /// The size of blocks in bytes.
+    pub block_size: u64,
+    /// Total number of blocks.
+    pub blocks: u64,
+    /// Number of free blocks.
+    pub blocks_free: u64,
+    /// Number of free blocks available to unprivileged users.
+    pub blocks_avail: u64,
+    /// Total number of files (inodes), or 0 if not limited.
+    pub files: u64,
+    /// Number of free files.
+    pub files_free: u64,
+    /// Maximum length of file names.
+    pub name_max: u64,
+    /// Whether the filesystem is read-only.
+    pub read_only: bool,
+}
+
+/// Limits of the usage of a mounted filesystem, see [`crate::api::set_quota`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// Maximum number of bytes of regular files, `None` for no limit.
+    pub max_bytes: Option<u64>,
+    /// Maximum number of files and directories, `None` for no limit.
+    pub max_files: Option<u64>,
+}
+
+/// A [`Quota`] along with the usage counted against it.
+pub(crate) struct QuotaUsage {
+    quota: Quota,
+    bytes: u64,
+    files: u64,
+}
+
+impl QuotaUsage {
+    /// Starts counting from the usage in `stats`.
+    pub fn new(quota: Quota, stats: &FileSystemStats) -> Self {
+        Self {
+            quota,
+            bytes: stats.blocks.saturating_sub(stats.blocks_free) * stats.block_size,
+            files: stats.files.saturating_sub(stats.files_free),
+        }
+    }
+
+    /// Counts `files` and `bytes` more, or fails with
+    /// [`AxError::StorageFull`] if it would exceed the quota.
+    pub fn charge(&mut self, files: u64, bytes: u64) -> AxResult {
+        let exceeds = |used: u64, more: u64, max: Option<u64>| {
+            max.is_some_and(|max| used.saturating_add(more) > max)
+        };
+        if (bytes > 0 && exceeds(self.bytes, bytes, self.quota.max_bytes))
+            || (files > 0 && exceeds(self.files, files, self.quota.max_files))
+        {
+            return Err(AxError::StorageFull);
+        }
+        self.bytes += bytes;
+        self.files += files;
+        Ok(())
+    }
+
+    /// Counts `files` and `bytes` less.
+    pub fn release(&mut self, files: u64, bytes: u64) {
+        self.bytes = self.bytes.saturating_sub(bytes);
+        self.files = self.files.saturating_sub(files);
+    }
+
+    /// Limits `stats` to the quota.
+    pub fn apply(&self, stats: &mut FileSystemStats) {
+        // filesystems that report no blocks or no files have no limit
+        let limit = |value: &mut u64, unknown: bool, max: u64| {
+            *value = if unknown { max } else { (*value).min(max) };
+        };
+        if let Some(max) = self.quota.max_bytes {
+            let bsize = stats.block_size.max(1);
+            let free = max.saturating_sub(self.bytes) / bsize;
+            let unknown = stats.blocks == 0;
+            limit(&mut stats.blocks, unknown, max / bsize);
+            limit(&mut stats.blocks_free, unknown, free);
+            limit(&mut stats.blocks_avail, unknown, free);
+        }
+        if let Some(max) = self.quota.max_files {
+            let unknown = stats.files == 0;
+            limit(&mut stats.files, unknown, max);
+            limit(
+                &mut stats.files_free,
+                unknown,
+                max.saturating_sub(self.files),
+            );
+        }
+    }
+}
+
+/// Returns the statistics of the filesystem of `mount`, regardless of its
+/// quota.
+pub(crate) fn fs_stats(mount: &MountPoint) -> AxResult<FileSystemStats> {
+    let magic = match mount.fstype() {
+        "vfat" => 0x4d44,
+        "ext4" => 0xef53,
+        "ramfs" => 0x8584_58f6,
+        "devfs" => 0x0102_1994,
+        "proc" => 0x9fa0,
+        "sysfs" => 0x6265_6572,
+        "overlay" => 0x794c_7630,
+        _ => 0,
+    };
+    let stats = node_stats(&mount.root_dir())?.unwrap_or(FileSystemStats {
+        block_size: 4096,
+        ..Default::default()
+    });
+    Ok(FileSystemStats {
+        magic,
+        name_max: 255,
+        ..stats
+    })
+}
+
+/// Returns the statistics of the filesystem whose root directory is `root`,
+/// or `None` if it doesn't know them.
+fn node_stats(root: &VfsNodeRef) -> AxResult<Option<FileSystemStats>> {
+    #[allow(unused_variables)]
+    let root = root.as_any();
+    #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+    if let Some(dir) = root.downcast_ref::<crate::fs::fatfs::DirWrapper<'static>>() {
+        return Ok(Some(dir.stats()?));
+    }
+    #[cfg(all(feature = "ext4", not(feature = "myfs")))]
+    if let Some(dir) = root.downcast_ref::<crate::fs::ext4::Ext4Node>() {
+        return Ok(Some(dir.stats()?));
+    }
+    #[cfg(all(feature = "tarfs", not(feature = "myfs")))]
+    if let Some(dir) = root.downcast_ref::<crate::fs::tarfs::TarNode>() {
+        return Ok(Some(dir.stats()));
+    }
+    #[cfg(feature = "ramfs")]
+    if let Some(dir) = root.downcast_ref::<crate::fs::ramfs::DirNode>() {
+        return Ok(Some(crate::fs::ramfs::stats(dir)));
+    }
+    #[cfg(feature = "overlay")]
+    if let Some(dir) = root.downcast_ref::<crate::fs::overlayfs::OverlayNode>() {
+        // files are only written to the upper layer
+        return node_stats(&dir.upper_root());
+    }
+    Ok(None)
+}
+
+/// Counts a file created at the canonical absolute `path` by `f` against the
+/// quota of its filesystem.
+pub(crate) fn create<T>(path: &str, f: impl FnOnce() -> AxResult<T>) -> AxResult<T> {
+    crate::root::mount_point_of(path).charged(1, 0, f)
+}
+
+/// Releases the quota of the file or directory at the canonical absolute
+/// `path` once `f` removes it.
+pub(crate) fn remove(path: &str, f: impl FnOnce() -> AxResult) -> AxResult {
+    let mount = crate::root::mount_point_of(path);
+    if !mount.has_quota() {
+        return f();
+    }
+    let attr = crate::root::lookup_no_follow(path)?.get_attr()?;
+    f()?;
+    let bytes = if attr.is_file() { attr.size() } else { 0 };
+    mount.release(1, bytes);
+    Ok(())
+}
+
+/// Counts the growth of the regular file `node` up to `size` against the
+/// quota of `mount` while `f` writes or truncates it, all or nothing, then
+/// corrects the usage by the size the file ends up with.
+pub(crate) fn resize<T>(
+    mount: &MountPoint,
+    node: &VfsNodeRef,
+    size: u64,
+    f: impl FnOnce() -> AxResult<T>,
+) -> AxResult<T> {
+    if !mount.has_quota() {
+        return f();
+    }
+    let old_size = node.get_attr()?.size();
+    let ret = mount.charged(0, size.saturating_sub(old_size), f)?;
+    if let Ok(attr) = node.get_attr() {
+        // what is not written, or is truncated
+        mount.release(0, old_size.max(size).saturating_sub(attr.size()));
+    }
+    Ok(ret)
+}
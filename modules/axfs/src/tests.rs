@@ -0,0 +1,83 @@
+//! Crash-consistency tests: the raw image of the ramdisk is taken at some
+//! point, as if the machine crashed, and mounted again to see what survived.
+
+use alloc::vec::Vec;
+
+use axdriver_block::ramdisk::RamDisk;
+use axio::{Read, Write};
+
+use crate::api::{self, File, OpenOptions};
+use crate::dev::Disk;
+
+const IMG_PATH: &str = "resources/fat16.img";
+
+/// Mounts the crashed `image` and reads the file at `path` in it.
+fn read_after_crash(image: &[u8], path: &str) -> Option<Vec<u8>> {
+    let (fs, _) = crate::mounts::diskfs(Disk::new(RamDisk::from(image))).unwrap();
+    let node = fs.root_dir().lookup(path).ok()?;
+    let mut buf = alloc::vec![0; node.get_attr().unwrap().size() as usize];
+    let len = node.read_at(0, &mut buf).unwrap();
+    buf.truncate(len);
+    Some(buf)
+}
+
+#[test]
+fn test_sync_crash_consistency() {
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    let data = std::fs::read(IMG_PATH).expect("failed to load disk image");
+    let disk = Disk::new(RamDisk::from(&data));
+    crate::partition::add_disk(0, disk.clone());
+    let (root_volume, root_disk) = crate::partition::take_root_volume();
+    crate::root::init_rootfs(&root_volume, root_disk);
+
+    // written data stays in the caches until synced
+    let mut file = File::create("/crash1.txt").unwrap();
+    file.write_all(b"fsync me").unwrap();
+    let image = disk.device_image();
+    assert_ne!(
+        read_after_crash(&image, "crash1.txt").as_deref(),
+        Some(&b"fsync me"[..])
+    );
+
+    file.sync_all().unwrap();
+    let image = disk.device_image();
+    assert_eq!(
+        read_after_crash(&image, "crash1.txt").as_deref(),
+        Some(&b"fsync me"[..])
+    );
+
+    // fsync also works on read-only files, fdatasync persists the size
+    drop(file);
+    let mut file = OpenOptions::new().append(true).open("/crash1.txt").unwrap();
+    file.write_all(b", twice").unwrap();
+    File::open("/crash1.txt").unwrap().sync_data().unwrap();
+    let image = disk.device_image();
+    assert_eq!(
+        read_after_crash(&image, "crash1.txt").as_deref(),
+        Some(&b"fsync me, twice"[..])
+    );
+    drop(file);
+
+    // sync covers files in directories and closed files
+    api::create_dir("/crash-dir").unwrap();
+    api::write("/crash-dir/crash2.txt", "sync me").unwrap();
+    let mut file = File::create("/crash3.txt").unwrap();
+    file.write_all(b"sync me too").unwrap();
+    api::sync().unwrap();
+    let image = disk.device_image();
+    assert_eq!(
+        read_after_crash(&image, "crash-dir/crash2.txt").as_deref(),
+        Some(&b"sync me"[..])
+    );
+    assert_eq!(
+        read_after_crash(&image, "crash3.txt").as_deref(),
+        Some(&b"sync me too"[..])
+    );
+
+    let mut buf = Vec::new();
+    File::open("/crash3.txt")
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, b"sync me too");
+}
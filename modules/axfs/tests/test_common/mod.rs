@@ -1,7 +1,8 @@
 use axfs::api as fs;
 use axio as io;
+use core::time::Duration;
 
-use fs::{File, FileType, OpenOptions};
+use fs::{File, FileTimes, FileType, OpenOptions, Permissions};
 use io::{Error, Result, prelude::*};
 
 macro_rules! assert_err {
@@ -231,7 +232,7 @@ fn test_devfs_ramfs() -> Result<()> {
     // parent of '/dev'
     assert_eq!(fs::create_dir("///dev//..//233//"), Ok(()));
     assert_eq!(fs::write(".///dev//..//233//.///test.txt", "test"), Ok(()));
-    assert_err!(fs::remove_file("./dev//../..//233//.///test.txt"), NotFound);
+    assert!(fs::metadata("./dev//../..//233//.///test.txt")?.is_file()); // '/..' is '/' itself
     assert_eq!(fs::remove_file("./dev//..//233//../233/./test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("dev//foo/../foo/../.././/233"), Ok(()));
     assert_err!(fs::remove_dir("very/../dev//"), PermissionDenied);
@@ -242,7 +243,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 1);
     assert_eq!(fs::write(".///tmp///dir//.///test.txt", "test"), Ok(()));
     assert_eq!(fs::read("tmp//././/dir//.///test.txt"), Ok("test".into()));
-    // assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty); // TODO
+    assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty);
     assert_err!(fs::remove_dir("/tmp/dir/../dir"), DirectoryNotEmpty);
     assert_eq!(fs::remove_file("./tmp//dir//test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("tmp/dir/.././dir///"), Ok(()));
@@ -252,6 +253,542 @@ fn test_devfs_ramfs() -> Result<()> {
     Ok(())
 }
 
+fn test_shared_file_data() -> Result<()> {
+    let fname = "/tmp/shared.txt";
+    let other = "/tmp//./../tmp/shared.txt";
+    println!("test data shared by opened files of {:?}:", fname);
+
+    let mut writer = File::create(fname)?;
+    let mut reader = File::open(other)?;
+    let mut buf = [0; 16];
+    assert_eq!(reader.read(&mut buf)?, 0);
+
+    // writes are visible to other opened files
+    writer.write_all(b"Hello, world!")?;
+    assert_eq!(reader.read(&mut buf)?, 13);
+    assert_eq!(&buf[..13], b"Hello, world!");
+    writer.write_all(b" Bye.")?;
+    assert_eq!(reader.read_to_string(&mut String::new())?, 5);
+
+    // truncation drops the data beyond the end
+    writer.set_len(5)?;
+    assert_eq!(fs::read_to_string(other)?, "Hello");
+    writer.set_len(7)?;
+    assert_eq!(fs::read(fname)?, b"Hello\0\0");
+
+    // a new file at the same path doesn't see the old data
+    drop(writer);
+    drop(reader);
+    fs::remove_file(fname)?;
+    File::create(fname)?;
+    assert_eq!(fs::read_to_string(fname)?, "");
+    fs::remove_file(fname)?;
+
+    println!("test_shared_file_data() OK!");
+    Ok(())
+}
+
+fn test_mount_points() -> Result<()> {
+    // a sibling of a mount point sharing the same prefix
+    println!("test mount points with shared prefixes:");
+    fs::create_dir("/devx")?;
+    fs::write("/devx/null", "not a device")?;
+    assert_eq!(fs::metadata("/devx/null")?.file_type(), FileType::File);
+    assert_eq!(fs::read_to_string("devx//./null")?, "not a device");
+    assert_eq!(fs::metadata("/dev/null")?.file_type(), FileType::CharDevice);
+    let dirents = fs::read_dir("/devx")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(dirents, ["null"]);
+    assert_eq!(fs::remove_file("/devx/null"), Ok(()));
+    assert_eq!(fs::remove_dir("/devx"), Ok(()));
+    assert_eq!(fs::metadata("/dev/null")?.file_type(), FileType::CharDevice);
+
+    // `..` crossing the boundaries of mount points
+    println!("test '..' across mount points:");
+    fs::write("/tmp/test.txt", "in tmp")?;
+    assert_eq!(fs::read_to_string("/dev/../tmp/test.txt")?, "in tmp");
+    assert_eq!(
+        fs::read_to_string("/dev/foo/../../tmp/./test.txt")?,
+        "in tmp"
+    );
+    assert_eq!(
+        fs::metadata("/tmp/../dev/zero")?.file_type(),
+        FileType::CharDevice
+    );
+    assert_eq!(
+        fs::metadata("/tmp/../../../dev/foo/bar")?.file_type(),
+        FileType::CharDevice
+    );
+    assert!(fs::metadata("/tmp/..")?.is_dir());
+    assert_err!(fs::metadata("/tmp/../test.txt"), NotFound);
+
+    // relative paths from a directory inside a mounted filesystem
+    fs::set_current_dir("/tmp")?;
+    assert_eq!(fs::current_dir()?, "/tmp/");
+    assert_eq!(fs::read_to_string("test.txt")?, "in tmp");
+    assert_eq!(
+        fs::metadata("../dev/null")?.file_type(),
+        FileType::CharDevice
+    );
+    assert!(fs::metadata("../very/long")?.is_dir());
+    fs::set_current_dir("../dev/foo")?;
+    assert_eq!(fs::read_to_string("../../tmp/test.txt")?, "in tmp");
+    fs::set_current_dir("/")?;
+
+    // rename across mount points
+    assert_err!(fs::rename("/tmp/test.txt", "/test.txt"), Unsupported);
+    assert_err!(fs::rename("/tmp", "/tmp2"), PermissionDenied);
+    assert_eq!(fs::remove_file("/dev/../tmp/test.txt"), Ok(()));
+
+    println!("test_mount_points() OK!");
+    Ok(())
+}
+
+fn test_mount_umount() -> Result<()> {
+    // nested mounts
+    println!("test nested mounts:");
+    fs::mount("/tmp/sub", fs::new_filesystem("ramfs")?)?;
+    fs::mount("/tmp/sub/inner", fs::new_filesystem("ramfs")?)?;
+    fs::write("/tmp/sub/a.txt", "in sub")?;
+    fs::write("/tmp/sub/inner/b.txt", "in inner")?;
+    assert_eq!(fs::read_to_string("/tmp/sub/inner/../a.txt")?, "in sub");
+    assert_eq!(
+        fs::read_to_string("/tmp/sub/inner/../../sub/inner/b.txt")?,
+        "in inner"
+    );
+    assert_err!(fs::metadata("/tmp/a.txt"), NotFound);
+    assert_err!(fs::metadata("/tmp/sub/b.txt"), NotFound);
+    let dirents = fs::read_dir("/tmp/sub")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert!(dirents.contains(&"a.txt".into()));
+    assert!(dirents.contains(&"inner".into()));
+
+    // error cases
+    assert_err!(fs::mount("/", fs::new_filesystem("ramfs")?), InvalidInput);
+    assert_err!(
+        fs::mount("/tmp/sub", fs::new_filesystem("ramfs")?),
+        InvalidInput
+    );
+    assert_err!(
+        fs::mount("/tmp/sub/a.txt", fs::new_filesystem("ramfs")?),
+        NotADirectory
+    );
+    assert_err!(fs::new_filesystem("unknown"), Unsupported);
+    assert_err!(fs::remove_dir("/tmp/sub/inner"), PermissionDenied);
+
+    // busy filesystems can not be unmounted
+    println!("test umount:");
+    let file = File::open("/tmp/sub/inner/b.txt")?;
+    assert_err!(fs::umount("/tmp/sub"), ResourceBusy); // has nested mounts
+    assert_err!(fs::umount("/tmp/sub/inner"), ResourceBusy); // has opened files
+    drop(file);
+    let dir = fs::read_dir("/tmp/sub/inner/")?;
+    assert_err!(fs::umount("/tmp/sub/inner"), ResourceBusy); // has opened directories
+    drop(dir);
+    assert_eq!(fs::umount("/tmp/sub/./inner/"), Ok(()));
+    assert_err!(fs::umount("/tmp/sub/inner"), InvalidInput);
+    assert_err!(fs::metadata("/tmp/sub/inner/b.txt"), NotFound);
+    assert!(fs::metadata("/tmp/sub/inner")?.is_dir()); // the mount point remains
+
+    assert_eq!(fs::umount("/tmp/sub"), Ok(()));
+    assert_err!(fs::metadata("/tmp/sub/a.txt"), NotFound);
+    assert_eq!(fs::remove_dir("/tmp/sub"), Ok(()));
+    assert_err!(fs::umount("/"), ResourceBusy);
+    assert_err!(fs::umount("/very"), InvalidInput);
+
+    println!("test_mount_umount() OK!");
+    Ok(())
+}
+
+#[cfg(feature = "procfs")]
+fn test_procfs() -> Result<()> {
+    println!("test procfs:");
+    assert_eq!(
+        fs::read_to_string("/proc/sys/net/core/somaxconn")?,
+        "4096\n"
+    );
+    let dirents = fs::read_dir("/proc")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert!(dirents.contains(&"mounts".into()));
+    assert!(dirents.contains(&"meminfo".into()));
+    assert_err!(fs::write("/proc/mounts", "test"), PermissionDenied);
+    assert_err!(fs::write("/proc/new.txt", "test"), PermissionDenied);
+    assert_err!(fs::create_dir("/proc/new"), PermissionDenied);
+    assert_err!(fs::remove_file("/proc/mounts"), PermissionDenied);
+
+    // generated on each read, not from the page cache
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    assert!(mounts.lines().next().unwrap().contains(" / "));
+    assert!(mounts.contains("proc /proc proc rw 0 0\n"));
+    assert!(!mounts.contains("/tmp/procfs-test"));
+    fs::mount_virtual("tmpfs", "/tmp/procfs-test")?;
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    assert!(mounts.contains("ramfs /tmp/procfs-test ramfs rw 0 0\n"));
+    assert_eq!(fs::umount("/tmp/procfs-test"), Ok(()));
+    assert_eq!(fs::remove_dir("/tmp/procfs-test"), Ok(()));
+    assert!(!fs::read_to_string("/proc/mounts")?.contains("/tmp/procfs-test"));
+
+    println!("test_procfs() OK!");
+    Ok(())
+}
+
+#[cfg(feature = "sysfs")]
+fn test_sysfs() -> Result<()> {
+    println!("test sysfs:");
+    let online = fs::read_to_string("/sys/devices/system/cpu/online")?;
+    assert!(online.starts_with('0'));
+    assert_eq!(
+        fs::read_to_string("/sys/devices/system/cpu/cpu0/online")?,
+        "1\n"
+    );
+    assert!(fs::metadata("/sys/class")?.is_dir());
+    assert_err!(
+        fs::write("/sys/devices/system/cpu/online", "0"),
+        PermissionDenied
+    );
+    assert_err!(fs::write("/sys/kernel/new", "test"), PermissionDenied);
+
+    // writable attributes take effect immediately
+    let log_level = "/sys/kernel/debug/log_level";
+    let old_level = fs::read_to_string(log_level)?;
+    fs::write(log_level, "debug\n")?;
+    assert_eq!(fs::read_to_string(log_level)?, "debug\n");
+    assert_err!(fs::write(log_level, "verbose"), InvalidInput);
+    fs::write(log_level, old_level.as_bytes())?;
+    assert_eq!(fs::read_to_string(log_level)?, old_level);
+
+    println!("test_sysfs() OK!");
+    Ok(())
+}
+
+#[cfg(feature = "ramfs")]
+fn test_links() -> Result<()> {
+    println!("test symbolic and hard links in /tmp:");
+    fs::create_dir("/tmp/links")?;
+    fs::write("/tmp/links/target.txt", "target")?;
+
+    // symbolic links, absolute and relative
+    fs::soft_link("/tmp/links/target.txt", "/tmp/links/abs")?;
+    fs::soft_link("target.txt", "/tmp/links/rel")?;
+    fs::soft_link("../links/", "/tmp/links/dir")?;
+    assert_eq!(fs::read_to_string("/tmp/links/abs")?, "target");
+    assert_eq!(fs::read_to_string("/tmp/links/rel")?, "target");
+    assert_eq!(fs::read_to_string("/tmp/links/dir/dir/rel")?, "target");
+    assert_eq!(fs::read_link("/tmp/links/rel")?, "target.txt");
+    assert_err!(fs::read_link("/tmp/links/target.txt"), InvalidInput);
+    assert!(fs::symlink_metadata("/tmp/links/abs")?.is_symlink());
+    assert!(fs::metadata("/tmp/links/abs")?.is_file());
+    assert!(fs::metadata("/tmp/links/dir")?.is_dir());
+    assert_eq!(
+        fs::canonicalize("/tmp/links/dir/rel")?,
+        "/tmp/links/target.txt"
+    );
+
+    // `..` is resolved in the tree, after the component before it
+    assert_err!(fs::metadata("/tmp/links/missing/../target.txt"), NotFound);
+    assert_err!(fs::metadata("/tmp/links/target.txt/../abs"), NotADirectory);
+    assert_err!(fs::metadata("/dev/nonexistent/../null"), NotFound);
+    assert!(fs::metadata("/tmp/links/dir/../links/abs")?.is_file());
+
+    // dangling links and loops
+    fs::soft_link("missing.txt", "/tmp/links/dangling")?;
+    assert!(fs::symlink_metadata("/tmp/links/dangling").is_ok());
+    assert_err!(fs::metadata("/tmp/links/dangling"), NotFound);
+    fs::write("/tmp/links/dangling", "created")?; // creates the target
+    assert_eq!(fs::read_to_string("/tmp/links/missing.txt")?, "created");
+    fs::soft_link("loop2", "/tmp/links/loop1")?;
+    fs::soft_link("loop1", "/tmp/links/loop2")?;
+    assert_err!(fs::read("/tmp/links/loop1"), InvalidData);
+    assert_err!(fs::soft_link("target.txt", "/tmp/links/abs"), AlreadyExists);
+
+    // hard links share the same data
+    fs::hard_link("/tmp/links/target.txt", "/tmp/links/hard")?;
+    fs::write("/tmp/links/hard", "changed")?;
+    assert_eq!(fs::read_to_string("/tmp/links/target.txt")?, "changed");
+    let (target, hard) = (
+        fs::metadata("/tmp/links/target.txt")?,
+        fs::metadata("/tmp/links/hard")?,
+    );
+    assert_eq!((target.ino(), target.nlink()), (hard.ino(), 2));
+    assert_ne!(fs::symlink_metadata("/tmp/links/abs")?.ino(), hard.ino());
+    assert_err!(
+        fs::hard_link("/tmp/links", "/tmp/dir-link"),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::hard_link("/tmp/links/target.txt", "/tmp/links/abs"),
+        AlreadyExists
+    );
+    assert_err!(fs::hard_link("/dev/null", "/tmp/links/null"), Unsupported);
+
+    // removing a link leaves its target
+    fs::remove_file("/tmp/links/abs")?;
+    fs::remove_file("/tmp/links/target.txt")?;
+    assert_eq!(fs::read_to_string("/tmp/links/hard")?, "changed");
+    assert_eq!(fs::metadata("/tmp/links/hard")?.nlink(), 1);
+    assert_err!(fs::read("/tmp/links/rel"), NotFound);
+    fs::remove_file("/tmp/links/dir")?;
+    assert!(fs::metadata("/tmp/links")?.is_dir());
+
+    for name in ["rel", "dangling", "missing.txt", "loop1", "loop2", "hard"] {
+        fs::remove_file(&format!("/tmp/links/{}", name))?;
+    }
+    fs::remove_dir("/tmp/links")?;
+
+    println!("test_links() OK!");
+    Ok(())
+}
+
+fn test_metadata() -> Result<()> {
+    println!("test timestamps, owners and permissions:");
+    let fname = "///meta.txt";
+    fs::write(fname, "metadata")?;
+    let meta = fs::metadata(fname)?;
+    assert_eq!((meta.uid(), meta.gid()), (0, 0));
+    assert!(meta.modified() <= meta.changed());
+
+    // at midnight, FAT only stores the date of the last access
+    let (atime, mtime) = (
+        Duration::from_secs(999_993_600),
+        Duration::from_secs(1_000_086_400),
+    );
+    let times = FileTimes::new().set_accessed(atime).set_modified(mtime);
+    fs::set_times(fname, times)?;
+    let meta = fs::metadata(fname)?;
+    assert_eq!((meta.accessed(), meta.modified()), (atime, mtime));
+
+    // only the modification time is changed by writing
+    let mut file = OpenOptions::new().append(true).open(fname)?;
+    file.write_all(b" appended")?;
+    let meta = file.metadata()?;
+    assert_eq!(meta.accessed(), atime);
+    assert_ne!(meta.modified(), mtime);
+    file.set_times(FileTimes::new().set_modified(mtime))?;
+    assert_eq!(file.metadata()?.modified(), mtime);
+
+    fs::chown(fname, Some(1000), None)?;
+    file.chown(None, Some(100))?;
+    let meta = fs::metadata(fname)?;
+    assert_eq!((meta.uid(), meta.gid()), (1000, 100));
+
+    let perm = Permissions::from_bits_truncate(0o400);
+    file.set_permissions(perm)?;
+    assert_eq!(fs::metadata(fname)?.permissions(), perm);
+    assert_err!(File::create(fname), PermissionDenied);
+    fs::set_permissions(fname, Permissions::from_bits_truncate(0o644))?;
+
+    // an opened file follows its renames
+    fs::rename(fname, "/meta-renamed.txt")?;
+    file.chown(Some(0), None)?;
+    assert_eq!(fs::metadata("/meta-renamed.txt")?.uid(), 0);
+    fs::rename("/meta-renamed.txt", fname)?;
+    drop(file);
+
+    // metadata is forgotten with the file
+    fs::remove_file(fname)?;
+    fs::write(fname, "")?;
+    assert_eq!(fs::metadata(fname)?.uid(), 0);
+    fs::remove_file(fname)?;
+
+    println!("test_metadata() OK!");
+    Ok(())
+}
+
+fn test_locks() -> Result<()> {
+    use axfs::fops::{self, LockKind, RecordLock};
+
+    println!("test advisory locks:");
+    let fname = "///locks.txt";
+    fs::write(fname, "0123456789")?;
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    let (f1, f2) = (
+        fops::File::open(fname, &opts)?,
+        fops::File::open(fname, &opts)?,
+    );
+    let (l1, l2) = (f1.locker(), f2.locker());
+
+    // whole-file locks are owned by opened files
+    l1.flock(Some(LockKind::Shared), false)?;
+    l2.flock(Some(LockKind::Shared), false)?;
+    assert_err!(l2.flock(Some(LockKind::Exclusive), false), WouldBlock);
+    l1.flock(None, false)?;
+    l2.flock(Some(LockKind::Exclusive), false)?;
+    assert_err!(l1.flock(Some(LockKind::Shared), false), WouldBlock);
+    drop(f2);
+    l1.flock(Some(LockKind::Exclusive), false)?;
+
+    // record locks are owned by processes, and do not conflict with the above
+    let lock = |kind, start, end, pid| RecordLock {
+        kind,
+        start,
+        end,
+        pid,
+    };
+    l1.lock_record(lock(LockKind::Exclusive, 0, 4, 1), false)?;
+    l1.lock_record(lock(LockKind::Shared, 4, u64::MAX, 1), false)?;
+    l1.lock_record(lock(LockKind::Shared, 6, 8, 2), false)?;
+    let wanted = lock(LockKind::Exclusive, 2, 6, 2);
+    assert_eq!(
+        l1.test_record(wanted),
+        Some(lock(LockKind::Exclusive, 0, 4, 1))
+    );
+    assert_err!(l1.lock_record(wanted, false), WouldBlock);
+    // unlocking a part of a lock keeps the rest
+    l1.unlock_record(1, 2, 5);
+    assert_eq!(
+        l1.test_record(wanted),
+        Some(lock(LockKind::Shared, 5, u64::MAX, 1))
+    );
+    l1.release_records(1);
+    assert_eq!(l1.test_record(wanted), None);
+    l1.lock_record(wanted, false)?;
+    l1.release_records(2);
+
+    drop(f1);
+    fs::remove_file(fname)?;
+    println!("test_locks() OK!");
+    Ok(())
+}
+
+fn test_notify() -> Result<()> {
+    use fs::{EventKind, EventMask, Watcher};
+
+    println!("test change notifications:");
+    fs::create_dir("/notify")?;
+    let watcher = Watcher::new();
+    let dir_wd = watcher.add_watch("/notify", EventMask::ALL, true)?;
+    let next = || {
+        let event = watcher.next_event().unwrap();
+        (event.wd, event.kind, event.name)
+    };
+    let named = |kind, name: &str| (Some(dir_wd), kind, Some(String::from(name)));
+
+    // entries of the watched directory
+    fs::write("/notify/a.txt", "hello")?;
+    assert_eq!(next(), named(EventKind::Create, "a.txt"));
+    assert_eq!(next(), named(EventKind::Modify, "a.txt"));
+    assert_eq!(next(), named(EventKind::CloseWrite, "a.txt"));
+    assert!(!watcher.has_events());
+
+    // the two events of a rename share a cookie
+    let file_wd = watcher.add_watch(
+        "/notify/a.txt",
+        EventMask::empty().with(EventKind::DeleteSelf),
+        true,
+    )?;
+    fs::rename("/notify/a.txt", "/notify/b.txt")?;
+    let (from, to) = (watcher.next_event().unwrap(), watcher.next_event().unwrap());
+    assert_eq!(
+        (from.kind, to.kind),
+        (EventKind::MovedFrom, EventKind::MovedTo)
+    );
+    assert_eq!(
+        (from.name.unwrap(), to.name.unwrap()),
+        ("a.txt".into(), "b.txt".into())
+    );
+    assert_eq!(from.cookie, to.cookie);
+    assert!(!watcher.has_events());
+
+    // the watch of a file follows it, and is removed with it
+    fs::remove_file("/notify/b.txt")?;
+    assert_eq!(next(), named(EventKind::Delete, "b.txt"));
+    assert_eq!(next(), (Some(file_wd), EventKind::DeleteSelf, None));
+    assert_eq!(next(), (Some(file_wd), EventKind::Ignored, None));
+    assert_err!(watcher.remove_watch(file_wd), InvalidInput);
+
+    watcher.remove_watch(dir_wd)?;
+    assert_eq!(next(), (Some(dir_wd), EventKind::Ignored, None));
+    fs::remove_dir("/notify")?;
+    assert!(!watcher.has_events());
+    println!("test_notify() OK!");
+    Ok(())
+}
+
+fn test_vectored_io() -> Result<()> {
+    use axfs::fops;
+
+    println!("test vectored read and write:");
+    let fname = "///vectored.txt";
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    opts.write(true);
+    opts.create(true);
+    let mut file = fops::File::open(fname, &opts)?;
+
+    assert_eq!(file.write_vectored(&[b"hello", b", ", b"world"])?, 12);
+    assert_eq!(file.write_vectored_at(7, &[b"W", b"orld!"])?, 6);
+    let (mut a, mut b, mut c) = ([0; 5], [0; 2], [0; 8]);
+    assert_eq!(file.read_vectored_at(0, &mut [&mut a, &mut b, &mut c])?, 13);
+    assert_eq!((&a, &b, &c[..6]), (b"hello", b", ", &b"World!"[..]));
+
+    // reads stop at the end of the file, and advance the cursor
+    file.seek(io::SeekFrom::Start(4))?;
+    let (mut a, mut b) = ([0; 4], [0; 8]);
+    assert_eq!(file.read_vectored(&mut [&mut a, &mut b])?, 9);
+    assert_eq!(&b[..5], b"orld!");
+    assert_eq!(file.read_vectored(&mut [&mut a])?, 0);
+
+    // all buffers go to the end in the append mode
+    drop(file);
+    opts.append(true);
+    let mut file = fops::File::open(fname, &opts)?;
+    assert_eq!(file.write_vectored(&[b" ", b"bye"])?, 4);
+    drop(file);
+    assert_eq!(fs::read_to_string(fname)?, "hello, World! bye");
+
+    fs::remove_file(fname)?;
+    println!("test_vectored_io() OK!");
+    Ok(())
+}
+
+#[cfg(feature = "ramfs")]
+fn test_statfs_quota() -> Result<()> {
+    use fs::Quota;
+
+    println!("test statfs and quotas:");
+    assert_eq!(fs::statfs("/tmp")?.magic, 0x8584_58f6);
+    assert_err!(fs::set_quota("/tmp/quota", None), InvalidInput);
+
+    let quota = Quota {
+        max_bytes: Some(8192),
+        max_files: Some(2),
+    };
+    fs::set_quota("/tmp", Some(quota))?;
+    let stats = fs::statfs("/tmp")?;
+    assert_eq!((stats.blocks, stats.blocks_free), (2, 2));
+    assert_eq!((stats.files, stats.files_free), (2, 2));
+
+    // writes beyond the quota fail as a whole
+    fs::write("/tmp/quota1", [1; 5000])?;
+    assert_err!(fs::write("/tmp/quota2", [2; 4000]), StorageFull);
+    assert_eq!(fs::metadata("/tmp/quota2")?.len(), 0);
+    assert_err!(File::create("/tmp/quota3"), StorageFull);
+    let stats = fs::statfs("/tmp")?;
+    assert_eq!((stats.blocks_free, stats.files_free), (0, 0));
+
+    // removed and truncated files release their quota
+    fs::remove_file("/tmp/quota2")?;
+    File::options()
+        .write(true)
+        .open("/tmp/quota1")?
+        .set_len(0)?;
+    fs::write("/tmp/quota2", [2; 8000])?;
+    assert_eq!(fs::statfs("/tmp")?.files_free, 0);
+
+    fs::remove_file("/tmp/quota1")?;
+    fs::remove_file("/tmp/quota2")?;
+    fs::set_quota("/tmp", None)?;
+    fs::write("/tmp/quota1", [1; 10000])?;
+    fs::remove_file("/tmp/quota1")?;
+    println!("test_statfs_quota() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
@@ -259,4 +796,19 @@ pub fn test_all() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_shared_file_data().expect("test_shared_file_data() failed");
+    test_mount_points().expect("test_mount_points() failed");
+    test_mount_umount().expect("test_mount_umount() failed");
+    #[cfg(feature = "procfs")]
+    test_procfs().expect("test_procfs() failed");
+    #[cfg(feature = "sysfs")]
+    test_sysfs().expect("test_sysfs() failed");
+    #[cfg(feature = "ramfs")]
+    test_links().expect("test_links() failed");
+    test_metadata().expect("test_metadata() failed");
+    test_locks().expect("test_locks() failed");
+    test_notify().expect("test_notify() failed");
+    test_vectored_io().expect("test_vectored_io() failed");
+    #[cfg(feature = "ramfs")]
+    test_statfs_quota().expect("test_statfs_quota() failed");
 }
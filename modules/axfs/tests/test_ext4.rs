@@ -0,0 +1,35 @@
+#![cfg(all(feature = "ext4", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+
+const SCRIPT_PATH: &str = "resources/create_test_img.sh";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("ext4.img");
+    println!("Creating disk image {:?} ...", path);
+    let status = std::process::Command::new("bash")
+        .arg(std::env::current_dir()?.join(SCRIPT_PATH))
+        .arg("ext4")
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        return Err(std::io::Error::other("failed to create disk image"));
+    }
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+#[test]
+fn test_ext4() {
+    println!("Testing ext4 with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+}
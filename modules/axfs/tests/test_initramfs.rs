@@ -0,0 +1,111 @@
+#![cfg(all(feature = "initramfs", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use std::time::Duration;
+
+const MTIME: u32 = 1_700_000_000;
+
+/// Appends an entry to a cpio archive in the "newc" format.
+fn add_entry(archive: &mut Vec<u8>, path: &str, mode: u32, ino: u32, nlink: u32, data: &[u8]) {
+    let fields = [
+        ino,
+        mode,
+        1000, // uid
+        100,  // gid
+        nlink,
+        MTIME,
+        data.len() as u32,
+        0, // devmajor
+        0, // devminor
+        0, // rdevmajor
+        0, // rdevminor
+        path.len() as u32 + 1,
+        0, // check
+    ];
+    archive.extend_from_slice(b"070701");
+    for field in fields {
+        archive.extend_from_slice(format!("{:08x}", field).as_bytes());
+    }
+    archive.extend_from_slice(path.as_bytes());
+    archive.push(0);
+    archive.resize(archive.len().next_multiple_of(4), 0);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().next_multiple_of(4), 0);
+}
+
+/// Creates an archive with the same files as `resources/fat16.img`, and some
+/// more to test links and metadata.
+fn make_archive() -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut ino = 0;
+    let mut add = |path: &str, mode: u32, data: &[u8]| {
+        ino += 1;
+        add_entry(&mut archive, path, mode, ino, 1, data);
+    };
+    add(".", 0o040755, b"");
+    add(
+        "long.txt",
+        0o100644,
+        "Rust is cool!\n".repeat(1000).as_bytes(),
+    );
+    add("short.txt", 0o100644, b"Rust is cool!\n");
+    add("very", 0o040755, b"");
+    add("very/long", 0o040755, b"");
+    add("very/long/path", 0o040755, b"");
+    add("very/long/path/test.txt", 0o100644, b"Rust is cool!\n");
+    add("very-long-dir-name", 0o040755, b"");
+    add(
+        "very-long-dir-name/very-long-file-name.txt",
+        0o100644,
+        b"Rust is cool!\n",
+    );
+    add("bin", 0o040711, b"");
+    add("bin/sh", 0o120777, b"busybox");
+    add("dev", 0o040755, b"");
+    add("dev/console", 0o020600, b""); // skipped
+    // hard links, with the data in the last entry
+    add_entry(&mut archive, "bin/busybox", 0o100755, 100, 2, b"");
+    add_entry(&mut archive, "bin/ls", 0o100755, 100, 2, b"#!busybox\n");
+    add_entry(&mut archive, "TRAILER!!!", 0, 0, 1, b"");
+    archive
+}
+
+fn test_unpacked() -> axio::Result<()> {
+    println!("test unpacked files:");
+    let mtime = Duration::from_secs(MTIME as u64);
+
+    let meta = fs::metadata("/bin")?;
+    assert!(meta.is_dir());
+    assert_eq!(meta.permissions().bits(), 0o711);
+    assert_eq!((meta.uid(), meta.gid()), (1000, 100));
+    assert_eq!(meta.modified(), mtime);
+
+    assert_eq!(fs::read_to_string("/bin/busybox")?, "#!busybox\n");
+    assert_eq!(fs::read_link("/bin/sh")?, "busybox");
+    assert_eq!(fs::read_to_string("/bin/sh")?, "#!busybox\n");
+    let meta = fs::metadata("/bin/ls")?;
+    assert_eq!(meta.permissions().bits(), 0o755);
+    assert_eq!(meta.modified(), mtime);
+    fs::write("/bin/ls", "changed")?;
+    assert_eq!(fs::read_to_string("/bin/busybox")?, "changed");
+
+    assert!(fs::metadata("/dev/null").is_ok());
+    assert!(fs::metadata("/dev/console").is_err());
+
+    println!("test_unpacked() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initramfs() {
+    println!("Testing initramfs without block devices ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems_with_initramfs(AxDeviceContainer::default(), &make_archive());
+
+    test_unpacked().expect("test_unpacked() failed");
+    test_common::test_all();
+}
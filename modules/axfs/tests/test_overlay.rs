@@ -0,0 +1,101 @@
+#![cfg(all(feature = "overlay", not(feature = "myfs")))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::{Error, Result, prelude::*};
+
+const IMG_PATH: &str = "resources/fat16.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn list_dir(path: &str) -> Result<Vec<String>> {
+    fs::read_dir(path)?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect()
+}
+
+fn test_copy_up() -> Result<()> {
+    println!("test copy-up of lower files:");
+    let contents = fs::read_to_string("/long.txt")?;
+    let mut file = fs::OpenOptions::new().append(true).open("/long.txt")?;
+    file.write_all(b"copied up\n")?;
+    drop(file);
+    assert_eq!(fs::read_to_string("/long.txt")?, contents + "copied up\n");
+
+    // the copy has the same content after renamed
+    fs::rename("/long.txt", "/very/long.txt")?;
+    assert!(fs::read_to_string("/very/long.txt")?.ends_with("copied up\n"));
+    assert_eq!(fs::metadata("/long.txt").err(), Some(Error::NotFound));
+
+    println!("test_copy_up() OK!");
+    Ok(())
+}
+
+fn test_whiteouts() -> Result<()> {
+    let dir = "/very-long-dir-name";
+    let fname = "/very-long-dir-name/very-long-file-name.txt";
+    println!("test whiteouts of lower files:");
+
+    // a removed lower file is hidden
+    fs::remove_file(fname)?;
+    assert_eq!(fs::metadata(fname).err(), Some(Error::NotFound));
+    assert!(list_dir(dir)?.is_empty());
+    assert!(list_dir("/")?.iter().all(|name| !name.starts_with(".wh.")));
+    assert_eq!(
+        fs::read_dir("/.wh.very-long-dir-name").err(),
+        Some(Error::NotFound)
+    );
+
+    // a lower file can be created again
+    fs::write(fname, "new file\n")?;
+    assert_eq!(fs::read_to_string(fname)?, "new file\n");
+    fs::remove_file(fname)?;
+
+    // a lower directory created again is empty
+    fs::remove_dir(dir)?;
+    assert_eq!(fs::metadata(dir).err(), Some(Error::NotFound));
+    fs::create_dir(dir)?;
+    assert!(list_dir(dir)?.is_empty());
+
+    // a lower file renamed keeps its content, renaming a lower directory
+    // is not supported
+    let contents = fs::read_to_string("/very/long/path/test.txt")?;
+    fs::rename("/very/long/path/test.txt", "/very/test.txt")?;
+    assert_eq!(fs::read_to_string("/very/test.txt")?, contents);
+    assert!(!list_dir("/very/long/path")?.contains(&"test.txt".into()));
+    assert_eq!(
+        fs::rename("/very/long", "/very/short").err(),
+        Some(Error::Unsupported)
+    );
+
+    println!("test_whiteouts() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_overlay() {
+    println!("Testing overlay of fatfs with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+
+    #[cfg(feature = "procfs")]
+    {
+        let mounts = fs::read_to_string("/proc/mounts").expect("failed to read mounts");
+        assert!(mounts.lines().next().unwrap().contains(" / overlay "));
+    }
+    test_copy_up().expect("test_copy_up() failed");
+    test_whiteouts().expect("test_whiteouts() failed");
+}
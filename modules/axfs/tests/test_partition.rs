@@ -0,0 +1,148 @@
+#![cfg(not(feature = "myfs"))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio as io;
+
+use fs::File;
+use io::{Result, prelude::*};
+
+const IMG_PATH: &str = "resources/fat16.img";
+const BLOCK_SIZE: usize = 512;
+
+const PART1_START: usize = 2048;
+const EXT_START: usize = PART1_START + 8192;
+
+macro_rules! assert_err {
+    ($expr: expr, $err: ident) => {
+        assert_eq!($expr.err(), Some(io::Error::$err))
+    };
+}
+
+fn set_entry(sector: &mut [u8], idx: usize, ty: u8, start: usize, num_blocks: usize) {
+    let entry = &mut sector[446 + idx * 16..][..16];
+    entry[4] = ty;
+    entry[8..12].copy_from_slice(&(start as u32).to_le_bytes());
+    entry[12..16].copy_from_slice(&(num_blocks as u32).to_le_bytes());
+}
+
+/// Builds a disk with the FAT image in the primary partition 1, and another
+/// copy of it in the logical partition 5.
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let img = std::fs::read(path)?;
+    let img_blocks = img.len().div_ceil(BLOCK_SIZE);
+    let ext_blocks = img_blocks + 1;
+
+    let mut data = vec![0u8; (EXT_START + ext_blocks) * BLOCK_SIZE];
+    let mbr = &mut data[..BLOCK_SIZE];
+    set_entry(mbr, 0, 0x06, PART1_START, img_blocks);
+    set_entry(mbr, 1, 0x05, EXT_START, ext_blocks);
+    mbr[510..].copy_from_slice(&[0x55, 0xAA]);
+
+    let ebr = &mut data[EXT_START * BLOCK_SIZE..][..BLOCK_SIZE];
+    set_entry(ebr, 0, 0x06, 1, img_blocks);
+    ebr[510..].copy_from_slice(&[0x55, 0xAA]);
+
+    data[PART1_START * BLOCK_SIZE..][..img.len()].copy_from_slice(&img);
+    data[(EXT_START + 1) * BLOCK_SIZE..][..img.len()].copy_from_slice(&img);
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+fn test_mount_volume() -> Result<()> {
+    assert_err!(fs::mount_volume("disk0p3", "/mnt/disk0p3"), NotFound);
+    // the root filesystem is on `disk0p1`
+    assert_err!(fs::mount_volume("disk0p1", "/mnt/disk0p1"), NotFound);
+
+    fs::create_dir("/mnt")?;
+    fs::mount_volume("/dev/disk0p5", "/mnt/disk0p5")?;
+    assert_err!(fs::mount_volume("disk0p5", "/mnt/again"), ResourceBusy);
+
+    // the same files as the root, but on another partition
+    let fname = "/mnt/disk0p5/only-on-p5.txt";
+    assert!(fs::metadata("/mnt/disk0p5/short.txt")?.is_file());
+    File::create(fname)?.write_all(b"partition 5")?;
+    assert_eq!(fs::read_to_string(fname)?, "partition 5");
+    assert_err!(fs::metadata("/only-on-p5.txt"), NotFound);
+
+    fs::umount("/mnt/disk0p5")?;
+    assert_err!(fs::metadata(fname), NotFound);
+
+    // data is written back to the partition
+    fs::mount_volume("disk0p5", "/mnt/disk0p5")?;
+    assert_eq!(fs::read_to_string(fname)?, "partition 5");
+    fs::umount("/mnt/disk0p5")?;
+
+    fs::remove_dir("/mnt/disk0p5")?;
+    fs::remove_dir("/mnt")?;
+    println!("test_mount_volume() OK!");
+    Ok(())
+}
+
+fn test_ramfs_swap() -> Result<()> {
+    assert_err!(
+        fs::mount_virtual_with_options("ramfs", "/swap", "size=1x"),
+        InvalidInput
+    );
+    assert_err!(
+        fs::mount_virtual_with_options("proc", "/swap", "size=1m"),
+        InvalidInput
+    );
+    assert_err!(
+        fs::mount_virtual_with_options("ramfs", "/swap", "swap=disk0p3"),
+        NotFound
+    );
+
+    // memory is always low in tests, so pages are spilled to `disk0p5`
+    fs::mount_virtual_with_options("tmpfs", "/swap", "size=64k,swap=disk0p5")?;
+    assert_err!(fs::mount_volume("disk0p5", "/mnt"), ResourceBusy);
+    let stats = fs::statfs("/swap")?;
+    assert_eq!(
+        (stats.block_size, stats.blocks, stats.blocks_free),
+        (4096, 16, 16)
+    );
+
+    let data = (0..40000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    File::create("/swap/a.bin")?.write_all(&data)?;
+    File::create("/swap/b.bin")?.write_all(&data[..20000])?;
+    assert_eq!(fs::read("/swap/a.bin")?, data);
+    assert_eq!(fs::read("/swap/b.bin")?, &data[..20000]);
+    assert_eq!(fs::statfs("/swap")?.blocks_free, 1);
+
+    // beyond the size limit
+    assert_err!(File::create("/swap/c.bin")?.write_all(&data), StorageFull);
+    assert_eq!(fs::statfs("/swap")?.blocks_free, 0);
+    fs::remove_file("/swap/c.bin")?;
+    File::options()
+        .write(true)
+        .open("/swap/a.bin")?
+        .set_len(4096)?;
+    assert_eq!(fs::read("/swap/a.bin")?, &data[..4096]);
+    assert_eq!(fs::statfs("/swap")?.blocks_free, 10);
+
+    fs::umount("/swap")?;
+    // the swap volume is released
+    fs::mount_virtual_with_options("ramfs", "/swap", "swap=disk0p5")?;
+    fs::umount("/swap")?;
+    fs::remove_dir("/swap")?;
+    println!("test_ramfs_swap() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_partition() {
+    println!("Testing MBR partitions with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+    test_mount_volume().expect("test_mount_volume() failed");
+    test_ramfs_swap().expect("test_ramfs_swap() failed");
+}
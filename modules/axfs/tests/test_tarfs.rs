@@ -0,0 +1,141 @@
+#![cfg(all(feature = "tarfs", not(feature = "myfs")))]
+
+use axdriver::AxDeviceContainer;
+use axdriver_block::ramdisk::RamDisk;
+use axfs::api as fs;
+use axio::{Error, Result};
+use std::time::Duration;
+
+const MTIME: u64 = 1_700_000_000;
+const LONG_NAME: &str = "etc/a-very-long-directory-name-that-does-not-fit-in-the-name-field/\
+                         and-a-long-file-name-too.txt";
+
+/// Appends a ustar entry to a tar image.
+fn add_entry(image: &mut Vec<u8>, name: &str, ty: u8, mode: u32, link: &str, data: &[u8]) {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    let octal = |header: &mut [u8; 512], range: std::ops::Range<usize>, n: u64| {
+        let field = format!("{:0width$o}\0", n, width = range.len() - 1);
+        header[range].copy_from_slice(field.as_bytes());
+    };
+    octal(&mut header, 100..108, mode as u64);
+    octal(&mut header, 108..116, 1000); // uid
+    octal(&mut header, 116..124, 100); // gid
+    octal(&mut header, 124..136, data.len() as u64);
+    octal(&mut header, 136..148, MTIME);
+    header[156] = ty;
+    header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+    header[257..265].copy_from_slice(b"ustar\x0000");
+    header[148..156].fill(b' ');
+    let sum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    image.extend_from_slice(&header);
+    image.extend_from_slice(data);
+    image.resize(image.len().next_multiple_of(512), 0);
+}
+
+fn make_image() -> Vec<u8> {
+    let mut image = Vec::new();
+    for dir in ["dev/", "tmp/", "proc/", "sys/", "etc/"] {
+        add_entry(&mut image, dir, b'5', 0o755, "", b"");
+    }
+    add_entry(&mut image, "etc/hostname", b'0', 0o644, "", b"arceos\n");
+    add_entry(&mut image, "etc/link", b'2', 0o777, "hostname", b"");
+    add_entry(&mut image, "etc/hard", b'1', 0o644, "etc/hostname", b"");
+    // a GNU long name, and a PAX path with a missing parent directory
+    add_entry(
+        &mut image,
+        "././@LongLink",
+        b'L',
+        0,
+        "",
+        LONG_NAME.as_bytes(),
+    );
+    add_entry(&mut image, "truncated", b'0', 0o600, "", b"long name\n");
+    let record = "20 path=opt/pax.txt\n";
+    add_entry(&mut image, "PaxHeader", b'x', 0, "", record.as_bytes());
+    add_entry(&mut image, "truncated", b'0', 0o644, "", b"pax\n");
+    image.resize(image.len() + 1024, 0); // end of archive
+    image
+}
+
+fn test_read_image() -> Result<()> {
+    println!("test reading the tar image:");
+    assert_eq!(fs::read_to_string("/etc/hostname")?, "arceos\n");
+    assert_eq!(fs::read_link("/etc/link")?, "hostname");
+    assert_eq!(fs::read_to_string("/etc/link")?, "arceos\n");
+    assert_eq!(fs::read_to_string("/etc/hard")?, "arceos\n");
+    assert_eq!(
+        fs::read_to_string(&format!("/{}", LONG_NAME))?,
+        "long name\n"
+    );
+    assert_eq!(fs::read_to_string("/opt/pax.txt")?, "pax\n");
+    assert_eq!(fs::metadata("/truncated").err(), Some(Error::NotFound));
+
+    let names = fs::read_dir("/etc")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"hostname".into()));
+    assert!(names.contains(&"hard".into()));
+
+    let meta = fs::metadata("/etc/hostname")?;
+    assert_eq!(meta.len(), 7);
+    assert_eq!(meta.permissions().bits(), 0o644);
+    assert_eq!((meta.uid(), meta.gid()), (1000, 100));
+    assert_eq!(meta.modified(), Duration::from_secs(MTIME));
+
+    println!("test_read_image() OK!");
+    Ok(())
+}
+
+fn test_read_only() -> Result<()> {
+    println!("test the tar image is read-only:");
+    let denied = Some(Error::PermissionDenied);
+    assert_eq!(fs::write("/etc/hostname", "changed").err(), denied);
+    assert_eq!(fs::write("/etc/new.txt", "new").err(), denied);
+    assert_eq!(fs::create_dir("/etc/new").err(), denied);
+    assert_eq!(fs::remove_file("/etc/hostname").err(), denied);
+    assert_eq!(fs::rename("/etc/hostname", "/etc/name").err(), denied);
+    assert_eq!(fs::read_to_string("/etc/hostname")?, "arceos\n");
+
+    // virtual filesystems are mounted on the directories of the image
+    fs::write("/tmp/test.txt", "writable")?;
+    assert_eq!(fs::read_to_string("/tmp/test.txt")?, "writable");
+    assert!(fs::metadata("/dev/null").is_ok());
+
+    println!("test_read_only() OK!");
+    Ok(())
+}
+
+fn test_memory_image() -> Result<()> {
+    println!("test mounting a tar image in memory:");
+    let image = make_image().leak();
+    fs::create_dir("/tmp/image")?;
+    fs::mount_tar_image(image, "/tmp/image")?;
+    assert_eq!(fs::read_to_string("/tmp/image/etc/hostname")?, "arceos\n");
+    assert_eq!(fs::read_to_string("/tmp/image/opt/pax.txt")?, "pax\n");
+    #[cfg(feature = "procfs")]
+    assert!(fs::read_to_string("/proc/mounts")?.contains("memory /tmp/image tar "));
+    fs::umount("/tmp/image")?;
+
+    println!("test_memory_image() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_tarfs() {
+    println!("Testing tarfs with ramdisk ...");
+
+    let disk = RamDisk::from(&make_image());
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    #[cfg(feature = "procfs")]
+    {
+        let mounts = fs::read_to_string("/proc/mounts").expect("failed to read mounts");
+        assert!(mounts.lines().next().unwrap().contains(" / tar "));
+    }
+    test_read_image().expect("test_read_image() failed");
+    test_read_only().expect("test_read_only() failed");
+    test_memory_image().expect("test_memory_image() failed");
+}
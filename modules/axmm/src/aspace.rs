@@ -1,3 +1,4 @@
+use alloc::sync::Arc;
 use core::fmt;
 
 use axerrno::{AxError, AxResult, ax_err};
@@ -9,7 +10,7 @@ use memory_addr::{
 };
 use memory_set::{MemoryArea, MemorySet};
 
-use crate::backend::Backend;
+use crate::backend::{Backend, FileMapping};
 use crate::mapping_err_to_ax_err;
 
 /// The virtual memory address space.
@@ -148,6 +149,39 @@ impl AddrSpace {
         Ok(())
     }
 
+    /// Add a new file mapping, `start` is mapped to `offset` of the file.
+    ///
+    /// See [`Backend`] for more details about the mapping backends.
+    ///
+    /// The `flags` parameter indicates the mapping permissions and attributes.
+    /// If `shared` is true, modifications are written back to the file.
+    ///
+    /// Returns an error if the address range is out of the address space or not
+    /// aligned.
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn FileMapping>,
+        offset: u64,
+        shared: bool,
+    ) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) || !is_aligned_4k(offset as usize) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+
+        let backend = Backend::new_file(file, start, offset, shared);
+        let area = MemoryArea::new(start, size, flags, backend);
+        self.areas
+            .map(area, &mut self.pt, false)
+            .map_err(mapping_err_to_ax_err)?;
+        Ok(())
+    }
+
     /// Removes mappings within the specified virtual address range.
     ///
     /// Returns an error if the address range is out of the address space or not
@@ -0,0 +1,155 @@
+use alloc::sync::Arc;
+use axhal::mem::{phys_to_virt, virt_to_phys};
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{MemoryAddr, PAGE_SIZE_4K, PageIter4K, VirtAddr};
+
+use super::Backend;
+use super::alloc::{alloc_frame, dealloc_frame};
+
+/// A file that can be mapped into address spaces, usually the page cache of a
+/// regular file.
+pub trait FileMapping: Send + Sync {
+    /// Gets the page at the page-aligned `offset` of the file, returns its
+    /// kernel virtual address, or `None` if it is beyond the end of the file.
+    ///
+    /// The page must stay valid until [`FileMapping::put_page`] is called. If
+    /// `writable` is true, the page may be modified through the mapping.
+    fn get_page(&self, offset: u64, writable: bool) -> Option<VirtAddr>;
+
+    /// Releases a page got by [`FileMapping::get_page`].
+    fn put_page(&self, offset: u64);
+}
+
+impl Backend {
+    /// Creates a new file mapping backend.
+    ///
+    /// `va_start` is mapped to `offset` of the file.
+    pub fn new_file(
+        file: Arc<dyn FileMapping>,
+        va_start: VirtAddr,
+        offset: u64,
+        shared: bool,
+    ) -> Self {
+        Self::File {
+            file,
+            va_start,
+            offset,
+            shared,
+        }
+    }
+
+    /// Returns the file offset of the page containing `vaddr`.
+    fn file_offset(&self, vaddr: VirtAddr) -> u64 {
+        match self {
+            Self::File {
+                va_start, offset, ..
+            } => offset + (vaddr.align_down_4k() - *va_start) as u64,
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn map_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+    ) -> bool {
+        debug!(
+            "map_file: [{:#x}, {:#x}) {:?} (offset={:#x})",
+            start,
+            start + size,
+            flags,
+            self.file_offset(start)
+        );
+        // Map to a empty entry for on-demand mapping.
+        pt.map_region(
+            start,
+            |_| 0.into(),
+            size,
+            MappingFlags::empty(),
+            false,
+            false,
+        )
+        .map(|tlb| tlb.ignore())
+        .is_ok()
+    }
+
+    pub(crate) fn unmap_file(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        file: &Arc<dyn FileMapping>,
+        shared: bool,
+    ) -> bool {
+        debug!("unmap_file: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            if let Ok((frame, page_size, tlb)) = pt.unmap(addr) {
+                if page_size.is_huge() {
+                    return false;
+                }
+                tlb.flush();
+                if shared {
+                    // The frame belongs to the page cache.
+                    file.put_page(self.file_offset(addr));
+                } else {
+                    dealloc_frame(frame);
+                }
+            }
+        }
+        true
+    }
+
+    pub(crate) fn handle_page_fault_file(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        file: &Arc<dyn FileMapping>,
+        shared: bool,
+    ) -> bool {
+        let offset = self.file_offset(vaddr);
+        let writable = orig_flags.contains(MappingFlags::WRITE);
+        let frame = if shared {
+            match file.get_page(offset, writable) {
+                Some(page) => virt_to_phys(page),
+                None => return false,
+            }
+        } else {
+            // Private mappings get a copy of the file page.
+            let Some(page) = file.get_page(offset, false) else {
+                return false;
+            };
+            let frame = alloc_frame(false);
+            if let Some(frame) = frame {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        page.as_ptr(),
+                        phys_to_virt(frame).as_mut_ptr(),
+                        PAGE_SIZE_4K,
+                    )
+                };
+            }
+            file.put_page(offset);
+            match frame {
+                Some(frame) => frame,
+                None => return false,
+            }
+        };
+        match pt.remap(vaddr, frame, orig_flags) {
+            Ok((_, tlb)) => {
+                tlb.flush();
+                true
+            }
+            Err(_) => {
+                if shared {
+                    file.put_page(offset);
+                } else {
+                    dealloc_frame(frame);
+                }
+                false
+            }
+        }
+    }
+}
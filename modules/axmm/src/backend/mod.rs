@@ -1,20 +1,26 @@
 //! Memory mapping backends.
 
+use ::alloc::sync::Arc;
 use axhal::paging::{MappingFlags, PageTable};
 use memory_addr::VirtAddr;
 use memory_set::MappingBackend;
 
 mod alloc;
+mod file;
 mod linear;
 
+pub use self::file::FileMapping;
+
 /// A unified enum type for different memory mapping backends.
 ///
-/// Currently, two backends are implemented:
+/// Currently, three backends are implemented:
 ///
 /// - **Linear**: used for linear mappings. The target physical frames are
 ///   contiguous and their addresses should be known when creating the mapping.
 /// - **Allocation**: used in general, or for lazy mappings. The target physical
 ///   frames are obtained from the global allocator.
+/// - **File**: used for mapping files. The target physical frames are pages of
+///   a [`FileMapping`], usually the page cache of a file, obtained on demand.
 #[derive(Clone)]
 pub enum Backend {
     /// Linear mapping backend.
@@ -36,6 +42,21 @@ pub enum Backend {
         /// Whether to populate the physical frames when creating the mapping.
         populate: bool,
     },
+    /// File mapping backend.
+    ///
+    /// Shared mappings map the pages of the file directly, so modifications
+    /// are visible to others and written back to the file. Private mappings
+    /// get a copy of the file pages on the first access.
+    File {
+        /// The mapped file.
+        file: Arc<dyn FileMapping>,
+        /// The virtual address mapped to `offset`.
+        va_start: VirtAddr,
+        /// The file offset of `va_start`.
+        offset: u64,
+        /// Whether the mapping is shared (`MAP_SHARED`).
+        shared: bool,
+    },
 }
 
 impl MappingBackend for Backend {
@@ -46,6 +67,7 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.map_linear(start, size, flags, pt, pa_va_offset),
             Self::Alloc { populate } => self.map_alloc(start, size, flags, pt, populate),
+            Self::File { .. } => self.map_file(start, size, flags, pt),
         }
     }
 
@@ -53,6 +75,9 @@ impl MappingBackend for Backend {
         match *self {
             Self::Linear { pa_va_offset } => self.unmap_linear(start, size, pt, pa_va_offset),
             Self::Alloc { populate } => self.unmap_alloc(start, size, pt, populate),
+            Self::File {
+                ref file, shared, ..
+            } => self.unmap_file(start, size, pt, file, shared),
         }
     }
 
@@ -82,6 +107,9 @@ impl Backend {
             Self::Alloc { populate } => {
                 self.handle_page_fault_alloc(vaddr, orig_flags, page_table, populate)
             }
+            Self::File {
+                ref file, shared, ..
+            } => self.handle_page_fault_file(vaddr, orig_flags, page_table, file, shared),
         }
     }
 }
@@ -9,6 +9,7 @@
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`tcp_sockets`]: Function to list TCP sockets, e.g. for `/proc/net/tcp`.
 //!
 //! # Cargo Features
 //!
@@ -32,6 +33,7 @@ cfg_if::cfg_if! {
 
 pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
+pub use self::net_impl::{TcpSocketInfo, tcp_sockets};
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
 
@@ -1,4 +1,4 @@
-use alloc::{boxed::Box, collections::VecDeque};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
 use core::ops::{Deref, DerefMut};
 
 use axerrno::{AxError, AxResult, ax_err};
@@ -78,6 +78,14 @@ impl ListenTable {
         *self.tcp[port as usize].lock() = None;
     }
 
+    /// Returns the endpoints of all listening ports.
+    pub fn listen_endpoints(&self) -> Vec<IpListenEndpoint> {
+        self.tcp
+            .iter()
+            .filter_map(|entry| entry.lock().as_ref().map(|entry| entry.listen_endpoint))
+            .collect()
+    }
+
     pub fn can_accept(&self, port: u16) -> AxResult<bool> {
         if let Some(entry) = self.tcp[port as usize].lock().deref() {
             Ok(entry.syn_queue.iter().any(|&handle| is_connected(handle)))
@@ -23,7 +23,7 @@ use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 use self::listen_table::ListenTable;
 
 pub use self::dns::dns_query;
-pub use self::tcp::TcpSocket;
+pub use self::tcp::{TcpSocket, TcpSocketInfo, tcp_sockets};
 pub use self::udp::UdpSocket;
 
 macro_rules! env_or_default {
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
@@ -7,10 +8,11 @@ use axio::PollState;
 use axsync::Mutex;
 
 use smoltcp::iface::SocketHandle;
+use smoltcp::socket::AnySocket;
 use smoltcp::socket::tcp::{self, ConnectError, State};
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
-use super::addr::UNSPECIFIED_ENDPOINT;
+use super::addr::{UNSPECIFIED_ENDPOINT, UNSPECIFIED_IP};
 use super::{ETH0, LISTEN_TABLE, SOCKET_SET, SocketSetWrapper};
 
 // State transitions:
@@ -539,6 +541,74 @@ impl Drop for TcpSocket {
     }
 }
 
+/// A snapshot of a TCP socket, as listed in `/proc/net/tcp`.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpSocketInfo {
+    /// The local address and port.
+    pub local_addr: SocketAddr,
+    /// The remote address and port, unspecified for listening sockets.
+    pub peer_addr: SocketAddr,
+    /// The connection state, numbered as in Linux (`TCP_ESTABLISHED` is 1).
+    pub state: u8,
+    /// The number of bytes waiting to be sent.
+    pub send_queue: usize,
+    /// The number of bytes waiting to be received.
+    pub recv_queue: usize,
+}
+
+/// Returns the listening ports and open connections of all TCP sockets.
+pub fn tcp_sockets() -> Vec<TcpSocketInfo> {
+    const TCP_LISTEN: u8 = 10;
+    if !SOCKET_SET.is_inited() {
+        return Vec::new();
+    }
+
+    let mut sockets: Vec<_> = LISTEN_TABLE
+        .listen_endpoints()
+        .into_iter()
+        .map(|endpoint| TcpSocketInfo {
+            local_addr: SocketAddr::from(IpEndpoint::new(
+                endpoint.addr.unwrap_or(UNSPECIFIED_IP),
+                endpoint.port,
+            )),
+            peer_addr: SocketAddr::from(UNSPECIFIED_ENDPOINT),
+            state: TCP_LISTEN,
+            send_queue: 0,
+            recv_queue: 0,
+        })
+        .collect();
+    for (_, socket) in SOCKET_SET.0.lock().iter() {
+        let Some(socket) = tcp::Socket::downcast(socket) else {
+            continue;
+        };
+        // sockets waiting in a SYN queue have no endpoints yet
+        let (Some(local), Some(peer)) = (socket.local_endpoint(), socket.remote_endpoint()) else {
+            continue;
+        };
+        let state = match socket.state() {
+            State::Established => 1,
+            State::SynSent => 2,
+            State::SynReceived => 3,
+            State::FinWait1 => 4,
+            State::FinWait2 => 5,
+            State::TimeWait => 6,
+            State::Closed => 7,
+            State::CloseWait => 8,
+            State::LastAck => 9,
+            State::Listen => TCP_LISTEN,
+            State::Closing => 11,
+        };
+        sockets.push(TcpSocketInfo {
+            local_addr: SocketAddr::from(local),
+            peer_addr: SocketAddr::from(peer),
+            state,
+            send_queue: socket.send_queue(),
+            recv_queue: socket.recv_queue(),
+        });
+    }
+    sockets
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
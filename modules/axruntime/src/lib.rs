@@ -21,6 +21,8 @@
 
 #[macro_use]
 extern crate axlog;
+#[cfg(feature = "fs")]
+extern crate alloc;
 
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
@@ -28,6 +30,9 @@ mod lang_items;
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "fs")]
+mod procfs;
+
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
@@ -166,7 +171,11 @@ pub fn rust_main(cpu_id: usize, arg: usize) -> ! {
         let all_devices = axdriver::init_drivers();
 
         #[cfg(feature = "fs")]
-        axfs::init_filesystems(all_devices.block);
+        {
+            axfs::set_clocks(axhal::time::wall_time, axhal::time::monotonic_time);
+            self::procfs::register();
+            axfs::init_filesystems(all_devices.block);
+        }
 
         #[cfg(feature = "net")]
         axnet::init_network(all_devices.net);
@@ -0,0 +1,145 @@
+//! Entries of the proc filesystem of `axfs` generated from the state of other
+//! modules.
+//!
+//! - `/proc/<tid>/status` and `/proc/self`: every task, with the `multitask`
+//!   feature.
+//! - `/proc/net/tcp` and `/proc/net/tcp6`: TCP sockets, with the `net`
+//!   feature.
+
+/// Registers the entries to the proc filesystem, before it is mounted.
+pub(crate) fn register() {
+    #[cfg(feature = "multitask")]
+    axfs::register_procfs_provider(task::add_entries);
+    #[cfg(feature = "net")]
+    axfs::register_procfs_provider(net::add_entries);
+}
+
+#[cfg(feature = "multitask")]
+mod task {
+    use alloc::{format, string::String, sync::Arc, vec::Vec};
+    use axfs::pseudo::PseudoDir;
+    use axfs_vfs::VfsNodeRef;
+    use axtask::{AxTaskRef, TaskState};
+
+    /// Adds `/proc/<tid>` for every task, and `/proc/self` for the current one.
+    pub(super) fn add_entries(root: &Arc<PseudoDir>) {
+        root.add_generated(|root| {
+            let mut entries: Vec<(String, VfsNodeRef)> = axtask::all_tasks()
+                .into_iter()
+                .map(|task| (format!("{}", task.id().as_u64()), task_dir(root, task)))
+                .collect();
+            if let Some(curr) = axtask::current_may_uninit() {
+                let curr = curr.as_task_ref().clone();
+                entries.push(("self".into(), task_dir(root, curr)));
+            }
+            entries
+        });
+    }
+
+    fn task_dir(parent: &Arc<PseudoDir>, task: AxTaskRef) -> VfsNodeRef {
+        let dir = PseudoDir::new(Some(parent.as_parent()));
+        let weak = Arc::downgrade(&task);
+        dir.add_file("status", move || {
+            weak.upgrade().map(|task| status(&task)).unwrap_or_default()
+        });
+        let weak = Arc::downgrade(&task);
+        dir.add_file("stat", move || {
+            weak.upgrade().map(|task| stat(&task)).unwrap_or_default()
+        });
+        dir
+    }
+
+    fn state(task: &AxTaskRef) -> (char, &'static str) {
+        match task.state() {
+            TaskState::Running | TaskState::Ready => ('R', "running"),
+            TaskState::Blocked => ('S', "sleeping"),
+            TaskState::Exited => ('Z', "zombie"),
+        }
+    }
+
+    fn status(task: &AxTaskRef) -> String {
+        let (state, state_name) = state(task);
+        let id = task.id().as_u64();
+        format!(
+            "Name:\t{}\nState:\t{} ({})\nTgid:\t{}\nPid:\t{}\nPPid:\t0\nCpu:\t{}\n",
+            task.name(),
+            state,
+            state_name,
+            id,
+            id,
+            task.cpu_id()
+        )
+    }
+
+    /// The first 3 fields of `/proc/<pid>/stat` in Linux, the rest are zeros.
+    fn stat(task: &AxTaskRef) -> String {
+        const NUM_FIELDS: usize = 52;
+        let mut stat = format!("{} ({}) {}", task.id().as_u64(), task.name(), state(task).0);
+        for _ in 3..NUM_FIELDS {
+            stat.push_str(" 0");
+        }
+        stat.push('\n');
+        stat
+    }
+}
+
+#[cfg(feature = "net")]
+mod net {
+    use alloc::{format, string::String, sync::Arc};
+    use core::fmt::Write;
+    use core::net::{IpAddr, SocketAddr};
+
+    use axfs::pseudo::PseudoDir;
+
+    /// Adds `/proc/net/tcp` and `/proc/net/tcp6`.
+    pub(super) fn add_entries(root: &Arc<PseudoDir>) {
+        let net_dir = root.add_dir("net");
+        net_dir.add_file("tcp", || tcp(false));
+        net_dir.add_file("tcp6", || tcp(true));
+    }
+
+    /// Lists TCP sockets in the format of Linux `/proc/net/tcp`, or
+    /// `/proc/net/tcp6` for IPv6 ones.
+    fn tcp(ipv6: bool) -> String {
+        let addr_width = if ipv6 { 32 } else { 8 };
+        let mut table = format!(
+            "  sl  {:<w$} {:<w$} st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n",
+            "local_address",
+            "rem_address",
+            w = addr_width + 5,
+        );
+        let sockets = axnet::tcp_sockets()
+            .into_iter()
+            .filter(|sock| sock.local_addr.is_ipv6() == ipv6);
+        for (i, sock) in sockets.enumerate() {
+            writeln!(
+                table,
+                "{:4}: {} {} {:02X} {:08X}:{:08X} 00:00000000 00000000     0        0 0",
+                i,
+                hex_addr(&sock.local_addr),
+                hex_addr(&sock.peer_addr),
+                sock.state,
+                sock.send_queue,
+                sock.recv_queue,
+            )
+            .unwrap();
+        }
+        table
+    }
+
+    /// Formats the address as Linux does, in 32-bit words of the host (little)
+    /// endian, followed by the port.
+    fn hex_addr(addr: &SocketAddr) -> String {
+        let mut hex = String::new();
+        let octets = match addr.ip() {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        for word in octets.chunks_exact(4) {
+            let word = u32::from_le_bytes(word.try_into().unwrap());
+            write!(hex, "{:08X}", word).unwrap();
+        }
+        write!(hex, ":{:04X}", addr.port()).unwrap();
+        hex
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! Currently supported primitives:
 //!
-//! - [`Mutex`]: A mutual exclusion primitive.
+//! - [`Mutex`]: A mutual exclusion primitive with priority inheritance.
 //! - mod [`spin`]: spinlocks imported from the [`kspin`] crate.
 //!
 //! # Cargo Features
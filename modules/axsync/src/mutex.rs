@@ -1,17 +1,23 @@
-//! A naïve sleeping mutex.
+//! A sleeping mutex with priority inheritance.
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
 
-use axtask::{WaitQueue, current};
+use axtask::{AxTaskRef, WaitQueue, WeakAxTaskRef, current};
 
 /// A [`lock_api::RawMutex`] implementation.
 ///
 /// When the mutex is locked, the current task will block and be put into the
-/// wait queue. When the mutex is unlocked, all tasks waiting on the queue
-/// will be woken up.
+/// wait queue. When the mutex is unlocked, it is handed over to the waiting
+/// task of the highest scheduling class, which is the only one woken up.
+///
+/// The owner inherits the highest scheduling class of the waiting tasks, so a
+/// real-time task is not blocked by a lower priority owner that can not run,
+/// see [`WaitQueue::wait_until_lend`].
 pub struct RawMutex {
     wq: WaitQueue,
-    owner_id: AtomicU64,
+    /// The owner task from [`WeakAxTaskRef::into_raw`], or null if unlocked.
+    owner: AtomicPtr<()>,
 }
 
 impl RawMutex {
@@ -20,7 +26,28 @@ impl RawMutex {
     pub const fn new() -> Self {
         Self {
             wq: WaitQueue::new(),
-            owner_id: AtomicU64::new(0),
+            owner: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Tries to set the owner to the current task if it is unlocked, or
+    /// returns the owner.
+    fn try_own(&self) -> Result<(), *mut ()> {
+        let me = WeakAxTaskRef::into_raw(AxTaskRef::downgrade(current().as_task_ref())) as *mut ();
+        // The reason for using a strong compare_exchange is explained here:
+        // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
+        self.owner
+            .compare_exchange(ptr::null_mut(), me, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| ())
+            .inspect_err(|_| drop(unsafe { WeakAxTaskRef::from_raw(me as _) }))
+    }
+}
+
+impl Drop for RawMutex {
+    fn drop(&mut self) {
+        let owner = *self.owner.get_mut();
+        if !owner.is_null() {
+            drop(unsafe { WeakAxTaskRef::from_raw(owner as _) });
         }
     }
 }
@@ -37,56 +64,61 @@ unsafe impl lock_api::RawMutex for RawMutex {
 
     #[inline(always)]
     fn lock(&self) {
-        let current_id = current().id().as_u64();
-        loop {
-            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
-            // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
-                0,
-                current_id,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(owner_id) => {
-                    assert_ne!(
-                        owner_id,
-                        current_id,
-                        "{} tried to acquire mutex it already owns.",
-                        current().id_name()
-                    );
-                    // Wait until the lock looks unlocked before retrying
-                    self.wq.wait_until(|| !self.is_locked());
-                }
-            }
+        if let Err(owner) = self.try_own() {
+            let me = AxTaskRef::as_ptr(current().as_task_ref()) as *mut ();
+            assert_ne!(
+                owner,
+                me,
+                "{} tried to acquire mutex it already owns.",
+                current().id_name()
+            );
+            // Wait until the lock is handed over to us, or is unlocked without
+            // waiters. The owner is only released by `unlock()` with the wait
+            // queue locked, so it is valid in `owner()`.
+            self.wq.wait_until_lend(
+                || self.owner.load(Ordering::Acquire) == me || self.try_lock(),
+                || {
+                    let owner = self.owner.load(Ordering::Relaxed);
+                    if owner.is_null() {
+                        return None;
+                    }
+                    let owner = unsafe { WeakAxTaskRef::from_raw(owner as _) };
+                    let task = owner.upgrade();
+                    let _ = WeakAxTaskRef::into_raw(owner);
+                    task
+                },
+            );
         }
     }
 
     #[inline(always)]
     fn try_lock(&self) -> bool {
-        let current_id = current().id().as_u64();
-        // The reason for using a strong compare_exchange is explained here:
-        // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
-        self.owner_id
-            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+        self.try_own().is_ok()
     }
 
     #[inline(always)]
     unsafe fn unlock(&self) {
-        let owner_id = self.owner_id.swap(0, Ordering::Release);
+        let owner = self.owner.load(Ordering::Relaxed);
         assert_eq!(
-            owner_id,
-            current().id().as_u64(),
+            owner,
+            AxTaskRef::as_ptr(current().as_task_ref()) as *mut (),
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
-        self.wq.notify_one(true);
+        // Hand the lock over to the next owner directly, so that it can not be
+        // taken by others before the woken task runs.
+        self.wq.notify_highest(true, |next| {
+            let next = next.map_or(ptr::null_mut(), |task| {
+                WeakAxTaskRef::into_raw(AxTaskRef::downgrade(task)) as *mut ()
+            });
+            self.owner.store(next, Ordering::Release);
+        });
+        drop(unsafe { WeakAxTaskRef::from_raw(owner as _) });
     }
 
     #[inline(always)]
     fn is_locked(&self) -> bool {
-        self.owner_id.load(Ordering::Relaxed) != 0
+        !self.owner.load(Ordering::Relaxed).is_null()
     }
 }
 
@@ -99,9 +131,10 @@ pub type MutexGuard<'a, T> = lock_api::MutexGuard<'a, RawMutex, T>;
 mod tests {
     use crate::Mutex;
     use axtask as thread;
-    use std::sync::Once;
+    use std::sync::{Mutex as StdMutex, Once};
 
     static INIT: Once = Once::new();
+    static SERIAL: StdMutex<()> = StdMutex::new(());
 
     fn may_interrupt() {
         // simulate interrupts
@@ -112,6 +145,7 @@ mod tests {
 
     #[test]
     fn lots_and_lots() {
+        let _lock = SERIAL.lock();
         INIT.call_once(thread::init_scheduler);
 
         const NUM_TASKS: u32 = 10;
@@ -147,4 +181,184 @@ mod tests {
         assert_eq!(*M.lock(), NUM_ITERS * NUM_TASKS * 3);
         println!("Mutex test OK");
     }
+
+    #[test]
+    fn handoff_by_priority() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(thread::init_scheduler);
+
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use thread::SchedPolicy;
+
+        static M: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+        static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+        let policies = [
+            SchedPolicy::Normal,
+            SchedPolicy::Fifo(10),
+            SchedPolicy::Fifo(20),
+        ];
+        let guard = M.lock();
+        let tasks: Vec<_> = policies
+            .iter()
+            .enumerate()
+            .map(|(i, &policy)| {
+                let task = thread::spawn(move || {
+                    STARTED.fetch_add(1, Ordering::Release);
+                    M.lock().push(i);
+                });
+                assert!(thread::set_task_policy(&task, policy));
+                task
+            })
+            .collect();
+        // All tasks block on the mutex once they start.
+        while STARTED.load(Ordering::Acquire) < policies.len() {
+            thread::yield_now();
+        }
+        drop(guard);
+
+        for task in tasks {
+            task.join();
+        }
+        assert_eq!(*M.lock(), [2, 1, 0]);
+    }
+
+    /// Spins as a real-time task of a middle priority until the task of the
+    /// highest priority is done, which never happens if the owner it waits
+    /// for does not inherit its priority.
+    fn spin_until(done: &'static core::sync::atomic::AtomicBool) -> thread::AxTaskRef {
+        use core::sync::atomic::Ordering;
+        let task = thread::spawn(move || {
+            for _ in 0..1000 {
+                if done.load(Ordering::Acquire) {
+                    break;
+                }
+                thread::yield_now();
+            }
+            ORDER.lock().unwrap().push("spinner");
+        });
+        assert!(thread::set_task_policy(
+            &task,
+            thread::SchedPolicy::Fifo(10)
+        ));
+        task
+    }
+
+    static ORDER: StdMutex<Vec<&str>> = StdMutex::new(Vec::new());
+
+    #[test]
+    fn inherit_priority() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(thread::init_scheduler);
+
+        use core::sync::atomic::{AtomicBool, Ordering};
+        use thread::SchedPolicy;
+
+        static M: Mutex<()> = Mutex::new(());
+        static LOCKED: AtomicBool = AtomicBool::new(false);
+        static GO: AtomicBool = AtomicBool::new(false);
+        static DONE: AtomicBool = AtomicBool::new(false);
+        ORDER.lock().unwrap().clear();
+
+        // A normal task owns the mutex, and keeps it for a while after it
+        // starts running again.
+        let owner = thread::spawn(|| {
+            let guard = M.lock();
+            LOCKED.store(true, Ordering::Release);
+            while !GO.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+            for _ in 0..10 {
+                thread::yield_now();
+            }
+            ORDER.lock().unwrap().push("owner");
+            drop(guard);
+        });
+        while !LOCKED.load(Ordering::Acquire) {
+            thread::yield_now();
+        }
+
+        let guard = kernel_guard::NoPreempt::new();
+        let waiter = thread::spawn(|| {
+            let _guard = M.lock();
+            ORDER.lock().unwrap().push("waiter");
+            DONE.store(true, Ordering::Release);
+        });
+        assert!(thread::set_task_policy(&waiter, SchedPolicy::Fifo(20)));
+        let spinner = spin_until(&DONE);
+        GO.store(true, Ordering::Release);
+        drop(guard);
+
+        for task in [waiter, spinner, owner] {
+            task.join();
+        }
+        // The owner runs before the spinner in the priority of the waiter.
+        assert_eq!(*ORDER.lock().unwrap(), ["owner", "waiter", "spinner"]);
+    }
+
+    #[test]
+    fn inherit_priority_through_chain() {
+        let _lock = SERIAL.lock();
+        INIT.call_once(thread::init_scheduler);
+
+        use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use thread::SchedPolicy;
+
+        static M1: Mutex<()> = Mutex::new(());
+        static M2: Mutex<()> = Mutex::new(());
+        static LOCKED: AtomicUsize = AtomicUsize::new(0);
+        static GO: AtomicBool = AtomicBool::new(false);
+        static DONE: AtomicBool = AtomicBool::new(false);
+        ORDER.lock().unwrap().clear();
+
+        // `last` owns `M2`, which `middle` waits for while owning `M1`.
+        let last = thread::spawn(|| {
+            let guard = M2.lock();
+            LOCKED.fetch_add(1, Ordering::Release);
+            while !GO.load(Ordering::Acquire) {
+                thread::yield_now();
+            }
+            for _ in 0..10 {
+                thread::yield_now();
+            }
+            ORDER.lock().unwrap().push("last");
+            drop(guard);
+        });
+        while LOCKED.load(Ordering::Acquire) < 1 {
+            thread::yield_now();
+        }
+        let middle = thread::spawn(|| {
+            let _guard1 = M1.lock();
+            LOCKED.fetch_add(1, Ordering::Release);
+            let _guard2 = M2.lock();
+            ORDER.lock().unwrap().push("middle");
+        });
+        while LOCKED.load(Ordering::Acquire) < 2 {
+            thread::yield_now();
+        }
+        // Wait for `middle` to block on `M2`.
+        for _ in 0..10 {
+            thread::yield_now();
+        }
+
+        let guard = kernel_guard::NoPreempt::new();
+        let waiter = thread::spawn(|| {
+            let _guard = M1.lock();
+            ORDER.lock().unwrap().push("waiter");
+            DONE.store(true, Ordering::Release);
+        });
+        assert!(thread::set_task_policy(&waiter, SchedPolicy::Fifo(20)));
+        let spinner = spin_until(&DONE);
+        GO.store(true, Ordering::Release);
+        drop(guard);
+
+        for task in [waiter, spinner, middle, last] {
+            task.join();
+        }
+        // Both owners along the chain run before the spinner.
+        assert_eq!(
+            *ORDER.lock().unwrap(),
+            ["last", "middle", "waiter", "spinner"]
+        );
+    }
 }
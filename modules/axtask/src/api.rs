@@ -1,21 +1,36 @@
 //! Task APIs for multi-task configuration.
 
-use alloc::{string::String, sync::Arc};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 
 use kernel_guard::NoPreemptIrqSave;
 
-pub(crate) use crate::run_queue::{current_run_queue, select_run_queue};
+pub(crate) use crate::run_queue::{current_run_queue, select_run_queue, task_run_queue};
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::sched::{RT_PRIO_MAX, RT_PRIO_MIN, RT_TIME_SLICE, SchedPolicy};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::stats::{CpuStats, SystemTimeGuard, TaskStats, all_tasks_stats, cpu_stats};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::task::{CurrentTask, TaskId, TaskInner, TaskState};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
 #[doc(cfg(feature = "multitask"))]
-pub use crate::wait_queue::WaitQueue;
+pub use crate::wait_queue::{MAX_LEND_DEPTH, WaitQueue};
+
+#[cfg(feature = "smp")]
+#[doc(cfg(all(feature = "multitask", feature = "smp")))]
+pub use crate::balance::{BalancePolicy, LeastLoaded, RoundRobin, set_balance_policy};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::balance::{cpu_load, run_queue_len};
 
 /// The reference type of a task.
 pub type AxTaskRef = Arc<AxTask>;
 
+/// The weak reference type of a task.
+pub type WeakAxTaskRef = Weak<AxTask>;
+
 /// The wrapper type for [`cpumask::CpuMask`] with SMP configuration.
 pub type AxCpuMask = cpumask::CpuMask<{ axconfig::plat::CPU_NUM }>;
 
@@ -23,14 +38,14 @@ cfg_if::cfg_if! {
     if #[cfg(feature = "sched-rr")] {
         const MAX_TIME_SLICE: usize = 5;
         pub(crate) type AxTask = axsched::RRTask<TaskInner, MAX_TIME_SLICE>;
-        pub(crate) type Scheduler = axsched::RRScheduler<TaskInner, MAX_TIME_SLICE>;
+        pub(crate) type NormalScheduler = axsched::RRScheduler<TaskInner, MAX_TIME_SLICE>;
     } else if #[cfg(feature = "sched-cfs")] {
         pub(crate) type AxTask = axsched::CFSTask<TaskInner>;
-        pub(crate) type Scheduler = axsched::CFScheduler<TaskInner>;
+        pub(crate) type NormalScheduler = axsched::CFScheduler<TaskInner>;
     } else {
         // If no scheduler features are set, use FIFO as the default.
         pub(crate) type AxTask = axsched::FifoTask<TaskInner>;
-        pub(crate) type Scheduler = axsched::FifoScheduler<TaskInner>;
+        pub(crate) type NormalScheduler = axsched::FifoScheduler<TaskInner>;
     }
 }
 
@@ -59,6 +74,13 @@ pub fn current_may_uninit() -> Option<CurrentTask> {
     CurrentTask::try_get()
 }
 
+/// Returns all tasks that have not been dropped, ordered by their IDs.
+///
+/// Exited tasks are included until all references to them are dropped.
+pub fn all_tasks() -> Vec<AxTaskRef> {
+    crate::task::all_tasks()
+}
+
 /// Gets the current task.
 ///
 /// # Panics
@@ -76,7 +98,7 @@ pub fn init_scheduler() {
     #[cfg(feature = "irq")]
     crate::timers::init();
 
-    info!("  use {} scheduler.", Scheduler::scheduler_name());
+    info!("  use {} scheduler.", NormalScheduler::scheduler_name());
 }
 
 /// Initializes the task scheduler for secondary CPUs.
@@ -142,11 +164,54 @@ pub fn set_priority(prio: isize) -> bool {
     current_run_queue::<NoPreemptIrqSave>().set_current_priority(prio)
 }
 
+/// Set the priority for the given task, which may be running, ready or
+/// blocked.
+///
+/// The range of the priority is the same as [`set_priority`].
+///
+/// Returns `true` if the priority is set successfully.
+pub fn set_task_priority(task: &AxTaskRef, prio: isize) -> bool {
+    // Retry if the task is moved to another run queue meanwhile.
+    loop {
+        if let Some(set) = task_run_queue::<NoPreemptIrqSave>(task).set_task_priority(task, prio) {
+            return set;
+        }
+    }
+}
+
+/// Set the scheduling policy for the current task, see [`SchedPolicy`].
+///
+/// Returns `true` if the policy is set successfully, or `false` if its
+/// parameters are invalid.
+pub fn set_current_policy(policy: SchedPolicy) -> bool {
+    set_task_policy(current().as_task_ref(), policy)
+}
+
+/// Set the scheduling policy for the given task, which may be running, ready
+/// or blocked.
+///
+/// A ready task is requeued by the new policy immediately, and the current
+/// task is preempted if it is outranked then (with the `preempt` feature).
+///
+/// Returns `true` if the policy is set successfully, or `false` if its
+/// parameters are invalid.
+pub fn set_task_policy(task: &AxTaskRef, policy: SchedPolicy) -> bool {
+    if !policy.is_valid() {
+        return false;
+    }
+    update_task_sched(task, |entity| entity.set_policy(policy));
+    true
+}
+
+/// Updates the scheduling states of the given task by `f`.
+pub(crate) fn update_task_sched(task: &AxTaskRef, f: impl Fn(&mut crate::sched::SchedEntity)) {
+    // Retry if the task is moved to another run queue meanwhile.
+    while !task_run_queue::<NoPreemptIrqSave>(task).update_task_sched(task, &f) {}
+}
+
 /// Set the affinity for the current task.
 /// [`AxCpuMask`] is used to specify the CPU affinity.
 /// Returns `true` if the affinity is set successfully.
-///
-/// TODO: support set the affinity for other tasks.
 pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
     if cpumask.is_empty() {
         false
@@ -158,16 +223,8 @@ pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
         // the affinity. If not, we need to migrate the task to the correct CPU.
         #[cfg(feature = "smp")]
         if !cpumask.get(axhal::percpu::this_cpu_id()) {
-            const MIGRATION_TASK_STACK_SIZE: usize = 4096;
-            // Spawn a new migration task for migrating.
-            let migration_task = TaskInner::new(
-                move || crate::run_queue::migrate_entry(curr),
-                "migration-task".into(),
-                MIGRATION_TASK_STACK_SIZE,
-            )
-            .into_arc();
-
             // Migrate the current task to the correct CPU using the migration task.
+            let migration_task = crate::run_queue::migration_task(curr);
             current_run_queue::<NoPreemptIrqSave>().migrate_current(migration_task);
 
             assert!(
@@ -179,6 +236,41 @@ pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
     }
 }
 
+/// Set the affinity for the given task, which may be running, ready or
+/// blocked.
+///
+/// A ready task is moved to an allowed CPU immediately. A task running on
+/// another CPU is moved when it yields or is preempted (it is asked to be
+/// preempted with the `preempt` feature), and a blocked task when it is woken
+/// up.
+///
+/// Returns `true` if the affinity is set successfully.
+pub fn set_task_affinity(task: &AxTaskRef, cpumask: AxCpuMask) -> bool {
+    if current().ptr_eq(task) {
+        return set_current_affinity(cpumask);
+    }
+    if cpumask.is_empty() {
+        return false;
+    }
+    task.set_cpumask(cpumask);
+    #[cfg(feature = "smp")]
+    if !cpumask.get(task.cpu_id() as usize) {
+        match task.state() {
+            TaskState::Ready => task_run_queue::<NoPreemptIrqSave>(task).migrate_task(task),
+            #[cfg(feature = "preempt")]
+            TaskState::Running => task.set_preempt_pending(true),
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Returns the task with the given ID, or [`None`] if it does not exist or
+/// has been dropped.
+pub fn get_task(id: u64) -> Option<AxTaskRef> {
+    crate::task::get_task(id)
+}
+
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
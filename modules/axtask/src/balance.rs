@@ -0,0 +1,204 @@
+//! Load tracking of run queues, and load balancing across CPUs.
+//!
+//! The load of a CPU is the number of ready tasks in its run queue, plus one
+//! if it is running a task other than the idle task.
+//!
+//! With the `smp` feature, new and woken tasks are placed on the CPU chosen by
+//! the [`BalancePolicy`], which is [`LeastLoaded`] by default and can be
+//! replaced by [`set_balance_policy`]. A CPU that runs out of ready tasks
+//! steals one from the CPU chosen by the policy before going idle.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use axconfig::plat::CPU_NUM;
+
+#[cfg(feature = "smp")]
+use kspin::SpinNoIrq;
+
+#[cfg(feature = "smp")]
+use crate::{AxCpuMask, AxTaskRef};
+
+struct CpuLoad {
+    online: AtomicBool,
+    nr_ready: AtomicUsize,
+    busy: AtomicBool,
+}
+
+impl CpuLoad {
+    const fn new() -> Self {
+        Self {
+            online: AtomicBool::new(false),
+            nr_ready: AtomicUsize::new(0),
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    fn load(&self) -> usize {
+        self.nr_ready.load(Ordering::Acquire) + self.busy.load(Ordering::Acquire) as usize
+    }
+}
+
+static CPU_LOADS: [CpuLoad; CPU_NUM] = [const { CpuLoad::new() }; CPU_NUM];
+
+/// Marks the run queue of `cpu_id` as initialized, tasks can be placed on it
+/// since then.
+pub(crate) fn set_online(cpu_id: usize) {
+    CPU_LOADS[cpu_id].online.store(true, Ordering::Release);
+}
+
+/// Counts a task put into the run queue of `cpu_id`.
+pub(crate) fn inc_ready(cpu_id: usize) {
+    CPU_LOADS[cpu_id].nr_ready.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Counts a task taken out of the run queue of `cpu_id`.
+pub(crate) fn dec_ready(cpu_id: usize) {
+    CPU_LOADS[cpu_id].nr_ready.fetch_sub(1, Ordering::AcqRel);
+}
+
+/// Records whether `cpu_id` is running a task other than the idle task.
+pub(crate) fn set_busy(cpu_id: usize, busy: bool) {
+    CPU_LOADS[cpu_id].busy.store(busy, Ordering::Release);
+}
+
+/// Returns the load of the CPU `cpu_id`, which is the number of ready tasks in
+/// its run queue, plus one if it is running a task other than the idle task.
+///
+/// # Panics
+///
+/// Panics if `cpu_id` is not less than [`axconfig::plat::CPU_NUM`].
+pub fn cpu_load(cpu_id: usize) -> usize {
+    CPU_LOADS[cpu_id].load()
+}
+
+/// Returns the number of ready tasks in the run queue of the CPU `cpu_id`.
+///
+/// # Panics
+///
+/// Panics if `cpu_id` is not less than [`axconfig::plat::CPU_NUM`].
+pub fn run_queue_len(cpu_id: usize) -> usize {
+    CPU_LOADS[cpu_id].nr_ready.load(Ordering::Acquire)
+}
+
+/// A policy to balance the load across CPUs.
+///
+/// Policies are given the loads of all CPUs indexed by CPU IDs, which are only
+/// a snapshot, as other CPUs keep running.
+#[cfg(feature = "smp")]
+pub trait BalancePolicy: Send + Sync {
+    /// Selects the CPU in `cpumask` to put the ready `task` on, which is new or
+    /// woken up.
+    ///
+    /// `cpumask` is never empty, and `task.cpu_id()` is the CPU where it ran
+    /// last. Returning a CPU not in `cpumask` is a bug of the policy, the task
+    /// is then placed by [`LeastLoaded`] instead.
+    fn select_cpu(&self, task: &AxTaskRef, cpumask: AxCpuMask, loads: &[usize]) -> usize;
+
+    /// Selects the CPU that the CPU `cpu_id`, which has no ready task, steals
+    /// a ready task from, or returns `None` not to steal.
+    ///
+    /// Only tasks allowed to run on `cpu_id` are stolen. The default selects
+    /// the busiest other CPU that has tasks waiting to run.
+    fn select_victim(&self, cpu_id: usize, loads: &[usize]) -> Option<usize> {
+        (0..loads.len())
+            .filter(|&i| i != cpu_id && loads[i] >= 2)
+            .max_by_key(|&i| loads[i])
+    }
+}
+
+/// Places tasks on the least loaded allowed CPU, preferring the CPU where the
+/// task ran last, then the current CPU, on a tie.
+///
+/// It is the default [`BalancePolicy`].
+#[cfg(feature = "smp")]
+pub struct LeastLoaded;
+
+#[cfg(feature = "smp")]
+impl BalancePolicy for LeastLoaded {
+    fn select_cpu(&self, task: &AxTaskRef, cpumask: AxCpuMask, loads: &[usize]) -> usize {
+        let last = task.cpu_id() as usize;
+        let this = axhal::percpu::this_cpu_id();
+        (0..loads.len())
+            .filter(|&i| cpumask.get(i))
+            .min_by_key(|&i| (loads[i], i != last, i != this))
+            .expect("No available CPU for task execution")
+    }
+}
+
+/// Places tasks on the allowed CPUs in turn regardless of their loads, and
+/// never steals tasks.
+#[cfg(feature = "smp")]
+pub struct RoundRobin;
+
+#[cfg(feature = "smp")]
+impl BalancePolicy for RoundRobin {
+    fn select_cpu(&self, _task: &AxTaskRef, cpumask: AxCpuMask, loads: &[usize]) -> usize {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let index = NEXT.fetch_add(1, Ordering::Relaxed) % loads.len();
+            if cpumask.get(index) {
+                return index;
+            }
+        }
+    }
+
+    fn select_victim(&self, _cpu_id: usize, _loads: &[usize]) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(feature = "smp")]
+static POLICY: SpinNoIrq<&'static dyn BalancePolicy> = SpinNoIrq::new(&LeastLoaded);
+
+/// Replaces the [`BalancePolicy`] used to place and steal tasks.
+#[cfg(feature = "smp")]
+pub fn set_balance_policy(policy: &'static dyn BalancePolicy) {
+    *POLICY.lock() = policy;
+}
+
+#[cfg(feature = "smp")]
+fn loads() -> [usize; CPU_NUM] {
+    core::array::from_fn(|i| CPU_LOADS[i].load())
+}
+
+/// Selects the CPU to put the ready `task` on by the policy.
+///
+/// Only CPUs whose run queues are initialized are selected, unless none of
+/// them is allowed by the affinity of `task`.
+///
+/// # Panics
+///
+/// Panics if the affinity of `task` is empty.
+#[cfg(feature = "smp")]
+pub(crate) fn select_cpu(task: &AxTaskRef) -> usize {
+    let allowed = task.cpumask();
+    assert!(!allowed.is_empty(), "No available CPU for task execution");
+    let mut cpumask = AxCpuMask::new();
+    for i in 0..CPU_NUM {
+        if allowed.get(i) && CPU_LOADS[i].online.load(Ordering::Acquire) {
+            cpumask.set(i, true);
+        }
+    }
+    if cpumask.is_empty() {
+        cpumask = allowed;
+    }
+    let loads = loads();
+    let policy = *POLICY.lock();
+    let cpu_id = policy.select_cpu(task, cpumask, &loads);
+    if cpu_id < CPU_NUM && cpumask.get(cpu_id) {
+        cpu_id
+    } else {
+        LeastLoaded.select_cpu(task, cpumask, &loads)
+    }
+}
+
+/// Selects the CPU that the idle CPU `cpu_id` steals from by the policy.
+#[cfg(feature = "smp")]
+pub(crate) fn select_victim(cpu_id: usize) -> Option<usize> {
+    let loads = loads();
+    let policy = *POLICY.lock();
+    let victim = policy.select_victim(cpu_id, &loads)?;
+    let valid =
+        victim != cpu_id && victim < CPU_NUM && CPU_LOADS[victim].online.load(Ordering::Acquire);
+    valid.then_some(victim)
+}
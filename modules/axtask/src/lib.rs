@@ -13,6 +13,10 @@
 //!   APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!   [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `smp`: Schedule tasks on multiple CPUs. New and woken tasks are placed on
+//!   the least loaded CPU allowed by their affinities, and idle CPUs steal
+//!   ready tasks from busy ones. The policy can be replaced by
+//!   [`set_balance_policy`].
 //! - `sched-fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -21,6 +25,12 @@
 //! - `sched-cfs`: Use the [Completely Fair Scheduler][3]. It also enables the
 //!   the `multitask` and `preempt` features if it is enabled.
 //!
+//! The scheduler selected above schedules normal tasks. Real-time and deadline
+//! tasks always run before them, see [`SchedPolicy`].
+//!
+//! The CPU time and context switches of each task and CPU are accounted, see
+//! [`TaskStats`] and [`CpuStats`].
+//!
 //! [1]: axsched::FifoScheduler
 //! [2]: axsched::RRScheduler
 //! [3]: axsched::CFScheduler
@@ -41,6 +51,9 @@ cfg_if::cfg_if! {
 
         #[macro_use]
         mod run_queue;
+        mod balance;
+        mod sched;
+        mod stats;
         mod task;
         mod task_ext;
         mod api;
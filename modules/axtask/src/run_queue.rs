@@ -12,9 +12,10 @@ use lazyinit::LazyInit;
 
 use axhal::percpu::this_cpu_id;
 
+use crate::sched::{SchedEntity, Scheduler};
 use crate::task::{CurrentTask, TaskState};
 use crate::wait_queue::WaitQueueGuard;
-use crate::{AxCpuMask, AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::{AxCpuMask, AxTaskRef, TaskInner, WaitQueue};
 
 macro_rules! percpu_static {
     ($(
@@ -76,42 +77,6 @@ pub(crate) fn current_run_queue<G: BaseGuard>() -> CurrentRunQueueRef<'static, G
     }
 }
 
-/// Selects the run queue index based on a CPU set bitmap and load balancing.
-///
-/// This function filters the available run queues based on the provided `cpumask` and
-/// selects the run queue index for the next task. The selection is based on a round-robin algorithm.
-///
-/// ## Arguments
-///
-/// * `cpumask` - A bitmap representing the CPUs that are eligible for task execution.
-///
-/// ## Returns
-///
-/// The index (cpu_id) of the selected run queue.
-///
-/// ## Panics
-///
-/// This function will panic if `cpu_mask` is empty, indicating that there are no available CPUs for task execution.
-///
-#[cfg(feature = "smp")]
-// The modulo operation is safe here because `axconfig::plat::CPU_NUM` is always greater than 1 with "smp" enabled.
-#[allow(clippy::modulo_one)]
-#[inline]
-fn select_run_queue_index(cpumask: AxCpuMask) -> usize {
-    use core::sync::atomic::{AtomicUsize, Ordering};
-    static RUN_QUEUE_INDEX: AtomicUsize = AtomicUsize::new(0);
-
-    assert!(!cpumask.is_empty(), "No available CPU for task execution");
-
-    // Round-robin selection of the run queue index.
-    loop {
-        let index = RUN_QUEUE_INDEX.fetch_add(1, Ordering::SeqCst) % axconfig::plat::CPU_NUM;
-        if cpumask.get(index) {
-            return index;
-        }
-    }
-}
-
 /// Retrieves a `'static` reference to the run queue corresponding to the given index.
 ///
 /// This function asserts that the provided index is within the range of available CPUs
@@ -138,7 +103,8 @@ fn get_run_queue(index: usize) -> &'static mut AxRunQueue {
 /// Selects the appropriate run queue for the provided task.
 ///
 /// * In a single-core system, this function always returns a reference to the global run queue.
-/// * In a multi-core system, this function selects the run queue based on the task's CPU affinity and load balance.
+/// * In a multi-core system, this function selects the run queue based on the task's CPU affinity,
+///   by the load balancing policy (see [`crate::balance`]).
 ///
 /// ## Arguments
 ///
@@ -148,10 +114,9 @@ fn get_run_queue(index: usize) -> &'static mut AxRunQueue {
 ///
 /// * [`AxRunQueueRef`] - a static reference to the selected [`AxRunQueue`] (current or remote).
 ///
-/// ## TODO
+/// ## Panics
 ///
-/// 1. Implement better load balancing across CPUs for more efficient task distribution.
-/// 2. Use a more generic load balancing algorithm that can be customized or replaced.
+/// This function will panic if the task's CPU affinity is empty.
 ///
 #[inline]
 pub(crate) fn select_run_queue<G: BaseGuard>(task: &AxTaskRef) -> AxRunQueueRef<'static, G> {
@@ -169,7 +134,7 @@ pub(crate) fn select_run_queue<G: BaseGuard>(task: &AxTaskRef) -> AxRunQueueRef<
     #[cfg(feature = "smp")]
     {
         // When SMP is enabled, select the run queue based on the task's CPU affinity and load balance.
-        let index = select_run_queue_index(task.cpumask());
+        let index = crate::balance::select_cpu(task);
         AxRunQueueRef {
             inner: get_run_queue(index),
             state: irq_state,
@@ -178,6 +143,25 @@ pub(crate) fn select_run_queue<G: BaseGuard>(task: &AxTaskRef) -> AxRunQueueRef<
     }
 }
 
+/// Returns a reference to the run queue where the task is ready, or ran last
+/// if it is running or blocked.
+#[inline]
+pub(crate) fn task_run_queue<G: BaseGuard>(task: &AxTaskRef) -> AxRunQueueRef<'static, G> {
+    let irq_state = G::acquire();
+    #[cfg(not(feature = "smp"))]
+    let inner = {
+        let _ = task;
+        unsafe { RUN_QUEUE.current_ref_mut_raw() }
+    };
+    #[cfg(feature = "smp")]
+    let inner = get_run_queue(task.cpu_id() as usize);
+    AxRunQueueRef {
+        inner,
+        state: irq_state,
+        _phantom: core::marker::PhantomData,
+    }
+}
+
 /// [`AxRunQueue`] represents a run queue for global system or a specific CPU.
 pub(crate) struct AxRunQueue {
     /// The ID of the CPU this run queue is associated with.
@@ -237,7 +221,11 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
             self.inner.cpu_id
         );
         assert!(task.is_ready());
-        self.inner.scheduler.lock().add_task(task);
+        task.accounting().restart();
+        self.inner
+            .enqueue(task, |scheduler, task| scheduler.add_task(task));
+        #[cfg(feature = "preempt")]
+        self.inner.check_preempt_current();
     }
 
     /// Unblock one task by inserting it into the run queue.
@@ -264,6 +252,80 @@ impl<G: BaseGuard> AxRunQueueRef<'_, G> {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
             }
+            #[cfg(feature = "preempt")]
+            self.inner.check_preempt_current();
+        }
+    }
+
+    /// Sets the priority of a task in this run queue, or that ran on it last.
+    ///
+    /// Returns whether the priority is set, or `None` if the task has been
+    /// moved to another run queue.
+    pub fn set_task_priority(&mut self, task: &AxTaskRef, prio: isize) -> Option<bool> {
+        let mut scheduler = self.inner.scheduler.lock();
+        // A ready task is only in the run queue its `cpu_id` refers to.
+        #[cfg(feature = "smp")]
+        if task.cpu_id() as usize != self.inner.cpu_id {
+            return None;
+        }
+        Some(scheduler.set_priority(task, prio))
+    }
+
+    /// Updates the scheduling states of a task in this run queue, or that ran
+    /// on it last, by `f`.
+    ///
+    /// Returns `false` without calling `f` if the task has been moved to
+    /// another run queue.
+    pub fn update_task_sched(
+        &mut self,
+        task: &AxTaskRef,
+        f: impl FnOnce(&mut SchedEntity),
+    ) -> bool {
+        let mut scheduler = self.inner.scheduler.lock();
+        // A ready task is only in the run queue its `cpu_id` refers to.
+        #[cfg(feature = "smp")]
+        if task.cpu_id() as usize != self.inner.cpu_id {
+            return false;
+        }
+        scheduler.update(task, f);
+        drop(scheduler);
+        #[cfg(feature = "preempt")]
+        self.inner.check_preempt_current();
+        true
+    }
+
+    /// Moves a ready task out of this run queue to a CPU allowed by its
+    /// affinity.
+    ///
+    /// This function does nothing if the task is not in this run queue, which
+    /// means it is running, blocked, or moved by other cores.
+    #[cfg(feature = "smp")]
+    pub fn migrate_task(&mut self, task: &AxTaskRef) {
+        let cpu_id = self.inner.cpu_id;
+        let removed = {
+            let mut scheduler = self.inner.scheduler.lock();
+            // A ready task is only in the run queue its `cpu_id` refers to,
+            // and it may be still switching out on this CPU if `on_cpu` is
+            // set, it will be migrated when it yields or is preempted then.
+            if !task.is_ready() || task.cpu_id() as usize != cpu_id || task.on_cpu() {
+                return;
+            }
+            let removed = scheduler.remove_task(task);
+            if removed.is_some() {
+                crate::balance::dec_ready(cpu_id);
+            }
+            removed
+        };
+        if let Some(task) = removed {
+            let target = crate::balance::select_cpu(&task);
+            debug!(
+                "task migrate: {} from run_queue {} to {}",
+                task.id_name(),
+                cpu_id,
+                target
+            );
+            get_run_queue(target)
+                .enqueue(task, |scheduler, task| scheduler.put_prev_task(task, false));
         }
     }
 }
@@ -287,6 +349,13 @@ impl<G: BaseGuard> CurrentRunQueueRef<'_, G> {
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
 
+        // The affinity may be changed by other tasks.
+        #[cfg(feature = "smp")]
+        if !curr.cpumask().get(self.inner.cpu_id) {
+            return self.migrate_current(migration_task(curr.clone()));
+        }
+
+        curr.sched_entity().yield_job();
         self.inner
             .put_task_with_state(curr.clone(), TaskState::Running, false);
 
@@ -342,6 +411,11 @@ impl<G: BaseGuard> CurrentRunQueueRef<'_, G> {
             can_preempt
         );
         if can_preempt {
+            // The affinity may be changed by other tasks.
+            #[cfg(feature = "smp")]
+            if !curr.cpumask().get(self.inner.cpu_id) {
+                return self.migrate_current(migration_task(curr.clone()));
+            }
             self.inner
                 .put_task_with_state(curr.clone(), TaskState::Running, true);
             self.inner.resched();
@@ -450,12 +524,74 @@ impl AxRunQueue {
         // gc task should be pinned to the current CPU.
         gc_task.set_cpumask(AxCpuMask::one_shot(cpu_id));
 
-        let mut scheduler = Scheduler::new();
-        scheduler.add_task(gc_task);
-        Self {
+        let rq = Self {
             cpu_id,
-            scheduler: SpinRaw::new(scheduler),
+            scheduler: SpinRaw::new(Scheduler::new()),
+        };
+        rq.enqueue(gc_task, |scheduler, task| scheduler.add_task(task));
+        rq
+    }
+
+    /// Inserts a ready task into the scheduler of this run queue by `f`, and
+    /// counts it in the load of this CPU.
+    fn enqueue(&self, task: AxTaskRef, f: impl FnOnce(&mut Scheduler, AxTaskRef)) {
+        #[cfg(feature = "smp")]
+        task.set_cpu_id(self.cpu_id as _);
+        let mut scheduler = self.scheduler.lock();
+        crate::balance::inc_ready(self.cpu_id);
+        f(&mut scheduler, task);
+    }
+
+    /// Asks the current task to be preempted if a ready task in this run
+    /// queue outranks it.
+    ///
+    /// Tasks on other CPUs are preempted at their next timer ticks instead.
+    #[cfg(feature = "preempt")]
+    fn check_preempt_current(&self) {
+        if self.cpu_id == this_cpu_id() {
+            let curr = crate::current();
+            if self.scheduler.lock().preempts(curr.as_task_ref()) {
+                curr.set_preempt_pending(true);
+            }
+        }
+    }
+
+    /// Picks the next task to run from the scheduler of this run queue, or
+    /// steals one from other CPUs if there is none.
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        let mut scheduler = self.scheduler.lock();
+        if let Some(task) = scheduler.pick_next_task() {
+            crate::balance::dec_ready(self.cpu_id);
+            return Some(task);
         }
+        drop(scheduler);
+        #[cfg(feature = "smp")]
+        return self.steal_task();
+        #[cfg(not(feature = "smp"))]
+        None
+    }
+
+    /// Takes a ready task allowed to run on this CPU out of the run queue of
+    /// the CPU selected by the load balancing policy.
+    #[cfg(feature = "smp")]
+    fn steal_task(&self) -> Option<AxTaskRef> {
+        let victim = crate::balance::select_victim(self.cpu_id)?;
+        if crate::balance::run_queue_len(victim) == 0 {
+            return None;
+        }
+        let task = get_run_queue(victim)
+            .scheduler
+            .lock()
+            .steal_task(self.cpu_id)?;
+        crate::balance::dec_ready(victim);
+        debug!(
+            "task steal: {} from run_queue {} to {}",
+            task.id_name(),
+            victim,
+            self.cpu_id
+        );
+        task.set_cpu_id(self.cpu_id as _);
+        Some(task)
     }
 
     /// Puts target task into current run queue with `Ready` state
@@ -492,11 +628,11 @@ impl AxRunQueue {
                     // Wait for the task to finish its scheduling process.
                     core::hint::spin_loop();
                 }
+                task.accounting().restart();
             }
-            // TODO: priority
-            #[cfg(feature = "smp")]
-            task.set_cpu_id(self.cpu_id as _);
-            self.scheduler.lock().put_prev_task(task, preempt);
+            self.enqueue(task, |scheduler, task| {
+                scheduler.put_prev_task(task, preempt)
+            });
             true
         } else {
             false
@@ -506,14 +642,10 @@ impl AxRunQueue {
     /// Core reschedule subroutine.
     /// Pick the next task to run and switch to it.
     fn resched(&mut self) {
-        let next = self
-            .scheduler
-            .lock()
-            .pick_next_task()
-            .unwrap_or_else(|| unsafe {
-                // Safety: IRQs must be disabled at this time.
-                IDLE_TASK.current_ref_raw().get_unchecked().clone()
-            });
+        let next = self.pick_next_task().unwrap_or_else(|| unsafe {
+            // Safety: IRQs must be disabled at this time.
+            IDLE_TASK.current_ref_raw().get_unchecked().clone()
+        });
         assert!(
             next.is_ready(),
             "next {} is not ready: {:?}",
@@ -538,9 +670,11 @@ impl AxRunQueue {
         #[cfg(feature = "preempt")]
         next_task.set_preempt_pending(false);
         next_task.set_state(TaskState::Running);
+        crate::balance::set_busy(self.cpu_id, !next_task.is_idle());
         if prev_task.ptr_eq(&next_task) {
             return;
         }
+        crate::stats::on_switch(self.cpu_id, &prev_task, &next_task);
 
         // Claim the task as running, we do this before switching to it
         // such that any running task will have this set.
@@ -599,6 +733,19 @@ fn gc_entry() {
     }
 }
 
+/// Creates a task to migrate the current task `curr` to a CPU allowed by its
+/// affinity, see [`CurrentRunQueueRef::migrate_current`].
+#[cfg(feature = "smp")]
+pub(crate) fn migration_task(curr: AxTaskRef) -> AxTaskRef {
+    const MIGRATION_TASK_STACK_SIZE: usize = 4096;
+    TaskInner::new(
+        move || migrate_entry(curr),
+        "migration-task".into(),
+        MIGRATION_TASK_STACK_SIZE,
+    )
+    .into_arc()
+}
+
 /// The task routine for migrating the current task to the correct CPU.
 ///
 /// It calls `select_run_queue` to get the correct run queue for the task, and
@@ -607,9 +754,9 @@ fn gc_entry() {
 pub(crate) fn migrate_entry(migrated_task: AxTaskRef) {
     select_run_queue::<kernel_guard::NoPreemptIrqSave>(&migrated_task)
         .inner
-        .scheduler
-        .lock()
-        .put_prev_task(migrated_task, false)
+        .enqueue(migrated_task, |scheduler, task| {
+            scheduler.put_prev_task(task, false)
+        })
 }
 
 /// Clear the `on_cpu` field of previous task running on this CPU.
@@ -631,6 +778,7 @@ pub(crate) fn init() {
     let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
     // idle task should be pinned to the current CPU.
     idle_task.set_cpumask(AxCpuMask::one_shot(cpu_id));
+    idle_task.accounting().restart();
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.into_arc());
     });
@@ -638,7 +786,9 @@ pub(crate) fn init() {
     // Put the subsequent execution into the `main` task.
     let main_task = TaskInner::new_init("main".into()).into_arc();
     main_task.set_state(TaskState::Running);
+    main_task.accounting().restart();
     unsafe { CurrentTask::init_current(main_task) }
+    crate::balance::set_busy(cpu_id, true);
 
     RUN_QUEUE.with_current(|rq| {
         rq.init_once(AxRunQueue::new(cpu_id));
@@ -646,6 +796,8 @@ pub(crate) fn init() {
     unsafe {
         RUN_QUEUES[cpu_id].write(RUN_QUEUE.current_ref_mut_raw());
     }
+    crate::stats::init_cpu(cpu_id);
+    crate::balance::set_online(cpu_id);
 }
 
 pub(crate) fn init_secondary() {
@@ -654,6 +806,7 @@ pub(crate) fn init_secondary() {
     // Put the subsequent execution into the `idle` task.
     let idle_task = TaskInner::new_init("idle".into()).into_arc();
     idle_task.set_state(TaskState::Running);
+    idle_task.accounting().restart();
     IDLE_TASK.with_current(|i| {
         i.init_once(idle_task.clone());
     });
@@ -665,4 +818,6 @@ pub(crate) fn init_secondary() {
     unsafe {
         RUN_QUEUES[cpu_id].write(RUN_QUEUE.current_ref_mut_raw());
     }
+    crate::stats::init_cpu(cpu_id);
+    crate::balance::set_online(cpu_id);
 }
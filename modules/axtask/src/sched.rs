@@ -0,0 +1,423 @@
+//! Scheduling classes of tasks.
+//!
+//! Each run queue schedules ready tasks in three classes, and a task is only
+//! picked when no task of a higher class is ready:
+//!
+//! 1. [`SchedPolicy::Deadline`]: the task with the earliest deadline first.
+//! 2. [`SchedPolicy::Fifo`] and [`SchedPolicy::RoundRobin`]: real-time tasks
+//!    with fixed priorities from [`RT_PRIO_MIN`] to [`RT_PRIO_MAX`], the
+//!    highest first. Tasks of the same priority run in FIFO order, or take
+//!    turns every [`RT_TIME_SLICE`] ticks for round-robin tasks.
+//! 3. [`SchedPolicy::Normal`]: the scheduler selected by cargo features.
+//!
+//! With the `preempt` feature, the current task is preempted as soon as a task
+//! outranking it becomes ready on its CPU, or at the next timer tick if that
+//! happens on another CPU. Otherwise, the task runs at the next yield.
+//!
+//! A task holding a lock that tasks of higher classes wait for inherits the
+//! highest class of them until it releases the lock, see
+//! [`WaitQueue::wait_until_lend`](crate::WaitQueue::wait_until_lend).
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use axsched::BaseScheduler;
+
+use crate::{AxTaskRef, NormalScheduler, WeakAxTaskRef};
+
+/// The lowest priority of real-time tasks.
+pub const RT_PRIO_MIN: u8 = 1;
+/// The highest priority of real-time tasks.
+pub const RT_PRIO_MAX: u8 = 99;
+/// The time slice of round-robin real-time tasks, in timer ticks.
+pub const RT_TIME_SLICE: usize = 5;
+
+/// The scheduling policy of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedPolicy {
+    /// Scheduled by the scheduler selected by cargo features. It is the
+    /// default policy.
+    Normal,
+    /// Real-time with the given priority, runs until it blocks, yields, or is
+    /// preempted by a task of a higher priority.
+    Fifo(u8),
+    /// Real-time with the given priority, like [`SchedPolicy::Fifo`] but
+    /// yields to tasks of the same priority when its time slice runs out.
+    RoundRobin(u8),
+    /// Earliest deadline first, each job of the task must be done in
+    /// `deadline` after it is released, and jobs are released every `period`.
+    ///
+    /// A job is released when the task is woken up or set to this policy with
+    /// the previous deadline passed, and the next job is released a `period`
+    /// after the current one when the task yields. The run time of jobs is not
+    /// enforced.
+    Deadline {
+        /// The relative deadline of each job.
+        deadline: Duration,
+        /// The interval between releases of jobs.
+        period: Duration,
+    },
+}
+
+impl SchedPolicy {
+    /// Returns whether the parameters of the policy are valid, that is, the
+    /// priority is in [`RT_PRIO_MIN`]..=[`RT_PRIO_MAX`], or the deadline is
+    /// not zero and does not exceed the period.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Self::Normal => true,
+            Self::Fifo(prio) | Self::RoundRobin(prio) => {
+                (RT_PRIO_MIN..=RT_PRIO_MAX).contains(&prio)
+            }
+            Self::Deadline { deadline, period } => !deadline.is_zero() && deadline <= period,
+        }
+    }
+}
+
+/// A scheduling class with the priority or the absolute deadline (in
+/// nanoseconds) of a task.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Class {
+    Normal,
+    RealTime(u8),
+    Deadline(u64),
+}
+
+impl Class {
+    /// Tasks of higher ranks run first, normal tasks have the same rank.
+    pub fn rank(self) -> (u8, u64) {
+        match self {
+            Self::Normal => (0, 0),
+            Self::RealTime(prio) => (1, prio as u64),
+            Self::Deadline(deadline) => (2, u64::MAX - deadline),
+        }
+    }
+}
+
+/// The scheduling states of a task.
+pub(crate) struct SchedEntity {
+    policy: SchedPolicy,
+    /// Where the task is queued. It is only set when the task is in the
+    /// scheduler of the run queue its `cpu_id` refers to.
+    queued: Option<Class>,
+    /// Remaining ticks of round-robin real-time tasks.
+    time_slice: usize,
+    /// The absolute deadline of the current job in nanoseconds.
+    deadline: u64,
+    /// Classes inherited from tasks waiting for the locks held by this task,
+    /// by the addresses of the locks.
+    inherited: Vec<(usize, Class)>,
+    /// The address of the lock the task waits for and its owner, to lend
+    /// classes along chains of owners.
+    waiting_for: Option<(usize, WeakAxTaskRef)>,
+}
+
+impl SchedEntity {
+    pub const fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            queued: None,
+            time_slice: 0,
+            deadline: 0,
+            inherited: Vec::new(),
+            waiting_for: None,
+        }
+    }
+
+    pub fn policy(&self) -> SchedPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: SchedPolicy) {
+        self.policy = policy;
+        self.time_slice = RT_TIME_SLICE;
+        if let SchedPolicy::Deadline { deadline, .. } = policy {
+            self.deadline = now_nanos().saturating_add(deadline.as_nanos() as u64);
+        }
+    }
+
+    /// Returns the class the task is scheduled by, which is the highest of
+    /// its own and the inherited ones, and whether it is its own.
+    pub fn class(&self) -> (Class, bool) {
+        let own = match self.policy {
+            SchedPolicy::Normal => Class::Normal,
+            SchedPolicy::Fifo(prio) | SchedPolicy::RoundRobin(prio) => Class::RealTime(prio),
+            SchedPolicy::Deadline { .. } => Class::Deadline(self.deadline),
+        };
+        let inherited = self.inherited.iter().map(|&(_, class)| class);
+        match inherited.max_by_key(|class| class.rank()) {
+            Some(class) if class.rank() > own.rank() => (class, false),
+            _ => (own, true),
+        }
+    }
+
+    /// Returns whether the task inherits a class through the lock `key`.
+    pub fn inherits(&self, key: usize) -> bool {
+        self.inherited.iter().any(|&(k, _)| k == key)
+    }
+
+    /// Inherits `class` through the lock `key`, or stops inheriting through
+    /// it if `class` is `None`.
+    pub fn set_inherited(&mut self, key: usize, class: Option<Class>) {
+        self.inherited.retain(|&(k, _)| k != key);
+        if let Some(class @ (Class::RealTime(_) | Class::Deadline(_))) = class {
+            self.inherited.push((key, class));
+        }
+    }
+
+    /// Inherits `class` through the lock `key` unless a higher class is
+    /// inherited through it already.
+    pub fn raise_inherited(&mut self, key: usize, class: Class) {
+        match self.inherited.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, inherited)) if class.rank() > inherited.rank() => *inherited = class,
+            Some(_) => {}
+            None => self.set_inherited(key, Some(class)),
+        }
+    }
+
+    /// Returns the lock the task waits for and its owner.
+    pub fn waiting_for(&self) -> Option<(usize, WeakAxTaskRef)> {
+        self.waiting_for.clone()
+    }
+
+    /// Returns whether the task waits for the lock `key` owned by `owner`.
+    pub fn waits_for(&self, key: usize, owner: &WeakAxTaskRef) -> bool {
+        self.waiting_for
+            .as_ref()
+            .is_some_and(|(k, o)| *k == key && Weak::ptr_eq(o, owner))
+    }
+
+    /// Records the lock the task waits for and its owner, or `None` if it
+    /// does not wait for a lock.
+    pub fn set_waiting_for(&mut self, waiting_for: Option<(usize, WeakAxTaskRef)>) {
+        self.waiting_for = waiting_for;
+    }
+
+    /// Releases the next job of a deadline task that yields.
+    pub fn yield_job(&mut self) {
+        if let SchedPolicy::Deadline { period, .. } = self.policy {
+            self.deadline = self.deadline.saturating_add(period.as_nanos() as u64);
+        }
+    }
+}
+
+fn now_nanos() -> u64 {
+    axhal::time::monotonic_time_nanos()
+}
+
+/// The scheduler of a run queue, which schedules tasks by classes, see the
+/// [module-level documentation](self).
+pub(crate) struct Scheduler {
+    normal: NormalScheduler,
+    /// Ready real-time tasks by priorities.
+    rt_queues: [VecDeque<AxTaskRef>; RT_PRIO_MAX as usize + 1],
+    /// Bit `i` is set if `rt_queues[i]` is not empty.
+    rt_bitmap: u128,
+    /// Ready deadline tasks by deadlines and IDs.
+    dl_queue: BTreeMap<(u64, u64), AxTaskRef>,
+    /// Ready normal tasks by IDs, as the normal scheduler cannot be searched
+    /// for tasks to steal.
+    #[cfg(feature = "smp")]
+    normal_tasks: BTreeMap<u64, AxTaskRef>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            normal: NormalScheduler::new(),
+            rt_queues: [const { VecDeque::new() }; RT_PRIO_MAX as usize + 1],
+            rt_bitmap: 0,
+            dl_queue: BTreeMap::new(),
+            #[cfg(feature = "smp")]
+            normal_tasks: BTreeMap::new(),
+        }
+    }
+
+    pub fn scheduler_name() -> &'static str {
+        NormalScheduler::scheduler_name()
+    }
+
+    /// Updates the scheduling states of `task` by `f`, and requeues it if it
+    /// is in this scheduler.
+    ///
+    /// The caller must ensure that the `cpu_id` of `task` refers to this run
+    /// queue.
+    pub fn update(&mut self, task: &AxTaskRef, f: impl FnOnce(&mut SchedEntity)) {
+        let removed = self.remove_task(task);
+        f(&mut task.sched_entity());
+        if let Some(task) = removed {
+            self.add_task(task);
+        }
+    }
+
+    /// Returns whether a ready task outranks the `current` task.
+    pub fn preempts(&self, current: &AxTaskRef) -> bool {
+        let top = if let Some(&(deadline, _)) = self.dl_queue.keys().next() {
+            Class::Deadline(deadline)
+        } else if self.rt_bitmap != 0 {
+            Class::RealTime(127 - self.rt_bitmap.leading_zeros() as u8)
+        } else {
+            return false;
+        };
+        top.rank() > current.sched_entity().class().0.rank()
+    }
+
+    /// Inserts a ready task into the queue of its class, or returns it back if
+    /// it is a normal task.
+    ///
+    /// If `preempt`, a real-time task is put at the front of its priority
+    /// unless its time slice runs out.
+    fn insert(&mut self, task: AxTaskRef, preempt: bool) -> Option<AxTaskRef> {
+        let (queued, front) = {
+            let mut entity = task.sched_entity();
+            if let SchedPolicy::Deadline { deadline, .. } = entity.policy {
+                let now = now_nanos();
+                if entity.deadline <= now {
+                    entity.deadline = now.saturating_add(deadline.as_nanos() as u64);
+                }
+            }
+            let queued = entity.class().0;
+            let front = preempt && entity.time_slice > 0;
+            if !front {
+                entity.time_slice = RT_TIME_SLICE;
+            }
+            entity.queued = Some(queued);
+            (queued, front)
+        };
+        match queued {
+            Class::Normal => return Some(task),
+            Class::RealTime(prio) => {
+                let queue = &mut self.rt_queues[prio as usize];
+                if front {
+                    queue.push_front(task);
+                } else {
+                    queue.push_back(task);
+                }
+                self.rt_bitmap |= 1 << prio;
+            }
+            Class::Deadline(deadline) => {
+                self.dl_queue.insert((deadline, task.id().as_u64()), task);
+            }
+        }
+        None
+    }
+
+    /// Removes a ready task allowed to run on the CPU `cpu_id` for it to
+    /// steal, trying higher classes first and the latest created normal tasks
+    /// first.
+    ///
+    /// Tasks still switching out on the CPU of this run queue are skipped.
+    #[cfg(feature = "smp")]
+    pub fn steal_task(&mut self, cpu_id: usize) -> Option<AxTaskRef> {
+        let movable = |task: &&AxTaskRef| !task.on_cpu() && task.cpumask().get(cpu_id);
+        let task = self
+            .dl_queue
+            .values()
+            .find(movable)
+            .or_else(|| {
+                self.rt_queues
+                    .iter()
+                    .rev()
+                    .flat_map(|queue| queue.iter().rev())
+                    .find(movable)
+            })
+            .or_else(|| self.normal_tasks.values().rev().find(movable))?
+            .clone();
+        self.remove_task(&task)
+    }
+
+    /// Inserts a ready normal task into the normal scheduler by `f`.
+    fn insert_normal(&mut self, task: AxTaskRef, f: impl FnOnce(&mut NormalScheduler, AxTaskRef)) {
+        #[cfg(feature = "smp")]
+        self.normal_tasks.insert(task.id().as_u64(), task.clone());
+        f(&mut self.normal, task);
+    }
+
+    fn pick_rt_task(&mut self) -> Option<AxTaskRef> {
+        if self.rt_bitmap == 0 {
+            return None;
+        }
+        let prio = 127 - self.rt_bitmap.leading_zeros() as usize;
+        let queue = &mut self.rt_queues[prio];
+        let task = queue.pop_front();
+        if queue.is_empty() {
+            self.rt_bitmap &= !(1 << prio);
+        }
+        task
+    }
+}
+
+impl BaseScheduler for Scheduler {
+    type SchedItem = AxTaskRef;
+
+    fn init(&mut self) {
+        self.normal.init();
+    }
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        if let Some(task) = self.insert(task, false) {
+            self.insert_normal(task, |normal, task| normal.add_task(task));
+        }
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        let queued = task.sched_entity().queued.take()?;
+        match queued {
+            Class::Normal => {
+                #[cfg(feature = "smp")]
+                self.normal_tasks.remove(&task.id().as_u64());
+                self.normal.remove_task(task)
+            }
+            Class::RealTime(prio) => {
+                let queue = &mut self.rt_queues[prio as usize];
+                let task = queue.remove(queue.iter().position(|t| Arc::ptr_eq(t, task))?);
+                if queue.is_empty() {
+                    self.rt_bitmap &= !(1 << prio);
+                }
+                task
+            }
+            Class::Deadline(deadline) => self.dl_queue.remove(&(deadline, task.id().as_u64())),
+        }
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        let task = match self.dl_queue.pop_first() {
+            Some((_, task)) => Some(task),
+            None => self.pick_rt_task(),
+        }
+        .or_else(|| self.normal.pick_next_task())?;
+        if let Some(Class::Normal) = task.sched_entity().queued.take() {
+            #[cfg(feature = "smp")]
+            self.normal_tasks.remove(&task.id().as_u64());
+        }
+        Some(task)
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        if let Some(prev) = self.insert(prev, preempt) {
+            self.insert_normal(prev, |normal, prev| normal.put_prev_task(prev, preempt));
+        }
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let expired = {
+            let mut entity = current.sched_entity();
+            match (entity.class(), entity.policy) {
+                ((Class::Normal, _), _) => None,
+                ((Class::RealTime(_), true), SchedPolicy::RoundRobin(_)) => {
+                    entity.time_slice = entity.time_slice.saturating_sub(1);
+                    Some(entity.time_slice == 0)
+                }
+                _ => Some(false),
+            }
+        };
+        let expired = expired.unwrap_or_else(|| self.normal.task_tick(current));
+        expired || self.preempts(current)
+    }
+
+    fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool {
+        self.normal.set_priority(task, prio)
+    }
+}
@@ -0,0 +1,271 @@
+//! CPU time accounting and scheduling statistics of tasks and CPUs.
+//!
+//! The time of a task is accounted when it is switched in or out, and when a
+//! [`SystemTimeGuard`] is created or dropped. The time spent in interrupt
+//! handlers is counted to the interrupted task.
+
+use core::marker::PhantomData;
+use core::ops::Add;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axconfig::plat::CPU_NUM;
+use kspin::SpinNoIrq;
+
+use crate::{TaskInner, TaskState};
+
+/// CPU time and scheduling statistics of a task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskStats {
+    /// Time running outside [`SystemTimeGuard`]s.
+    pub user_time: Duration,
+    /// Time running inside [`SystemTimeGuard`]s, such as in POSIX system
+    /// calls.
+    pub system_time: Duration,
+    /// Time waiting in run queues to run.
+    pub wait_time: Duration,
+    /// Number of times switched out because it blocked or exited.
+    pub voluntary_switches: u64,
+    /// Number of times switched out when it could still run, because it
+    /// yielded or was preempted.
+    pub involuntary_switches: u64,
+}
+
+impl Add for TaskStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            user_time: self.user_time + other.user_time,
+            system_time: self.system_time + other.system_time,
+            wait_time: self.wait_time + other.wait_time,
+            voluntary_switches: self.voluntary_switches + other.voluntary_switches,
+            involuntary_switches: self.involuntary_switches + other.involuntary_switches,
+        }
+    }
+}
+
+/// CPU time and scheduling statistics of a CPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuStats {
+    /// Time running tasks other than the idle task.
+    pub busy_time: Duration,
+    /// Time running the idle task.
+    pub idle_time: Duration,
+    /// Number of context switches.
+    pub context_switches: u64,
+}
+
+/// The accounting states of a task, in nanoseconds.
+pub(crate) struct TaskAccounting {
+    user_ns: AtomicU64,
+    system_ns: AtomicU64,
+    wait_ns: AtomicU64,
+    nvcsw: AtomicU64,
+    nivcsw: AtomicU64,
+    /// When the time of the running task is accounted last, or when the task
+    /// becomes ready.
+    since_ns: AtomicU64,
+    /// Number of [`SystemTimeGuard`]s alive.
+    system_depth: AtomicUsize,
+}
+
+impl TaskAccounting {
+    pub const fn new() -> Self {
+        Self {
+            user_ns: AtomicU64::new(0),
+            system_ns: AtomicU64::new(0),
+            wait_ns: AtomicU64::new(0),
+            nvcsw: AtomicU64::new(0),
+            nivcsw: AtomicU64::new(0),
+            since_ns: AtomicU64::new(0),
+            system_depth: AtomicUsize::new(0),
+        }
+    }
+
+    fn run_time(&self) -> &AtomicU64 {
+        if self.system_depth.load(Ordering::Relaxed) > 0 {
+            &self.system_ns
+        } else {
+            &self.user_ns
+        }
+    }
+
+    /// Accounts the time since the last time of the running task.
+    fn charge(&self, now: u64) {
+        let elapsed = now.saturating_sub(self.since_ns.swap(now, Ordering::AcqRel));
+        self.run_time().fetch_add(elapsed, Ordering::Relaxed);
+    }
+
+    /// Starts accounting from now, when the task is created, woken up, or
+    /// becomes the current task of a CPU during initialization.
+    pub fn restart(&self) {
+        self.since_ns.store(now_nanos(), Ordering::Release);
+    }
+
+    /// Returns the statistics, including the time since the last accounting
+    /// if the task is running or ready in `state`.
+    pub fn stats(&self, state: TaskState) -> TaskStats {
+        let pending = now_nanos().saturating_sub(self.since_ns.load(Ordering::Acquire));
+        let mut stats = TaskStats {
+            user_time: Duration::from_nanos(self.user_ns.load(Ordering::Relaxed)),
+            system_time: Duration::from_nanos(self.system_ns.load(Ordering::Relaxed)),
+            wait_time: Duration::from_nanos(self.wait_ns.load(Ordering::Relaxed)),
+            voluntary_switches: self.nvcsw.load(Ordering::Relaxed),
+            involuntary_switches: self.nivcsw.load(Ordering::Relaxed),
+        };
+        let pending = Duration::from_nanos(pending);
+        match state {
+            TaskState::Running if self.system_depth.load(Ordering::Relaxed) > 0 => {
+                stats.system_time += pending
+            }
+            TaskState::Running => stats.user_time += pending,
+            TaskState::Ready => stats.wait_time += pending,
+            _ => {}
+        }
+        stats
+    }
+}
+
+struct CpuAccounting {
+    busy_ns: AtomicU64,
+    idle_ns: AtomicU64,
+    switches: AtomicU64,
+    since_ns: AtomicU64,
+}
+
+impl CpuAccounting {
+    const fn new() -> Self {
+        Self {
+            busy_ns: AtomicU64::new(0),
+            idle_ns: AtomicU64::new(0),
+            switches: AtomicU64::new(0),
+            since_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+static CPU_STATS: [CpuAccounting; CPU_NUM] = [const { CpuAccounting::new() }; CPU_NUM];
+
+/// Statistics of dropped tasks, except idle tasks.
+static DROPPED_STATS: SpinNoIrq<TaskStats> = SpinNoIrq::new(TaskStats {
+    user_time: Duration::ZERO,
+    system_time: Duration::ZERO,
+    wait_time: Duration::ZERO,
+    voluntary_switches: 0,
+    involuntary_switches: 0,
+});
+
+fn now_nanos() -> u64 {
+    axhal::time::monotonic_time_nanos()
+}
+
+/// Starts accounting the time of the CPU `cpu_id`.
+pub(crate) fn init_cpu(cpu_id: usize) {
+    CPU_STATS[cpu_id]
+        .since_ns
+        .store(now_nanos(), Ordering::Release);
+}
+
+/// Accounts a context switch on the CPU `cpu_id` from `prev` to `next`.
+pub(crate) fn on_switch(cpu_id: usize, prev: &TaskInner, next: &TaskInner) {
+    let now = now_nanos();
+
+    let cpu = &CPU_STATS[cpu_id];
+    let elapsed = now.saturating_sub(cpu.since_ns.swap(now, Ordering::AcqRel));
+    if prev.is_idle() {
+        cpu.idle_ns.fetch_add(elapsed, Ordering::Relaxed);
+    } else {
+        cpu.busy_ns.fetch_add(elapsed, Ordering::Relaxed);
+    }
+    cpu.switches.fetch_add(1, Ordering::Relaxed);
+
+    // A task that is still ready is switched out by yielding or preemption,
+    // and waits in the run queue since now.
+    let prev_ready = prev.state() == TaskState::Ready;
+    let prev = prev.accounting();
+    prev.charge(now);
+    if prev_ready {
+        prev.nivcsw.fetch_add(1, Ordering::Relaxed);
+    } else {
+        prev.nvcsw.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let next = next.accounting();
+    let waited = now.saturating_sub(next.since_ns.swap(now, Ordering::AcqRel));
+    next.wait_ns.fetch_add(waited, Ordering::Relaxed);
+}
+
+/// Adds the statistics of a dropped task to the total.
+pub(crate) fn on_drop(task: &TaskInner) {
+    if !task.is_idle() {
+        let stats = task.stats();
+        let mut dropped = DROPPED_STATS.lock();
+        *dropped = *dropped + stats;
+    }
+}
+
+/// Returns the statistics of the CPU `cpu_id`.
+///
+/// # Panics
+///
+/// Panics if `cpu_id` is not less than [`axconfig::plat::CPU_NUM`].
+pub fn cpu_stats(cpu_id: usize) -> CpuStats {
+    let cpu = &CPU_STATS[cpu_id];
+    CpuStats {
+        busy_time: Duration::from_nanos(cpu.busy_ns.load(Ordering::Relaxed)),
+        idle_time: Duration::from_nanos(cpu.idle_ns.load(Ordering::Relaxed)),
+        context_switches: cpu.switches.load(Ordering::Relaxed),
+    }
+}
+
+/// Returns the sum of the statistics of all tasks except idle tasks,
+/// including the dropped ones.
+pub fn all_tasks_stats() -> TaskStats {
+    crate::task::all_tasks()
+        .iter()
+        .filter(|task| !task.is_idle())
+        .fold(*DROPPED_STATS.lock(), |total, task| total + task.stats())
+}
+
+/// A guard that counts the time of the current task as its system time until
+/// it is dropped.
+///
+/// Guards can be nested, and the current task may block while holding them.
+pub struct SystemTimeGuard {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl SystemTimeGuard {
+    /// Starts counting the time of the current task as its system time.
+    pub fn new() -> Self {
+        if let Some(curr) = crate::current_may_uninit() {
+            let accounting = curr.accounting();
+            accounting.charge(now_nanos());
+            accounting.system_depth.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Default for SystemTimeGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SystemTimeGuard {
+    fn drop(&mut self) {
+        if let Some(curr) = crate::current_may_uninit() {
+            let accounting = curr.accounting();
+            accounting.charge(now_nanos());
+            let _ = accounting.system_depth.fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |depth| depth.checked_sub(1),
+            );
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use core::ops::Deref;
 use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU32, AtomicU64, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
@@ -6,13 +8,15 @@ use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 #[cfg(feature = "preempt")]
 use core::sync::atomic::AtomicUsize;
 
-use kspin::SpinNoIrq;
+use kspin::{SpinNoIrq, SpinNoIrqGuard};
 use memory_addr::{VirtAddr, align_up_4k};
 
 use axhal::context::TaskContext;
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
 
+use crate::sched::{SchedEntity, SchedPolicy};
+use crate::stats::{TaskAccounting, TaskStats};
 use crate::task_ext::AxTaskExt;
 use crate::{AxCpuMask, AxTask, AxTaskRef, WaitQueue};
 
@@ -23,7 +27,7 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
     /// Task is running on some CPU.
     Running = 1,
     /// Task is ready to run on some scheduler's ready queue.
@@ -35,6 +39,23 @@ pub(crate) enum TaskState {
     Exited = 4,
 }
 
+/// All tasks that have not been dropped, by their IDs.
+static TASK_TABLE: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Returns all tasks that have not been dropped, ordered by their IDs.
+pub(crate) fn all_tasks() -> Vec<AxTaskRef> {
+    TASK_TABLE
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+/// Returns the task with the given ID if it has not been dropped.
+pub(crate) fn get_task(id: u64) -> Option<AxTaskRef> {
+    TASK_TABLE.lock().get(&id)?.upgrade()
+}
+
 /// The inner task structure.
 pub struct TaskInner {
     id: TaskId,
@@ -47,6 +68,10 @@ pub struct TaskInner {
 
     /// CPU affinity mask.
     cpumask: SpinNoIrq<AxCpuMask>,
+    /// Scheduling policy and states.
+    sched: SpinNoIrq<SchedEntity>,
+    /// CPU time accounting and scheduling statistics.
+    accounting: TaskAccounting,
 
     /// Mark whether the task is in the wait queue.
     in_wait_queue: AtomicBool,
@@ -219,6 +244,18 @@ impl TaskInner {
     pub fn set_cpumask(&self, cpumask: AxCpuMask) {
         *self.cpumask.lock() = cpumask
     }
+
+    /// Gets the scheduling policy of the task.
+    #[inline]
+    pub fn sched_policy(&self) -> SchedPolicy {
+        self.sched.lock().policy()
+    }
+
+    /// Gets the CPU time and scheduling statistics of the task.
+    #[inline]
+    pub fn stats(&self) -> TaskStats {
+        self.accounting.stats(self.state())
+    }
 }
 
 // private methods
@@ -233,6 +270,8 @@ impl TaskInner {
             state: AtomicU8::new(TaskState::Ready as u8),
             // By default, the task is allowed to run on all CPUs.
             cpumask: SpinNoIrq::new(AxCpuMask::full()),
+            sched: SpinNoIrq::new(SchedEntity::new()),
+            accounting: TaskAccounting::new(),
             in_wait_queue: AtomicBool::new(false),
             #[cfg(feature = "irq")]
             timer_ticket_id: AtomicU64::new(0),
@@ -272,12 +311,21 @@ impl TaskInner {
         t
     }
 
+    #[inline]
+    pub(crate) fn sched_entity(&self) -> SpinNoIrqGuard<'_, SchedEntity> {
+        self.sched.lock()
+    }
+
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let id = self.id.as_u64();
+        let task = Arc::new(AxTask::new(self));
+        TASK_TABLE.lock().insert(id, Arc::downgrade(&task));
+        task
     }
 
+    /// Gets the scheduling state of the task.
     #[inline]
-    pub(crate) fn state(&self) -> TaskState {
+    pub fn state(&self) -> TaskState {
         self.state.load(Ordering::Acquire).into()
     }
 
@@ -321,6 +369,11 @@ impl TaskInner {
         self.is_idle
     }
 
+    #[inline]
+    pub(crate) fn accounting(&self) -> &TaskAccounting {
+        &self.accounting
+    }
+
     #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
         self.in_wait_queue.load(Ordering::Acquire)
@@ -449,6 +502,8 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        crate::stats::on_drop(self);
+        TASK_TABLE.lock().remove(&self.id.as_u64());
     }
 }
 
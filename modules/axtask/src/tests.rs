@@ -128,3 +128,140 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_cpu_load() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_TASKS: usize = 5;
+    static WQ: WaitQueue = WaitQueue::new();
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    axtask::yield_now(); // let other ready tasks run first
+    let base = axtask::run_queue_len(0);
+    let tasks = (0..NUM_TASKS)
+        .map(|_| {
+            axtask::spawn(|| {
+                STARTED.fetch_add(1, Ordering::Release);
+                WQ.wait();
+            })
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(axtask::run_queue_len(0), base + NUM_TASKS);
+    // the current task is running
+    assert_eq!(axtask::cpu_load(0), base + NUM_TASKS + 1);
+
+    while STARTED.load(Ordering::Acquire) < NUM_TASKS {
+        axtask::yield_now();
+    }
+    // all tasks are blocked
+    assert_eq!(axtask::run_queue_len(0), base);
+
+    WQ.notify_all(true);
+    for task in tasks {
+        task.join();
+    }
+}
+
+#[test]
+fn test_task_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    let task = axtask::spawn(|| {
+        STARTED.fetch_add(1, Ordering::Release);
+        WQ.wait();
+    });
+    let found = axtask::get_task(task.id().as_u64()).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&found, &task));
+
+    // ready
+    let cpumask = axtask::AxCpuMask::one_shot(0);
+    assert!(axtask::set_task_affinity(&task, cpumask));
+    assert!(task.cpumask() == cpumask);
+    assert!(!axtask::set_task_affinity(&task, axtask::AxCpuMask::new()));
+
+    // blocked
+    while STARTED.load(Ordering::Acquire) == 0 {
+        axtask::yield_now();
+    }
+    assert!(axtask::set_task_affinity(&task, axtask::AxCpuMask::full()));
+    assert!(task.cpumask() == axtask::AxCpuMask::full());
+
+    WQ.notify_all(true);
+    task.join();
+}
+
+#[test]
+fn test_sched_policy() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    use axtask::SchedPolicy;
+    static ORDER: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    let policies = [
+        SchedPolicy::Normal,
+        SchedPolicy::Fifo(10),
+        SchedPolicy::RoundRobin(20),
+        SchedPolicy::Fifo(20),
+    ];
+    // Set all policies before any task runs, even if the main task would be
+    // preempted.
+    let guard = kernel_guard::NoPreempt::new();
+    let tasks: Vec<_> = policies
+        .iter()
+        .enumerate()
+        .map(|(i, &policy)| {
+            let task = axtask::spawn(move || ORDER.lock().unwrap().push(i));
+            assert!(axtask::set_task_policy(&task, policy));
+            assert_eq!(task.sched_policy(), policy);
+            task
+        })
+        .collect();
+    assert!(!axtask::set_task_policy(&tasks[0], SchedPolicy::Fifo(0)));
+    assert_eq!(tasks[0].sched_policy(), SchedPolicy::Normal);
+    drop(guard);
+
+    for task in tasks {
+        task.join();
+    }
+    // Higher priorities first, then the order of setting.
+    assert_eq!(*ORDER.lock().unwrap(), [2, 3, 1, 0]);
+}
+
+#[test]
+fn test_task_stats() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+    static BLOCKED: AtomicUsize = AtomicUsize::new(0);
+    const NUM_YIELDS: u64 = 5;
+
+    let switches = axtask::cpu_stats(0).context_switches;
+    let task = axtask::spawn(|| {
+        for _ in 0..NUM_YIELDS {
+            axtask::yield_now();
+        }
+        BLOCKED.store(1, Ordering::Release);
+        WQ.wait_until(|| BLOCKED.load(Ordering::Acquire) == 2);
+    });
+    while BLOCKED.load(Ordering::Acquire) != 1 {
+        axtask::yield_now();
+    }
+    BLOCKED.store(2, Ordering::Release);
+    WQ.notify_one(true);
+    let stats = task.stats();
+    task.join();
+
+    assert!(stats.involuntary_switches >= NUM_YIELDS);
+    assert!(stats.voluntary_switches >= 1);
+    assert!(axtask::cpu_stats(0).context_switches >= switches + 2 * NUM_YIELDS);
+    let total = axtask::all_tasks_stats();
+    assert!(total.involuntary_switches >= stats.involuntary_switches);
+}
@@ -5,7 +5,8 @@ use alloc::vec::Vec;
 use kernel_guard::{NoOp, NoPreemptIrqSave};
 use kspin::{SpinNoIrq, SpinNoIrqGuard};
 
-use crate::{AxTaskRef, CurrentTask, current_run_queue, select_run_queue};
+use crate::sched::Class;
+use crate::{AxTaskRef, CurrentTask, current_run_queue, select_run_queue, update_task_sched};
 
 /// A queue to store sleeping tasks.
 ///
@@ -50,6 +51,11 @@ impl WaitQueue {
         }
     }
 
+    /// The key of this queue for the classes lent through it.
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
     /// Cancel events by removing the task from the wait queue.
     /// If `from_timer_list` is true, try to remove the task from the timer list.
     fn cancel_events(&self, curr: CurrentTask, _from_timer_list: bool) {
@@ -103,6 +109,43 @@ impl WaitQueue {
         self.cancel_events(curr, false);
     }
 
+    /// Blocks the current task and put it into the wait queue, until the given
+    /// `condition` becomes true, lending the highest scheduling class of the
+    /// waiting tasks to the task returned by `owner` meanwhile.
+    ///
+    /// It implements priority inheritance for locks, where `owner` returns the
+    /// task holding the lock, and must not change while the queue is locked.
+    /// The owner runs in the class of the most urgent waiter until it wakes one
+    /// by [`WaitQueue::notify_highest`]. If the owner waits for another lock
+    /// in this way, the class is lent further to the owner of that lock, up to
+    /// [`MAX_LEND_DEPTH`] owners.
+    pub fn wait_until_lend<F, O>(&self, condition: F, owner: O)
+    where
+        F: Fn() -> bool,
+        O: Fn() -> Option<AxTaskRef>,
+    {
+        let key = self.key();
+        let curr = crate::current();
+        loop {
+            let mut rq = current_run_queue::<NoPreemptIrqSave>();
+            let wq = self.queue.lock();
+            if condition() {
+                curr.sched_entity().set_waiting_for(None);
+                break;
+            }
+            if let Some(owner) = owner() {
+                curr.sched_entity()
+                    .set_waiting_for(Some((key, Arc::downgrade(&owner))));
+                let class = highest_class(wq.iter().chain([curr.as_task_ref()]));
+                update_task_sched(&owner, |entity| entity.set_inherited(key, class));
+                lend_along_chain(owner);
+            }
+            rq.blocked_resched(wq);
+            // Preemption may occur here.
+        }
+        self.cancel_events(curr, false);
+    }
+
     /// Blocks the current task and put it into the wait queue, until other tasks
     /// notify it, or the given duration has elapsed.
     #[cfg(feature = "irq")]
@@ -179,6 +222,62 @@ impl WaitQueue {
         }
     }
 
+    /// Wakes up the task of the highest scheduling class in the wait queue, the
+    /// first one among equals, after calling `f` with it (or [`None`] if the
+    /// queue is empty) with the queue locked.
+    ///
+    /// The class lent to the current task through this queue by
+    /// [`WaitQueue::wait_until_lend`] is revoked, and the highest class of the
+    /// remaining tasks is lent to the woken task instead, which is handed the
+    /// lock.
+    ///
+    /// If `resched` is true, the current task will be preempted when the
+    /// preemption is enabled.
+    pub fn notify_highest<F>(&self, resched: bool, f: F) -> bool
+    where
+        F: FnOnce(Option<&AxTaskRef>),
+    {
+        let key = self.key();
+        let mut wq = self.queue.lock();
+        let mut highest: Option<(usize, (u8, u64))> = None;
+        for (i, task) in wq.iter().enumerate() {
+            let rank = task.sched_entity().class().0.rank();
+            if highest.is_none_or(|(_, top)| rank > top) {
+                highest = Some((i, rank));
+            }
+        }
+        let task = highest.and_then(|(i, _)| wq.remove(i));
+        // The woken task owns the lock now, which the remaining tasks wait
+        // for. Update them before revoking the class of the current task, so
+        // that classes lent along chains in the meantime are revoked too, see
+        // `lend_along_chain()`.
+        if let Some(task) = &task {
+            task.sched_entity().set_waiting_for(None);
+            let owner = Arc::downgrade(task);
+            for waiter in wq.iter() {
+                let mut entity = waiter.sched_entity();
+                if entity.waiting_for().is_some_and(|(k, _)| k == key) {
+                    entity.set_waiting_for(Some((key, owner.clone())));
+                }
+            }
+        }
+        let curr = crate::current();
+        if curr.sched_entity().inherits(key) {
+            update_task_sched(curr.as_task_ref(), |entity| entity.set_inherited(key, None));
+        }
+        let Some(task) = task else {
+            f(None);
+            return false;
+        };
+        f(Some(&task));
+        if !wq.is_empty() {
+            let class = highest_class(wq.iter());
+            update_task_sched(&task, |entity| entity.set_inherited(key, class));
+        }
+        unblock_one_task(task, resched);
+        true
+    }
+
     /// Wakes all tasks in the wait queue.
     ///
     /// If `resched` is true, the current task will be preempted when the
@@ -237,6 +336,54 @@ impl WaitQueue {
     }
 }
 
+/// Returns the highest scheduling class of `tasks`.
+fn highest_class<'a>(tasks: impl Iterator<Item = &'a AxTaskRef>) -> Option<Class> {
+    tasks
+        .map(|task| task.sched_entity().class().0)
+        .max_by_key(|class| class.rank())
+}
+
+/// The maximum number of owners a class is lent to along a chain of locks,
+/// which also stops lending around deadlocked chains.
+pub const MAX_LEND_DEPTH: usize = 8;
+
+/// Lends the class of `task`, the owner of a lock that inherits the classes of
+/// the waiting tasks, further to the owners of the locks it waits for.
+///
+/// Only the queue of the first lock is locked, so owners further along may
+/// release their locks meanwhile. If the lock is handed over, which
+/// `WaitQueue::notify_highest()` records before revoking the classes lent to
+/// the previous owner, the class lent to it is recomputed from the tasks that
+/// still wait for it. They are found in the task table, as their queue may be
+/// gone.
+fn lend_along_chain(mut task: AxTaskRef) {
+    for _ in 1..MAX_LEND_DEPTH {
+        let (class, waiting_for) = {
+            let entity = task.sched_entity();
+            (entity.class().0, entity.waiting_for())
+        };
+        let Some((key, owner)) = waiting_for else {
+            return;
+        };
+        let Some(owner_task) = owner.upgrade() else {
+            return;
+        };
+        update_task_sched(&owner_task, |entity| entity.raise_inherited(key, class));
+        if !task.sched_entity().waits_for(key, &owner) {
+            // keep only the classes of the tasks still waiting for the owner,
+            // as when the lock is handed over
+            let tasks = crate::task::all_tasks();
+            let waiters = tasks
+                .iter()
+                .filter(|waiter| waiter.sched_entity().waits_for(key, &owner));
+            let class = highest_class(waiters);
+            update_task_sched(&owner_task, |entity| entity.set_inherited(key, class));
+            return;
+        }
+        task = owner_task;
+    }
+}
+
 fn unblock_one_task(task: AxTaskRef, resched: bool) {
     // Mark task as not in wait queue.
     task.set_in_wait_queue(false);
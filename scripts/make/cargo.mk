@@ -51,5 +51,9 @@ endef
 define unit_test
   $(call run_cmd,cargo test,-p axfs $(1) $(verbose) -- --nocapture)
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "ext4" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "tarfs" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "overlay" $(verbose) -- --nocapture)
+  $(call run_cmd,cargo test,-p axfs $(1) --features "initramfs" $(verbose) -- --nocapture)
   $(call run_cmd,cargo test,--workspace --exclude axfs $(1) $(verbose) -- --nocapture)
 endef
@@ -46,18 +46,38 @@ int dirfd(DIR *d)
     return d->fd;
 }
 
-// TODO
-DIR *opendir(const char *__name)
+DIR *opendir(const char *name)
 {
-    unimplemented();
-    return NULL;
+    int fd;
+    DIR *dir;
+
+    if ((fd = open(name, O_RDONLY | O_DIRECTORY | O_CLOEXEC)) < 0) {
+        return 0;
+    }
+    if (!(dir = calloc(1, sizeof(*dir)))) {
+        close(fd);
+        return 0;
+    }
+    dir->fd = fd;
+    return dir;
 }
 
-// TODO
-struct dirent *readdir(DIR *__dirp)
+struct dirent *readdir(DIR *dir)
 {
-    unimplemented();
-    return NULL;
+    struct dirent *de;
+
+    if (dir->buf_pos >= dir->buf_end) {
+        int len = getdents64(dir->fd, dir->buf, sizeof(dir->buf));
+        if (len <= 0) {
+            return 0;
+        }
+        dir->buf_end = len;
+        dir->buf_pos = 0;
+    }
+    de = (void *)(dir->buf + dir->buf_pos);
+    dir->buf_pos += de->d_reclen;
+    dir->tell = de->d_off;
+    return de;
 }
 
 // TODO
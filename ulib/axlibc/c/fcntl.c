@@ -39,15 +39,25 @@ int open(const char *filename, int flags, ...)
     return ax_open(filename, flags, mode);
 }
 
-// TODO
-int posix_fadvise(int __fd, unsigned long __offset, unsigned long __len, int __advise)
+// TODO: remove this function in future work
+int ax_openat(int dirfd, const char *filename, int flags, mode_t mode);
+
+int openat(int dirfd, const char *filename, int flags, ...)
 {
-    unimplemented();
-    return 0;
+    mode_t mode = 0;
+
+    if ((flags & O_CREAT) || (flags & O_TMPFILE) == O_TMPFILE) {
+        va_list ap;
+        va_start(ap, flags);
+        mode = va_arg(ap, mode_t);
+        va_end(ap);
+    }
+
+    return ax_openat(dirfd, filename, flags, mode);
 }
 
 // TODO
-int sync_file_range(int fd, off_t pos, off_t len, unsigned flags)
+int posix_fadvise(int __fd, unsigned long __offset, unsigned long __len, int __advise)
 {
     unimplemented();
     return 0;
@@ -160,13 +160,6 @@ int gettimeofday(struct timeval *tv, struct timezone *tz)
     return 0;
 }
 
-// TODO:
-int utimes(const char *filename, const struct timeval times[2])
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO
 void tzset()
 {
@@ -188,11 +181,12 @@ char *ctime_r(const time_t *t, char *buf)
     return NULL;
 }
 
-// TODO
 clock_t clock(void)
 {
-    unimplemented();
-    return 0;
+    struct timespec ts;
+    if (clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &ts) < 0)
+        return -1;
+    return ts.tv_sec * CLOCKS_PER_SEC + ts.tv_nsec / (1000000000L / CLOCKS_PER_SEC);
 }
 
 #ifdef AX_CONFIG_FP_SIMD
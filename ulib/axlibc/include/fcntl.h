@@ -97,8 +97,21 @@
 #define POSIX_FADV_NOREUSE  5
 #endif
 
-#define AT_FDCWD      (-100)
-#define AT_EMPTY_PATH 0x1000
+#define AT_FDCWD              (-100)
+#define AT_SYMLINK_NOFOLLOW   0x100
+#define AT_REMOVEDIR          0x200
+#define AT_SYMLINK_FOLLOW     0x400
+#define AT_EACCESS            0x200
+#define AT_NO_AUTOMOUNT       0x800
+#define AT_EMPTY_PATH         0x1000
+#define AT_STATX_SYNC_TYPE    0x6000
+#define AT_STATX_SYNC_AS_STAT 0x0000
+#define AT_STATX_FORCE_SYNC   0x2000
+#define AT_STATX_DONT_SYNC    0x4000
+
+#define RENAME_NOREPLACE (1 << 0)
+#define RENAME_EXCHANGE  (1 << 1)
+#define RENAME_WHITEOUT  (1 << 2)
 
 #define SYNC_FILE_RANGE_WAIT_BEFORE 1
 #define SYNC_FILE_RANGE_WRITE       2
@@ -119,5 +132,6 @@ int posix_fadvise(int __fd, unsigned long __offset, unsigned long __len, int __a
 int sync_file_range(int, off_t, off_t, unsigned);
 
 int open(const char *filename, int flags, ...);
+int openat(int dirfd, const char *filename, int flags, ...);
 
 #endif
@@ -61,6 +61,8 @@ int fclose(FILE *);
 
 int remove(const char *);
 int rename(const char *, const char *);
+int renameat(int, const char *, int, const char *);
+int renameat2(int, const char *, int, const char *, unsigned);
 
 int feof(FILE *__stream);
 int ferror(FILE *);
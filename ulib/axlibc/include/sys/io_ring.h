@@ -0,0 +1,58 @@
+#ifndef _SYS_IO_RING_H
+#define _SYS_IO_RING_H
+
+/*
+ * Asynchronous I/O rings, an ArceOS extension similar to io_uring.
+ *
+ * Requests are copied to the ring by io_ring_submit(), and run in the
+ * background while the caller goes on. Their buffers must stay valid until
+ * the completion is reaped by io_ring_wait(). The ring file descriptor is
+ * readable when completions are available.
+ */
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <stdint.h>
+
+#define IO_RING_MAX_ENTRIES 4096
+
+#define IO_RING_OP_NOP    0
+#define IO_RING_OP_READ   1
+#define IO_RING_OP_WRITE  2
+#define IO_RING_OP_READV  3
+#define IO_RING_OP_WRITEV 4
+#define IO_RING_OP_FSYNC  5
+
+/* rw_flags of IO_RING_OP_FSYNC */
+#define IO_RING_FSYNC_DATASYNC 1
+
+/* A submission queue entry. */
+struct io_ring_sqe {
+    uint8_t opcode;     /* IO_RING_OP_* */
+    uint8_t __pad[3];
+    int32_t fd;
+    int64_t off;        /* -1 for the current file offset */
+    uint64_t addr;      /* buffer, or array of struct iovec */
+    uint32_t len;       /* buffer size, or number of iovecs */
+    int32_t rw_flags;   /* RWF_* of preadv2 and pwritev2, or IO_RING_FSYNC_* */
+    uint64_t user_data; /* passed back in the completion */
+};
+
+/* A completion queue entry. */
+struct io_ring_cqe {
+    uint64_t user_data;
+    int32_t res; /* result of the request, or -errno */
+    uint32_t flags;
+};
+
+int io_ring_setup(unsigned entries);
+int io_ring_submit(int ring, const struct io_ring_sqe *sqes, unsigned nr);
+int io_ring_wait(int ring, struct io_ring_cqe *cqes, unsigned nr, unsigned min_complete);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // _SYS_IO_RING_H
@@ -0,0 +1,36 @@
+#ifndef _SYS_STATFS_H
+#define _SYS_STATFS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <sys/types.h>
+
+typedef struct __fsid_t {
+    int __val[2];
+} fsid_t;
+
+struct statfs {
+    unsigned long f_type;    /* type of filesystem, a magic number */
+    unsigned long f_bsize;   /* block size */
+    fsblkcnt_t f_blocks;     /* total number of blocks */
+    fsblkcnt_t f_bfree;      /* number of free blocks */
+    fsblkcnt_t f_bavail;     /* number of free blocks for unprivileged users */
+    fsfilcnt_t f_files;      /* total number of inodes, 0 if not limited */
+    fsfilcnt_t f_ffree;      /* number of free inodes */
+    fsid_t f_fsid;           /* filesystem ID */
+    unsigned long f_namelen; /* maximum length of file names */
+    unsigned long f_frsize;  /* fragment size */
+    unsigned long f_flags;   /* ST_* of <sys/statvfs.h> */
+    unsigned long f_spare[4];
+};
+
+int statfs(const char *, struct statfs *);
+int fstatfs(int, struct statfs *);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // _SYS_STATFS_H
@@ -0,0 +1,35 @@
+#ifndef _SYS_STATVFS_H
+#define _SYS_STATVFS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <sys/types.h>
+
+struct statvfs {
+    unsigned long f_bsize;   /* block size */
+    unsigned long f_frsize;  /* fragment size, the unit of f_blocks */
+    fsblkcnt_t f_blocks;     /* total number of blocks */
+    fsblkcnt_t f_bfree;      /* number of free blocks */
+    fsblkcnt_t f_bavail;     /* number of free blocks for unprivileged users */
+    fsfilcnt_t f_files;      /* total number of inodes, 0 if not limited */
+    fsfilcnt_t f_ffree;      /* number of free inodes */
+    fsfilcnt_t f_favail;     /* number of free inodes for unprivileged users */
+    unsigned long f_fsid;    /* filesystem ID */
+    unsigned long f_flag;    /* ST_* */
+    unsigned long f_namemax; /* maximum length of file names */
+    int __reserved[6];
+};
+
+#define ST_RDONLY 1
+#define ST_NOSUID 2
+
+int statvfs(const char *__restrict, struct statvfs *__restrict);
+int fstatvfs(int, struct statvfs *);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // _SYS_STATVFS_H
@@ -2,12 +2,24 @@
 #define _SYS_UIO_H
 
 #include <stddef.h>
+#include <sys/types.h>
 
 struct iovec {
     void *iov_base; /* Pointer to data.  */
     size_t iov_len; /* Length of data.  */
 };
 
+#define RWF_HIPRI  0x00000001
+#define RWF_DSYNC  0x00000002
+#define RWF_SYNC   0x00000004
+#define RWF_NOWAIT 0x00000008
+#define RWF_APPEND 0x00000010
+
+ssize_t readv(int, const struct iovec *, int);
 ssize_t writev(int, const struct iovec *, int);
+ssize_t preadv(int, const struct iovec *, int, off_t);
+ssize_t pwritev(int, const struct iovec *, int, off_t);
+ssize_t preadv2(int, const struct iovec *, int, off_t, int);
+ssize_t pwritev2(int, const struct iovec *, int, off_t, int);
 
 #endif
@@ -25,6 +25,8 @@ int dup3(int, int, int);
 off_t lseek(int, off_t, int);
 int fsync(int);
 int fdatasync(int);
+void sync(void);
+int syncfs(int);
 
 ssize_t read(int, void *, size_t);
 ssize_t write(int, const void *, size_t);
@@ -1,5 +1,5 @@
 use crate::{ctypes, utils::e};
-use arceos_posix_api::{sys_close, sys_dup, sys_dup2, sys_fcntl};
+use arceos_posix_api::{sys_close, sys_dup, sys_dup2, sys_fcntl, sys_fdatasync, sys_fsync};
 use axerrno::LinuxError;
 use core::ffi::c_int;
 
@@ -21,6 +21,18 @@ pub unsafe extern "C" fn dup2(old_fd: c_int, new_fd: c_int) -> c_int {
     e(sys_dup2(old_fd, new_fd))
 }
 
+/// Synchronize the data and metadata of a file to the storage device.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fsync(fd: c_int) -> c_int {
+    e(sys_fsync(fd))
+}
+
+/// Synchronize the data of a file to the storage device.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fdatasync(fd: c_int) -> c_int {
+    e(sys_fdatasync(fd))
+}
+
 /// Duplicate a file descriptor, the caller can force the close-on-exec flag to
 /// be set for the new file descriptor by specifying `O_CLOEXEC` in flags.
 ///
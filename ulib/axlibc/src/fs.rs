@@ -1,8 +1,14 @@
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
 
 use arceos_posix_api::{
-    sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat,
+    sys_faccessat, sys_fchmod, sys_fchmodat, sys_fchown, sys_fchownat, sys_flock, sys_fstat,
+    sys_fstatat, sys_fstatfs, sys_getcwd, sys_getdents64, sys_inotify_add_watch, sys_inotify_init1,
+    sys_inotify_rm_watch, sys_link, sys_lseek, sys_lstat, sys_mkdirat, sys_mount, sys_open,
+    sys_openat, sys_readlink, sys_rename, sys_renameat2, sys_stat, sys_statfs, sys_statx,
+    sys_symlink, sys_sync, sys_sync_file_range, sys_syncfs, sys_umount2, sys_unlinkat,
+    sys_utimensat,
 };
+use axerrno::LinuxError;
 
 use crate::{ctypes, utils::e};
 
@@ -19,6 +25,20 @@ pub unsafe extern "C" fn ax_open(
     e(sys_open(filename, flags, mode))
 }
 
+/// Open a file by `filename` relative to the directory `dirfd` and insert it
+/// into the file descriptor table.
+///
+/// Return its index in the file table (`fd`).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ax_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    e(sys_openat(dirfd, filename, flags, mode))
+}
+
 /// Set the position of the file indicated by `fd`.
 ///
 /// Return its position after seek.
@@ -51,6 +71,243 @@ pub unsafe extern "C" fn lstat(path: *const c_char, buf: *mut ctypes::stat) -> c
     e(sys_lstat(path, buf) as _)
 }
 
+/// Get the metadata of `path` relative to the directory `dirfd` and write into
+/// `buf`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    e(sys_fstatat(dirfd, path, buf, flags))
+}
+
+/// Get the extended metadata of `path` relative to the directory `dirfd` and
+/// write into `buf`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn statx(
+    dirfd: c_int,
+    path: *const c_char,
+    flags: c_int,
+    mask: c_uint,
+    buf: *mut ctypes::statx,
+) -> c_int {
+    e(sys_statx(dirfd, path, flags, mask, buf))
+}
+
+/// Check whether the calling task can access `path`.
+///
+/// Return 0 if the access is permitted, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn access(path: *const c_char, mode: c_int) -> c_int {
+    e(sys_faccessat(ctypes::AT_FDCWD, path, mode, 0))
+}
+
+/// Check whether the calling task can access `path` relative to the directory
+/// `dirfd`.
+///
+/// Return 0 if the access is permitted, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn faccessat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: c_int,
+    flags: c_int,
+) -> c_int {
+    e(sys_faccessat(dirfd, path, mode, flags))
+}
+
+/// Change the access and modification times of `path` relative to the
+/// directory `dirfd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    e(sys_utimensat(dirfd, path, times, flags))
+}
+
+/// Change the access and modification times of the opened file `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn futimens(fd: c_int, times: *const ctypes::timespec) -> c_int {
+    e(sys_utimensat(fd, core::ptr::null(), times, 0))
+}
+
+/// Change the access and modification times of `path`, in microseconds.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn utimes(path: *const c_char, times: *const ctypes::timeval) -> c_int {
+    if times.is_null() {
+        return e(sys_utimensat(ctypes::AT_FDCWD, path, core::ptr::null(), 0));
+    }
+    let times = unsafe { *times.cast::<[ctypes::timeval; 2]>() }.map(|tv| ctypes::timespec {
+        tv_sec: tv.tv_sec as _,
+        tv_nsec: tv.tv_usec as c_long * 1000,
+    });
+    e(sys_utimensat(ctypes::AT_FDCWD, path, times.as_ptr(), 0))
+}
+
+/// Change the permissions of `path` to `mode`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_fchmodat(ctypes::AT_FDCWD, path, mode, 0))
+}
+
+/// Change the permissions of the opened file `fd` to `mode`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    e(sys_fchmod(fd, mode))
+}
+
+/// Change the permissions of `path` relative to the directory `dirfd` to
+/// `mode`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fchmodat(
+    dirfd: c_int,
+    path: *const c_char,
+    mode: ctypes::mode_t,
+    flags: c_int,
+) -> c_int {
+    e(sys_fchmodat(dirfd, path, mode, flags))
+}
+
+/// Change the owner and the group of `path`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_fchownat(ctypes::AT_FDCWD, path, owner, group, 0))
+}
+
+/// Change the owner and the group of the opened file `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    e(sys_fchown(fd, owner, group))
+}
+
+/// Change the owner and the group of `path`, without following symbolic
+/// links.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lchown(
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+) -> c_int {
+    e(sys_fchownat(
+        ctypes::AT_FDCWD,
+        path,
+        owner,
+        group,
+        ctypes::AT_SYMLINK_NOFOLLOW as _,
+    ))
+}
+
+/// Change the owner and the group of `path` relative to the directory
+/// `dirfd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fchownat(
+    dirfd: c_int,
+    path: *const c_char,
+    owner: ctypes::uid_t,
+    group: ctypes::gid_t,
+    flags: c_int,
+) -> c_int {
+    e(sys_fchownat(dirfd, path, owner, group, flags))
+}
+
+/// Apply or remove an advisory lock on the opened file `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn flock(fd: c_int, operation: c_int) -> c_int {
+    e(sys_flock(fd, operation))
+}
+
+/// Create an inotify instance.
+///
+/// Return its file descriptor.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_init() -> c_int {
+    e(sys_inotify_init1(0))
+}
+
+/// Create an inotify instance with `flags` (`IN_NONBLOCK`, `IN_CLOEXEC`).
+///
+/// Return its file descriptor.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_init1(flags: c_int) -> c_int {
+    e(sys_inotify_init1(flags))
+}
+
+/// Watch the file or directory `path` for the events in `mask` with the
+/// inotify instance `fd`.
+///
+/// Return the watch descriptor.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int {
+    e(sys_inotify_add_watch(fd, path, mask))
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+///
+/// Return 0 if success.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn inotify_rm_watch(fd: c_int, wd: c_int) -> c_int {
+    e(sys_inotify_rm_watch(fd, wd))
+}
+
+/// Create a directory `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkdir(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(ctypes::AT_FDCWD, path, mode))
+}
+
+/// Create a directory `path` relative to the directory `dirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    e(sys_mkdirat(dirfd, path, mode))
+}
+
+/// Read the entries of the directory `fd` into `dirp`.
+///
+/// Return the number of bytes read, or 0 at the end of the directory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn getdents64(fd: c_int, dirp: *mut c_void, count: usize) -> ctypes::ssize_t {
+    e(sys_getdents64(fd, dirp, count) as _) as _
+}
+
 /// Get the path of the current directory.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
@@ -65,3 +322,215 @@ pub unsafe extern "C" fn getcwd(buf: *mut c_char, size: usize) -> *mut c_char {
 pub unsafe extern "C" fn rename(old: *const c_char, new: *const c_char) -> c_int {
     e(sys_rename(old, new))
 }
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    e(sys_renameat2(olddirfd, old, newdirfd, new, 0))
+}
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`, with `RENAME_*` flags.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn renameat2(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+    flags: c_uint,
+) -> c_int {
+    e(sys_renameat2(olddirfd, old, newdirfd, new, flags))
+}
+
+/// Create a symbolic link `linkpath` which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    e(sys_symlink(target, linkpath))
+}
+
+/// Read the target of the symbolic link `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    e(sys_readlink(path, buf, bufsiz) as _) as _
+}
+
+/// Create a hard link `new` to the existing file `old`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn link(old: *const c_char, new: *const c_char) -> c_int {
+    e(sys_link(old, new))
+}
+
+/// Remove the directory entry `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unlink(path: *const c_char) -> c_int {
+    e(sys_unlinkat(ctypes::AT_FDCWD, path, 0))
+}
+
+/// Remove the empty directory `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rmdir(path: *const c_char) -> c_int {
+    e(sys_unlinkat(
+        ctypes::AT_FDCWD,
+        path,
+        ctypes::AT_REMOVEDIR as _,
+    ))
+}
+
+/// Remove the directory entry `path` relative to `dirfd`, or the empty
+/// directory if `flags` contains `AT_REMOVEDIR`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    e(sys_unlinkat(dirfd, path, flags))
+}
+
+/// Mount the filesystem of type `fstype` on the directory `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: c_ulong,
+    data: *const c_void,
+) -> c_int {
+    e(sys_mount(source, target, fstype, flags, data))
+}
+
+/// Unmount the filesystem mounted on the directory `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn umount(target: *const c_char) -> c_int {
+    e(sys_umount2(target, 0))
+}
+
+/// Unmount the filesystem mounted on the directory `target` with `flags`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn umount2(target: *const c_char, flags: c_int) -> c_int {
+    e(sys_umount2(target, flags))
+}
+
+/// Write all cached modifications of all filesystems to the storage devices.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sync() {
+    sys_sync();
+}
+
+/// Write all cached modifications of the filesystem containing `fd` to its
+/// storage device.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn syncfs(fd: c_int) -> c_int {
+    e(sys_syncfs(fd))
+}
+
+/// Write the modified pages of a range of the file `fd` back to the
+/// filesystem.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sync_file_range(
+    fd: c_int,
+    offset: ctypes::off_t,
+    nbytes: ctypes::off_t,
+    flags: c_uint,
+) -> c_int {
+    e(sys_sync_file_range(fd, offset, nbytes, flags))
+}
+
+/// Get the statistics of the filesystem containing `path`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn statfs(path: *const c_char, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_statfs(path, buf))
+}
+
+/// Get the statistics of the filesystem containing the opened file `fd`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fstatfs(fd: c_int, buf: *mut ctypes::statfs) -> c_int {
+    e(sys_fstatfs(fd, buf))
+}
+
+fn statfs_to_statvfs(st: &ctypes::statfs) -> ctypes::statvfs {
+    ctypes::statvfs {
+        f_bsize: st.f_bsize,
+        f_frsize: st.f_frsize,
+        f_blocks: st.f_blocks,
+        f_bfree: st.f_bfree,
+        f_bavail: st.f_bavail,
+        f_files: st.f_files,
+        f_ffree: st.f_ffree,
+        f_favail: st.f_ffree,
+        f_fsid: st.f_fsid.__val[0] as _,
+        f_flag: st.f_flags,
+        f_namemax: st.f_namelen,
+        ..Default::default()
+    }
+}
+
+/// Get the statistics of the filesystem containing `path`, in the POSIX
+/// format.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn statvfs(path: *const c_char, buf: *mut ctypes::statvfs) -> c_int {
+    if buf.is_null() {
+        return e((LinuxError::EFAULT as c_int).wrapping_neg());
+    }
+    let mut st = ctypes::statfs::default();
+    if e(sys_statfs(path, &mut st)) < 0 {
+        return -1;
+    }
+    unsafe { *buf = statfs_to_statvfs(&st) };
+    0
+}
+
+/// Get the statistics of the filesystem containing the opened file `fd`, in
+/// the POSIX format.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fstatvfs(fd: c_int, buf: *mut ctypes::statvfs) -> c_int {
+    if buf.is_null() {
+        return e((LinuxError::EFAULT as c_int).wrapping_neg());
+    }
+    let mut st = ctypes::statfs::default();
+    if e(sys_fstatfs(fd, &mut st)) < 0 {
+        return -1;
+    }
+    unsafe { *buf = statfs_to_statvfs(&st) };
+    0
+}
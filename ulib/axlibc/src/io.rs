@@ -1,6 +1,11 @@
-use core::ffi::{c_int, c_void};
+use core::ffi::{c_int, c_uint, c_void};
 
-use arceos_posix_api::{sys_read, sys_write, sys_writev};
+#[cfg(feature = "fd")]
+use arceos_posix_api::{
+    sys_io_ring_setup, sys_io_ring_submit, sys_io_ring_wait, sys_pread64, sys_preadv, sys_preadv2,
+    sys_pwrite64, sys_pwritev, sys_pwritev2,
+};
+use arceos_posix_api::{sys_read, sys_readv, sys_write, sys_writev};
 
 use crate::{ctypes, utils::e};
 
@@ -21,6 +26,16 @@ pub unsafe extern "C" fn write(fd: c_int, buf: *const c_void, count: usize) -> c
     e(sys_write(fd, buf, count) as _) as _
 }
 
+/// Read into a vector.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn readv(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+) -> ctypes::ssize_t {
+    e(sys_readv(fd, iov, iocnt) as _) as _
+}
+
 /// Write a vector.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn writev(
@@ -30,3 +45,113 @@ pub unsafe extern "C" fn writev(
 ) -> ctypes::ssize_t {
     e(sys_writev(fd, iov, iocnt) as _) as _
 }
+
+/// Read data from the file indicated by `fd` at the given offset, without
+/// changing the file offset.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn pread(
+    fd: c_int,
+    buf: *mut c_void,
+    count: usize,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    e(sys_pread64(fd, buf, count, offset) as _) as _
+}
+
+/// Write data to the file indicated by `fd` at the given offset, without
+/// changing the file offset.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn pwrite(
+    fd: c_int,
+    buf: *const c_void,
+    count: usize,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    e(sys_pwrite64(fd, buf, count, offset) as _) as _
+}
+
+/// Read into a vector at the given offset, without changing the file offset.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn preadv(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    e(sys_preadv(fd, iov, iocnt, offset) as _) as _
+}
+
+/// Write a vector at the given offset, without changing the file offset.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn pwritev(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+) -> ctypes::ssize_t {
+    e(sys_pwritev(fd, iov, iocnt, offset) as _) as _
+}
+
+/// Read into a vector at the given offset, or at the current file offset if
+/// it is `-1`, with the `RWF_*` flags.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn preadv2(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    e(sys_preadv2(fd, iov, iocnt, offset, flags) as _) as _
+}
+
+/// Write a vector at the given offset, or at the current file offset if it is
+/// `-1`, with the `RWF_*` flags.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn pwritev2(
+    fd: c_int,
+    iov: *const ctypes::iovec,
+    iocnt: c_int,
+    offset: ctypes::off_t,
+    flags: c_int,
+) -> ctypes::ssize_t {
+    e(sys_pwritev2(fd, iov, iocnt, offset, flags) as _) as _
+}
+
+/// Create an asynchronous I/O ring, which can have up to `entries` requests
+/// in flight.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn io_ring_setup(entries: c_uint) -> c_int {
+    e(sys_io_ring_setup(entries))
+}
+
+/// Submit `nr` requests to the I/O ring, and return the number submitted.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn io_ring_submit(
+    ring: c_int,
+    sqes: *const ctypes::io_ring_sqe,
+    nr: c_uint,
+) -> c_int {
+    e(sys_io_ring_submit(ring, sqes, nr))
+}
+
+/// Wait for at least `min_complete` requests of the I/O ring to complete, and
+/// return the number of completions stored in `cqes`.
+#[unsafe(no_mangle)]
+#[cfg(feature = "fd")]
+pub unsafe extern "C" fn io_ring_wait(
+    ring: c_int,
+    cqes: *mut ctypes::io_ring_cqe,
+    nr: c_uint,
+    min_complete: c_uint,
+) -> c_int {
+    e(sys_io_ring_wait(ring, cqes, nr, min_complete))
+}
@@ -62,6 +62,8 @@ mod net;
 mod pipe;
 #[cfg(feature = "multitask")]
 mod pthread;
+#[cfg(feature = "multitask")]
+mod sched;
 #[cfg(feature = "alloc")]
 mod strftime;
 #[cfg(feature = "fp-simd")]
@@ -79,15 +81,19 @@ mod unistd;
 
 #[cfg(not(test))]
 pub use self::io::write;
-pub use self::io::{read, writev};
+#[cfg(feature = "fd")]
+pub use self::io::{
+    io_ring_setup, io_ring_submit, io_ring_wait, pread, preadv, preadv2, pwrite, pwritev, pwritev2,
+};
+pub use self::io::{read, readv, writev};
 
 pub use self::errno::strerror;
 pub use self::mktime::mktime;
 pub use self::rand::{rand, random, srand};
-pub use self::resource::{getrlimit, setrlimit};
+pub use self::resource::{getrlimit, getrusage, setrlimit};
 pub use self::setjmp::{longjmp, setjmp};
 pub use self::sys::sysconf;
-pub use self::time::{clock_gettime, nanosleep};
+pub use self::time::{clock_gettime, nanosleep, times};
 pub use self::unistd::{abort, exit, getpid};
 
 #[cfg(feature = "alloc")]
@@ -96,10 +102,16 @@ pub use self::malloc::{free, malloc};
 pub use self::strftime::strftime;
 
 #[cfg(feature = "fd")]
-pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
+pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3, fdatasync, fsync};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{
+    access, ax_open, ax_openat, chmod, chown, faccessat, fchmod, fchmodat, fchown, fchownat, flock,
+    fstat, fstatat, fstatfs, fstatvfs, futimens, getcwd, getdents64, inotify_add_watch,
+    inotify_init, inotify_init1, inotify_rm_watch, lchown, link, lseek, lstat, mkdir, mkdirat,
+    mount, readlink, rename, renameat, renameat2, rmdir, stat, statfs, statvfs, statx, symlink,
+    sync, sync_file_range, syncfs, umount, umount2, unlink, unlinkat, utimensat, utimes,
+};
 
 #[cfg(feature = "net")]
 pub use self::net::{
@@ -111,6 +123,13 @@ pub use self::net::{
 pub use self::pthread::{pthread_create, pthread_exit, pthread_join, pthread_self};
 #[cfg(feature = "multitask")]
 pub use self::pthread::{pthread_mutex_init, pthread_mutex_lock, pthread_mutex_unlock};
+#[cfg(feature = "multitask")]
+pub use self::resource::setpriority;
+#[cfg(feature = "multitask")]
+pub use self::sched::{
+    sched_get_priority_max, sched_get_priority_min, sched_getaffinity, sched_getparam,
+    sched_getscheduler, sched_setaffinity, sched_setparam, sched_setscheduler,
+};
 
 #[cfg(feature = "pipe")]
 pub use self::pipe::pipe;
@@ -0,0 +1,64 @@
+use crate::{ctypes, utils::e};
+use arceos_posix_api as api;
+use core::ffi::c_int;
+
+/// Set the CPU affinity of the thread `pid` (0 for the current thread).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_setaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    mask: *const ctypes::cpu_set_t,
+) -> c_int {
+    e(unsafe { api::sys_sched_setaffinity(pid, cpusetsize, mask) })
+}
+
+/// Get the CPU affinity of the thread `pid` (0 for the current thread).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_getaffinity(
+    pid: c_int,
+    cpusetsize: usize,
+    mask: *mut ctypes::cpu_set_t,
+) -> c_int {
+    e(unsafe { api::sys_sched_getaffinity(pid, cpusetsize, mask) })
+}
+
+/// Set the scheduling policy and priority of the thread `pid` (0 for the
+/// current thread).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_setscheduler(
+    pid: c_int,
+    policy: c_int,
+    param: *const ctypes::sched_param,
+) -> c_int {
+    e(unsafe { api::sys_sched_setscheduler(pid, policy, param) })
+}
+
+/// Get the scheduling policy of the thread `pid` (0 for the current thread).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_getscheduler(pid: c_int) -> c_int {
+    e(api::sys_sched_getscheduler(pid))
+}
+
+/// Set the priority of the thread `pid` (0 for the current thread).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_setparam(pid: c_int, param: *const ctypes::sched_param) -> c_int {
+    e(unsafe { api::sys_sched_setparam(pid, param) })
+}
+
+/// Get the priority of the thread `pid` (0 for the current thread).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_getparam(pid: c_int, param: *mut ctypes::sched_param) -> c_int {
+    e(unsafe { api::sys_sched_getparam(pid, param) })
+}
+
+/// Get the maximum priority of the scheduling policy.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_get_priority_max(policy: c_int) -> c_int {
+    e(api::sys_sched_get_priority_max(policy))
+}
+
+/// Get the minimum priority of the scheduling policy.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sched_get_priority_min(policy: c_int) -> c_int {
+    e(api::sys_sched_get_priority_min(policy))
+}
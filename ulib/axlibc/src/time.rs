@@ -1,4 +1,4 @@
-use arceos_posix_api::{sys_clock_gettime, sys_nanosleep};
+use arceos_posix_api::{sys_clock_gettime, sys_nanosleep, sys_times};
 use core::ffi::c_int;
 
 use crate::{ctypes, utils::e};
@@ -19,3 +19,9 @@ pub unsafe extern "C" fn nanosleep(
 ) -> c_int {
     e(sys_nanosleep(req, rem))
 }
+
+/// Get the CPU time of the process in clock ticks
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn times(buf: *mut ctypes::tms) -> ctypes::clock_t {
+    e(sys_times(buf) as _) as _
+}
@@ -16,7 +16,7 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(api::AxFileAttr);
+pub struct Metadata(pub(super) api::AxFileAttr);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -90,6 +90,12 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link, which is only
+    /// possible for the one from [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        self.0.file_type().is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -118,6 +124,7 @@ impl fmt::Debug for Metadata {
             .field("file_type", &self.file_type())
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
+            .field("is_symlink", &self.is_symlink())
             .field("permissions", &self.permissions())
             .finish_non_exhaustive()
     }
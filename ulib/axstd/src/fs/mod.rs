@@ -42,6 +42,30 @@ pub fn metadata(path: &str) -> io::Result<Metadata> {
     File::open(path)?.metadata()
 }
 
+/// Query the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    arceos_api::fs::ax_symlink_attr(path).map(Metadata)
+}
+
+/// Reads a symbolic link, returning the path it points to.
+#[cfg(feature = "alloc")]
+pub fn read_link(path: &str) -> io::Result<String> {
+    arceos_api::fs::ax_read_link(path)
+}
+
+/// Creates a new symbolic link `link` which points to `original`.
+pub fn soft_link(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_symlink(original, link)
+}
+
+/// Creates a new hard link `link` to the file `original`.
+///
+/// This only works then both are in the same mounted fs, which supports hard
+/// links.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    arceos_api::fs::ax_hard_link(original, link)
+}
+
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir<'_>> {
     ReadDir::new(path)
@@ -30,6 +30,10 @@
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext4`: Use ext4 as the root filesystem if the root volume has one.
+//!     - `tarfs`: Use a read-only tar image as the root filesystem if the root volume has one.
+//!     - `overlay`: Use a writable overlay of the root filesystem as the root.
+//!     - `initramfs`: Use a RAM filesystem unpacked from a cpio archive as the root.
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.